@@ -97,7 +97,7 @@ impl Credentials {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Crate {
     pub name: String,
     pub vers: String,
@@ -141,7 +141,7 @@ pub struct Crate {
     pub v: Option<u32>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, PartialOrd, Ord, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
 pub struct Dependency {
     pub name: String,
     pub req: String,
@@ -152,6 +152,11 @@ pub struct Dependency {
     pub kind: Option<DependencyKind>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub package: Option<String>,
+    /// The index URL of the registry hosting this dependency.
+    ///
+    /// If this is None, the dependency is hosted on the same registry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
 }
 
 #[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, PartialOrd, Ord, Eq)]
@@ -282,6 +287,12 @@ impl Repository {
         })
     }
 
+    /// Returns the absolute path to the root of the local checkout of the
+    /// crate index.
+    pub fn checkout_path(&self) -> &Path {
+        self.checkout_path.path()
+    }
+
     /// Returns the absolute path to the crate index file that corresponds to
     /// the given crate name.
     ///
@@ -335,15 +346,22 @@ impl Repository {
         Ok(head.target().unwrap())
     }
 
-    /// Commits the specified file with the specified commit message and pushes
-    /// the commit to the `master` branch on the `origin` remote.
+    /// Commits the specified files with the specified commit message and pushes
+    /// the commit to the `master` branch on the `origin` remote. Files that no
+    /// longer exist in the working folder are removed from the repository.
     ///
-    /// Note that `modified_file` expects a file path **relative** to the
+    /// Note that `modified_files` expects file paths **relative** to the
     /// repository working folder!
-    fn perform_commit_and_push(&self, msg: &str, modified_file: &Path) -> anyhow::Result<()> {
-        // git add $file
+    fn perform_commit_and_push(&self, msg: &str, modified_files: &[&Path]) -> anyhow::Result<()> {
+        // git add $files
         let mut index = self.repository.index()?;
-        index.add_path(modified_file)?;
+        for modified_file in modified_files {
+            if self.checkout_path.path().join(modified_file).exists() {
+                index.add_path(modified_file)?;
+            } else {
+                index.remove_path(modified_file)?;
+            }
+        }
         index.write()?;
         let tree_id = index.write_tree()?;
         let tree = self.repository.find_tree(tree_id)?;
@@ -439,10 +457,30 @@ impl Repository {
     /// This function also prints the commit message and a success or failure
     /// message to the console.
     pub fn commit_and_push(&self, message: &str, modified_file: &Path) -> anyhow::Result<()> {
+        self.commit_and_push_all(message, &[modified_file])
+    }
+
+    /// Commits all of the specified files in a single commit with the
+    /// specified commit message and pushes the commit to the `master` branch
+    /// on the `origin` remote.
+    ///
+    /// Note that `modified_files` expects **absolute** file paths!
+    ///
+    /// This function also prints the commit message and a success or failure
+    /// message to the console.
+    pub fn commit_and_push_all(
+        &self,
+        message: &str,
+        modified_files: &[&Path],
+    ) -> anyhow::Result<()> {
         info!("Committing and pushing \"{message}\"");
 
-        let relative_path = modified_file.strip_prefix(self.checkout_path.path())?;
-        self.perform_commit_and_push(message, relative_path)
+        let relative_paths = modified_files
+            .iter()
+            .map(|path| path.strip_prefix(self.checkout_path.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.perform_commit_and_push(message, &relative_paths)
             .map(|_| info!("Commit and push finished for \"{message}\""))
             .map_err(|err| {
                 error!(?err, "Commit and push for \"{message}\" errored");
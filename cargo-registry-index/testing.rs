@@ -62,6 +62,74 @@ impl UpstreamIndex {
     }
 }
 
+/// The git index of another registry, which is used as the upstream registry
+/// in tests of the mirror mode.
+pub struct UpstreamRegistryIndex {
+    pub repository: Repository,
+}
+
+impl UpstreamRegistryIndex {
+    pub fn new() -> anyhow::Result<Self> {
+        let path = root().join("upstream-registry");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path)?;
+
+        let repository = Repository::init_opts(
+            &path,
+            git2::RepositoryInitOptions::new()
+                .bare(true)
+                .initial_head("master"),
+        )?;
+        let mut config = repository.config()?;
+        config.set_str("user.name", "name")?;
+        config.set_str("user.email", "email")?;
+
+        Ok(Self { repository })
+    }
+
+    pub fn url(&self) -> Url {
+        Url::from_file_path(self.repository.path()).unwrap()
+    }
+
+    /// Commits a file with the given contents to the `master` branch.
+    pub fn commit_file(&self, path: &str, contents: &str) -> anyhow::Result<()> {
+        let repo = &self.repository;
+
+        let parent = match repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(_) => None,
+        };
+
+        let mut index = repo.index()?;
+        if let Some(parent) = &parent {
+            index.read_tree(&parent.tree()?)?;
+        }
+
+        let entry = git2::IndexEntry {
+            ctime: git2::IndexTime::new(0, 0),
+            mtime: git2::IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100644,
+            uid: 0,
+            gid: 0,
+            file_size: contents.len() as u32,
+            id: git2::Oid::zero(),
+            flags: 0,
+            flags_extended: 0,
+            path: path.as_bytes().to_vec(),
+        };
+        index.add_frombuffer(&entry, contents.as_bytes())?;
+
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let sig = repo.signature()?;
+        let parents = parent.iter().collect::<Vec<_>>();
+        repo.commit(Some("HEAD"), &sig, &sig, path, &tree, &parents)?;
+
+        Ok(())
+    }
+}
+
 fn root() -> PathBuf {
     env::current_dir()
         .unwrap()
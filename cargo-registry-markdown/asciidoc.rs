@@ -0,0 +1,577 @@
+//! Render the subset of AsciiDoc that is commonly used in readmes:
+//! section titles, paragraphs, lists, listing and literal blocks,
+//! admonitions, images, links and tables.
+//!
+//! The generated HTML still has to be sanitized, see `asciidoc_to_html`.
+
+use crate::{
+    dedent, find_closing, find_delimiter, has_delimiter_at, indentation, is_markup_boundary,
+    push_code_block, push_table, split_lines, url_len, Heading, Outline,
+};
+use htmlescape::{encode_attribute, encode_minimal};
+use std::fmt::Write;
+
+/// Renders AsciiDoc to unsanitized HTML and returns its outline.
+pub(crate) fn to_html(text: &str, highlight_code: bool) -> (String, Vec<Heading>) {
+    let lines = split_lines(text, 4);
+    let mut renderer = Renderer {
+        outline: Outline::default(),
+        html: String::new(),
+        highlight_code,
+    };
+    renderer.blocks(&lines);
+    (renderer.html, renderer.outline.headings)
+}
+
+struct Renderer {
+    outline: Outline,
+    html: String,
+    /// Whether code blocks are highlighted on the server.
+    highlight_code: bool,
+}
+
+/// An item of a (possibly nested) list.
+struct ListItem {
+    ordered: bool,
+    /// The number of characters in the marker, like `**` or `...`.
+    depth: usize,
+    text: String,
+}
+
+impl Renderer {
+    fn blocks(&mut self, lines: &[String]) {
+        // The attribute list (like `[source,rust]`) and title (like `.Example`)
+        // of the next block.
+        let mut attributes = None;
+        let mut title = None;
+
+        let mut i = 0;
+        while i < lines.len() {
+            let line = &lines[i];
+            if line.is_empty() {
+                i += 1;
+                continue;
+            }
+            if is_delimiter(line) && line.starts_with('/') {
+                i = (closing_delimiter(lines, i) + 1).min(lines.len());
+                continue;
+            }
+            if line.starts_with("//") || is_attribute_entry(line) || line.starts_with("[[") {
+                i += 1;
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                attributes = Some(line[1..line.len() - 1].to_string());
+                i += 1;
+                continue;
+            }
+            if let Some(text) = block_title(line) {
+                title = Some(text.to_string());
+                i += 1;
+                continue;
+            }
+
+            if let Some(title) = title.take() {
+                let title = self.inline(&title);
+                let _ = writeln!(self.html, "<p><strong>{title}</strong></p>");
+            }
+            let attributes = attributes.take().unwrap_or_default();
+            i = self.block(lines, i, &attributes);
+        }
+    }
+
+    /// Renders the block starting at line `i` and returns the line after it.
+    fn block(&mut self, lines: &[String], i: usize, attributes: &str) -> usize {
+        let line = &lines[i];
+        if let Some((level, title)) = section_title(line) {
+            let content = self.inline(title);
+            self.outline.push_heading(&mut self.html, level, &content);
+            return i + 1;
+        }
+
+        if let Some(language) = line.strip_prefix("```") {
+            let end = (i + 1..lines.len())
+                .find(|&j| lines[j] == "```")
+                .unwrap_or(lines.len());
+            push_code_block(
+                &mut self.html,
+                Some(language.trim()),
+                &lines[i + 1..end],
+                self.highlight_code,
+            );
+            return end + 1;
+        }
+
+        if is_delimiter(line) {
+            let close = closing_delimiter(lines, i);
+            let content = &lines[i + 1..close];
+            match line.chars().next() {
+                Some('-') => {
+                    // `[source,rust]` marks a source code listing.
+                    let mut attributes = attributes.split(',').map(str::trim);
+                    let language = match attributes.next() {
+                        Some("source") => attributes.next(),
+                        _ => None,
+                    };
+                    push_code_block(&mut self.html, language, content, self.highlight_code);
+                }
+                Some('.') => push_code_block(&mut self.html, None, content, self.highlight_code),
+                Some('_') => {
+                    self.html.push_str("<blockquote>\n");
+                    self.blocks(content);
+                    self.html.push_str("</blockquote>\n");
+                }
+                Some('+') => {
+                    for line in content {
+                        let _ = writeln!(self.html, "{line}");
+                    }
+                }
+                _ => match admonition_title(attributes) {
+                    Some(title) => {
+                        let _ =
+                            writeln!(self.html, "<blockquote>\n<p><strong>{title}</strong></p>");
+                        self.blocks(content);
+                        self.html.push_str("</blockquote>\n");
+                    }
+                    None => {
+                        self.html.push_str("<div>\n");
+                        self.blocks(content);
+                        self.html.push_str("</div>\n");
+                    }
+                },
+            }
+            return (close + 1).min(lines.len());
+        }
+
+        if line == "|===" {
+            return self.table(lines, i, attributes);
+        }
+        if line == "'''" || line == "---" || line == "***" {
+            self.html.push_str("<hr>\n");
+            return i + 1;
+        }
+        if line == "<<<" {
+            return i + 1;
+        }
+        let block_image = line.strip_prefix("image::").and_then(|rest| {
+            let chars = rest.chars().collect::<Vec<_>>();
+            macro_parts(&chars, 0)
+        });
+        if let Some((url, alt, _)) = block_image {
+            let _ = writeln!(self.html, "<p>{}</p>", image(&url, &alt));
+            return i + 1;
+        }
+        if list_item(line).is_some() {
+            return self.list(lines, i);
+        }
+        if indentation(line) > 0 {
+            let end = (i..lines.len())
+                .find(|&j| lines[j].is_empty())
+                .unwrap_or(lines.len());
+            push_code_block(
+                &mut self.html,
+                None,
+                &dedent(&lines[i..end]),
+                self.highlight_code,
+            );
+            return end;
+        }
+        self.paragraph(lines, i, attributes)
+    }
+
+    fn paragraph(&mut self, lines: &[String], i: usize, attributes: &str) -> usize {
+        let end = (i + 1..lines.len())
+            .find(|&j| lines[j].is_empty() || starts_block(&lines[j]))
+            .unwrap_or(lines.len());
+
+        let mut text = lines[i..end].join("\n");
+        let mut admonition = admonition_title(attributes);
+        if let Some((label, rest)) = text.split_once(": ") {
+            if let Some(title) = admonition_title(label) {
+                admonition = Some(title);
+                text = rest.to_string();
+            }
+        }
+
+        // Lines ending with ` +` are followed by a hard line break.
+        let content = text
+            .split(" +\n")
+            .map(|part| self.inline(part))
+            .collect::<Vec<_>>()
+            .join("<br>\n");
+        match admonition {
+            Some(title) => {
+                let _ = writeln!(
+                    self.html,
+                    "<blockquote>\n<p><strong>{title}</strong></p>\n<p>{content}</p>\n</blockquote>"
+                );
+            }
+            None => {
+                let _ = writeln!(self.html, "<p>{content}</p>");
+            }
+        }
+        end
+    }
+
+    fn list(&mut self, lines: &[String], i: usize) -> usize {
+        let mut items: Vec<ListItem> = Vec::new();
+        let mut j = i;
+        while j < lines.len() {
+            let line = &lines[j];
+            if line.is_empty() || line == "+" {
+                // Blank lines may separate the items of a list, but not
+                // a list from a nested list with a new kind of marker.
+                let next = (j..lines.len()).find(|&k| !lines[k].is_empty() && lines[k] != "+");
+                match next.and_then(|next| Some((next, list_item(&lines[next])?))) {
+                    Some((next, item)) if items.iter().any(|other| other.is_sibling(&item)) => {
+                        j = next
+                    }
+                    _ => break,
+                }
+                continue;
+            }
+            if let Some(item) = list_item(line) {
+                items.push(item);
+            } else if starts_block(line) {
+                break;
+            } else if let Some(item) = items.last_mut() {
+                item.text.push('\n');
+                item.text.push_str(line.trim());
+            }
+            j += 1;
+        }
+
+        // Lists are nested by their kind of marker: an item with a marker
+        // that is already in use continues that list, any other marker
+        // starts a nested list.
+        let mut open: Vec<(bool, usize)> = Vec::new();
+        for item in &items {
+            let key = (item.ordered, item.depth);
+            match open.iter().position(|k| *k == key) {
+                Some(position) => {
+                    while open.len() > position + 1 {
+                        let (ordered, _) = open.pop().unwrap();
+                        let _ = writeln!(self.html, "</li>\n</{}>", list_tag(ordered));
+                    }
+                    self.html.push_str("</li>\n<li>");
+                }
+                None => {
+                    if !open.is_empty() {
+                        self.html.push('\n');
+                    }
+                    let _ = write!(self.html, "<{}>\n<li>", list_tag(item.ordered));
+                    open.push(key);
+                }
+            }
+            let content = self.inline(&item.text);
+            self.html.push_str(&content);
+        }
+        while let Some((ordered, _)) = open.pop() {
+            let _ = writeln!(self.html, "</li>\n</{}>", list_tag(ordered));
+        }
+        j
+    }
+
+    fn table(&mut self, lines: &[String], i: usize, attributes: &str) -> usize {
+        let end = (i + 1..lines.len())
+            .find(|&j| lines[j] == "|===")
+            .unwrap_or(lines.len());
+        let body = &lines[i + 1..end];
+
+        let mut cells: Vec<String> = Vec::new();
+        let mut first_row_len = None;
+        for line in body {
+            if let Some(row) = line.strip_prefix('|') {
+                let row = row.split('|').map(|cell| cell.trim().to_string());
+                let len = cells.len();
+                cells.extend(row);
+                first_row_len.get_or_insert(cells.len() - len);
+            } else if let Some(cell) = cells.last_mut() {
+                if !line.is_empty() {
+                    cell.push(' ');
+                    cell.push_str(line.trim());
+                }
+            }
+        }
+
+        // The first line is a header row if it is followed by a blank line.
+        let implicit_header = body.len() > 2 && body[0].starts_with('|') && body[1].is_empty();
+        let has_header = implicit_header
+            || attributes.contains("%header")
+            || attributes.contains("options=\"header\"")
+            || attributes.contains("options=header");
+
+        let columns = column_count(attributes)
+            .or(first_row_len)
+            .unwrap_or(1)
+            .max(1);
+        let mut rows = cells
+            .chunks(columns)
+            .map(|row| row.iter().map(|cell| self.inline(cell)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let header = if has_header && !rows.is_empty() {
+            Some(rows.remove(0))
+        } else {
+            None
+        };
+
+        push_table(&mut self.html, header.as_deref(), &rows);
+        end + 1
+    }
+
+    /// Renders inline markup: strong, emphasis and monospace text, links,
+    /// cross references, inline images and standalone URLs.
+    fn inline(&self, text: &str) -> String {
+        let chars = text.chars().collect::<Vec<_>>();
+        let mut html = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i] == '\\'
+                && matches!(chars.get(i + 1), Some('*' | '_' | '`' | '+' | '<' | '\\'))
+            {
+                html.push_str(&encode_minimal(&chars[i + 1].to_string()));
+                i += 2;
+                continue;
+            }
+            let at_start = i == 0 || is_markup_boundary(chars.get(i - 1));
+            if let Some((rendered, next)) = self.inline_markup(&chars, i, at_start) {
+                html.push_str(&rendered);
+                i = next;
+                continue;
+            }
+            html.push_str(&encode_minimal(&chars[i].to_string()));
+            i += 1;
+        }
+        html
+    }
+
+    /// Renders the inline markup starting at position `i`, if there is any,
+    /// and returns the position after it.
+    fn inline_markup(&self, chars: &[char], i: usize, at_start: bool) -> Option<(String, usize)> {
+        let content = |start: usize, end: usize| chars[start..end].iter().collect::<String>();
+
+        // Unconstrained formatting may appear anywhere, even inside words.
+        for (delimiter, tag) in [("**", "strong"), ("__", "em"), ("``", "code")] {
+            if has_delimiter_at(chars, i, delimiter) {
+                let end = find_delimiter(chars, i + 3, delimiter)?;
+                let inner = content(i + 2, end);
+                let inner = if tag == "code" {
+                    encode_minimal(&inner)
+                } else {
+                    self.inline(&inner)
+                };
+                return Some((format!("<{tag}>{inner}</{tag}>"), end + 2));
+            }
+        }
+
+        if !at_start {
+            return None;
+        }
+        for (delimiter, tag) in [("*", "strong"), ("_", "em"), ("`", "code")] {
+            if has_delimiter_at(chars, i, delimiter) {
+                let end = find_closing(chars, i + 1, delimiter)?;
+                let inner = content(i + 1, end);
+                let inner = if tag == "code" {
+                    encode_minimal(&inner)
+                } else {
+                    self.inline(&inner)
+                };
+                return Some((format!("<{tag}>{inner}</{tag}>"), end + 1));
+            }
+        }
+        if chars[i] == '+' {
+            let end = find_closing(chars, i + 1, "+")?;
+            return Some((encode_minimal(&content(i + 1, end)), end + 1));
+        }
+        if has_delimiter_at(chars, i, "<<") {
+            let end = find_delimiter(chars, i + 2, ">>")?;
+            let reference = content(i + 2, end);
+            let (id, text) = match reference.split_once(',') {
+                Some((id, text)) => (id.trim(), text.trim()),
+                None => (reference.trim(), reference.trim()),
+            };
+            let href = format!("#{id}");
+            return Some((link(&href, &self.inline(text)), end + 2));
+        }
+        if has_delimiter_at(chars, i, "image:") && !has_delimiter_at(chars, i, "image::") {
+            let (url, alt, next) = macro_parts(chars, i + 6)?;
+            return Some((image(&url, &alt), next));
+        }
+        if has_delimiter_at(chars, i, "link:") {
+            let (url, text, next) = macro_parts(chars, i + 5)?;
+            let text = if text.is_empty() { &url } else { &text };
+            return Some((link(&url, &self.inline(text)), next));
+        }
+        if let Some(len) = url_len(&chars[i..]) {
+            let url = content(i, i + len);
+            if chars.get(i + len) == Some(&'[') {
+                let end = find_delimiter(chars, i + len + 1, "]")?;
+                let text = content(i + len + 1, end);
+                let text = if text.is_empty() { &url } else { &text };
+                return Some((link(&url, &self.inline(text)), end + 1));
+            }
+            return Some((link(&url, &encode_minimal(&url)), i + len));
+        }
+        None
+    }
+}
+
+/// Returns the level and text of a section title like `== Usage`.
+fn section_title(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '=').count();
+    let title = line[level..].strip_prefix(' ')?.trim();
+    let title = title.trim_end_matches('=').trim_end();
+    ((1..=6).contains(&level) && !title.is_empty()).then_some((level, title))
+}
+
+/// Returns the text of a block title like `.Example`.
+fn block_title(line: &str) -> Option<&str> {
+    let title = line.strip_prefix('.')?;
+    title
+        .chars()
+        .next()
+        .filter(|c| !c.is_whitespace() && *c != '.')
+        .map(|_| title)
+}
+
+fn is_attribute_entry(line: &str) -> bool {
+    line.strip_prefix(':')
+        .and_then(|rest| rest.split_once(':'))
+        .map_or(false, |(name, value)| {
+            !name.is_empty()
+                && name
+                    .trim_end_matches('!')
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+                && (value.is_empty() || value.starts_with(' '))
+        })
+}
+
+/// Whether the line is the delimiter of a listing, literal, example,
+/// sidebar, quote, passthrough or comment block.
+fn is_delimiter(line: &str) -> bool {
+    line.len() >= 4
+        && line
+            .chars()
+            .next()
+            .filter(|c| matches!(c, '-' | '.' | '=' | '*' | '_' | '+' | '/'))
+            .map_or(false, |first| line.chars().all(|c| c == first))
+}
+
+/// Returns the line of the delimiter that closes the block opened at line `i`.
+/// Blocks that are not closed extend to the end of the document.
+fn closing_delimiter(lines: &[String], i: usize) -> usize {
+    (i + 1..lines.len())
+        .find(|&j| lines[j] == lines[i])
+        .unwrap_or(lines.len())
+}
+
+/// Whether the line starts a block and thereby interrupts a paragraph.
+fn starts_block(line: &str) -> bool {
+    section_title(line).is_some()
+        || list_item(line).is_some()
+        || is_delimiter(line)
+        || line.starts_with("```")
+        || line == "|==="
+        || (line.starts_with('[') && line.ends_with(']'))
+        || block_title(line).is_some()
+}
+
+fn list_item(line: &str) -> Option<ListItem> {
+    let line = line.trim_start();
+    let marker = line.chars().next()?;
+    let (ordered, depth) = match marker {
+        '*' | '.' => (
+            marker == '.',
+            line.chars().take_while(|c| *c == marker).count(),
+        ),
+        '-' => (false, 1),
+        _ => (true, line.chars().take_while(char::is_ascii_digit).count()),
+    };
+    if depth == 0 {
+        return None;
+    }
+    let rest = &line[depth..];
+    let rest = if marker.is_ascii_digit() {
+        rest.strip_prefix('.')?
+    } else {
+        rest
+    };
+    let text = rest.strip_prefix(' ')?.trim();
+    if text.is_empty() {
+        return None;
+    }
+    // Numbered items all belong to the same list, regardless of their number.
+    let depth = if marker.is_ascii_digit() { 1 } else { depth };
+    Some(ListItem {
+        ordered,
+        depth,
+        text: text.to_string(),
+    })
+}
+
+impl ListItem {
+    fn is_sibling(&self, other: &ListItem) -> bool {
+        self.ordered == other.ordered && self.depth == other.depth
+    }
+}
+
+fn list_tag(ordered: bool) -> &'static str {
+    if ordered {
+        "ol"
+    } else {
+        "ul"
+    }
+}
+
+/// Returns the title of an admonition like `NOTE` or `[TIP]`.
+fn admonition_title(label: &str) -> Option<&'static str> {
+    match label {
+        "NOTE" => Some("Note"),
+        "TIP" => Some("Tip"),
+        "IMPORTANT" => Some("Important"),
+        "WARNING" => Some("Warning"),
+        "CAUTION" => Some("Caution"),
+        _ => None,
+    }
+}
+
+/// Returns the number of columns of the `cols` attribute of a table, which
+/// is either a list of column specifiers or a multiplier like `3*`.
+fn column_count(attributes: &str) -> Option<usize> {
+    let (_, cols) = attributes.split_once("cols=")?;
+    let cols = cols.trim_start_matches('"');
+    let cols = &cols[..cols.find('"').unwrap_or(cols.len())];
+    match cols.split_once('*') {
+        Some((count, _)) => count.trim().parse().ok(),
+        None => Some(cols.split(',').count()),
+    }
+}
+
+/// Splits the target and the attribute list of a macro like `image:logo.png[Logo]`
+/// starting at `start`, and returns the position after the macro.
+fn macro_parts(chars: &[char], start: usize) -> Option<(String, String, usize)> {
+    let open = find_delimiter(chars, start, "[")?;
+    let target = &chars[start..open];
+    if target.is_empty() || target.iter().any(|c| c.is_whitespace()) {
+        return None;
+    }
+    let close = find_delimiter(chars, open + 1, "]")?;
+    let target = target.iter().collect();
+    let attributes = chars[open + 1..close].iter().collect();
+    Some((target, attributes, close + 1))
+}
+
+fn image(url: &str, alt: &str) -> String {
+    let alt = alt.split(',').next().unwrap_or("").trim();
+    format!(
+        "<img src=\"{}\" alt=\"{}\">",
+        encode_attribute(url),
+        encode_attribute(alt)
+    )
+}
+
+/// Renders a link around already rendered text.
+fn link(url: &str, text: &str) -> String {
+    format!("<a href=\"{}\">{text}</a>", encode_attribute(url))
+}
@@ -0,0 +1,493 @@
+//! Server-side syntax highlighting of code blocks in the languages that are
+//! most common in readmes: Rust, TOML, shell and JSON.
+//!
+//! Tokens are wrapped in `<span>` elements with one of the `hl-*` classes in
+//! `CLASSES`, which the sanitizer allows, so that the page can style them.
+//! Code in other languages is only escaped.
+
+use comrak::adapters::SyntaxHighlighterAdapter;
+use htmlescape::{encode_attribute, encode_minimal};
+use std::collections::HashMap;
+
+/// The classes of highlighted tokens.
+pub(crate) const CLASSES: [&str; 11] = [
+    "hl-attribute",
+    "hl-comment",
+    "hl-key",
+    "hl-keyword",
+    "hl-lifetime",
+    "hl-literal",
+    "hl-macro",
+    "hl-number",
+    "hl-section",
+    "hl-string",
+    "hl-variable",
+];
+
+static RUST_KEYWORDS: [&str; 38] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "type", "union", "unsafe",
+    "use", "where", "while", "yield",
+];
+
+static SHELL_KEYWORDS: [&str; 19] = [
+    "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in",
+    "local", "readonly", "return", "select", "then", "until", "while",
+];
+
+/// Highlights code in the given language, which is matched case-insensitively,
+/// and returns it as HTML.
+pub(crate) fn highlight(language: &str, code: &str) -> String {
+    let mut highlighter = Highlighter {
+        chars: code.chars().collect(),
+        position: 0,
+        html: String::with_capacity(code.len()),
+    };
+    match language.to_lowercase().as_str() {
+        "rust" | "rs" => highlighter.rust(),
+        "toml" => highlighter.toml(),
+        "json" => highlighter.json(),
+        "bash" | "console" | "sh" | "shell" | "shell-session" | "zsh" => highlighter.shell(),
+        _ => return encode_minimal(code),
+    }
+    highlighter.html
+}
+
+/// Highlights the fenced code blocks of Markdown documents.
+pub(crate) struct CodeHighlighter;
+
+impl SyntaxHighlighterAdapter for CodeHighlighter {
+    fn highlight(&self, lang: Option<&str>, code: &str) -> String {
+        match lang {
+            Some(lang) => highlight(lang, code),
+            None => encode_minimal(code),
+        }
+    }
+
+    fn build_pre_tag(&self, attributes: &HashMap<String, String>) -> String {
+        opening_tag("pre", attributes)
+    }
+
+    fn build_code_tag(&self, attributes: &HashMap<String, String>) -> String {
+        opening_tag("code", attributes)
+    }
+}
+
+fn opening_tag(tag: &str, attributes: &HashMap<String, String>) -> String {
+    let mut attributes = attributes.iter().collect::<Vec<_>>();
+    attributes.sort();
+    let attributes = attributes
+        .into_iter()
+        .map(|(name, value)| format!(" {name}=\"{}\"", encode_attribute(value)))
+        .collect::<String>();
+    format!("<{tag}{attributes}>")
+}
+
+struct Highlighter {
+    chars: Vec<char>,
+    position: usize,
+    html: String,
+}
+
+impl Highlighter {
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        prefix
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| self.peek(offset) == Some(c))
+    }
+
+    /// Returns the next character that is not a space or tab, starting at `offset`.
+    fn next_non_blank(&self, offset: usize) -> Option<char> {
+        self.chars[(self.position + offset).min(self.chars.len())..]
+            .iter()
+            .copied()
+            .find(|c| *c != ' ' && *c != '\t')
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.chars[..self.position]
+            .iter()
+            .rev()
+            .take_while(|c| **c != '\n')
+            .all(|c| *c == ' ' || *c == '\t')
+    }
+
+    /// Consumes the next `len` characters, wrapped in a span with the given class.
+    fn emit(&mut self, class: Option<&str>, len: usize) {
+        let end = (self.position + len).min(self.chars.len());
+        let text = self.chars[self.position..end].iter().collect::<String>();
+        match class {
+            Some(class) => {
+                self.html.push_str(&format!(
+                    "<span class=\"{class}\">{}</span>",
+                    encode_minimal(&text)
+                ));
+            }
+            None => self.html.push_str(&encode_minimal(&text)),
+        }
+        self.position = end;
+    }
+
+    /// Returns the length of the token that continues as long as `f` matches.
+    fn len_while(&self, offset: usize, f: impl Fn(char) -> bool) -> usize {
+        self.chars[(self.position + offset).min(self.chars.len())..]
+            .iter()
+            .take_while(|c| f(**c))
+            .count()
+            + offset
+    }
+
+    /// Returns the length of the token that ends with the first occurrence of
+    /// `delimiter`, or of the rest of the code if it is never closed.
+    fn len_until(&self, offset: usize, delimiter: &str) -> usize {
+        let delimiter = delimiter.chars().collect::<Vec<_>>();
+        let start = self.position + offset;
+        (start..self.chars.len())
+            .find(|i| self.chars[*i..].starts_with(&delimiter))
+            .map_or(self.chars.len() - self.position, |i| {
+                i + delimiter.len() - self.position
+            })
+    }
+
+    /// Returns the length of a string starting with `quote`, which may contain
+    /// escaped characters if `escapes` is set.
+    fn string_len(&self, quote: char, escapes: bool) -> usize {
+        let mut len = 1;
+        while let Some(c) = self.peek(len) {
+            len += 1;
+            if c == quote {
+                break;
+            } else if c == '\\' && escapes {
+                len += 1;
+            }
+        }
+        len.min(self.chars.len() - self.position)
+    }
+
+    fn rust(&mut self) {
+        while let Some(c) = self.peek(0) {
+            if self.starts_with("//") {
+                let len = self.len_while(0, |c| c != '\n');
+                self.emit(Some("hl-comment"), len);
+            } else if self.starts_with("/*") {
+                let len = self.len_until(2, "*/");
+                self.emit(Some("hl-comment"), len);
+            } else if self.starts_with("#[") || self.starts_with("#![") {
+                let len = self.len_until(1, "]");
+                self.emit(Some("hl-attribute"), len);
+            } else if let Some(len) = self.rust_string_len() {
+                self.emit(Some("hl-string"), len);
+            } else if c == '\'' {
+                self.rust_quote();
+            } else if c.is_ascii_digit() {
+                let len = self.number_len();
+                self.emit(Some("hl-number"), len);
+            } else if c.is_alphabetic() || c == '_' {
+                let len = self.len_while(0, |c| c.is_alphanumeric() || c == '_');
+                let word = self.chars[self.position..self.position + len]
+                    .iter()
+                    .collect::<String>();
+                if self.peek(len) == Some('!') && self.peek(len + 1) != Some('=') {
+                    self.emit(Some("hl-macro"), len + 1);
+                } else if RUST_KEYWORDS.contains(&word.as_str()) {
+                    self.emit(Some("hl-keyword"), len);
+                } else if word == "true" || word == "false" {
+                    self.emit(Some("hl-literal"), len);
+                } else {
+                    self.emit(None, len);
+                }
+            } else {
+                self.emit(None, 1);
+            }
+        }
+    }
+
+    /// Returns the length of a (byte or raw) string literal at the current position.
+    fn rust_string_len(&self) -> Option<usize> {
+        let (prefix, raw) = match (self.peek(0), self.peek(1)) {
+            (Some('b'), Some('r')) => (2, true),
+            (Some('r'), _) => (1, true),
+            (Some('b'), _) => (1, false),
+            _ => (0, false),
+        };
+        if raw {
+            let hashes = self.len_while(prefix, |c| c == '#') - prefix;
+            if self.peek(prefix + hashes) != Some('"') {
+                return None;
+            }
+            let end = format!("\"{}", "#".repeat(hashes));
+            return Some(self.len_until(prefix + hashes + 1, &end));
+        }
+        match self.peek(prefix) {
+            Some('"') => {
+                let mut len = prefix + 1;
+                while let Some(c) = self.peek(len) {
+                    len += 1;
+                    if c == '"' {
+                        break;
+                    } else if c == '\\' {
+                        len += 1;
+                    }
+                }
+                Some(len.min(self.chars.len() - self.position))
+            }
+            Some('\'') if prefix == 1 => self.rust_char_len(1),
+            _ => None,
+        }
+    }
+
+    /// Returns the length of a character literal that starts at `offset`.
+    fn rust_char_len(&self, offset: usize) -> Option<usize> {
+        if self.peek(offset + 1) == Some('\\') {
+            (offset + 2..offset + 12)
+                .find(|i| self.peek(*i) == Some('\''))
+                .map(|i| i + 1)
+        } else if self.peek(offset + 2) == Some('\'') && self.peek(offset + 1) != Some('\n') {
+            Some(offset + 3)
+        } else {
+            None
+        }
+    }
+
+    /// Highlights a character literal or a lifetime.
+    fn rust_quote(&mut self) {
+        if let Some(len) = self.rust_char_len(0) {
+            self.emit(Some("hl-string"), len);
+        } else {
+            let len = self.len_while(1, |c| c.is_alphanumeric() || c == '_');
+            let class = (len > 1).then_some("hl-lifetime");
+            self.emit(class, len);
+        }
+    }
+
+    /// Returns the length of a number, including its suffix, but not a following range.
+    fn number_len(&self) -> usize {
+        let mut len = 0;
+        while let Some(c) = self.peek(len) {
+            let is_fraction = c == '.' && self.peek(len + 1).map_or(false, |c| c.is_ascii_digit());
+            if c.is_ascii_alphanumeric() || c == '_' || is_fraction {
+                len += 1;
+            } else {
+                break;
+            }
+        }
+        len
+    }
+
+    fn toml(&mut self) {
+        while let Some(c) = self.peek(0) {
+            if c == '#' {
+                let len = self.len_while(0, |c| c != '\n');
+                self.emit(Some("hl-comment"), len);
+            } else if c == '[' && self.at_line_start() {
+                let len = self.len_while(0, |c| c != '\n' && c != '#');
+                let len = self.chars[self.position..self.position + len]
+                    .iter()
+                    .rposition(|c| *c == ']')
+                    .map_or(len, |end| end + 1);
+                self.emit(Some("hl-section"), len);
+            } else if c == '"' || c == '\'' {
+                let len = if self.starts_with("\"\"\"") || self.starts_with("'''") {
+                    self.len_until(3, &c.to_string().repeat(3))
+                } else {
+                    self.string_len(c, c == '"')
+                };
+                self.emit(Some(self.key_or(len, '=', "hl-string")), len);
+            } else if c.is_alphanumeric() || c == '_' || c == '-' || c == '+' {
+                let len = self.len_while(0, |c| {
+                    c.is_alphanumeric() || matches!(c, '_' | '-' | '+' | '.' | ':')
+                });
+                let word = self.chars[self.position..self.position + len]
+                    .iter()
+                    .collect::<String>();
+                if self.next_non_blank(len) == Some('=') {
+                    self.emit(Some("hl-key"), len);
+                } else if word == "true" || word == "false" {
+                    self.emit(Some("hl-literal"), len);
+                } else if word
+                    .trim_start_matches(['+', '-'])
+                    .starts_with(char::is_numeric)
+                    || matches!(word.trim_start_matches(['+', '-']), "inf" | "nan")
+                {
+                    self.emit(Some("hl-number"), len);
+                } else {
+                    self.emit(None, len);
+                }
+            } else {
+                self.emit(None, 1);
+            }
+        }
+    }
+
+    /// Returns the class of a string token of length `len`, which is a key if it
+    /// is followed by the `separator` of keys and values.
+    fn key_or(&self, len: usize, separator: char, class: &'static str) -> &'static str {
+        if self.next_non_blank(len) == Some(separator) {
+            "hl-key"
+        } else {
+            class
+        }
+    }
+
+    fn json(&mut self) {
+        while let Some(c) = self.peek(0) {
+            if c == '"' {
+                let len = self.string_len('"', true);
+                self.emit(Some(self.key_or(len, ':', "hl-string")), len);
+            } else if c.is_ascii_digit()
+                || (c == '-' && self.peek(1).map_or(false, |c| c.is_ascii_digit()))
+            {
+                let len = self.len_while(1, |c| {
+                    c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-')
+                });
+                self.emit(Some("hl-number"), len);
+            } else if c.is_ascii_alphabetic() {
+                let len = self.len_while(0, |c| c.is_ascii_alphabetic());
+                let word = self.chars[self.position..self.position + len]
+                    .iter()
+                    .collect::<String>();
+                let class =
+                    matches!(word.as_str(), "true" | "false" | "null").then_some("hl-literal");
+                self.emit(class, len);
+            } else {
+                self.emit(None, 1);
+            }
+        }
+    }
+
+    fn shell(&mut self) {
+        while let Some(c) = self.peek(0) {
+            let at_word_start = self.position == 0
+                || self.chars[self.position - 1].is_whitespace()
+                || matches!(self.chars[self.position - 1], ';' | '|' | '&' | '(');
+            if c == '#' && at_word_start {
+                let len = self.len_while(0, |c| c != '\n');
+                self.emit(Some("hl-comment"), len);
+            } else if c == '\'' {
+                let len = self.string_len('\'', false);
+                self.emit(Some("hl-string"), len);
+            } else if c == '"' {
+                let len = self.string_len('"', true);
+                self.emit(Some("hl-string"), len);
+            } else if c == '$' {
+                let len = match self.peek(1) {
+                    Some('{') => self.len_until(2, "}"),
+                    Some(c)
+                        if c.is_ascii_digit() || matches!(c, '?' | '@' | '*' | '#' | '$' | '!') =>
+                    {
+                        2
+                    }
+                    _ => self.len_while(1, |c| c.is_ascii_alphanumeric() || c == '_'),
+                };
+                let class = (len > 1).then_some("hl-variable");
+                self.emit(class, len);
+            } else if at_word_start && c.is_ascii_alphabetic() {
+                let len = self.len_while(0, |c| c.is_ascii_alphanumeric() || c == '_');
+                let word = self.chars[self.position..self.position + len]
+                    .iter()
+                    .collect::<String>();
+                let ends_word = self.peek(len).map_or(true, |c| {
+                    c.is_whitespace() || matches!(c, ';' | '|' | '&' | ')')
+                });
+                let class =
+                    (ends_word && SHELL_KEYWORDS.contains(&word.as_str())).then_some("hl-keyword");
+                self.emit(class, len);
+            } else {
+                self.emit(None, 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Highlights code, with quotes unescaped to keep the expected HTML readable.
+    fn highlighted(language: &str, code: &str) -> String {
+        highlight(language, code)
+            .replace("&quot;", "\"")
+            .replace("&#x27;", "'")
+    }
+
+    #[test]
+    fn rust() {
+        let code = "#[derive(Debug)]\nfn main<'a>() -> &'a str {\n    // Say hi\n    let c = '\\n';\n    println!(\"Hello, {}!\", 1..=2_u8);\n    r#\"raw \"string\"\"#\n}\n";
+        assert_eq!(
+            highlighted("rust", code),
+            "<span class=\"hl-attribute\">#[derive(Debug)]</span>\n\
+             <span class=\"hl-keyword\">fn</span> main&lt;<span class=\"hl-lifetime\">'a</span>&gt;() -&gt; &amp;<span class=\"hl-lifetime\">'a</span> str {\n    \
+             <span class=\"hl-comment\">// Say hi</span>\n    \
+             <span class=\"hl-keyword\">let</span> c = <span class=\"hl-string\">'\\n'</span>;\n    \
+             <span class=\"hl-macro\">println!</span>(<span class=\"hl-string\">\"Hello, {}!\"</span>, <span class=\"hl-number\">1</span>..=<span class=\"hl-number\">2_u8</span>);\n    \
+             <span class=\"hl-string\">r#\"raw \"string\"\"#</span>\n}\n"
+        );
+    }
+
+    #[test]
+    fn toml() {
+        let code = "[dependencies]\n# Comment\nserde = { version = \"1.0\", features = [\"derive\"], optional = true }\n\"quoted.key\" = 3\n";
+        assert_eq!(
+            highlighted("TOML", code),
+            "<span class=\"hl-section\">[dependencies]</span>\n\
+             <span class=\"hl-comment\"># Comment</span>\n\
+             <span class=\"hl-key\">serde</span> = { <span class=\"hl-key\">version</span> = <span class=\"hl-string\">\"1.0\"</span>, \
+             <span class=\"hl-key\">features</span> = [<span class=\"hl-string\">\"derive\"</span>], \
+             <span class=\"hl-key\">optional</span> = <span class=\"hl-literal\">true</span> }\n\
+             <span class=\"hl-key\">\"quoted.key\"</span> = <span class=\"hl-number\">3</span>\n"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let code =
+            "{\"name\": \"serde\", \"downloads\": -1.5e3, \"yanked\": false, \"license\": null}";
+        assert_eq!(
+            highlighted("json", code),
+            "{<span class=\"hl-key\">\"name\"</span>: <span class=\"hl-string\">\"serde\"</span>, \
+             <span class=\"hl-key\">\"downloads\"</span>: <span class=\"hl-number\">-1.5e3</span>, \
+             <span class=\"hl-key\">\"yanked\"</span>: <span class=\"hl-literal\">false</span>, \
+             <span class=\"hl-key\">\"license\"</span>: <span class=\"hl-literal\">null</span>}"
+        );
+    }
+
+    #[test]
+    fn shell() {
+        let code =
+            "$ cargo add serde # latest\nif [ -n \"$HOME\" ]; then echo '${no}' ${PATH}; fi\n";
+        assert_eq!(
+            highlighted("console", code),
+            "$ cargo add serde <span class=\"hl-comment\"># latest</span>\n\
+             <span class=\"hl-keyword\">if</span> [ -n <span class=\"hl-string\">\"$HOME\"</span> ]; \
+             <span class=\"hl-keyword\">then</span> echo <span class=\"hl-string\">'${no}'</span> \
+             <span class=\"hl-variable\">${PATH}</span>; <span class=\"hl-keyword\">fi</span>\n"
+        );
+    }
+
+    #[test]
+    fn unknown_languages_are_escaped() {
+        assert_eq!(highlight("python", "print(1 < 2)"), "print(1 &lt; 2)");
+    }
+
+    #[test]
+    fn unterminated_tokens() {
+        assert_eq!(
+            highlighted("rust", "/* open \"x"),
+            "<span class=\"hl-comment\">/* open \"x</span>"
+        );
+        assert_eq!(
+            highlighted("rust", "let s = \"open"),
+            "<span class=\"hl-keyword\">let</span> s = <span class=\"hl-string\">\"open</span>"
+        );
+        assert_eq!(
+            highlighted("json", "\"a\\"),
+            "<span class=\"hl-string\">\"a\\</span>"
+        );
+    }
+}
@@ -1,15 +1,103 @@
-//! Render Markdown files to HTML.
+//! Render Markdown, reStructuredText and AsciiDoc files to HTML.
+
+mod asciidoc;
+mod highlight;
+mod rst;
 
 use ammonia::{Builder, UrlRelative, UrlRelativeEvaluate};
-use comrak::nodes::{AstNode, NodeValue};
+use comrak::nodes::{AstNode, NodeCode, NodeValue};
+use comrak::Anchorizer;
 use htmlescape::encode_minimal;
 use std::borrow::Cow;
+use std::fmt::Write;
 use std::path::Path;
+use std::sync::Arc;
 use url::Url;
 
+/// Prefix of the `id` attributes in rendered HTML, so that they cannot clash with
+/// the ones of the surrounding page.
+const ID_PREFIX: &str = "user-content-";
+
+/// The version of the renderer, which is recorded for every rendered readme.
+///
+/// Increment it whenever a change to the rendering or sanitization should be applied to the
+/// readmes of already published crates, so that they are picked up for re-rendering.
+pub const RENDERER_VERSION: i32 = 1;
+
+/// A heading of a rendered document, as an entry of its table of contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// The level of the heading, from 1 to 6.
+    pub level: u8,
+    /// The text of the heading, without any markup.
+    pub text: String,
+    /// The `id` of the heading's anchor in the rendered HTML.
+    pub anchor: String,
+}
+
+/// A text file rendered to sanitized HTML.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedText {
+    /// The sanitized HTML.
+    pub html: String,
+    /// The headings of the document, in order of their appearance.
+    pub outline: Vec<Heading>,
+}
+
+/// Collects the headings of a document while assigning unique anchors to them.
+#[derive(Default)]
+struct Outline {
+    anchorizer: Anchorizer,
+    headings: Vec<Heading>,
+}
+
+impl Outline {
+    /// Records a heading and returns its anchor, without the `id` prefix.
+    fn add(&mut self, level: usize, text: String) -> String {
+        let anchor = self.anchorizer.anchorize(text.clone());
+        self.headings.push(Heading {
+            level: level.clamp(1, 6) as u8,
+            text,
+            anchor: format!("{ID_PREFIX}{anchor}"),
+        });
+        anchor
+    }
+
+    /// Appends a heading with an anchor, like the ones comrak adds to Markdown headings.
+    /// The prefix of the anchor's `id` is added by the sanitizer.
+    fn push_heading(&mut self, html: &mut String, level: usize, content: &str) {
+        let level = level.clamp(1, 6);
+        let anchor = self.add(level, plain_text(content));
+        let _ = writeln!(
+            html,
+            "<h{level}><a href=\"#{anchor}\" id=\"{anchor}\"></a>{content}</h{level}>"
+        );
+    }
+}
+
+/// Rewrites the URLs of images in rendered documents, so that readers load them through
+/// a proxy instead of directly from arbitrary third-party hosts.
+pub trait ImageProxy: Send + Sync {
+    /// Returns the URL under which the proxy serves the image at the given absolute URL.
+    ///
+    /// The returned URL must be absolute, so that it is not resolved as a relative link.
+    fn proxy_url(&self, url: &Url) -> String;
+}
+
+/// Options for rendering text files to HTML.
+#[derive(Clone, Default)]
+pub struct RenderOptions {
+    /// If set, all images are loaded through this proxy.
+    pub image_proxy: Option<Arc<dyn ImageProxy>>,
+    /// Whether code blocks in Rust, TOML, shell and JSON are highlighted with `<span>`
+    /// elements with `hl-*` classes, instead of leaving that to the client.
+    pub highlight_code: bool,
+}
+
 /// Context for markdown to HTML rendering.
 struct MarkdownRenderer<'a> {
     html_sanitizer: Builder<'a>,
+    highlight_code: bool,
 }
 
 impl<'a> MarkdownRenderer<'a> {
@@ -17,27 +105,35 @@ impl<'a> MarkdownRenderer<'a> {
     ///
     /// Per `text_to_html`, `base_url` is the base URL prepended to any
     /// relative links in the input document.  See that function for more detail.
-    fn new(base_url: Option<&'a str>, base_dir: &'a str) -> MarkdownRenderer<'a> {
-        let allowed_classes = hashmap(&[(
-            "code",
-            hashset(&[
-                "language-bash",
-                "language-clike",
-                "language-glsl",
-                "language-go",
-                "language-ini",
-                "language-javascript",
-                "language-json",
-                "language-markup",
-                "language-protobuf",
-                "language-ruby",
-                "language-rust",
-                "language-scss",
-                "language-sql",
-                "language-toml",
-                "language-yaml",
-            ]),
-        )]);
+    /// See `RenderOptions` for the other settings.
+    fn new(
+        base_url: Option<&'a str>,
+        base_dir: &'a str,
+        options: &RenderOptions,
+    ) -> MarkdownRenderer<'a> {
+        let allowed_classes = hashmap(&[
+            (
+                "code",
+                hashset(&[
+                    "language-bash",
+                    "language-clike",
+                    "language-glsl",
+                    "language-go",
+                    "language-ini",
+                    "language-javascript",
+                    "language-json",
+                    "language-markup",
+                    "language-protobuf",
+                    "language-ruby",
+                    "language-rust",
+                    "language-scss",
+                    "language-sql",
+                    "language-toml",
+                    "language-yaml",
+                ]),
+            ),
+            ("span", hashset(&highlight::CLASSES)),
+        ]);
         let sanitize_url = UrlRelative::Custom(Box::new(SanitizeUrl::new(base_url, base_dir)));
 
         let mut html_sanitizer = Builder::default();
@@ -49,15 +145,30 @@ impl<'a> MarkdownRenderer<'a> {
             .add_tag_attributes("input", &["checked", "disabled", "type"])
             .allowed_classes(allowed_classes)
             .url_relative(sanitize_url)
-            .id_prefix(Some("user-content-"));
-        MarkdownRenderer { html_sanitizer }
+            .id_prefix(Some(ID_PREFIX));
+        if let Some(image_proxy) = options.image_proxy.clone() {
+            // The attribute filter runs before relative URLs are resolved, so the image
+            // URLs are resolved here as well.
+            let sanitize_url = SanitizeUrl::new(base_url, base_dir);
+            html_sanitizer.attribute_filter(move |element, attribute, value| {
+                if element == "img" && attribute == "src" {
+                    proxied_image_url(&*image_proxy, &sanitize_url, value).map(Cow::Owned)
+                } else {
+                    Some(Cow::Borrowed(value))
+                }
+            });
+        }
+        MarkdownRenderer {
+            html_sanitizer,
+            highlight_code: options.highlight_code,
+        }
     }
 
     /// Renders the given markdown to HTML using the current settings.
-    fn to_html(&self, text: &str) -> String {
+    fn to_html(&self, text: &str) -> RenderedText {
         use comrak::{
-            format_html, parse_document, Arena, ComrakExtensionOptions, ComrakOptions,
-            ComrakRenderOptions,
+            format_html_with_plugins, parse_document, Arena, ComrakExtensionOptions, ComrakOptions,
+            ComrakPlugins, ComrakRenderOptions,
         };
 
         let options = ComrakOptions {
@@ -71,7 +182,7 @@ impl<'a> MarkdownRenderer<'a> {
                 table: true,
                 tagfilter: true,
                 tasklist: true,
-                header_ids: Some("user-content-".to_string()),
+                header_ids: Some(ID_PREFIX.to_string()),
                 ..ComrakExtensionOptions::default()
             },
             ..ComrakOptions::default()
@@ -94,10 +205,36 @@ impl<'a> MarkdownRenderer<'a> {
             }
         });
 
+        // comrak assigns the anchors of headings in the same order.
+        let mut outline = Outline::default();
+        for node in root.descendants() {
+            if let NodeValue::Heading(ref heading) = node.data.borrow().value {
+                let mut text = Vec::new();
+                collect_text(node, &mut text);
+                outline.add(
+                    heading.level as usize,
+                    String::from_utf8_lossy(&text).into(),
+                );
+            }
+        }
+
+        let mut plugins = ComrakPlugins::default();
+        if self.highlight_code {
+            plugins.render.codefence_syntax_highlighter = Some(&highlight::CodeHighlighter);
+        }
+
         let mut html = Vec::new();
-        format_html(root, &options, &mut html).unwrap();
+        format_html_with_plugins(root, &options, &mut html, &plugins).unwrap();
         let rendered = String::from_utf8(html).unwrap();
-        self.html_sanitizer.clean(&rendered).to_string()
+        RenderedText {
+            html: self.sanitize(&rendered),
+            outline: outline.headings,
+        }
+    }
+
+    /// Sanitizes HTML that was rendered from any of the supported markup languages.
+    fn sanitize(&self, html: &str) -> String {
+        self.html_sanitizer.clean(html).to_string()
     }
 }
 
@@ -112,6 +249,21 @@ where
     }
 }
 
+/// Collect the text of a node in the CommonMark AST, like comrak does for heading anchors.
+fn collect_text<'a>(node: &'a AstNode<'a>, output: &mut Vec<u8>) {
+    match node.data.borrow().value {
+        NodeValue::Text(ref literal) | NodeValue::Code(NodeCode { ref literal, .. }) => {
+            output.extend_from_slice(literal)
+        }
+        NodeValue::LineBreak | NodeValue::SoftBreak => output.push(b' '),
+        _ => {
+            for child in node.children() {
+                collect_text(child, output);
+            }
+        }
+    }
+}
+
 /// Add trailing slash and remove `.git` suffix of base URL.
 fn canon_base_url(mut base_url: String) -> String {
     if !base_url.ends_with('/') {
@@ -222,18 +374,78 @@ impl UrlRelativeEvaluate for SanitizeUrl {
     }
 }
 
+/// Resolves the `src` of an image like other relative URLs, and returns the URL of the
+/// resolved image on the proxy. Images that cannot be loaded over HTTP are dropped.
+fn proxied_image_url(
+    image_proxy: &dyn ImageProxy,
+    sanitize_url: &SanitizeUrl,
+    src: &str,
+) -> Option<String> {
+    let url = match Url::parse(src) {
+        Ok(url) => url,
+        Err(url::ParseError::RelativeUrlWithoutBase) => {
+            Url::parse(&sanitize_url.evaluate(src)?).ok()?
+        }
+        Err(_) => return None,
+    };
+    matches!(url.scheme(), "http" | "https").then(|| image_proxy.proxy_url(&url))
+}
+
 /// Renders Markdown text to sanitized HTML with a given `base_url`.
 /// See `text_to_html` for the interpretation of `base_url`.
-fn markdown_to_html(text: &str, base_url: Option<&str>, base_dir: &str) -> String {
-    let renderer = MarkdownRenderer::new(base_url, base_dir);
+fn markdown_to_html(
+    text: &str,
+    base_url: Option<&str>,
+    base_dir: &str,
+    options: &RenderOptions,
+) -> RenderedText {
+    let renderer = MarkdownRenderer::new(base_url, base_dir, options);
     renderer.to_html(text)
 }
 
+/// Renders reStructuredText to sanitized HTML with a given `base_url`.
+/// See `text_to_html` for the interpretation of `base_url`.
+fn rst_to_html(
+    text: &str,
+    base_url: Option<&str>,
+    base_dir: &str,
+    options: &RenderOptions,
+) -> RenderedText {
+    let renderer = MarkdownRenderer::new(base_url, base_dir, options);
+    let (html, outline) = rst::to_html(text, options.highlight_code);
+    RenderedText {
+        html: renderer.sanitize(&html),
+        outline,
+    }
+}
+
+/// Renders AsciiDoc to sanitized HTML with a given `base_url`.
+/// See `text_to_html` for the interpretation of `base_url`.
+fn asciidoc_to_html(
+    text: &str,
+    base_url: Option<&str>,
+    base_dir: &str,
+    options: &RenderOptions,
+) -> RenderedText {
+    let renderer = MarkdownRenderer::new(base_url, base_dir, options);
+    let (html, outline) = asciidoc::to_html(text, options.highlight_code);
+    RenderedText {
+        html: renderer.sanitize(&html),
+        outline,
+    }
+}
+
 /// Any file with a filename ending in one of these extensions will be rendered as Markdown.
 /// Note we also render a file as Markdown if _no_ extension is on the filename.
 static MARKDOWN_EXTENSIONS: [&str; 7] =
     ["md", "markdown", "mdown", "mdwn", "mkd", "mkdn", "mkdown"];
 
+/// Any file with a filename ending in one of these extensions will be rendered as reStructuredText.
+static RST_EXTENSIONS: [&str; 2] = ["rst", "rest"];
+
+/// Any file with a filename ending in one of these extensions will be rendered as AsciiDoc.
+static ASCIIDOC_EXTENSIONS: [&str; 3] = ["adoc", "asciidoc", "asc"];
+
 /// Renders a text file to sanitized HTML.  An appropriate rendering method is chosen depending
 /// on the extension of the supplied `filename`.
 ///
@@ -241,7 +453,7 @@ static MARKDOWN_EXTENSIONS: [&str; 7] =
 /// onclick, onmouseover, etc.).
 ///
 /// The `base_url` parameter will be used as the base for any relative links found in the
-/// Markdown, as long as its host part is github.com, gitlab.com, or bitbucket.org.  The
+/// document, as long as its host part is github.com, gitlab.com, or bitbucket.org.  The
 /// supplied URL will be used as a directory base whether or not the relative link is
 /// prefixed with '/'.  If `None` is passed, relative links will be omitted.
 ///
@@ -260,20 +472,223 @@ pub fn text_to_html(
     base_url: Option<&str>,
     pkg_path_in_vcs: Option<&str>,
 ) -> String {
+    let options = RenderOptions::default();
+    render_text(
+        text,
+        readme_path_in_pkg,
+        base_url,
+        pkg_path_in_vcs,
+        &options,
+    )
+    .html
+}
+
+/// Renders a text file like `text_to_html`, and also returns the outline of its headings,
+/// which can be used as a table of contents.
+///
+/// The `options` enable the image proxy and server-side syntax highlighting.
+pub fn render_text(
+    text: &str,
+    readme_path_in_pkg: &str,
+    base_url: Option<&str>,
+    pkg_path_in_vcs: Option<&str>,
+    options: &RenderOptions,
+) -> RenderedText {
     let path_in_vcs = Path::new(pkg_path_in_vcs.unwrap_or("")).join(readme_path_in_pkg);
     let base_dir = path_in_vcs.parent().and_then(|p| p.to_str()).unwrap_or("");
 
     if path_in_vcs.extension().is_none() {
-        return markdown_to_html(text, base_url, base_dir);
+        return markdown_to_html(text, base_url, base_dir, options);
     }
 
     if let Some(ext) = path_in_vcs.extension().and_then(|ext| ext.to_str()) {
-        if MARKDOWN_EXTENSIONS.contains(&ext.to_lowercase().as_str()) {
-            return markdown_to_html(text, base_url, base_dir);
+        let ext = ext.to_lowercase();
+        if MARKDOWN_EXTENSIONS.contains(&ext.as_str()) {
+            return markdown_to_html(text, base_url, base_dir, options);
+        }
+        if RST_EXTENSIONS.contains(&ext.as_str()) {
+            return rst_to_html(text, base_url, base_dir, options);
+        }
+        if ASCIIDOC_EXTENSIONS.contains(&ext.as_str()) {
+            return asciidoc_to_html(text, base_url, base_dir, options);
+        }
+    }
+
+    RenderedText {
+        html: encode_minimal(text).replace('\n', "<br>\n"),
+        outline: Vec::new(),
+    }
+}
+
+/// Returns the number of leading spaces of a line.
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Removes the indentation that all non-blank lines have in common.
+fn dedent(lines: &[String]) -> Vec<String> {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| indentation(line))
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or("").to_string())
+        .collect()
+}
+
+/// Splits a document into lines without trailing whitespace, expanding tabs.
+fn split_lines(text: &str, tab_width: usize) -> Vec<String> {
+    let tab = " ".repeat(tab_width);
+    text.lines()
+        .map(|line| line.replace('\t', &tab).trim_end().to_string())
+        .collect()
+}
+
+/// Appends a code block, annotated with its language for syntax highlighting.
+/// If `highlight_code` is set, the code is highlighted on the server already.
+fn push_code_block(
+    html: &mut String,
+    language: Option<&str>,
+    lines: &[String],
+    highlight_code: bool,
+) {
+    let start = lines
+        .iter()
+        .position(|line| !line.trim().is_empty())
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(start, |end| end + 1);
+    let code = lines[start..end]
+        .iter()
+        .map(|line| format!("{line}\n"))
+        .collect::<String>();
+    match language.map(str::to_lowercase) {
+        Some(language) if !language.is_empty() => {
+            let code = if highlight_code {
+                highlight::highlight(&language, &code)
+            } else {
+                encode_minimal(&code)
+            };
+            let language = encode_minimal(&language);
+            let _ = writeln!(
+                html,
+                "<pre><code class=\"language-{language}\">{code}</code></pre>"
+            );
+        }
+        _ => {
+            let _ = writeln!(html, "<pre><code>{}</code></pre>", encode_minimal(&code));
+        }
+    }
+}
+
+/// Appends a table of already rendered cells.
+fn push_table(html: &mut String, header: Option<&[String]>, rows: &[Vec<String>]) {
+    html.push_str("<table>\n");
+    if let Some(header) = header {
+        html.push_str("<thead>\n<tr>\n");
+        for cell in header {
+            let _ = writeln!(html, "<th>{cell}</th>");
+        }
+        html.push_str("</tr>\n</thead>\n");
+    }
+    if !rows.is_empty() {
+        html.push_str("<tbody>\n");
+        for row in rows {
+            html.push_str("<tr>\n");
+            for cell in row {
+                let _ = writeln!(html, "<td>{cell}</td>");
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</tbody>\n");
+    }
+    html.push_str("</table>\n");
+}
+
+/// Strips the tags of rendered inline HTML, leaving the text that is displayed.
+fn plain_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
         }
     }
+    htmlescape::decode_html(&text).unwrap_or(text)
+}
+
+/// Returns the length of the URL at the start of `chars`, if there is one.
+/// Trailing punctuation is not considered to be part of the URL.
+fn url_len(chars: &[char]) -> Option<usize> {
+    let start = chars.iter().take(8).collect::<String>();
+    if !start.starts_with("http://") && !start.starts_with("https://") {
+        return None;
+    }
+    let mut len = chars
+        .iter()
+        .position(|c| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '[' | '`'))
+        .unwrap_or(chars.len());
+    while len > 0
+        && matches!(
+            chars[len - 1],
+            '.' | ',' | ';' | ':' | '!' | '?' | ')' | '\''
+        )
+    {
+        len -= 1;
+    }
+    Some(len)
+}
+
+/// Checks whether `chars` contains `delimiter` at the given position.
+fn has_delimiter_at(chars: &[char], position: usize, delimiter: &str) -> bool {
+    let mut rest = chars.get(position..).unwrap_or_default().iter();
+    delimiter.chars().all(|c| rest.next() == Some(&c))
+}
+
+/// Whether inline markup may start or end next to the given character.
+fn is_markup_boundary(c: Option<&char>) -> bool {
+    c.map_or(true, |c| !c.is_alphanumeric())
+}
+
+/// The maximum length of inline markup, so that unclosed delimiters don't make
+/// rendering a paragraph take quadratic time.
+const MAX_INLINE_MARKUP_LEN: usize = 1000;
+
+/// Finds the end of inline markup whose content starts at `start`, which is
+/// the position of a `delimiter` that is preceded by non-whitespace and not
+/// followed by alphanumeric characters.
+fn find_closing(chars: &[char], start: usize, delimiter: &str) -> Option<usize> {
+    if chars.get(start).map_or(true, |c| c.is_whitespace()) {
+        return None;
+    }
+    let first = delimiter.chars().next()?;
+    let len = delimiter.chars().count();
+    (start + 1..inline_markup_limit(chars, start)).find(|&end| {
+        chars[end] == first
+            && has_delimiter_at(chars, end, delimiter)
+            && !chars[end - 1].is_whitespace()
+            && is_markup_boundary(chars.get(end + len))
+    })
+}
 
-    encode_minimal(text).replace('\n', "<br>\n")
+/// Finds the next `delimiter` after `start`, within the maximum length of inline markup.
+fn find_delimiter(chars: &[char], start: usize, delimiter: &str) -> Option<usize> {
+    let first = delimiter.chars().next()?;
+    (start..inline_markup_limit(chars, start))
+        .find(|&end| chars[end] == first && has_delimiter_at(chars, end, delimiter))
+}
+
+/// The position up to which inline markup starting at `start` may extend.
+fn inline_markup_limit(chars: &[char], start: usize) -> usize {
+    chars.len().min(start.saturating_add(MAX_INLINE_MARKUP_LEN))
 }
 
 /// Helper function to build a new `HashSet` from the items slice.
@@ -300,14 +715,14 @@ mod tests {
     #[test]
     fn empty_text() {
         let text = "";
-        let result = markdown_to_html(text, None, "");
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
         assert_eq!(result, "");
     }
 
     #[test]
     fn text_with_script_tag() {
         let text = "foo_readme\n\n<script>alert('Hello World')</script>";
-        let result = markdown_to_html(text, None, "");
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
         assert_eq!(
             result,
             "<p>foo_readme</p>\n&lt;script&gt;alert(\'Hello World\')&lt;/script&gt;\n"
@@ -317,7 +732,7 @@ mod tests {
     #[test]
     fn text_with_iframe_tag() {
         let text = "foo_readme\n\n<iframe>alert('Hello World')</iframe>";
-        let result = markdown_to_html(text, None, "");
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
         assert_eq!(
             result,
             "<p>foo_readme</p>\n&lt;iframe&gt;alert(\'Hello World\')&lt;/iframe&gt;\n"
@@ -327,14 +742,14 @@ mod tests {
     #[test]
     fn text_with_unknown_tag() {
         let text = "foo_readme\n\n<unknown>alert('Hello World')</unknown>";
-        let result = markdown_to_html(text, None, "");
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
         assert_eq!(result, "<p>foo_readme</p>\n<p>alert(\'Hello World\')</p>\n");
     }
 
     #[test]
     fn text_with_inline_javascript() {
         let text = r#"foo_readme\n\n<a href="https://crates.io/crates/cargo-registry" onclick="window.alert('Got you')">Crate page</a>"#;
-        let result = markdown_to_html(text, None, "");
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
         assert_eq!(
             result,
             "<p>foo_readme\\n\\n<a href=\"https://crates.io/crates/cargo-registry\" rel=\"nofollow noopener noreferrer\">Crate page</a></p>\n"
@@ -346,7 +761,7 @@ mod tests {
     #[test]
     fn text_with_fancy_single_quotes() {
         let text = "wb’";
-        let result = markdown_to_html(text, None, "");
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
         assert_eq!(result, "<p>wb’</p>\n");
     }
 
@@ -355,7 +770,7 @@ mod tests {
         let code_block = r#"```rust \
                             println!("Hello World"); \
                            ```"#;
-        let result = markdown_to_html(code_block, None, "");
+        let result = markdown_to_html(code_block, None, "", &RenderOptions::default()).html;
         assert!(result.contains("<code class=\"language-rust\">"));
     }
 
@@ -364,14 +779,63 @@ mod tests {
         let code_block = r#"```rust  ,  no_run \
                             println!("Hello World"); \
                            ```"#;
-        let result = markdown_to_html(code_block, None, "");
+        let result = markdown_to_html(code_block, None, "", &RenderOptions::default()).html;
         assert!(result.contains("<code class=\"language-rust\">"));
     }
 
+    #[test]
+    fn code_blocks_are_highlighted_on_the_server() {
+        let options = RenderOptions {
+            highlight_code: true,
+            ..RenderOptions::default()
+        };
+
+        let text = "```rust,no_run\nlet x = \"<b>\"; // hi\n```\n\n```python\nx = 1 < 2\n```\n\n```\nfn main() {}\n```\n";
+        let result = markdown_to_html(text, None, "", &options).html;
+        assert_eq!(
+            result,
+            "<pre><code class=\"language-rust\"><span class=\"hl-keyword\">let</span> x = <span class=\"hl-string\">\"&lt;b&gt;\"</span>; <span class=\"hl-comment\">// hi</span>\n</code></pre>\n\
+             <pre><code class=\"\">x = 1 &lt; 2\n</code></pre>\n\
+             <pre><code>fn main() {}\n</code></pre>\n"
+        );
+
+        let text =
+            "Example\n=======\n\n.. code-block:: toml\n\n    [dependencies]\n    serde = \"1\"\n";
+        let result = render_text(text, "README.rst", None, None, &options).html;
+        assert!(result.contains(
+            "<pre><code class=\"language-toml\"><span class=\"hl-section\">[dependencies]</span>\n<span class=\"hl-key\">serde</span> = <span class=\"hl-string\">\"1\"</span>\n</code></pre>"
+        ));
+
+        let text = "[source,json]\n----\n{\"yanked\": false}\n----\n";
+        let result = render_text(text, "README.adoc", None, None, &options).html;
+        assert_eq!(
+            result,
+            "<pre><code class=\"language-json\">{<span class=\"hl-key\">\"yanked\"</span>: <span class=\"hl-literal\">false</span>}\n</code></pre>\n"
+        );
+
+        // Without the option, code blocks are only escaped.
+        let text = "```bash\necho $HOME\n```\n";
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
+        assert_eq!(
+            result,
+            "<pre><code class=\"language-bash\">echo $HOME\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn text_with_forbidden_span_class() {
+        let text = "<span class=\"hl-keyword\">fn</span> <span class=\"evil\">main</span>";
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
+        assert_eq!(
+            result,
+            "<p><span class=\"hl-keyword\">fn</span> <span class=\"\">main</span></p>\n"
+        );
+    }
+
     #[test]
     fn text_with_forbidden_class_attribute() {
         let text = "<p class='bad-class'>Hello World!</p>";
-        let result = markdown_to_html(text, None, "");
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
         assert_eq!(result, "<p>Hello World!</p>\n");
     }
 
@@ -392,7 +856,8 @@ mod tests {
                     if extra_slash { "/" } else { "" },
                 );
 
-                let result = markdown_to_html(absolute, Some(&url), "");
+                let result =
+                    markdown_to_html(absolute, Some(&url), "", &RenderOptions::default()).html;
                 assert_eq!(
                     result,
                     format!(
@@ -400,7 +865,8 @@ mod tests {
                     )
                 );
 
-                let result = markdown_to_html(relative, Some(&url), "");
+                let result =
+                    markdown_to_html(relative, Some(&url), "", &RenderOptions::default()).html;
                 assert_eq!(
                     result,
                     format!(
@@ -408,7 +874,8 @@ mod tests {
                     )
                 );
 
-                let result = markdown_to_html(image, Some(&url), "");
+                let result =
+                    markdown_to_html(image, Some(&url), "", &RenderOptions::default()).html;
                 assert_eq!(
                     result,
                     format!(
@@ -416,7 +883,8 @@ mod tests {
                     )
                 );
 
-                let result = markdown_to_html(html_image, Some(&url), "");
+                let result =
+                    markdown_to_html(html_image, Some(&url), "", &RenderOptions::default()).html;
                 assert_eq!(
                     result,
                     format!(
@@ -424,7 +892,7 @@ mod tests {
                     )
                 );
 
-                let result = markdown_to_html(svg, Some(&url), "");
+                let result = markdown_to_html(svg, Some(&url), "", &RenderOptions::default()).html;
                 assert_eq!(
                     result,
                     format!(
@@ -432,7 +900,8 @@ mod tests {
                     )
                 );
 
-                let result = markdown_to_html(svg, Some(&url), "subdir");
+                let result =
+                    markdown_to_html(svg, Some(&url), "subdir", &RenderOptions::default()).html;
                 assert_eq!(
                     result,
                     format!(
@@ -440,7 +909,13 @@ mod tests {
                     )
                 );
 
-                let result = markdown_to_html(svg, Some(&url), "subdir1/subdir2");
+                let result = markdown_to_html(
+                    svg,
+                    Some(&url),
+                    "subdir1/subdir2",
+                    &RenderOptions::default(),
+                )
+                .html;
                 assert_eq!(
                     result,
                     format!(
@@ -450,7 +925,13 @@ mod tests {
             }
         }
 
-        let result = markdown_to_html(absolute, Some("https://google.com/"), "");
+        let result = markdown_to_html(
+            absolute,
+            Some("https://google.com/"),
+            "",
+            &RenderOptions::default(),
+        )
+        .html;
         assert_eq!(
             result,
             "<p><a rel=\"nofollow noopener noreferrer\">hi</a></p>\n"
@@ -462,7 +943,7 @@ mod tests {
         let text =
             "[![Crates.io](https://img.shields.io/crates/v/clap.svg)](https://crates.io/crates/clap)";
         let repository = "https://github.com/kbknapp/clap-rs/";
-        let result = markdown_to_html(text, Some(repository), "");
+        let result = markdown_to_html(text, Some(repository), "", &RenderOptions::default()).html;
 
         assert_eq!(
             result,
@@ -470,17 +951,76 @@ mod tests {
         );
     }
 
+    struct TestImageProxy;
+
+    impl ImageProxy for TestImageProxy {
+        fn proxy_url(&self, url: &Url) -> String {
+            let mut proxy_url = Url::parse("https://crates.io/image-proxy").unwrap();
+            proxy_url.query_pairs_mut().append_pair("url", url.as_str());
+            proxy_url.into()
+        }
+    }
+
+    #[test]
+    fn images_are_loaded_through_the_image_proxy() {
+        let options = RenderOptions {
+            image_proxy: Some(Arc::new(TestImageProxy)),
+            ..RenderOptions::default()
+        };
+        let repository = "https://github.com/rust-lang/test";
+
+        let text =
+            "[![Crates.io](https://img.shields.io/crates/v/clap.svg)](https://crates.io/crates/clap)";
+        let result = markdown_to_html(text, Some(repository), "", &options).html;
+        assert_eq!(
+            result,
+            "<p><a href=\"https://crates.io/crates/clap\" rel=\"nofollow noopener noreferrer\"><img src=\"https://crates.io/image-proxy?url=https%3A%2F%2Fimg.shields.io%2Fcrates%2Fv%2Fclap.svg\" alt=\"Crates.io\"></a></p>\n"
+        );
+
+        let text = "![alt](img.png) <img src=\"/docs/logo.svg\">";
+        let result = markdown_to_html(text, Some(repository), "subdir", &options).html;
+        assert_eq!(
+            result,
+            "<p><img src=\"https://crates.io/image-proxy?url=https%3A%2F%2Fgithub.com%2Frust-lang%2Ftest%2Fraw%2FHEAD%2Fsubdir%2Fimg.png\" alt=\"alt\"> <img src=\"https://crates.io/image-proxy?url=https%3A%2F%2Fgithub.com%2Frust-lang%2Ftest%2Fraw%2FHEAD%2Fsubdir%2Fdocs%2Flogo.svg%3Fsanitize%3Dtrue\"></p>\n"
+        );
+
+        let text = "![alt](img.png) ![alt](#anchor) [relative](docs)";
+        let result = markdown_to_html(text, None, "", &options).html;
+        assert_eq!(
+            result,
+            "<p><img alt=\"alt\"> <img alt=\"alt\"> <a rel=\"nofollow noopener noreferrer\">relative</a></p>\n"
+        );
+
+        let text = "Logo\n====\n\n.. image:: https://example.com/logo.png\n";
+        let result = render_text(text, "README.rst", None, None, &options).html;
+        assert!(result.contains(
+            "<img src=\"https://crates.io/image-proxy?url=https%3A%2F%2Fexample.com%2Flogo.png\""
+        ));
+    }
+
     #[test]
     fn rustdoc_links() {
         let repository = "https://github.com/foo/bar/";
 
         assert_eq!(
-            markdown_to_html("[stylish](::stylish)", Some(repository), ""),
+            markdown_to_html(
+                "[stylish](::stylish)",
+                Some(repository),
+                "",
+                &RenderOptions::default()
+            )
+            .html,
             "<p><a rel=\"nofollow noopener noreferrer\">stylish</a></p>\n"
         );
 
         assert_eq!(
-            markdown_to_html("[Display](stylish::Display)", Some(repository), ""),
+            markdown_to_html(
+                "[Display](stylish::Display)",
+                Some(repository),
+                "",
+                &RenderOptions::default()
+            )
+            .html,
             "<p><a rel=\"nofollow noopener noreferrer\">Display</a></p>\n"
         );
     }
@@ -525,7 +1065,7 @@ mod tests {
 
     #[test]
     fn text_to_html_renders_other_things() {
-        for f in &["readme.exe", "readem.org", "blah.adoc"] {
+        for f in &["readme.exe", "readem.org", "blah.txt"] {
             assert_eq!(
                 text_to_html("<script>lobster</script>\n\nis my friend\n", f, None, None),
                 "&lt;script&gt;lobster&lt;/script&gt;<br>\n<br>\nis my friend<br>\n"
@@ -536,7 +1076,7 @@ mod tests {
     #[test]
     fn header_has_tags() {
         let text = "# My crate\n\nHello, world!\n";
-        let result = markdown_to_html(text, None, "");
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
         assert_eq!(
             result,
             "<h1><a href=\"#my-crate\" id=\"user-content-my-crate\" rel=\"nofollow noopener noreferrer\"></a>My crate</h1>\n<p>Hello, world!</p>\n"
@@ -547,7 +1087,7 @@ mod tests {
     fn manual_anchor_is_sanitized() {
         let text =
             "<h1><a href=\"#my-crate\" id=\"my-crate\"></a>My crate</h1>\n<p>Hello, world!</p>\n";
-        let result = markdown_to_html(text, None, "");
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
         assert_eq!(
             result,
             "<h1><a href=\"#my-crate\" id=\"user-content-my-crate\" rel=\"nofollow noopener noreferrer\"></a>My crate</h1>\n<p>Hello, world!</p>\n"
@@ -557,7 +1097,7 @@ mod tests {
     #[test]
     fn tables_with_rowspan_and_colspan() {
         let text = "<table><tr><th rowspan=\"1\" colspan=\"2\">Target</th></tr></table>\n";
-        let result = markdown_to_html(text, None, "");
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
         assert_eq!(
             result,
             "<table><tbody><tr><th rowspan=\"1\" colspan=\"2\">Target</th></tr></tbody></table>\n"
@@ -567,7 +1107,7 @@ mod tests {
     #[test]
     fn text_alignment() {
         let text = "<h1 align=\"center\">foo-bar</h1>\n<h5 align=\"center\">Hello World!</h5>\n";
-        let result = markdown_to_html(text, None, "");
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
         assert_eq!(
             result,
             "<h1 align=\"center\">foo-bar</h1>\n<h5 align=\"center\">Hello World!</h5>\n"
@@ -578,10 +1118,231 @@ mod tests {
     fn image_alignment() {
         let text =
             "<p align=\"center\"><img src=\"https://img.shields.io/crates/v/clap.svg\" alt=\"\"></p>\n";
-        let result = markdown_to_html(text, None, "");
+        let result = markdown_to_html(text, None, "", &RenderOptions::default()).html;
         assert_eq!(
             result,
             "<p align=\"center\"><img src=\"https://img.shields.io/crates/v/clap.svg\" alt=\"\"></p>\n"
         );
     }
+
+    #[test]
+    fn outline_matches_heading_anchors() {
+        fn heading(level: u8, text: &str, anchor: &str) -> Heading {
+            let (text, anchor) = (text.to_string(), anchor.to_string());
+            Heading {
+                level,
+                text,
+                anchor,
+            }
+        }
+
+        let text = "# My `crate`\n\n## Usage\n\n<h2>Ignored</h2>\n\n### Usage\n";
+        let rendered = render_text(text, "README.md", None, None, &RenderOptions::default());
+        assert!(rendered
+            .html
+            .contains("<h3><a href=\"#usage-1\" id=\"user-content-usage-1\""));
+        assert_eq!(
+            rendered.outline,
+            vec![
+                heading(1, "My crate", "user-content-my-crate"),
+                heading(2, "Usage", "user-content-usage"),
+                heading(3, "Usage", "user-content-usage-1"),
+            ]
+        );
+
+        let text = "Title\n=====\n\nA *section*\n-----------\n";
+        let rendered = render_text(text, "README.rst", None, None, &RenderOptions::default());
+        assert_eq!(
+            rendered.outline,
+            vec![
+                heading(1, "Title", "user-content-title"),
+                heading(2, "A section", "user-content-a-section"),
+            ]
+        );
+
+        let text = "= Title\n\n== A _section_\n";
+        let rendered = render_text(text, "README.adoc", None, None, &RenderOptions::default());
+        assert_eq!(
+            rendered.outline,
+            vec![
+                heading(1, "Title", "user-content-title"),
+                heading(2, "A section", "user-content-a-section"),
+            ]
+        );
+
+        assert_eq!(
+            render_text(
+                "# Title",
+                "README.txt",
+                None,
+                None,
+                &RenderOptions::default()
+            )
+            .outline,
+            vec![]
+        );
+    }
+
+    #[test]
+    fn rst_headings_and_paragraphs() {
+        let text = "=====\nTitle\n=====\n\nSection\n-------\n\nSome *emphasis*, **strong** and ``code``.\n\nSection\n-------\n";
+        let result = text_to_html(text, "README.rst", None, None);
+        assert_eq!(
+            result,
+            "<h1><a href=\"#title\" id=\"user-content-title\" rel=\"nofollow noopener noreferrer\"></a>Title</h1>\n\
+             <h2><a href=\"#section\" id=\"user-content-section\" rel=\"nofollow noopener noreferrer\"></a>Section</h2>\n\
+             <p>Some <em>emphasis</em>, <strong>strong</strong> and <code>code</code>.</p>\n\
+             <h2><a href=\"#section-1\" id=\"user-content-section-1\" rel=\"nofollow noopener noreferrer\"></a>Section</h2>\n"
+        );
+    }
+
+    #[test]
+    fn rst_lists() {
+        let text = "* one\n* two\n\n  - nested\n\n1. first\n2. second\n";
+        let result = text_to_html(text, "README.rst", None, None);
+        assert_eq!(
+            result,
+            "<ul>\n<li>one</li>\n<li><p>two</p>\n<ul>\n<li>nested</li>\n</ul></li>\n</ul>\n<ol>\n<li>first</li>\n<li>second</li>\n</ol>\n"
+        );
+    }
+
+    #[test]
+    fn rst_code_blocks() {
+        let text = "Example::\n\n    let x = 1 < 2;\n\n.. code-block:: rust\n\n    fn main() {}\n";
+        let result = text_to_html(text, "README.rst", None, None);
+        assert_eq!(
+            result,
+            "<p>Example:</p>\n<pre><code>let x = 1 &lt; 2;\n</code></pre>\n<pre><code class=\"language-rust\">fn main() {}\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn rst_links_and_images() {
+        let text = "See `the docs <docs/usage.md>`_, the `website`_ and https://rust-lang.org.\n\n.. _website: https://crates.io/\n\n.. image:: logo.png\n   :alt: Logo\n";
+        let result = text_to_html(
+            text,
+            "README.rst",
+            Some("https://github.com/rust-lang/test"),
+            None,
+        );
+        assert_eq!(
+            result,
+            "<p>See <a href=\"https://github.com/rust-lang/test/blob/HEAD/docs/usage.md\" rel=\"nofollow noopener noreferrer\">the docs</a>, \
+             the <a href=\"https://crates.io/\" rel=\"nofollow noopener noreferrer\">website</a> \
+             and <a href=\"https://rust-lang.org\" rel=\"nofollow noopener noreferrer\">https://rust-lang.org</a>.</p>\n\
+             <p><img src=\"https://github.com/rust-lang/test/raw/HEAD/logo.png\" alt=\"Logo\"></p>\n"
+        );
+    }
+
+    #[test]
+    fn rst_tables() {
+        let expected = "<table>\n<thead>\n<tr>\n<th>Name</th>\n<th>Value</th>\n</tr>\n</thead>\n<tbody>\n<tr>\n<td>a</td>\n<td><em>1</em></td>\n</tr>\n</tbody>\n</table>\n";
+
+        let text = "+------+-------+\n| Name | Value |\n+======+=======+\n| a    | *1*   |\n+------+-------+\n";
+        assert_eq!(text_to_html(text, "README.rst", None, None), expected);
+
+        let text = "====  =====\nName  Value\n====  =====\na     *1*\n====  =====\n";
+        assert_eq!(text_to_html(text, "README.rst", None, None), expected);
+    }
+
+    #[test]
+    fn rst_is_sanitized() {
+        let text = ".. raw:: html\n\n    <script>alert(1)</script>\n\n<script>lobster</script> `click <javascript:alert(1)>`_\n";
+        let result = text_to_html(text, "README.rst", None, None);
+        assert_eq!(result, "<p>&lt;script&gt;lobster&lt;/script&gt; <a rel=\"nofollow noopener noreferrer\">click</a></p>\n");
+    }
+
+    #[test]
+    fn asciidoc_headings_and_paragraphs() {
+        let text = "= Title\n:toc:\n\n== Usage\n\nSome _emphasis_, *strong* and `code`. +\nNext line.\n\n// A comment\nNOTE: Be careful.\n";
+        let result = text_to_html(text, "README.adoc", None, None);
+        assert_eq!(
+            result,
+            "<h1><a href=\"#title\" id=\"user-content-title\" rel=\"nofollow noopener noreferrer\"></a>Title</h1>\n\
+             <h2><a href=\"#usage\" id=\"user-content-usage\" rel=\"nofollow noopener noreferrer\"></a>Usage</h2>\n\
+             <p>Some <em>emphasis</em>, <strong>strong</strong> and <code>code</code>.<br>\nNext line.</p>\n\
+             <blockquote>\n<p><strong>Note</strong></p>\n<p>Be careful.</p>\n</blockquote>\n"
+        );
+    }
+
+    #[test]
+    fn asciidoc_indented_literals() {
+        let text = "Intro.\n\n  $ cargo install foo\n";
+        let result = text_to_html(text, "README.adoc", None, None);
+        assert_eq!(
+            result,
+            "<p>Intro.</p>\n<pre><code>$ cargo install foo\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn asciidoc_lists() {
+        let text = "* one\n** nested\n* two\n\n. first\n. second\n";
+        let result = text_to_html(text, "README.adoc", None, None);
+        assert_eq!(
+            result,
+            "<ul>\n<li>one\n<ul>\n<li>nested</li>\n</ul>\n</li>\n<li>two</li>\n</ul>\n<ol>\n<li>first</li>\n<li>second</li>\n</ol>\n"
+        );
+    }
+
+    #[test]
+    fn asciidoc_code_blocks() {
+        let text = "[source,rust]\n----\nfn main() {}\n----\n\n....\n1 < 2\n....\n";
+        let result = text_to_html(text, "README.adoc", None, None);
+        assert_eq!(
+            result,
+            "<pre><code class=\"language-rust\">fn main() {}\n</code></pre>\n<pre><code>1 &lt; 2\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn asciidoc_links_and_images() {
+        let text = "See link:docs/usage.adoc[the docs], https://crates.io[crates.io] and <<usage,usage>>.\n\nimage::logo.png[Logo]\n";
+        let result = text_to_html(
+            text,
+            "README.adoc",
+            Some("https://github.com/rust-lang/test"),
+            None,
+        );
+        assert_eq!(
+            result,
+            "<p>See <a href=\"https://github.com/rust-lang/test/blob/HEAD/docs/usage.adoc\" rel=\"nofollow noopener noreferrer\">the docs</a>, \
+             <a href=\"https://crates.io\" rel=\"nofollow noopener noreferrer\">crates.io</a> \
+             and <a href=\"#usage\" rel=\"nofollow noopener noreferrer\">usage</a>.</p>\n\
+             <p><img src=\"https://github.com/rust-lang/test/raw/HEAD/logo.png\" alt=\"Logo\"></p>\n"
+        );
+    }
+
+    #[test]
+    fn asciidoc_tables() {
+        let text = "|===\n| Name | Value\n\n| a | *1*\n|===\n";
+        let result = text_to_html(text, "README.adoc", None, None);
+        assert_eq!(
+            result,
+            "<table>\n<thead>\n<tr>\n<th>Name</th>\n<th>Value</th>\n</tr>\n</thead>\n<tbody>\n<tr>\n<td>a</td>\n<td><strong>1</strong></td>\n</tr>\n</tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn unclosed_inline_markup() {
+        let text = "link:http://a[".repeat(5_000);
+        let result = text_to_html(&text, "README.adoc", None, None);
+        assert!(result.starts_with("<p>link:"));
+
+        let text = "`a ".repeat(5_000);
+        let result = text_to_html(&text, "README.adoc", None, None);
+        assert!(result.starts_with("<p>`a `a"));
+        let result = text_to_html(&text, "README.rst", None, None);
+        assert!(result.starts_with("<p>`a `a"));
+    }
+
+    #[test]
+    fn asciidoc_is_sanitized() {
+        let text = "++++\n<script>alert(1)</script><b onclick=\"alert(1)\">bold</b>\n++++\n\nlink:javascript:alert(1)[click]\n";
+        let result = text_to_html(text, "README.adoc", None, None);
+        assert_eq!(
+            result,
+            "<b>bold</b>\n<p><a rel=\"nofollow noopener noreferrer\">click</a></p>\n"
+        );
+    }
 }
@@ -0,0 +1,623 @@
+//! Render the subset of reStructuredText that is commonly used in readmes:
+//! section titles, paragraphs, bullet, enumerated and definition lists,
+//! literal and code blocks, images, links and tables.
+//!
+//! The generated HTML still has to be sanitized, see `rst_to_html`.
+
+use crate::{
+    dedent, find_closing, has_delimiter_at, indentation, is_markup_boundary, push_code_block,
+    push_table, split_lines, url_len, Heading, Outline,
+};
+use htmlescape::{encode_attribute, encode_minimal};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Renders reStructuredText to unsanitized HTML and returns its outline.
+pub(crate) fn to_html(text: &str, highlight_code: bool) -> (String, Vec<Heading>) {
+    let lines = split_lines(text, 8);
+    let mut renderer = Renderer {
+        outline: Outline::default(),
+        section_styles: Vec::new(),
+        targets: collect_targets(&lines),
+        html: String::new(),
+        highlight_code,
+    };
+    renderer.blocks(&lines, false);
+    (renderer.html, renderer.outline.headings)
+}
+
+struct Renderer {
+    outline: Outline,
+    /// The adornment styles of section titles in the order of their first
+    /// use, which determines the level of the sections.
+    section_styles: Vec<(char, bool)>,
+    /// The URLs of hyperlink targets, by normalized reference name.
+    targets: HashMap<String, String>,
+    html: String,
+    /// Whether code blocks are highlighted on the server.
+    highlight_code: bool,
+}
+
+impl Renderer {
+    /// Renders a sequence of body elements. In `tight` lists, paragraphs are
+    /// rendered without `<p>` tags.
+    fn blocks(&mut self, lines: &[String], tight: bool) {
+        let mut i = 0;
+        while i < lines.len() {
+            let line = &lines[i];
+            if line.is_empty() {
+                i += 1;
+            } else if indentation(line) > 0 {
+                let end = indented_block_end(lines, i);
+                self.html.push_str("<blockquote>\n");
+                self.blocks(&dedent(&lines[i..end]), false);
+                self.html.push_str("</blockquote>\n");
+                i = end;
+            } else if let Some(next) = self.section_title(lines, i) {
+                i = next;
+            } else if adornment_char(line).is_some() && line.len() >= 4 {
+                self.html.push_str("<hr>\n");
+                i += 1;
+            } else if is_grid_table_border(line) {
+                i = self.grid_table(lines, i);
+            } else if simple_table_columns(line).len() > 1 {
+                i = self.simple_table(lines, i);
+            } else if line == ".." || line.starts_with(".. ") {
+                i = self.directive(lines, i);
+            } else if list_marker(line).is_some() {
+                i = self.list(lines, i);
+            } else if !line.ends_with("::")
+                && lines
+                    .get(i + 1)
+                    .map_or(false, |next| !next.is_empty() && indentation(next) > 0)
+            {
+                i = self.definition_list(lines, i);
+            } else {
+                i = self.paragraph(lines, i, tight);
+            }
+        }
+    }
+
+    /// Renders the section title at line `i`, if there is one, and returns
+    /// the line after it.
+    fn section_title(&mut self, lines: &[String], i: usize) -> Option<usize> {
+        let line = &lines[i];
+        let (title, style, next) = if let Some(c) = adornment_char(line) {
+            let title = lines.get(i + 1)?;
+            if title.trim().is_empty() || adornment_char(lines.get(i + 2)?) != Some(c) {
+                return None;
+            }
+            (title.trim(), (c, true), i + 3)
+        } else {
+            let underline = lines.get(i + 1)?;
+            let c = adornment_char(underline)?;
+            if underline.chars().count() < line.chars().count() {
+                return None;
+            }
+            (line.as_str(), (c, false), i + 2)
+        };
+
+        let level = match self.section_styles.iter().position(|s| *s == style) {
+            Some(position) => position + 1,
+            None => {
+                self.section_styles.push(style);
+                self.section_styles.len()
+            }
+        };
+        let content = self.inline(title);
+        self.outline.push_heading(&mut self.html, level, &content);
+        Some(next)
+    }
+
+    fn paragraph(&mut self, lines: &[String], i: usize, tight: bool) -> usize {
+        let end = (i..lines.len())
+            .find(|&j| lines[j].is_empty())
+            .unwrap_or(lines.len());
+        let mut text = lines[i..end]
+            .iter()
+            .map(|line| line.trim())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // A paragraph ending with `::` introduces a literal block. The marker
+        // is removed if it is separated by whitespace, and shortened to a
+        // single colon otherwise.
+        let literal = text.ends_with("::");
+        if literal {
+            let stripped = &text[..text.len() - 2];
+            if stripped.is_empty() || stripped.ends_with(char::is_whitespace) {
+                text = stripped.trim_end().to_string();
+            } else {
+                text.pop();
+            }
+        }
+
+        if !text.is_empty() {
+            let content = self.inline(&text);
+            if tight {
+                let _ = writeln!(self.html, "{content}");
+            } else {
+                let _ = writeln!(self.html, "<p>{content}</p>");
+            }
+        }
+
+        if literal {
+            if let Some(start) = (end..lines.len()).find(|&j| !lines[j].is_empty()) {
+                if indentation(&lines[start]) > 0 {
+                    let block_end = indented_block_end(lines, start);
+                    push_code_block(
+                        &mut self.html,
+                        None,
+                        &dedent(&lines[start..block_end]),
+                        self.highlight_code,
+                    );
+                    return block_end;
+                }
+            }
+        }
+        end
+    }
+
+    fn list(&mut self, lines: &[String], i: usize) -> usize {
+        let first = list_marker(&lines[i]).unwrap();
+        let tag = if first.ordered { "ol" } else { "ul" };
+        let _ = writeln!(self.html, "<{tag}>");
+
+        let mut j = i;
+        while let Some(marker) = lines.get(j).and_then(|line| list_marker(line)) {
+            if marker.ordered != first.ordered || marker.bullet != first.bullet {
+                break;
+            }
+
+            let end = indented_block_end(lines, j + 1);
+            let mut body_end = end;
+            while body_end > j + 1 && lines[body_end - 1].is_empty() {
+                body_end -= 1;
+            }
+            let mut body = vec![lines[j]
+                .get(marker.width..)
+                .unwrap_or("")
+                .trim_start()
+                .to_string()];
+            body.extend(dedent(&lines[j + 1..body_end]));
+            let tight = !body.iter().any(|line| line.is_empty());
+
+            self.html.push_str("<li>");
+            self.blocks(&body, tight);
+            if self.html.ends_with('\n') {
+                self.html.pop();
+            }
+            self.html.push_str("</li>\n");
+            j = end;
+        }
+
+        let _ = writeln!(self.html, "</{tag}>");
+        j
+    }
+
+    fn definition_list(&mut self, lines: &[String], i: usize) -> usize {
+        self.html.push_str("<dl>\n");
+        let mut j = i;
+        while j + 1 < lines.len()
+            && !lines[j].is_empty()
+            && indentation(&lines[j]) == 0
+            && !lines[j + 1].is_empty()
+            && indentation(&lines[j + 1]) > 0
+        {
+            let end = indented_block_end(lines, j + 1);
+            let term = self.inline(&lines[j]);
+            let _ = writeln!(self.html, "<dt>{term}</dt>");
+            self.html.push_str("<dd>\n");
+            self.blocks(&dedent(&lines[j + 1..end]), false);
+            self.html.push_str("</dd>\n");
+            j = end;
+        }
+        self.html.push_str("</dl>\n");
+        j
+    }
+
+    /// Renders a directive. Comments, hyperlink targets and unsupported
+    /// directives are omitted from the output.
+    fn directive(&mut self, lines: &[String], i: usize) -> usize {
+        let end = indented_block_end(lines, i + 1);
+        let body = dedent(&lines[i + 1..end]);
+        let (options, content) = split_options(&body);
+
+        let text = lines[i][2..].trim();
+        let Some((name, argument)) = text.split_once("::") else {
+            return end;
+        };
+        let argument = argument.trim();
+        match name.trim() {
+            name @ ("image" | "figure") => {
+                let alt = options.get("alt").copied().unwrap_or("");
+                let image = format!(
+                    "<img src=\"{}\" alt=\"{}\">",
+                    encode_attribute(argument),
+                    encode_attribute(alt)
+                );
+                match options.get("target") {
+                    Some(target) => {
+                        let target = encode_attribute(target);
+                        let _ = writeln!(self.html, "<p><a href=\"{target}\">{image}</a></p>");
+                    }
+                    None => {
+                        let _ = writeln!(self.html, "<p>{image}</p>");
+                    }
+                }
+                if name == "figure" {
+                    self.blocks(content, false);
+                }
+            }
+            "code" | "code-block" | "sourcecode" => {
+                let language = argument.split_whitespace().next();
+                push_code_block(&mut self.html, language, content, self.highlight_code);
+            }
+            name @ ("attention" | "caution" | "danger" | "error" | "hint" | "important"
+            | "note" | "tip" | "warning") => {
+                let mut title = name.to_string();
+                title[..1].make_ascii_uppercase();
+                let _ = writeln!(self.html, "<blockquote>\n<p><strong>{title}</strong></p>");
+                if !argument.is_empty() {
+                    let argument = self.inline(argument);
+                    let _ = writeln!(self.html, "<p>{argument}</p>");
+                }
+                self.blocks(content, false);
+                self.html.push_str("</blockquote>\n");
+            }
+            _ => {}
+        }
+        end
+    }
+
+    fn grid_table(&mut self, lines: &[String], i: usize) -> usize {
+        let end = (i..lines.len())
+            .find(|&j| !lines[j].starts_with('+') && !lines[j].starts_with('|'))
+            .unwrap_or(lines.len());
+        let columns = lines[i]
+            .chars()
+            .enumerate()
+            .filter(|(_, c)| *c == '+')
+            .map(|(position, _)| position)
+            .collect::<Vec<_>>();
+
+        let mut header = None;
+        let mut rows = Vec::new();
+        let mut cells = vec![Vec::new(); columns.len() - 1];
+        for line in &lines[i + 1..end] {
+            let chars = line.chars().collect::<Vec<_>>();
+            if is_grid_table_border(line) {
+                let row = cells
+                    .iter()
+                    .map(|cell: &Vec<String>| self.inline(&cell.join(" ")))
+                    .collect::<Vec<_>>();
+                if line.contains('=') && header.is_none() && rows.is_empty() {
+                    header = Some(row);
+                } else {
+                    rows.push(row);
+                }
+                cells = vec![Vec::new(); columns.len() - 1];
+            } else {
+                for (cell, bounds) in cells.iter_mut().zip(columns.windows(2)) {
+                    let start = (bounds[0] + 1).min(chars.len());
+                    let end = bounds[1].min(chars.len());
+                    let text = chars[start..end].iter().collect::<String>();
+                    if !text.trim().is_empty() {
+                        cell.push(text.trim().to_string());
+                    }
+                }
+            }
+        }
+
+        push_table(&mut self.html, header.as_deref(), &rows);
+        end
+    }
+
+    fn simple_table(&mut self, lines: &[String], i: usize) -> usize {
+        let columns = simple_table_columns(&lines[i]);
+
+        // The table ends with a border that is followed by a blank line.
+        let mut borders = vec![i];
+        let mut end = lines.len();
+        for j in i + 1..lines.len() {
+            if simple_table_columns(&lines[j]).len() > 1 {
+                borders.push(j);
+                if lines.get(j + 1).map_or(true, |line| line.is_empty()) {
+                    end = j + 1;
+                    break;
+                }
+            }
+        }
+
+        let mut header = None;
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        for (j, line) in lines.iter().enumerate().take(end).skip(i + 1) {
+            if line.is_empty() || borders.contains(&j) {
+                if borders.len() > 2 && j == borders[1] {
+                    header = rows.pop();
+                }
+                continue;
+            }
+
+            let chars = line.chars().collect::<Vec<_>>();
+            let cells = columns
+                .iter()
+                .enumerate()
+                .map(|(k, &start)| {
+                    let start = start.min(chars.len());
+                    let end = match columns.get(k + 1) {
+                        Some(&next) => next.min(chars.len()),
+                        None => chars.len(),
+                    };
+                    chars[start..end]
+                        .iter()
+                        .collect::<String>()
+                        .trim()
+                        .to_string()
+                })
+                .collect::<Vec<_>>();
+
+            // Rows with an empty first column continue the previous row.
+            match rows.last_mut() {
+                Some(row) if cells[0].is_empty() => {
+                    for (cell, text) in row.iter_mut().zip(cells) {
+                        if !text.is_empty() {
+                            cell.push(' ');
+                            cell.push_str(&text);
+                        }
+                    }
+                }
+                _ => rows.push(cells),
+            }
+        }
+
+        let header = header.map(|row| row.iter().map(|cell| self.inline(cell)).collect::<Vec<_>>());
+        let rows = rows
+            .iter()
+            .map(|row| row.iter().map(|cell| self.inline(cell)).collect())
+            .collect::<Vec<_>>();
+        push_table(&mut self.html, header.as_deref(), &rows);
+        end
+    }
+
+    /// Renders inline markup: emphasis, strong emphasis, inline literals,
+    /// roles, hyperlink references and standalone URLs.
+    fn inline(&self, text: &str) -> String {
+        let chars = text.chars().collect::<Vec<_>>();
+        let mut html = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let at_start = i == 0 || is_markup_boundary(chars.get(i - 1));
+            if chars[i] == '\\' && i + 1 < chars.len() {
+                html.push_str(&encode_minimal(&chars[i + 1].to_string()));
+                i += 2;
+                continue;
+            }
+            if at_start {
+                if let Some((rendered, next)) = self.inline_markup(&chars, i) {
+                    html.push_str(&rendered);
+                    i = next;
+                    continue;
+                }
+            }
+            html.push_str(&encode_minimal(&chars[i].to_string()));
+            i += 1;
+        }
+        html
+    }
+
+    /// Renders the inline markup starting at position `i`, if there is any,
+    /// and returns the position after it.
+    fn inline_markup(&self, chars: &[char], i: usize) -> Option<(String, usize)> {
+        let content = |start: usize, end: usize| chars[start..end].iter().collect::<String>();
+
+        if has_delimiter_at(chars, i, "``") {
+            let end = find_closing(chars, i + 2, "``")?;
+            let code = encode_minimal(&content(i + 2, end));
+            return Some((format!("<code>{code}</code>"), end + 2));
+        }
+        if has_delimiter_at(chars, i, "**") {
+            let end = find_closing(chars, i + 2, "**")?;
+            let strong = encode_minimal(&content(i + 2, end));
+            return Some((format!("<strong>{strong}</strong>"), end + 2));
+        }
+        if chars[i] == '*' {
+            let end = find_closing(chars, i + 1, "*")?;
+            let em = encode_minimal(&content(i + 1, end));
+            return Some((format!("<em>{em}</em>"), end + 1));
+        }
+        if chars[i] == '`' {
+            let end = find_closing(chars, i + 1, "`")?;
+            let text = content(i + 1, end);
+            let underscores = chars[end + 1..]
+                .iter()
+                .take(2)
+                .take_while(|c| **c == '_')
+                .count();
+            if underscores == 0 {
+                return Some((format!("<em>{}</em>", encode_minimal(&text)), end + 1));
+            }
+            let next = end + 1 + underscores;
+            if let Some((label, url)) = embedded_uri(&text) {
+                return Some((link(url, label), next));
+            }
+            return match self.targets.get(&normalize_name(&text)) {
+                Some(url) => Some((link(url, &text), next)),
+                None => Some((encode_minimal(&text), next)),
+            };
+        }
+        if chars[i] == ':' {
+            let name_end = (i + 1..chars.len()).find(|&j| !is_name_char(chars[j]))?;
+            if name_end == i + 1 || !has_delimiter_at(chars, name_end, ":`") {
+                return None;
+            }
+            let end = find_closing(chars, name_end + 2, "`")?;
+            let text = encode_minimal(&content(name_end + 2, end));
+            let rendered = match content(i + 1, name_end).as_str() {
+                "code" | "literal" | "samp" => format!("<code>{text}</code>"),
+                "strong" => format!("<strong>{text}</strong>"),
+                "emphasis" => format!("<em>{text}</em>"),
+                _ => text,
+            };
+            return Some((rendered, end + 1));
+        }
+        if let Some(len) = url_len(&chars[i..]) {
+            let url = content(i, i + len);
+            return Some((link(&url, &url), i + len));
+        }
+
+        // A simple reference name, followed by an underscore.
+        let name_end = (i..chars.len())
+            .find(|&j| !is_name_char(chars[j]))
+            .unwrap_or(chars.len());
+        if name_end > i && chars.get(name_end) == Some(&'_') {
+            let next = name_end + 1 + usize::from(chars.get(name_end + 1) == Some(&'_'));
+            if is_markup_boundary(chars.get(next)) {
+                let name = content(i, name_end);
+                if let Some(url) = self.targets.get(&normalize_name(&name)) {
+                    return Some((link(url, &name), next));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Marker of a bullet or enumerated list item.
+struct ListMarker {
+    ordered: bool,
+    bullet: Option<char>,
+    /// The width of the marker, including the following space.
+    width: usize,
+}
+
+fn list_marker(line: &str) -> Option<ListMarker> {
+    let mut chars = line.chars();
+    let first = chars.next()?;
+    if matches!(first, '*' | '+' | '-' | '•') {
+        return match chars.next() {
+            None | Some(' ') => Some(ListMarker {
+                ordered: false,
+                bullet: Some(first),
+                width: first.len_utf8() + 1,
+            }),
+            _ => None,
+        };
+    }
+
+    let (prefix, number) = match line.strip_prefix('(') {
+        Some(rest) => ("(", rest),
+        None => ("", line),
+    };
+    let digits = number.chars().take_while(char::is_ascii_digit).count();
+    let digits = if digits == 0 && number.starts_with('#') {
+        1
+    } else {
+        digits
+    };
+    if digits == 0 {
+        return None;
+    }
+    let suffix = match (prefix, &number[digits..]) {
+        ("(", rest) if rest.starts_with(") ") || rest == ")" => ")",
+        ("", rest) if rest.starts_with(". ") || rest == "." => ".",
+        ("", rest) if rest.starts_with(") ") || rest == ")" => ")",
+        _ => return None,
+    };
+    Some(ListMarker {
+        ordered: true,
+        bullet: None,
+        width: prefix.len() + digits + suffix.len() + 1,
+    })
+}
+
+/// Returns the character of a section title adornment or transition.
+fn adornment_char(line: &str) -> Option<char> {
+    let c = line.chars().next()?;
+    let is_adornment =
+        c.is_ascii_punctuation() && line.len() >= 2 && line.chars().all(|other| other == c);
+    is_adornment.then_some(c)
+}
+
+fn is_grid_table_border(line: &str) -> bool {
+    line.len() > 2
+        && line.starts_with('+')
+        && line.ends_with('+')
+        && line.chars().all(|c| matches!(c, '+' | '-' | '='))
+}
+
+/// Returns the start positions of the columns of a simple table border.
+fn simple_table_columns(line: &str) -> Vec<usize> {
+    if line.is_empty() || !line.chars().all(|c| c == '=' || c == ' ') {
+        return Vec::new();
+    }
+    line.char_indices()
+        .filter(|&(position, c)| c == '=' && (position == 0 || line[..position].ends_with(' ')))
+        .map(|(position, _)| position)
+        .collect()
+}
+
+/// Returns the end of the block of indented (or blank) lines that starts at `start`.
+fn indented_block_end(lines: &[String], start: usize) -> usize {
+    (start..lines.len())
+        .find(|&j| !lines[j].is_empty() && indentation(&lines[j]) == 0)
+        .unwrap_or(lines.len())
+}
+
+/// Splits the body of a directive into its field list of options and its content.
+fn split_options(body: &[String]) -> (HashMap<&str, &str>, &[String]) {
+    let mut options = HashMap::new();
+    let mut i = 0;
+    while let Some(option) = body.get(i).and_then(|line| line.strip_prefix(':')) {
+        if let Some((name, value)) = option.split_once(':') {
+            options.insert(name, value.trim());
+        }
+        i += 1;
+    }
+    (options, &body[i..])
+}
+
+/// Collects the URLs of all hyperlink targets, like `.. _name: https://example.com`.
+fn collect_targets(lines: &[String]) -> HashMap<String, String> {
+    lines
+        .iter()
+        .filter_map(|line| line.trim_start().strip_prefix(".. _"))
+        .filter_map(|target| {
+            let (name, url) = match target.strip_prefix('`') {
+                Some(quoted) => {
+                    let (name, rest) = quoted.split_once('`')?;
+                    (name, rest.strip_prefix(':')?)
+                }
+                None => target.split_once(": ")?,
+            };
+            let url = url.trim();
+            (!url.is_empty()).then(|| (normalize_name(name), url.to_string()))
+        })
+        .collect()
+}
+
+/// Splits `text <url>` of an embedded hyperlink into its text and URL.
+fn embedded_uri(text: &str) -> Option<(&str, &str)> {
+    let (label, url) = text.strip_suffix('>')?.rsplit_once('<')?;
+    let label = label.trim();
+    Some((if label.is_empty() { url } else { label }, url))
+}
+
+fn link(url: &str, text: &str) -> String {
+    format!(
+        "<a href=\"{}\">{}</a>",
+        encode_attribute(url),
+        encode_minimal(text)
+    )
+}
+
+fn normalize_name(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '.' | '+')
+}
@@ -0,0 +1,13 @@
+create table index_consistency_checks
+(
+    id              serial primary key,
+    checked_at      timestamp not null default now(),
+    crates_checked  integer   not null,
+    git_mismatches  text[]    not null default '{}',
+    http_mismatches text[]    not null default '{}'
+);
+
+comment on table index_consistency_checks is 'Results of the periodic `check_index` background job';
+comment on column index_consistency_checks.crates_checked is 'Number of crates that were compared by the check';
+comment on column index_consistency_checks.git_mismatches is 'Names of the crates whose git index file did not match the database';
+comment on column index_consistency_checks.http_mismatches is 'Names of the crates whose HTTP index file did not match the git index';
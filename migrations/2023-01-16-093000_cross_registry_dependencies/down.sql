@@ -0,0 +1,7 @@
+delete from dependencies where crate_id is null;
+
+alter table dependencies
+    drop constraint dependencies_crate_or_registry_check,
+    drop column registry,
+    drop column registry_crate_name,
+    alter column crate_id set not null;
@@ -0,0 +1,11 @@
+alter table dependencies
+    alter column crate_id drop not null,
+    add column registry varchar,
+    add column registry_crate_name varchar,
+    add constraint dependencies_crate_or_registry_check check (
+        (crate_id is null) = (registry is not null)
+        and (registry is null) = (registry_crate_name is null)
+    );
+
+comment on column dependencies.registry is 'Index URL of the registry hosting the dependency, if it is not hosted on this registry';
+comment on column dependencies.registry_crate_name is 'Name of the dependency on the registry given by `registry`';
@@ -0,0 +1,8 @@
+delete from publish_rate_overrides where action != 'publish_new';
+
+alter table publish_rate_overrides
+    drop constraint publish_rate_overrides_pkey,
+    drop column action,
+    add primary key (user_id);
+
+drop table rate_limit_buckets;
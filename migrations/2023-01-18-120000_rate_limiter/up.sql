@@ -0,0 +1,19 @@
+create table rate_limit_buckets
+(
+    action      varchar   not null,
+    key         varchar   not null,
+    tokens      integer   not null,
+    last_refill timestamp not null default now(),
+    primary key (action, key)
+);
+
+comment on table rate_limit_buckets is 'Token buckets of the rate limiter for API endpoints';
+comment on column rate_limit_buckets.action is 'Name of the limited action, e.g. `search`';
+comment on column rate_limit_buckets.key is 'The user, API token or IP address the bucket belongs to, e.g. `user:42`';
+
+alter table publish_rate_overrides
+    add column action varchar not null default 'publish_new',
+    drop constraint publish_rate_overrides_pkey,
+    add primary key (user_id, action);
+
+comment on column publish_rate_overrides.action is 'Name of the limited action the override applies to';
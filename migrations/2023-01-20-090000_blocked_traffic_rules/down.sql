@@ -0,0 +1,4 @@
+drop table blocked_traffic_rules;
+
+alter table users
+    drop column is_admin;
@@ -0,0 +1,22 @@
+alter table users
+    add column is_admin boolean not null default false;
+
+comment on column users.is_admin is 'Whether the user may use the admin API, e.g. to manage blocked traffic';
+
+create table blocked_traffic_rules
+(
+    id                 serial primary key,
+    ip_range           varchar,
+    user_agent_pattern varchar,
+    reason             varchar   not null,
+    created_at         timestamp not null default now(),
+    expires_at         timestamp,
+    constraint blocked_traffic_rules_matcher_check
+        check (ip_range is not null or user_agent_pattern is not null)
+);
+
+comment on table blocked_traffic_rules is 'Rules for blocking requests, managed through `crates-admin block` and the admin API';
+comment on column blocked_traffic_rules.ip_range is 'CIDR block of client IP addresses that are blocked';
+comment on column blocked_traffic_rules.user_agent_pattern is 'Regular expression matching the user agents that are blocked';
+comment on column blocked_traffic_rules.reason is 'Why the traffic is blocked, for the operators';
+comment on column blocked_traffic_rules.expires_at is 'The rule is ignored after this time. A rule without expiry is permanent.';
@@ -0,0 +1,11 @@
+create table crate_owner_team_invitations
+(
+    invited_team_id    integer   not null references teams (id) on delete cascade,
+    crate_id           integer   not null references crates (id) on delete cascade,
+    invited_by_user_id integer   not null references users (id) on delete cascade,
+    created_at         timestamp not null default now(),
+    primary key (invited_team_id, crate_id)
+);
+
+comment on table crate_owner_team_invitations is 'Pending invitations of teams to become crate owners, which must be accepted by a maintainer of the team';
+comment on column crate_owner_team_invitations.invited_by_user_id is 'The crate owner who sent the invitation';
@@ -0,0 +1,2 @@
+alter table crate_owners
+    drop column role;
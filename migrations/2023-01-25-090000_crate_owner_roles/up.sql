@@ -0,0 +1,4 @@
+alter table crate_owners
+    add column role integer not null default 0;
+
+comment on column crate_owners.role is 'The role of the owner: 0 = admin, 1 = publisher, 2 = yanker';
@@ -0,0 +1,3 @@
+drop table crate_adoption_requests;
+drop table crate_transfer_requests;
+drop table crate_owner_actions;
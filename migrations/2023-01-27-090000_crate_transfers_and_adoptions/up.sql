@@ -0,0 +1,37 @@
+create table crate_owner_actions
+(
+    id             serial primary key,
+    crate_id       integer   not null references crates (id) on delete cascade,
+    user_id        integer   not null references users (id) on delete cascade,
+    target_user_id integer references users (id) on delete cascade,
+    action         integer   not null,
+    time           timestamp not null default now()
+);
+
+create index crate_owner_actions_crate_id on crate_owner_actions (crate_id);
+
+comment on table crate_owner_actions is 'Audit trail of changes to the ownership of crates';
+comment on column crate_owner_actions.user_id is 'The user who performed the action';
+comment on column crate_owner_actions.target_user_id is 'The user the action was performed for, e.g. the recipient of a transfer';
+
+create table crate_transfer_requests
+(
+    crate_id     integer primary key references crates (id) on delete cascade,
+    from_user_id integer   not null references users (id) on delete cascade,
+    to_user_id   integer   not null references users (id) on delete cascade,
+    created_at   timestamp not null default now()
+);
+
+comment on table crate_transfer_requests is 'Pending requests to transfer a crate to a new owner, which must be accepted by the recipient';
+
+create table crate_adoption_requests
+(
+    id         serial primary key,
+    crate_id   integer   not null references crates (id) on delete cascade,
+    user_id    integer   not null references users (id) on delete cascade,
+    reason     varchar   not null,
+    created_at timestamp not null default now(),
+    unique (crate_id, user_id)
+);
+
+comment on table crate_adoption_requests is 'Pending requests to adopt an orphaned crate, which must be approved by an admin';
@@ -0,0 +1,4 @@
+delete from reserved_crate_names where expires_at is not null;
+alter table reserved_crate_names drop column expires_at;
+
+drop table deleted_crates;
@@ -0,0 +1,18 @@
+create table deleted_crates
+(
+    id         serial primary key,
+    name       varchar   not null,
+    created_at timestamp not null,
+    deleted_at timestamp not null default now(),
+    deleted_by integer   not null references users (id) on delete cascade,
+    downloads  integer   not null
+);
+
+create index deleted_crates_name on deleted_crates (name);
+
+comment on table deleted_crates is 'Audit trail of crates that have been deleted by their owners';
+comment on column deleted_crates.created_at is 'When the deleted crate was originally published';
+
+alter table reserved_crate_names add column expires_at timestamp;
+
+comment on column reserved_crate_names.expires_at is 'When the reservation ends, e.g. for names of deleted crates. NULL means the name is reserved indefinitely';
@@ -0,0 +1 @@
+drop table publish_uploads;
@@ -0,0 +1,17 @@
+create table publish_uploads
+(
+    id         serial primary key,
+    crate_name varchar   not null,
+    num        varchar   not null,
+    user_id    integer   not null references users (id) on delete cascade,
+    state      integer   not null default 0,
+    error      varchar,
+    tarball    bytea,
+    created_at timestamp not null default now(),
+    updated_at timestamp not null default now(),
+    unique (crate_name, num)
+);
+
+comment on table publish_uploads is 'Versions that were uploaded asynchronously, and the state of the job finalizing them';
+comment on column publish_uploads.state is '0 = pending, 1 = published, 2 = failed';
+comment on column publish_uploads.tarball is 'The uploaded crate file, which is cleared once the version has been finalized';
@@ -0,0 +1,3 @@
+drop index versions_staged;
+
+alter table versions drop column staged;
@@ -0,0 +1,5 @@
+alter table versions add column staged boolean not null default false;
+
+comment on column versions.staged is 'Staged versions have been uploaded, but are neither in the index nor listed by the API until they are released';
+
+create index versions_staged on versions (crate_id) where staged;
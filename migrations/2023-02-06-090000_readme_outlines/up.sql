@@ -0,0 +1,3 @@
+alter table readme_renderings add column outline jsonb;
+
+comment on column readme_renderings.outline is 'Headings of the rendered readme, with their level, text and anchor id';
@@ -0,0 +1 @@
+drop table proxied_images;
@@ -0,0 +1,13 @@
+create table proxied_images
+(
+    url          text primary key,
+    digest       text      not null,
+    content_type varchar   not null,
+    cached_at    timestamp not null default now()
+);
+
+comment on table proxied_images is 'Readme images that were fetched by the image proxy and cached in storage';
+comment on column proxied_images.url is 'URL that the image was fetched from';
+comment on column proxied_images.digest is 'Hex-encoded SHA-256 digest of the image, under which it is stored';
+comment on column proxied_images.content_type is 'Content type of the image';
+comment on column proxied_images.cached_at is 'Date and time when the image was cached';
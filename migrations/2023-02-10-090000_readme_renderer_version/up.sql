@@ -0,0 +1,3 @@
+alter table readme_renderings add column renderer_version integer not null default 0;
+
+comment on column readme_renderings.renderer_version is 'Version of the readme renderer that the readme was last rendered with, to find readmes that need to be re-rendered';
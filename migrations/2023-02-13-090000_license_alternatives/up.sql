@@ -0,0 +1,3 @@
+alter table versions add column license_alternatives jsonb;
+
+comment on column versions.license_alternatives is 'Sets of licenses that each satisfy the license expression of the version on their own, e.g. [["MIT"], ["Apache-2.0"]] for "MIT OR Apache-2.0"';
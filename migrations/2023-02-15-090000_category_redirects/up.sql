@@ -0,0 +1,10 @@
+create table category_redirects
+(
+    slug        varchar   not null primary key,
+    category_id integer   not null references categories (id) on delete cascade,
+    created_at  timestamp not null default now()
+);
+
+comment on table category_redirects is 'Former slugs of renamed and merged categories, which keep pointing to the category that replaced them';
+
+create index category_redirects_category_id on category_redirects (category_id);
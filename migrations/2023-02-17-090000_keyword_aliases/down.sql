@@ -0,0 +1,2 @@
+drop table banned_keywords;
+drop table keyword_aliases;
@@ -0,0 +1,18 @@
+create table keyword_aliases
+(
+    alias      text      not null primary key,
+    keyword_id integer   not null references keywords (id) on delete cascade,
+    created_at timestamp not null default now()
+);
+
+comment on table keyword_aliases is 'Variants of keywords, which are replaced by their canonical keyword when a crate is published';
+
+create index keyword_aliases_keyword_id on keyword_aliases (keyword_id);
+
+create table banned_keywords
+(
+    keyword    text      not null primary key,
+    created_at timestamp not null default now()
+);
+
+comment on table banned_keywords is 'Keywords that are ignored when a crate is published';
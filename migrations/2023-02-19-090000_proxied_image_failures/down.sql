@@ -0,0 +1 @@
+drop table proxied_image_failures;
@@ -0,0 +1,11 @@
+create table proxied_image_failures
+(
+    url       text primary key,
+    error     text      not null,
+    failed_at timestamp not null default now()
+);
+
+comment on table proxied_image_failures is 'Readme images that the image proxy recently failed to fetch';
+comment on column proxied_image_failures.url is 'URL that the image was fetched from';
+comment on column proxied_image_failures.error is 'Error message that is returned until the image is fetched again';
+comment on column proxied_image_failures.failed_at is 'Date and time of the last failed attempt to fetch the image';
@@ -0,0 +1,92 @@
+use crate::db;
+use crate::models::{BlockedTrafficRule, NewBlockedTrafficRule};
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Utc};
+
+/// Manage the rules for blocking traffic by IP range or user agent.
+///
+/// Running instances pick up changes once their cached rules expire
+/// (see `BLOCKED_TRAFFIC_RULES_TTL`).
+#[derive(clap::Parser, Debug)]
+#[command(name = "block", about = "Manage the rules for blocking traffic")]
+pub enum Command {
+    /// Add a new rule. Requests are blocked if they match all given matchers.
+    Add {
+        /// CIDR block of client IP addresses to block, e.g. `192.168.0.0/24`
+        #[arg(long)]
+        ip_range: Option<String>,
+        /// Regular expression matching the user agents to block
+        #[arg(long)]
+        user_agent: Option<String>,
+        /// Why the traffic is blocked
+        #[arg(long)]
+        reason: String,
+        /// Remove the rule after this many hours. Rules are permanent by default.
+        #[arg(long)]
+        expires_in_hours: Option<i64>,
+    },
+    /// List the rules that have not expired yet.
+    List {
+        /// Include the expired rules
+        #[arg(long)]
+        all: bool,
+    },
+    /// Remove the rule with the given id.
+    Remove { id: i32 },
+}
+
+pub fn run(command: Command) -> Result<()> {
+    let conn = db::oneoff_connection()?;
+
+    match command {
+        Command::Add {
+            ip_range,
+            user_agent,
+            reason,
+            expires_in_hours,
+        } => {
+            let new_rule = NewBlockedTrafficRule {
+                ip_range: ip_range.as_deref(),
+                user_agent_pattern: user_agent.as_deref(),
+                reason: &reason,
+                expires_at: expires_in_hours
+                    .map(|hours| (Utc::now() + Duration::hours(hours)).naive_utc()),
+            };
+            new_rule.validate()?;
+
+            let rule = new_rule.save(&conn)?;
+            println!("Added blocked traffic rule {}", rule.id);
+        }
+        Command::List { all } => {
+            let rules = match all {
+                true => BlockedTrafficRule::all(&conn)?,
+                false => BlockedTrafficRule::active(&conn)?,
+            };
+
+            let now = Utc::now().naive_utc();
+            for rule in rules {
+                let expiry = match rule.expires_at {
+                    Some(_) if rule.is_expired(now) => "expired".to_string(),
+                    Some(expires_at) => format!("expires {expires_at}"),
+                    None => "permanent".to_string(),
+                };
+
+                println!(
+                    "{}\tip_range={}\tuser_agent={}\t{expiry}\t{}",
+                    rule.id,
+                    rule.ip_range.as_deref().unwrap_or("*"),
+                    rule.user_agent_pattern.as_deref().unwrap_or("*"),
+                    rule.reason,
+                );
+            }
+        }
+        Command::Remove { id } => {
+            if !BlockedTrafficRule::delete(&conn, id)? {
+                return Err(anyhow!("No blocked traffic rule with id {id}"));
+            }
+            println!("Removed blocked traffic rule {id}");
+        }
+    }
+
+    Ok(())
+}
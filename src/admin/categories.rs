@@ -0,0 +1,99 @@
+use crate::db;
+use crate::models::Category;
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+
+/// Manage the crate categories.
+///
+/// The former slugs of renamed and merged categories keep redirecting to the
+/// category that replaced them.
+#[derive(clap::Parser, Debug)]
+#[command(name = "categories", about = "Manage the crate categories")]
+pub enum Command {
+    /// Create a new category.
+    Create {
+        /// Slug of the category, including the slugs of its parent categories,
+        /// e.g. `development-tools::testing`
+        slug: String,
+        /// Display name of the category, not including the names of its parent categories
+        #[arg(long)]
+        name: String,
+        /// Description of the crates that belong in this category
+        #[arg(long, default_value = "")]
+        description: String,
+    },
+    /// Change the slug and display name of a category and its subcategories.
+    Rename {
+        slug: String,
+        /// New slug, not including the slugs of the parent categories
+        new_slug: String,
+        /// New display name, not including the names of the parent categories
+        #[arg(long)]
+        name: String,
+    },
+    /// Move a category and its subcategories below another category.
+    Reparent {
+        slug: String,
+        /// Slug of the new parent category. The category is moved to the top
+        /// level if this is omitted.
+        #[arg(long)]
+        parent: Option<String>,
+    },
+    /// Move the crates of a category to another category and remove it.
+    Merge {
+        slug: String,
+        /// Slug of the category that the crates are moved to
+        into: String,
+    },
+}
+
+pub fn run(command: Command) -> Result<()> {
+    let conn = db::oneoff_connection()?;
+
+    match command {
+        Command::Create {
+            slug,
+            name,
+            description,
+        } => {
+            let category =
+                Category::create(&conn, &slug, &name, &description).map_err(|e| anyhow!("{e}"))?;
+            println!("Created category {}", category.slug);
+        }
+        Command::Rename {
+            slug,
+            new_slug,
+            name,
+        } => {
+            let category = find_category(&conn, &slug)?
+                .rename(&conn, &new_slug, &name)
+                .map_err(|e| anyhow!("{e}"))?;
+            println!("Renamed category {slug} to {}", category.slug);
+        }
+        Command::Reparent { slug, parent } => {
+            let parent = parent
+                .map(|parent| find_category(&conn, &parent))
+                .transpose()?;
+            let category = find_category(&conn, &slug)?
+                .reparent(&conn, parent.as_ref())
+                .map_err(|e| anyhow!("{e}"))?;
+            println!("Moved category {slug} to {}", category.slug);
+        }
+        Command::Merge { slug, into } => {
+            let target = find_category(&conn, &into)?;
+            find_category(&conn, &slug)?
+                .merge_into(&conn, &target)
+                .map_err(|e| anyhow!("{e}"))?;
+            println!("Merged category {slug} into {into}");
+        }
+    }
+
+    Ok(())
+}
+
+fn find_category(conn: &PgConnection, slug: &str) -> Result<Category> {
+    Category::by_slug(slug)
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| anyhow!("No category with slug {slug}"))
+}
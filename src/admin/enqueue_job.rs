@@ -19,10 +19,16 @@ pub enum Command {
     },
     DailyDbMaintenance,
     SquashIndex,
+    CheckIndex {
+        /// Only check this many randomly selected crates
+        #[arg(long = "sample-size")]
+        sample_size: Option<i64>,
+    },
     NormalizeIndex {
         #[arg(long = "dry-run")]
         dry_run: bool,
     },
+    SyncMirror,
 }
 
 pub fn run(command: Command) -> Result<()> {
@@ -50,6 +56,8 @@ pub fn run(command: Command) -> Result<()> {
         } => Ok(worker::dump_db(database_url, target_name).enqueue(&conn)?),
         Command::DailyDbMaintenance => Ok(worker::daily_db_maintenance().enqueue(&conn)?),
         Command::SquashIndex => Ok(worker::squash_index().enqueue(&conn)?),
+        Command::CheckIndex { sample_size } => Ok(worker::check_index(sample_size).enqueue(&conn)?),
         Command::NormalizeIndex { dry_run } => Ok(worker::normalize_index(dry_run).enqueue(&conn)?),
+        Command::SyncMirror => Ok(worker::sync_mirror().enqueue(&conn)?),
     }
 }
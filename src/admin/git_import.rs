@@ -153,3 +153,107 @@ fn import_data(conn: &PgConnection, krate: &cargo_registry_index::Crate) -> anyh
     }
     Ok(())
 }
+
+/// Creates the version of an index entry of an upstream registry in the
+/// database, together with its crate and dependencies.
+///
+/// This is used by the mirror mode, in which all crates are imported from
+/// the index of the upstream registry instead of being published.
+pub fn import_version(
+    conn: &PgConnection,
+    krate: &cargo_registry_index::Crate,
+    crate_size: i32,
+) -> anyhow::Result<()> {
+    conn.transaction(|| {
+        let crate_id = find_or_create_crate(conn, &krate.name)?;
+
+        let mut features = krate.features.clone();
+        features.extend(krate.features2.clone().unwrap_or_default());
+
+        let version_id: i32 = diesel::insert_into(versions::table)
+            .values((
+                versions::crate_id.eq(crate_id),
+                versions::num.eq(&krate.vers),
+                versions::features.eq(serde_json::to_value(features)?),
+                versions::yanked.eq(krate.yanked.unwrap_or(false)),
+                versions::crate_size.eq(crate_size),
+                versions::checksum.eq(&krate.cksum),
+                versions::links.eq(&krate.links),
+            ))
+            .returning(versions::id)
+            .get_result(conn)
+            .with_context(|| format!("Failed to insert {}#{}", krate.name, krate.vers))?;
+
+        for dep in &krate.deps {
+            // Renamed dependencies are stored with their original crate name
+            let (dep_crate_name, explicit_name) = match &dep.package {
+                Some(package) => (package, Some(&dep.name)),
+                None => (&dep.name, None),
+            };
+
+            // Dependencies on other registries are stored with the index URL
+            // of their registry instead of a local crate
+            let (dep_crate_id, registry_crate_name) = match &dep.registry {
+                Some(_) => (None, Some(dep_crate_name)),
+                None => (Some(find_or_create_crate(conn, dep_crate_name)?), None),
+            };
+
+            diesel::insert_into(dependencies::table)
+                .values((
+                    dependencies::version_id.eq(version_id),
+                    dependencies::crate_id.eq(dep_crate_id),
+                    dependencies::req.eq(&dep.req),
+                    dependencies::optional.eq(dep.optional),
+                    dependencies::default_features.eq(dep.default_features),
+                    dependencies::features.eq(&dep.features),
+                    dependencies::target.eq(&dep.target),
+                    dependencies::kind.eq(dep.kind.map(|k| k as i32).unwrap_or_default()),
+                    dependencies::explicit_name.eq(explicit_name),
+                    dependencies::registry.eq(&dep.registry),
+                    dependencies::registry_crate_name.eq(registry_crate_name),
+                ))
+                .execute(conn)
+                .with_context(|| {
+                    format!(
+                        "{}#{}: Failed to insert dependency {dep_crate_name}",
+                        krate.name, krate.vers
+                    )
+                })?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Updates the `yanked` flag of an imported version from its index entry.
+pub fn import_yanked(conn: &PgConnection, krate: &cargo_registry_index::Crate) -> QueryResult<()> {
+    let crate_id = crates::table
+        .filter(crates::name.eq(&krate.name))
+        .select(crates::id);
+
+    diesel::update(versions::table)
+        .filter(versions::crate_id.eq_any(crate_id))
+        .filter(versions::num.eq(&krate.vers))
+        .set(versions::yanked.eq(krate.yanked.unwrap_or(false)))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Returns the id of the crate with the given name, creating it if it does
+/// not exist yet.
+fn find_or_create_crate(conn: &PgConnection, name: &str) -> QueryResult<i32> {
+    let crate_id = crates::table
+        .filter(crates::name.eq(name))
+        .select(crates::id)
+        .first(conn)
+        .optional()?;
+
+    match crate_id {
+        Some(crate_id) => Ok(crate_id),
+        None => diesel::insert_into(crates::table)
+            .values(crates::name.eq(name))
+            .returning(crates::id)
+            .get_result(conn),
+    }
+}
@@ -0,0 +1,81 @@
+use crate::db;
+use crate::models::Keyword;
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+
+/// Moderate the keywords of crates.
+///
+/// Aliases are replaced by their canonical keyword when a crate is published,
+/// and banned keywords are ignored.
+#[derive(clap::Parser, Debug)]
+#[command(name = "keywords", about = "Moderate the keywords of crates")]
+pub enum Command {
+    /// Make a variant of a keyword an alias of it.
+    Alias {
+        keyword: String,
+        /// Variant that is replaced by the keyword when crates are published
+        alias: String,
+    },
+    /// Change the name of a keyword. The former name becomes an alias.
+    Rename { keyword: String, new_name: String },
+    /// Move the crates of a keyword to another keyword and make it an alias of it.
+    Merge {
+        keyword: String,
+        /// Keyword that the crates are moved to
+        into: String,
+    },
+    /// Remove a keyword from all crates and ignore it when crates are published.
+    Ban { keyword: String },
+    /// Allow a banned keyword again.
+    Unban { keyword: String },
+    /// Recompute the number of crates of all keywords.
+    Recount,
+}
+
+pub fn run(command: Command) -> Result<()> {
+    let conn = db::oneoff_connection()?;
+
+    match command {
+        Command::Alias { keyword, alias } => {
+            find_keyword(&conn, &keyword)?
+                .add_alias(&conn, &alias)
+                .map_err(|e| anyhow!("{e}"))?;
+            println!("Added alias {alias} to keyword {keyword}");
+        }
+        Command::Rename { keyword, new_name } => {
+            let renamed = find_keyword(&conn, &keyword)?
+                .rename(&conn, &new_name)
+                .map_err(|e| anyhow!("{e}"))?;
+            println!("Renamed keyword {keyword} to {}", renamed.keyword);
+        }
+        Command::Merge { keyword, into } => {
+            let target = find_keyword(&conn, &into)?;
+            find_keyword(&conn, &keyword)?
+                .merge_into(&conn, &target)
+                .map_err(|e| anyhow!("{e}"))?;
+            println!("Merged keyword {keyword} into {into}");
+        }
+        Command::Ban { keyword } => {
+            let removed = Keyword::ban(&conn, &keyword).map_err(|e| anyhow!("{e}"))?;
+            println!("Banned keyword {keyword} and removed it from {removed} crates");
+        }
+        Command::Unban { keyword } => {
+            if !Keyword::unban(&conn, &keyword)? {
+                return Err(anyhow!("Keyword {keyword} is not banned"));
+            }
+            println!("Unbanned keyword {keyword}");
+        }
+        Command::Recount => {
+            let updated = Keyword::recount_crates(&conn)?;
+            println!("Fixed the number of crates of {updated} keywords");
+        }
+    }
+
+    Ok(())
+}
+
+fn find_keyword(conn: &PgConnection, keyword: &str) -> Result<Keyword> {
+    Keyword::find_by_keyword(conn, keyword)
+        .optional()?
+        .ok_or_else(|| anyhow!("No keyword {keyword}"))
+}
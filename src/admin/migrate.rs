@@ -23,7 +23,7 @@ pub fn run(_opts: Opts) -> Result<(), Error> {
         // TODO: Check `any_pending_migrations()` with a read-only connection and error if true.
         // It looks like this requires changes upstream to make this pub in `migration_macros`.
 
-        warn!("Skipping migrations and category sync (read-only mode)");
+        warn!("Skipping migrations and category seeding (read-only mode)");
 
         // The service is undergoing maintenance or mitigating an outage.
         // Exit with success to ensure configuration changes can be made.
@@ -37,8 +37,8 @@ pub fn run(_opts: Opts) -> Result<(), Error> {
     info!("Migrating the database");
     embedded_migrations::run_with_output(&conn, &mut std::io::stdout())?;
 
-    info!("Synchronizing crate categories");
-    crate::boot::categories::sync_with_connection(CATEGORIES_TOML, &conn).unwrap();
+    info!("Seeding crate categories");
+    crate::boot::categories::seed_with_connection(CATEGORIES_TOML, &conn).unwrap();
 
     Ok(())
 }
@@ -1,11 +1,16 @@
+pub mod block;
+pub mod categories;
 pub mod delete_crate;
 pub mod delete_version;
 pub mod dialoguer;
 pub mod enqueue_job;
 pub mod git_import;
+pub mod keywords;
 pub mod migrate;
+pub mod normalize_licenses;
 pub mod on_call;
 pub mod populate;
+pub mod rebuild_index;
 pub mod render_readmes;
 pub mod test_pagerduty;
 pub mod transfer_crates;
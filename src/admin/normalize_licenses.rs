@@ -0,0 +1,71 @@
+use crate::models::LicenseExpression;
+use crate::{db, schema::versions};
+
+use diesel::prelude::*;
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "normalize-licenses",
+    about = "Normalize the license expressions of versions that were published before \
+        license expressions were normalized, so that the license search filter finds them."
+)]
+pub struct Opts {
+    /// How many versions should be updated in each transaction.
+    #[arg(long, default_value = "1000", value_parser = clap::value_parser!(i64).range(1..))]
+    batch_size: i64,
+}
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    let conn = db::oneoff_connection()?;
+
+    let mut last_id = 0;
+    let (mut normalized, mut invalid) = (0, 0);
+    loop {
+        let batch: Vec<(i32, Option<String>)> = versions::table
+            .filter(versions::id.gt(last_id))
+            .filter(versions::license_alternatives.is_null())
+            .filter(versions::license.ne("non-standard"))
+            .select((versions::id, versions::license))
+            .order(versions::id)
+            .limit(opts.batch_size)
+            .load(&conn)?;
+
+        let (id, _) = match batch.last() {
+            Some(last) => last,
+            None => break,
+        };
+        last_id = *id;
+
+        conn.transaction(|| {
+            for (id, license) in &batch {
+                // Versions without a license are excluded by the `ne` filter
+                let license = license.as_deref().unwrap_or_default();
+                let parsed = LicenseExpression::parse(license).and_then(|expression| {
+                    let alternatives = expression.alternatives()?;
+                    Ok((expression, alternatives))
+                });
+                let (expression, alternatives) = match parsed {
+                    Ok(parsed) => parsed,
+                    Err(_) => {
+                        println!("Skipping version {id} with the invalid license `{license}`");
+                        invalid += 1;
+                        continue;
+                    }
+                };
+
+                diesel::update(versions::table.find(id))
+                    .set((
+                        versions::license.eq(expression.normalized()),
+                        versions::license_alternatives.eq(serde_json::to_value(alternatives)?),
+                    ))
+                    .execute(&conn)?;
+                normalized += 1;
+            }
+            Ok::<_, anyhow::Error>(())
+        })?;
+    }
+
+    println!("Normalized the licenses of {normalized} versions, skipped {invalid} versions");
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use crate::{admin::dialoguer, db, worker};
+use cargo_registry_index::{Repository, RepositoryConfig};
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "rebuild-index",
+    about = "Regenerate the index files from the database",
+    after_help = "Compares the index files generated from the database with the current index, \
+        and enqueues a background job to push the fixed files and resync the http-based index."
+)]
+pub struct Opts {
+    /// Only report the index files that do not match the database.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+pub fn run(opts: Opts) -> anyhow::Result<()> {
+    let conn = db::oneoff_connection()?;
+
+    println!("fetching git repo");
+    let config = RepositoryConfig::from_environment();
+    let repo = Repository::open(&config)?;
+    repo.reset_head()?;
+    println!("HEAD is at {}", repo.head_oid()?);
+
+    let discrepancies = worker::find_index_discrepancies(&repo, &conn)?;
+    for discrepancy in &discrepancies {
+        println!("{}: {}", discrepancy.crate_name, discrepancy.change);
+    }
+    println!(
+        "found {} index files that do not match the database",
+        discrepancies.len()
+    );
+
+    if opts.dry_run || discrepancies.is_empty() {
+        return Ok(());
+    }
+
+    if !dialoguer::confirm("enqueue a job to rebuild the index?") {
+        return Ok(());
+    }
+
+    worker::rebuild_index().enqueue(&conn)?;
+    println!("enqueued rebuild_index job");
+
+    Ok(())
+}
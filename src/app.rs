@@ -10,6 +10,7 @@ use crate::downloads_counter::DownloadsCounter;
 use crate::email::Emails;
 use crate::github::{GitHubClient, RealGitHubClient};
 use crate::metrics::{InstanceMetrics, ServiceMetrics};
+use crate::models::BlockedTrafficRuleSet;
 use axum::extract::FromRef;
 use diesel::r2d2;
 use moka::sync::{Cache, CacheBuilder};
@@ -41,6 +42,13 @@ pub struct App {
     /// `version_id` is only cached under the canonical spelling of the crate name.
     pub(crate) version_id_cacher: Cache<(String, String), i32>,
 
+    /// Cache of the active blocked traffic rules for the `block_by_rules` middleware
+    ///
+    /// The single entry expires after `config.blocked_traffic_rules_ttl` and is
+    /// invalidated when the rules are changed through the admin API. If the rules
+    /// can't be loaded, an empty set is cached until the entry expires.
+    pub(crate) blocked_traffic_rules: Cache<(), Arc<BlockedTrafficRuleSet>>,
+
     /// Count downloads and periodically persist them in the database
     pub downloads_counter: DownloadsCounter,
 
@@ -173,6 +181,10 @@ impl App {
             .time_to_live(config.version_id_cache_ttl)
             .build();
 
+        let blocked_traffic_rules = CacheBuilder::new(1)
+            .time_to_live(config.blocked_traffic_rules_ttl)
+            .build();
+
         let fastboot_client = match dotenv::var("USE_FASTBOOT") {
             Ok(val) if val == "staging-experimental" => Some(reqwest::Client::new()),
             _ => None,
@@ -184,6 +196,7 @@ impl App {
             github,
             github_oauth,
             version_id_cacher,
+            blocked_traffic_rules,
             downloads_counter: DownloadsCounter::new(),
             emails: Emails::from_environment(&config),
             service_metrics: ServiceMetrics::new().expect("could not initialize service metrics"),
@@ -13,6 +13,7 @@ use http::{header, Request};
 #[derive(Debug, Clone)]
 pub struct AuthCheck {
     allow_token: bool,
+    require_admin: bool,
     endpoint_scope: Option<EndpointScope>,
     crate_name: Option<String>,
 }
@@ -24,6 +25,7 @@ impl AuthCheck {
     pub fn default() -> Self {
         Self {
             allow_token: true,
+            require_admin: false,
             endpoint_scope: None,
             crate_name: None,
         }
@@ -33,6 +35,18 @@ impl AuthCheck {
     pub fn only_cookie() -> Self {
         Self {
             allow_token: false,
+            require_admin: false,
+            endpoint_scope: None,
+            crate_name: None,
+        }
+    }
+
+    /// Only allows cookie authentication of users with the `is_admin` flag.
+    #[must_use]
+    pub fn only_admin() -> Self {
+        Self {
+            allow_token: false,
+            require_admin: true,
             endpoint_scope: None,
             crate_name: None,
         }
@@ -41,6 +55,7 @@ impl AuthCheck {
     pub fn with_endpoint_scope(&self, endpoint_scope: EndpointScope) -> Self {
         Self {
             allow_token: self.allow_token,
+            require_admin: self.require_admin,
             endpoint_scope: Some(endpoint_scope),
             crate_name: self.crate_name.clone(),
         }
@@ -49,6 +64,7 @@ impl AuthCheck {
     pub fn for_crate(&self, crate_name: &str) -> Self {
         Self {
             allow_token: self.allow_token,
+            require_admin: self.require_admin,
             endpoint_scope: self.endpoint_scope,
             crate_name: Some(crate_name.to_string()),
         }
@@ -75,6 +91,11 @@ impl AuthCheck {
             request.add_custom_metadata("tokenid", id);
         }
 
+        if self.require_admin && !auth.user.is_admin {
+            let error_message = "This API is only available to admins";
+            return Err(internal(error_message).chain(forbidden()));
+        }
+
         if let Some(ref token) = auth.token {
             if !self.allow_token {
                 let error_message =
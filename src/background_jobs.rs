@@ -1,49 +1,100 @@
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use reqwest::blocking::Client;
 use std::panic::AssertUnwindSafe;
 use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
+use crate::config::{ImageProxyConfig, MirrorConfig};
 use crate::db::DieselPool;
+use crate::models::CrateMetadata;
 use crate::swirl::errors::EnqueueError;
 use crate::swirl::PerformError;
 use crate::uploaders::Uploader;
+use crate::util::PackageDocuments;
 use crate::worker;
 use crate::worker::cloudfront::CloudFront;
+use crate::worker::IndexBatchConfig;
 use cargo_registry_index::Repository;
+use cargo_registry_markdown::{ImageProxy, RenderOptions};
 
 pub enum Job {
     DailyDbMaintenance,
+    DeleteCrateFiles(DeleteCrateFilesJob),
     DumpDb(DumpDbJob),
+    FinalizePublish(FinalizePublishJob),
     IndexAddCrate(IndexAddCrateJob),
+    IndexAddCrates(IndexAddCratesJob),
+    IndexCheck(IndexCheckJob),
+    IndexDeleteCrate(IndexDeleteCrateJob),
     IndexSquash,
     IndexSyncToHttp(IndexSyncToHttpJob),
     IndexUpdateYanked(IndexUpdateYankedJob),
     NormalizeIndex(NormalizeIndexJob),
+    RebuildIndex,
+    RenderAndUploadDocuments(RenderAndUploadDocumentsJob),
     RenderAndUploadReadme(RenderAndUploadReadmeJob),
+    RerenderReadmes(RerenderReadmesJob),
+    RerenderReadmesBatch(RerenderReadmesBatchJob),
+    SyncMirror,
     UpdateDownloads,
 }
 
 impl Job {
     const DAILY_DB_MAINTENANCE: &str = "daily_db_maintenance";
+    const DELETE_CRATE_FILES: &str = "delete_crate_files";
     const DUMP_DB: &str = "dump_db";
+    const FINALIZE_PUBLISH: &str = "finalize_publish";
     const INDEX_ADD_CRATE: &str = "add_crate";
+    const INDEX_ADD_CRATES: &str = "add_crates";
+    const INDEX_CHECK: &str = "check_index";
+    const INDEX_DELETE_CRATE: &str = "delete_crate_from_index";
     const INDEX_SQUASH: &str = "squash_index";
     const INDEX_SYNC_TO_HTTP: &str = "update_crate_index";
     const INDEX_UPDATE_YANKED: &str = "sync_yanked";
     const NORMALIZE_INDEX: &str = "normalize_index";
+    const REBUILD_INDEX: &str = "rebuild_index";
+    const RENDER_AND_UPLOAD_DOCUMENTS: &str = "render_and_upload_documents";
     const RENDER_AND_UPLOAD_README: &str = "render_and_upload_readme";
+    const RERENDER_READMES: &str = "rerender_readmes";
+    pub(crate) const RERENDER_READMES_BATCH: &str = "rerender_readmes_batch";
+    const SYNC_MIRROR: &str = "sync_mirror";
     const UPDATE_DOWNLOADS: &str = "update_downloads";
 
+    /// Job types that can be coalesced into a single index commit when index
+    /// batching is enabled.
+    pub(crate) const BATCHED_INDEX_JOBS: &'static [&'static str] =
+        &[Self::INDEX_ADD_CRATE, Self::INDEX_UPDATE_YANKED];
+
+    /// Job types that update the index file of a single crate.
+    pub(crate) const CRATE_INDEX_JOBS: &'static [&'static str] = &[
+        Self::FINALIZE_PUBLISH,
+        Self::INDEX_ADD_CRATE,
+        Self::INDEX_ADD_CRATES,
+        Self::INDEX_DELETE_CRATE,
+        Self::INDEX_SYNC_TO_HTTP,
+        Self::INDEX_UPDATE_YANKED,
+    ];
+
     fn as_type_str(&self) -> &'static str {
         match self {
             Job::DailyDbMaintenance => Self::DAILY_DB_MAINTENANCE,
+            Job::DeleteCrateFiles(_) => Self::DELETE_CRATE_FILES,
             Job::DumpDb(_) => Self::DUMP_DB,
+            Job::FinalizePublish(_) => Self::FINALIZE_PUBLISH,
             Job::IndexAddCrate(_) => Self::INDEX_ADD_CRATE,
+            Job::IndexAddCrates(_) => Self::INDEX_ADD_CRATES,
+            Job::IndexCheck(_) => Self::INDEX_CHECK,
+            Job::IndexDeleteCrate(_) => Self::INDEX_DELETE_CRATE,
             Job::IndexSquash => Self::INDEX_SQUASH,
             Job::IndexSyncToHttp(_) => Self::INDEX_SYNC_TO_HTTP,
             Job::IndexUpdateYanked(_) => Self::INDEX_UPDATE_YANKED,
             Job::NormalizeIndex(_) => Self::NORMALIZE_INDEX,
+            Job::RebuildIndex => Self::REBUILD_INDEX,
+            Job::RenderAndUploadDocuments(_) => Self::RENDER_AND_UPLOAD_DOCUMENTS,
             Job::RenderAndUploadReadme(_) => Self::RENDER_AND_UPLOAD_README,
+            Job::RerenderReadmes(_) => Self::RERENDER_READMES,
+            Job::RerenderReadmesBatch(_) => Self::RERENDER_READMES_BATCH,
+            Job::SyncMirror => Self::SYNC_MIRROR,
             Job::UpdateDownloads => Self::UPDATE_DOWNLOADS,
         }
     }
@@ -51,13 +102,23 @@ impl Job {
     fn to_value(&self) -> serde_json::Result<serde_json::Value> {
         match self {
             Job::DailyDbMaintenance => Ok(serde_json::Value::Null),
+            Job::DeleteCrateFiles(inner) => serde_json::to_value(inner),
             Job::DumpDb(inner) => serde_json::to_value(inner),
+            Job::FinalizePublish(inner) => serde_json::to_value(inner),
             Job::IndexAddCrate(inner) => serde_json::to_value(inner),
+            Job::IndexAddCrates(inner) => serde_json::to_value(inner),
+            Job::IndexCheck(inner) => serde_json::to_value(inner),
+            Job::IndexDeleteCrate(inner) => serde_json::to_value(inner),
             Job::IndexSquash => Ok(serde_json::Value::Null),
             Job::IndexSyncToHttp(inner) => serde_json::to_value(inner),
             Job::IndexUpdateYanked(inner) => serde_json::to_value(inner),
             Job::NormalizeIndex(inner) => serde_json::to_value(inner),
+            Job::RebuildIndex => Ok(serde_json::Value::Null),
+            Job::RenderAndUploadDocuments(inner) => serde_json::to_value(inner),
             Job::RenderAndUploadReadme(inner) => serde_json::to_value(inner),
+            Job::RerenderReadmes(inner) => serde_json::to_value(inner),
+            Job::RerenderReadmesBatch(inner) => serde_json::to_value(inner),
+            Job::SyncMirror => Ok(serde_json::Value::Null),
             Job::UpdateDownloads => Ok(serde_json::Value::Null),
         }
     }
@@ -79,20 +140,44 @@ impl Job {
         use serde_json::from_value;
         Ok(match job_type {
             Self::DAILY_DB_MAINTENANCE => Job::DailyDbMaintenance,
+            Self::DELETE_CRATE_FILES => Job::DeleteCrateFiles(from_value(value)?),
             Self::DUMP_DB => Job::DumpDb(from_value(value)?),
+            Self::FINALIZE_PUBLISH => Job::FinalizePublish(from_value(value)?),
             Self::INDEX_ADD_CRATE => Job::IndexAddCrate(from_value(value)?),
+            Self::INDEX_ADD_CRATES => Job::IndexAddCrates(from_value(value)?),
+            Self::INDEX_CHECK => Job::IndexCheck(from_value(value)?),
+            Self::INDEX_DELETE_CRATE => Job::IndexDeleteCrate(from_value(value)?),
             Self::INDEX_SQUASH => Job::IndexSquash,
             Self::INDEX_SYNC_TO_HTTP => Job::IndexSyncToHttp(from_value(value)?),
             Self::INDEX_UPDATE_YANKED => Job::IndexUpdateYanked(from_value(value)?),
             Self::NORMALIZE_INDEX => Job::NormalizeIndex(from_value(value)?),
+            Self::REBUILD_INDEX => Job::RebuildIndex,
+            Self::RENDER_AND_UPLOAD_DOCUMENTS => Job::RenderAndUploadDocuments(from_value(value)?),
             Self::RENDER_AND_UPLOAD_README => Job::RenderAndUploadReadme(from_value(value)?),
+            Self::RERENDER_READMES => Job::RerenderReadmes(from_value(value)?),
+            Self::RERENDER_READMES_BATCH => Job::RerenderReadmesBatch(from_value(value)?),
+            Self::SYNC_MIRROR => Job::SyncMirror,
             Self::UPDATE_DOWNLOADS => Job::UpdateDownloads,
             job_type => Err(PerformError::from(format!("Unknown job type {job_type}")))?,
         })
     }
 
+    /// Returns the names of the crates whose index files are updated by this job.
+    pub(crate) fn index_crate_names(&self) -> Vec<&str> {
+        match self {
+            Job::FinalizePublish(args) => vec![&args.krate.name],
+            Job::IndexAddCrate(args) => vec![&args.krate.name],
+            Job::IndexAddCrates(args) => args.krates.iter().map(|k| k.name.as_str()).collect(),
+            Job::IndexDeleteCrate(args) => vec![&args.crate_name],
+            Job::IndexSyncToHttp(args) => vec![&args.crate_name],
+            Job::IndexUpdateYanked(args) => vec![&args.krate],
+            _ => vec![],
+        }
+    }
+
     pub(super) fn perform(
         self,
+        job_id: i64,
         env: &Option<Environment>,
         conn: &DieselPool,
     ) -> Result<(), PerformError> {
@@ -101,15 +186,39 @@ impl Job {
             .expect("Application should configure a background runner environment");
         match self {
             Job::DailyDbMaintenance => conn.with_connection(&worker::perform_daily_db_maintenance),
+            Job::DeleteCrateFiles(args) => {
+                worker::perform_delete_crate_files(env, &args.crate_name, &args.versions)
+            }
             Job::DumpDb(args) => worker::perform_dump_db(env, args.database_url, args.target_name),
-            Job::IndexAddCrate(args) => conn
-                .with_connection(&|conn| worker::perform_index_add_crate(env, conn, &args.krate)),
+            Job::FinalizePublish(args) => {
+                conn.with_connection(&|conn| worker::perform_finalize_publish(env, conn, &args))
+            }
+            Job::IndexAddCrate(args) => conn.with_connection(&|conn| match &env.index_batch {
+                Some(config) => worker::perform_index_batch(env, conn, config, job_id),
+                None => worker::perform_index_add_crate(env, conn, &args.krate),
+            }),
+            Job::IndexAddCrates(args) => conn
+                .with_connection(&|conn| worker::perform_index_add_crates(env, conn, &args.krates)),
+            Job::IndexCheck(args) => conn
+                .with_connection(&|conn| worker::perform_index_check(env, conn, args.sample_size)),
+            Job::IndexDeleteCrate(args) => conn.with_connection(&|conn| {
+                worker::perform_index_delete_crate(env, conn, &args.crate_name)
+            }),
             Job::IndexSquash => worker::perform_index_squash(env),
             Job::IndexSyncToHttp(args) => worker::perform_index_sync_to_http(env, args.crate_name),
-            Job::IndexUpdateYanked(args) => conn.with_connection(&|conn| {
-                worker::perform_index_update_yanked(env, conn, &args.krate, &args.version_num)
+            Job::IndexUpdateYanked(args) => conn.with_connection(&|conn| match &env.index_batch {
+                Some(config) => worker::perform_index_batch(env, conn, config, job_id),
+                None => {
+                    worker::perform_index_update_yanked(env, conn, &args.krate, &args.version_num)
+                }
             }),
             Job::NormalizeIndex(args) => worker::perform_normalize_index(env, args),
+            Job::RebuildIndex => {
+                conn.with_connection(&|conn| worker::perform_rebuild_index(env, conn))
+            }
+            Job::RenderAndUploadDocuments(args) => conn.with_connection(&|conn| {
+                worker::perform_render_and_upload_documents(conn, env, &args)
+            }),
             Job::RenderAndUploadReadme(args) => conn.with_connection(&|conn| {
                 worker::perform_render_and_upload_readme(
                     conn,
@@ -121,22 +230,65 @@ impl Job {
                     args.pkg_path_in_vcs.as_deref(),
                 )
             }),
+            Job::RerenderReadmes(args) => {
+                conn.with_connection(&|conn| worker::perform_rerender_readmes(conn, &args))
+            }
+            Job::RerenderReadmesBatch(args) => conn
+                .with_connection(&|conn| worker::perform_rerender_readmes_batch(conn, env, &args)),
+            Job::SyncMirror => conn.with_connection(&|conn| worker::perform_sync_mirror(env, conn)),
             Job::UpdateDownloads => conn.with_connection(&worker::perform_update_downloads),
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct DeleteCrateFilesJob {
+    pub(super) crate_name: String,
+    pub(super) versions: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct DumpDbJob {
     pub(super) database_url: String,
     pub(super) target_name: String,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct FinalizePublishJob {
+    pub(super) upload_id: i32,
+    pub(super) version_id: i32,
+    pub(super) krate: cargo_registry_index::Crate,
+    pub(super) readme: Option<String>,
+    pub(super) readme_file: String,
+    #[serde(default)]
+    pub(super) license_file: Option<String>,
+    pub(super) repository: Option<String>,
+    pub(super) max_unpack_size: u64,
+    /// The metadata of the crate before the upload, if the crate already existed
+    #[serde(default)]
+    pub(super) previous_metadata: Option<CrateMetadata>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct IndexAddCrateJob {
     pub(super) krate: cargo_registry_index::Crate,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct IndexAddCratesJob {
+    pub(super) krates: Vec<cargo_registry_index::Crate>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct IndexCheckJob {
+    pub(super) sample_size: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct IndexDeleteCrateJob {
+    pub(super) crate_name: String,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct IndexSyncToHttpJob {
     pub(super) crate_name: String,
@@ -153,6 +305,14 @@ pub struct NormalizeIndexJob {
     pub dry_run: bool,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RenderAndUploadDocumentsJob {
+    pub(super) version_id: i32,
+    pub(super) documents: PackageDocuments,
+    pub(super) base_url: Option<String>,
+    pub(super) pkg_path_in_vcs: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RenderAndUploadReadmeJob {
     pub(super) version_id: i32,
@@ -162,11 +322,89 @@ pub struct RenderAndUploadReadmeJob {
     pub(super) pkg_path_in_vcs: Option<String>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RerenderReadmesJob {
+    pub(super) batch_size: i64,
+    pub(super) crate_name: Option<String>,
+    #[serde(default)]
+    pub(super) older_than: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RerenderReadmesBatchJob {
+    pub(super) start_id: i32,
+    pub(super) end_id: i32,
+    pub(super) crate_name: Option<String>,
+    #[serde(default)]
+    pub(super) older_than: Option<NaiveDateTime>,
+}
+
+/// Whether the index files of a crate still have queued updates.
+pub(crate) struct PendingIndexUpdates {
+    pub git: bool,
+    pub http: bool,
+}
+
+impl PendingIndexUpdates {
+    pub(crate) fn for_crate(conn: &PgConnection, crate_name: &str) -> QueryResult<Self> {
+        // Updates of the HTTP index are queued once the git index has been updated
+        let git = Self::has_pending_jobs(
+            conn,
+            &[
+                Job::FINALIZE_PUBLISH,
+                Job::INDEX_ADD_CRATE,
+                Job::INDEX_ADD_CRATES,
+                Job::INDEX_UPDATE_YANKED,
+            ],
+            crate_name,
+        )?;
+        let http = git || Self::has_pending_jobs(conn, &[Job::INDEX_SYNC_TO_HTTP], crate_name)?;
+
+        Ok(Self { git, http })
+    }
+
+    /// Whether a job of one of the types is queued for the crate, without
+    /// loading the queue.
+    fn has_pending_jobs(
+        conn: &PgConnection,
+        job_types: &[&str],
+        crate_name: &str,
+    ) -> QueryResult<bool> {
+        use crate::schema::background_jobs;
+        use diesel::dsl::{exists, sql};
+        use diesel::sql_types::{Bool, Jsonb};
+
+        // The crate names are stored in a different field depending on the
+        // job type, see `Job::index_crate_names`
+        let patterns = json!([
+            { "krate": { "name": crate_name } },
+            { "krates": [{ "name": crate_name }] },
+            { "krate": crate_name },
+            { "crate_name": crate_name },
+        ]);
+
+        diesel::select(exists(
+            background_jobs::table
+                .filter(background_jobs::job_type.eq_any(job_types))
+                .filter(
+                    sql::<Bool>("EXISTS (SELECT 1 FROM jsonb_array_elements(")
+                        .bind::<Jsonb, _>(patterns)
+                        .sql(") AS pattern WHERE background_jobs.data @> pattern)"),
+                ),
+        ))
+        .get_result(conn)
+    }
+}
+
 pub struct Environment {
     index: Arc<Mutex<Repository>>,
     pub uploader: Uploader,
     http_client: AssertUnwindSafe<Client>,
     cloudfront: Option<CloudFront>,
+    index_batch: Option<IndexBatchConfig>,
+    mirror: Option<MirrorConfig>,
+    image_proxy: Option<Arc<ImageProxyConfig>>,
+    highlight_code_blocks: bool,
 }
 
 impl Clone for Environment {
@@ -176,6 +414,10 @@ impl Clone for Environment {
             uploader: self.uploader.clone(),
             http_client: AssertUnwindSafe(self.http_client.0.clone()),
             cloudfront: self.cloudfront.clone(),
+            index_batch: self.index_batch.clone(),
+            mirror: self.mirror.clone(),
+            image_proxy: self.image_proxy.clone(),
+            highlight_code_blocks: self.highlight_code_blocks,
         }
     }
 }
@@ -206,9 +448,43 @@ impl Environment {
             uploader,
             http_client: AssertUnwindSafe(http_client),
             cloudfront,
+            index_batch: None,
+            mirror: None,
+            image_proxy: None,
+            highlight_code_blocks: false,
         }
     }
 
+    /// Enables coalescing of index jobs into batched git commits.
+    ///
+    /// See [`IndexBatchConfig`] for more details.
+    pub fn with_index_batch(mut self, index_batch: Option<IndexBatchConfig>) -> Self {
+        self.index_batch = index_batch;
+        self
+    }
+
+    /// Enables syncing this registry from an upstream registry.
+    ///
+    /// See [`MirrorConfig`] for more details.
+    pub fn with_mirror(mut self, mirror: Option<MirrorConfig>) -> Self {
+        self.mirror = mirror;
+        self
+    }
+
+    /// Enables loading the images of rendered readmes through the image proxy.
+    ///
+    /// See [`ImageProxyConfig`] for more details.
+    pub fn with_image_proxy(mut self, image_proxy: Option<ImageProxyConfig>) -> Self {
+        self.image_proxy = image_proxy.map(Arc::new);
+        self
+    }
+
+    /// Enables syntax highlighting of code blocks when readmes are rendered.
+    pub fn with_code_highlighting(mut self, highlight_code_blocks: bool) -> Self {
+        self.highlight_code_blocks = highlight_code_blocks;
+        self
+    }
+
     pub fn lock_index(&self) -> Result<MutexGuard<'_, Repository>, PerformError> {
         let repo = self.index.lock().unwrap_or_else(PoisonError::into_inner);
         repo.reset_head()?;
@@ -223,4 +499,19 @@ impl Environment {
     pub(crate) fn cloudfront(&self) -> Option<&CloudFront> {
         self.cloudfront.as_ref()
     }
+
+    pub(crate) fn mirror(&self) -> Option<&MirrorConfig> {
+        self.mirror.as_ref()
+    }
+
+    /// Returns the options for rendering readmes and other documents of crates.
+    pub(crate) fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            image_proxy: self
+                .image_proxy
+                .clone()
+                .map(|image_proxy| image_proxy as Arc<dyn ImageProxy>),
+            highlight_code: self.highlight_code_blocks,
+        }
+    }
 }
@@ -17,6 +17,7 @@ extern crate tracing;
 
 use cargo_registry::config;
 use cargo_registry::worker::cloudfront::CloudFront;
+use cargo_registry::worker::IndexBatchConfig;
 use cargo_registry::{background_jobs::*, db};
 use cargo_registry_index::{Repository, RepositoryConfig};
 use reqwest::blocking::Client;
@@ -63,6 +64,13 @@ fn main() {
     info!("Index cloned");
 
     let cloudfront = CloudFront::from_environment();
+    let index_batch = IndexBatchConfig::from_environment();
+    if let Some(index_batch) = &index_batch {
+        info!(?index_batch, "Batching index updates");
+    }
+    if let Some(mirror) = &config.mirror {
+        info!(upstream_index = %mirror.upstream_index, "Mirroring upstream registry");
+    }
 
     let build_runner = || {
         let client = Client::builder()
@@ -74,7 +82,11 @@ fn main() {
             uploader.clone(),
             client,
             cloudfront.clone(),
-        );
+        )
+        .with_index_batch(index_batch.clone())
+        .with_mirror(config.mirror.clone())
+        .with_image_proxy(config.image_proxy.clone())
+        .with_code_highlighting(config.highlight_code_blocks);
         swirl::Runner::production_runner(environment, db_url.clone(), job_start_timeout)
     };
     let mut runner = build_runner();
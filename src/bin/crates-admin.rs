@@ -1,8 +1,9 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 use cargo_registry::admin::{
-    delete_crate, delete_version, enqueue_job, git_import, migrate, populate, render_readmes,
-    test_pagerduty, transfer_crates, upload_index, verify_token, yank_version,
+    block, categories, delete_crate, delete_version, enqueue_job, git_import, keywords, migrate,
+    normalize_licenses, populate, rebuild_index, render_readmes, test_pagerduty, transfer_crates,
+    upload_index, verify_token, yank_version,
 };
 
 #[derive(clap::Parser, Debug)]
@@ -10,6 +11,7 @@ use cargo_registry::admin::{
 enum Command {
     DeleteCrate(delete_crate::Opts),
     DeleteVersion(delete_version::Opts),
+    NormalizeLicenses(normalize_licenses::Opts),
     Populate(populate::Opts),
     RenderReadmes(render_readmes::Opts),
     TestPagerduty(test_pagerduty::Opts),
@@ -19,8 +21,15 @@ enum Command {
     UploadIndex(upload_index::Opts),
     YankVersion(yank_version::Opts),
     GitImport(git_import::Opts),
+    RebuildIndex(rebuild_index::Opts),
     #[clap(subcommand)]
     EnqueueJob(enqueue_job::Command),
+    #[clap(subcommand)]
+    Block(block::Command),
+    #[clap(subcommand)]
+    Categories(categories::Command),
+    #[clap(subcommand)]
+    Keywords(keywords::Command),
 }
 
 fn main() -> anyhow::Result<()> {
@@ -36,6 +45,7 @@ fn main() -> anyhow::Result<()> {
     match command {
         Command::DeleteCrate(opts) => delete_crate::run(opts),
         Command::DeleteVersion(opts) => delete_version::run(opts),
+        Command::NormalizeLicenses(opts) => normalize_licenses::run(opts)?,
         Command::Populate(opts) => populate::run(opts),
         Command::RenderReadmes(opts) => render_readmes::run(opts)?,
         Command::TestPagerduty(opts) => test_pagerduty::run(opts)?,
@@ -45,7 +55,11 @@ fn main() -> anyhow::Result<()> {
         Command::UploadIndex(opts) => upload_index::run(opts)?,
         Command::YankVersion(opts) => yank_version::run(opts),
         Command::GitImport(opts) => git_import::run(opts)?,
+        Command::RebuildIndex(opts) => rebuild_index::run(opts)?,
         Command::EnqueueJob(command) => enqueue_job::run(command)?,
+        Command::Block(command) => block::run(command)?,
+        Command::Categories(command) => categories::run(command)?,
+        Command::Keywords(command) => keywords::run(command)?,
     }
 
     Ok(())
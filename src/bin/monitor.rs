@@ -7,7 +7,7 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 use anyhow::Result;
-use cargo_registry::{admin::on_call, db, schema::*};
+use cargo_registry::{admin::on_call, db, models::IndexConsistencyCheck, schema::*};
 use diesel::prelude::*;
 
 fn main() -> Result<()> {
@@ -16,6 +16,7 @@ fn main() -> Result<()> {
     check_failing_background_jobs(&conn)?;
     check_stalled_update_downloads(&conn)?;
     check_spam_attack(&conn)?;
+    check_index_consistency(&conn)?;
     Ok(())
 }
 
@@ -148,6 +149,41 @@ fn check_spam_attack(conn: &PgConnection) -> Result<()> {
     Ok(())
 }
 
+/// Check the result of the most recent `check_index` background job
+fn check_index_consistency(conn: &PgConnection) -> Result<()> {
+    const EVENT_KEY: &str = "index_consistency";
+
+    println!("Checking the result of the last index consistency check");
+
+    let event = match IndexConsistencyCheck::latest(conn)? {
+        Some(check) if !check.is_consistent() => {
+            let mut crate_names = check.git_mismatches.clone();
+            crate_names.extend(check.http_mismatches.iter().cloned());
+            crate_names.sort();
+            crate_names.dedup();
+
+            on_call::Event::Trigger {
+                incident_key: Some(EVENT_KEY.into()),
+                description: format!(
+                    "The index check at {} found {} crates with mismatched git index files \
+                     and {} crates with mismatched HTTP index files: {}",
+                    check.checked_at,
+                    check.git_mismatches.len(),
+                    check.http_mismatches.len(),
+                    crate_names.join(", ")
+                ),
+            }
+        }
+        _ => on_call::Event::Resolve {
+            incident_key: EVENT_KEY.into(),
+            description: Some("No index inconsistencies detected".into()),
+        },
+    };
+
+    log_and_trigger_event(event)?;
+    Ok(())
+}
+
 fn log_and_trigger_event(event: on_call::Event) -> Result<()> {
     match event {
         on_call::Event::Trigger {
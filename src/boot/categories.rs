@@ -1,5 +1,9 @@
-// Sync available crate categories from `src/categories.toml`.
-// Runs when the server is started.
+// Seed the crate categories from `src/boot/categories.toml`.
+// Runs when the database is migrated.
+//
+// Categories are managed with the admin endpoints and `crates-admin categories`
+// afterwards, so existing categories are never updated or deleted here, and the
+// former slugs of renamed and merged categories are not added again.
 
 use anyhow::{Context, Result};
 use diesel::prelude::*;
@@ -75,40 +79,36 @@ fn categories_from_toml(
     Ok(result)
 }
 
-pub fn sync_with_connection(toml_str: &str, conn: &PgConnection) -> Result<()> {
+pub fn seed_with_connection(toml_str: &str, conn: &PgConnection) -> Result<()> {
     use crate::schema::categories::dsl::*;
-    use diesel::dsl::all;
-    use diesel::pg::upsert::excluded;
+    use crate::schema::category_redirects;
 
     let toml: toml::value::Table =
         toml::from_str(toml_str).context("Could not parse categories toml")?;
 
-    let to_insert = categories_from_toml(&toml, None)
-        .expect("Could not convert categories from TOML")
-        .into_iter()
-        .map(|c| {
-            (
-                slug.eq(c.slug.to_lowercase()),
-                category.eq(c.name),
-                description.eq(c.description),
-            )
-        })
-        .collect::<Vec<_>>();
+    let seeds = categories_from_toml(&toml, None).expect("Could not convert categories from TOML");
 
     conn.transaction(|| {
-        let slugs: Vec<String> = diesel::insert_into(categories)
+        let redirected_slugs: Vec<String> = category_redirects::table
+            .select(category_redirects::slug)
+            .load(conn)?;
+
+        let to_insert = seeds
+            .into_iter()
+            .filter(|c| !redirected_slugs.contains(&c.slug.to_lowercase()))
+            .map(|c| {
+                (
+                    slug.eq(c.slug.to_lowercase()),
+                    category.eq(c.name),
+                    description.eq(c.description),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        diesel::insert_into(categories)
             .values(&to_insert)
             .on_conflict(slug)
-            .do_update()
-            .set((
-                category.eq(excluded(category)),
-                description.eq(excluded(description)),
-            ))
-            .returning(slug)
-            .get_results(conn)?;
-
-        diesel::delete(categories)
-            .filter(slug.ne(all(slugs)))
+            .do_nothing()
             .execute(conn)?;
         Ok(())
     })
@@ -2,14 +2,21 @@ use anyhow::{anyhow, Context};
 use ipnetwork::IpNetwork;
 
 use crate::publish_rate_limit::PublishRateLimit;
+use crate::rate_limiter::RateLimiter;
 use crate::{env, env_optional, uploaders::Uploader, Env};
 
 mod balance_capacity;
 mod base;
 mod database_pools;
+mod image_proxy;
+mod license_policy;
+mod mirror;
 
 pub use self::base::Base;
 pub use self::database_pools::{DatabasePools, DbPoolConfig};
+pub use self::image_proxy::{ImageFetchError, ImageProxyConfig};
+pub use self::license_policy::LicensePolicy;
+pub use self::mirror::MirrorConfig;
 pub use crate::config::balance_capacity::BalanceCapacityConfig;
 use http::HeaderValue;
 use std::collections::HashSet;
@@ -17,6 +24,7 @@ use std::time::Duration;
 
 const DEFAULT_VERSION_ID_CACHE_SIZE: u64 = 10_000;
 const DEFAULT_VERSION_ID_CACHE_TTL: u64 = 5 * 60; // 5 minutes
+const DEFAULT_BLOCKED_TRAFFIC_RULES_TTL: u64 = 30; // 30 seconds
 
 pub struct Server {
     pub base: Base,
@@ -29,15 +37,22 @@ pub struct Server {
     pub max_unpack_size: u64,
     pub publish_rate_limit: PublishRateLimit,
     pub new_version_rate_limit: Option<u32>,
+    pub rate_limiter: RateLimiter,
     pub blocked_traffic: Vec<(String, Vec<String>)>,
+    pub blocked_traffic_rules_ttl: Duration,
     pub max_allowed_page_offset: u32,
     pub page_offset_ua_blocklist: Vec<String>,
     pub page_offset_cidr_blocklist: Vec<IpNetwork>,
     pub excluded_crate_names: Vec<String>,
+    pub allowed_dependency_registries: Vec<String>,
     pub domain_name: String,
     pub allowed_origins: AllowedOrigins,
     pub downloads_persist_interval_ms: usize,
     pub ownership_invitations_expiration_days: u64,
+    pub crate_deletion_max_age_hours: u64,
+    pub crate_deletion_max_downloads: i32,
+    pub deleted_crate_name_cooldown_days: u64,
+    pub require_team_owner_invitations: bool,
     pub metrics_authorization_token: Option<String>,
     pub use_test_database_pool: bool,
     pub instance_metrics_log_every_seconds: Option<u64>,
@@ -47,6 +62,10 @@ pub struct Server {
     pub version_id_cache_ttl: Duration,
     pub cdn_user_agent: String,
     pub balance_capacity: BalanceCapacityConfig,
+    pub mirror: Option<MirrorConfig>,
+    pub image_proxy: Option<ImageProxyConfig>,
+    pub highlight_code_blocks: bool,
+    pub license_policy: LicensePolicy,
 }
 
 impl Default for Server {
@@ -56,6 +75,9 @@ impl Default for Server {
     ///
     /// - `Config::max_upload_size`: 10MiB
     /// - `Config::ownership_invitations_expiration_days`: 30
+    /// - `Config::crate_deletion_max_age_hours`: 72
+    /// - `Config::crate_deletion_max_downloads`: 500
+    /// - `Config::deleted_crate_name_cooldown_days`: 30
     ///
     /// Pulls values from the following environment variables:
     ///
@@ -64,6 +86,8 @@ impl Default for Server {
     /// - `GH_CLIENT_SECRET`: The client secret of the associated GitHub application.
     /// - `BLOCKED_TRAFFIC`: A list of headers and environment variables to use for blocking
     ///   traffic. See the `block_traffic` module for more documentation.
+    /// - `BLOCKED_TRAFFIC_RULES_TTL`: How many seconds the blocked traffic rules from the database
+    ///   are cached before they are reloaded. Defaults to 30.
     /// - `DOWNLOADS_PERSIST_INTERVAL_MS`: how frequent to persist download counts (in ms).
     /// - `METRICS_AUTHORIZATION_TOKEN`: authorization token needed to query metrics. If missing,
     ///   querying metrics will be completely disabled.
@@ -82,6 +106,24 @@ impl Default for Server {
     ///   endpoint even with a healthy database pool.
     /// - `BLOCKED_ROUTES`: A comma separated list of HTTP route patterns that are manually blocked
     ///   by an operator (e.g. `/crates/:crate_id/:version/download`).
+    /// - `RATE_LIMITER_<CLASS>_RATE_SECONDS` and `RATE_LIMITER_<CLASS>_BURST`: The token bucket
+    ///   of a class of rate limited routes, e.g. `RATE_LIMITER_SEARCH_BURST`. See the
+    ///   `rate_limiter` module for more documentation.
+    /// - `ALLOWED_DEPENDENCY_REGISTRIES`: A comma separated list of index URLs of other registries
+    ///   (e.g. `https://github.com/rust-lang/crates.io-index`) that published crates may depend on.
+    ///   If not set or empty, cross-registry dependencies are rejected.
+    /// - `REQUIRE_TEAM_OWNER_INVITATIONS`: If set, teams are invited to become crate owners
+    ///   instead of being added immediately, and a maintainer of the team has to accept.
+    /// - `MIRROR_UPSTREAM_INDEX`: The git index of an upstream registry. If set, this registry
+    ///   runs as a read-only mirror of it. See [`MirrorConfig`] for more details.
+    /// - `IMAGE_PROXY_KEY`: The key that the URLs of proxied readme images are signed with. If
+    ///   set, readme images are served through the image proxy. See [`ImageProxyConfig`] for
+    ///   more details.
+    /// - `HIGHLIGHT_CODE_BLOCKS`: If set, code blocks in Rust, TOML, shell and JSON are
+    ///   syntax highlighted when readmes are rendered, instead of by the frontend.
+    /// - `LICENSE_POLICY_ALLOW`, `LICENSE_POLICY_DENY`, `LICENSE_POLICY_DENY_COPYLEFT` and
+    ///   `LICENSE_POLICY_REQUIRE_OSI_APPROVED`: The licenses that published crates may use. See
+    ///   [`LicensePolicy`] for more details.
     ///
     /// # Panics
     ///
@@ -100,7 +142,9 @@ impl Default for Server {
                 Some(s) if s.is_empty() => vec![],
                 Some(s) => s
                     .split(',')
-                    .map(parse_cidr_block)
+                    .map(|block| {
+                        parse_cidr_block(block).context("Invalid WEB_PAGE_OFFSET_CIDR_BLOCKLIST")
+                    })
                     .collect::<Result<_, _>>()
                     .unwrap(),
             };
@@ -111,6 +155,12 @@ impl Default for Server {
             Some(s) if s.is_empty() => vec![],
             Some(s) => s.split(',').map(String::from).collect(),
         };
+        let allowed_dependency_registries =
+            match env_optional::<String>("ALLOWED_DEPENDENCY_REGISTRIES") {
+                None => vec![],
+                Some(s) if s.is_empty() => vec![],
+                Some(s) => s.split(',').map(String::from).collect(),
+            };
         Server {
             db: DatabasePools::full_from_environment(&base),
             base,
@@ -122,11 +172,13 @@ impl Default for Server {
             max_unpack_size: 512 * 1024 * 1024, // 512 MB max when decompressed
             publish_rate_limit: Default::default(),
             new_version_rate_limit: env_optional("MAX_NEW_VERSIONS_DAILY"),
+            rate_limiter: RateLimiter::from_environment(),
             blocked_traffic: blocked_traffic(),
             max_allowed_page_offset: env_optional("WEB_MAX_ALLOWED_PAGE_OFFSET").unwrap_or(200),
             page_offset_ua_blocklist,
             page_offset_cidr_blocklist,
             excluded_crate_names,
+            allowed_dependency_registries,
             domain_name: domain_name(),
             allowed_origins,
             downloads_persist_interval_ms: dotenv::var("DOWNLOADS_PERSIST_INTERVAL_MS")
@@ -137,6 +189,10 @@ impl Default for Server {
                 })
                 .unwrap_or(60_000), // 1 minute
             ownership_invitations_expiration_days: 30,
+            crate_deletion_max_age_hours: 72,
+            crate_deletion_max_downloads: 500,
+            deleted_crate_name_cooldown_days: 30,
+            require_team_owner_invitations: dotenv::var("REQUIRE_TEAM_OWNER_INVITATIONS").is_ok(),
             metrics_authorization_token: dotenv::var("METRICS_AUTHORIZATION_TOKEN").ok(),
             use_test_database_pool: false,
             instance_metrics_log_every_seconds: env_optional("INSTANCE_METRICS_LOG_EVERY_SECONDS"),
@@ -144,6 +200,10 @@ impl Default for Server {
             blocked_routes: env_optional("BLOCKED_ROUTES")
                 .map(|routes: String| routes.split(',').map(|s| s.into()).collect())
                 .unwrap_or_else(HashSet::new),
+            blocked_traffic_rules_ttl: Duration::from_secs(
+                env_optional("BLOCKED_TRAFFIC_RULES_TTL")
+                    .unwrap_or(DEFAULT_BLOCKED_TRAFFIC_RULES_TTL),
+            ),
             version_id_cache_size: env_optional("VERSION_ID_CACHE_SIZE")
                 .unwrap_or(DEFAULT_VERSION_ID_CACHE_SIZE),
             version_id_cache_ttl: Duration::from_secs(
@@ -152,6 +212,10 @@ impl Default for Server {
             cdn_user_agent: dotenv::var("WEB_CDN_USER_AGENT")
                 .unwrap_or_else(|_| "Amazon CloudFront".into()),
             balance_capacity: BalanceCapacityConfig::from_environment(),
+            mirror: MirrorConfig::from_environment(),
+            image_proxy: ImageProxyConfig::from_environment(&domain_name()),
+            highlight_code_blocks: highlight_code_blocks(),
+            license_policy: LicensePolicy::from_environment(),
         }
     }
 }
@@ -170,19 +234,25 @@ pub(crate) fn domain_name() -> String {
     dotenv::var("DOMAIN_NAME").unwrap_or_else(|_| "crates.io".into())
 }
 
+pub(crate) fn highlight_code_blocks() -> bool {
+    dotenv::var("HIGHLIGHT_CODE_BLOCKS").is_ok()
+}
+
 /// Parses a CIDR block string to a valid `IpNetwork` struct.
 ///
-/// The purpose is to be able to block IP ranges that overload the API that uses pagination.
+/// The purpose is to be able to block IP ranges that overload the API, either through
+/// `WEB_PAGE_OFFSET_CIDR_BLOCKLIST` for the API that uses pagination or through the
+/// blocked traffic rules in the database.
 ///
 /// The minimum number of bits for a host prefix must be
 ///
 /// * at least 16 for IPv4 based CIDRs.
 /// * at least 64 for IPv6 based CIDRs
 ///
-fn parse_cidr_block(block: &str) -> anyhow::Result<IpNetwork> {
+pub(crate) fn parse_cidr_block(block: &str) -> anyhow::Result<IpNetwork> {
     let cidr = block
         .parse()
-        .context("WEB_PAGE_OFFSET_CIDR_BLOCKLIST must contain IPv4 or IPv6 CIDR blocks.")?;
+        .with_context(|| format!("`{block}` is not an IPv4 or IPv6 CIDR block."))?;
 
     let host_prefix = match cidr {
         IpNetwork::V4(_) => 16,
@@ -190,7 +260,7 @@ fn parse_cidr_block(block: &str) -> anyhow::Result<IpNetwork> {
     };
 
     if cidr.prefix() < host_prefix {
-        return Err(anyhow!("WEB_PAGE_OFFSET_CIDR_BLOCKLIST only allows CIDR blocks with a host prefix of at least 16 bits (IPv4) or 64 bits (IPv6)."));
+        return Err(anyhow!("Only CIDR blocks with a host prefix of at least 16 bits (IPv4) or 64 bits (IPv6) are allowed."));
     }
 
     Ok(cidr)
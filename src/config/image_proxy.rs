@@ -0,0 +1,295 @@
+use cargo_registry_markdown::ImageProxy;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use ring::hmac;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use url::{Host, Url};
+
+const DEFAULT_MAX_SIZE: u64 = 5 * 1024 * 1024; // 5 MiB
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REDIRECTS: usize = 5;
+
+/// Configuration of the image proxy, which serves the images of rendered readmes from
+/// our own domain, so that readers don't load them from arbitrary third-party hosts.
+///
+/// The proxy is enabled by setting `IMAGE_PROXY_KEY` to the secret that the URLs of
+/// proxied images are signed with. `IMAGE_PROXY_MAX_SIZE` limits the size of proxied
+/// images in bytes, and defaults to 5 MiB.
+#[derive(Clone, Debug)]
+pub struct ImageProxyConfig {
+    /// The URL of the image proxy endpoint.
+    pub base_url: Url,
+    /// The maximum size of a proxied image in bytes.
+    pub max_size: u64,
+    /// Whether images may be fetched from the loopback interface and private networks,
+    /// which is only useful in development and tests.
+    pub allow_local_hosts: bool,
+    key: hmac::Key,
+}
+
+impl ImageProxyConfig {
+    pub fn new(base_url: Url, key: &[u8]) -> Self {
+        Self {
+            base_url,
+            max_size: DEFAULT_MAX_SIZE,
+            allow_local_hosts: false,
+            key: hmac::Key::new(hmac::HMAC_SHA256, key),
+        }
+    }
+
+    pub fn from_environment(domain_name: &str) -> Option<Self> {
+        let key = dotenv::var("IMAGE_PROXY_KEY").ok()?;
+        let base_url = Url::parse(&format!("https://{domain_name}/api/v1/image-proxy"))
+            .expect("DOMAIN_NAME must be a valid host name");
+
+        let mut config = Self::new(base_url, key.as_bytes());
+        if let Ok(max_size) = dotenv::var("IMAGE_PROXY_MAX_SIZE") {
+            config.max_size = max_size
+                .parse()
+                .expect("IMAGE_PROXY_MAX_SIZE must be a number of bytes");
+        }
+        Some(config)
+    }
+
+    /// Returns the signature of an image URL, which proves that the URL was proxied
+    /// by us and not by someone using the proxy for their own images.
+    fn signature(&self, url: &str) -> String {
+        hex::encode(hmac::sign(&self.key, url.as_bytes()))
+    }
+
+    /// Checks that the signature of an image URL is valid.
+    pub fn verify(&self, url: &str, signature: &str) -> bool {
+        hex::decode(signature)
+            .map(|signature| hmac::verify(&self.key, url.as_bytes(), &signature).is_ok())
+            .unwrap_or(false)
+    }
+
+    /// Checks whether images may be fetched from the host of the given URL.
+    ///
+    /// This only looks at the URL itself, the addresses that a domain resolves
+    /// to are checked by `fetch`.
+    pub fn is_allowed_host(&self, url: &Url) -> bool {
+        if self.allow_local_hosts {
+            return true;
+        }
+
+        match url.host() {
+            Some(Host::Domain(domain)) => domain != "localhost" && !domain.ends_with(".localhost"),
+            Some(Host::Ipv4(ip)) => is_global_ip(ip.into()),
+            Some(Host::Ipv6(ip)) => is_global_ip(ip.into()),
+            None => false,
+        }
+    }
+
+    /// Resolves the host of the given URL, and returns its addresses if images
+    /// may be fetched from all of them.
+    fn allowed_addrs(&self, url: &Url) -> Option<Vec<SocketAddr>> {
+        if !matches!(url.scheme(), "http" | "https") || !self.is_allowed_host(url) {
+            return None;
+        }
+
+        let addrs = url.socket_addrs(|| None).ok()?;
+        let allowed = self.allow_local_hosts || addrs.iter().all(|addr| is_global_ip(addr.ip()));
+        if addrs.is_empty() || !allowed {
+            return None;
+        }
+        Some(addrs)
+    }
+
+    /// Fetches an image, following redirects to allowed hosts.
+    ///
+    /// The host of every request is resolved and checked before connecting to
+    /// the checked addresses, so that a domain can't point the proxy to a local
+    /// address, e.g. by resolving to another address once it was checked.
+    pub fn fetch(&self, url: Url) -> Result<Response, ImageFetchError> {
+        let mut url = url;
+        for _ in 0..=MAX_REDIRECTS {
+            let addrs = self
+                .allowed_addrs(&url)
+                .ok_or(ImageFetchError::HostNotAllowed)?;
+
+            // A proxy would resolve the host again by itself
+            let mut client = Client::builder()
+                .timeout(FETCH_TIMEOUT)
+                .redirect(Policy::none())
+                .no_proxy();
+            if let Some(Host::Domain(domain)) = url.host() {
+                client = client.resolve_to_addrs(domain, &addrs);
+            }
+
+            let response = client.build()?.get(url.clone()).send()?;
+            if !response.status().is_redirection() {
+                return Ok(response);
+            }
+
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or(ImageFetchError::InvalidRedirect)?;
+            url = url
+                .join(location)
+                .map_err(|_| ImageFetchError::InvalidRedirect)?;
+        }
+
+        Err(ImageFetchError::TooManyRedirects)
+    }
+}
+
+/// The reasons why `ImageProxyConfig::fetch` failed to fetch an image.
+#[derive(Debug, thiserror::Error)]
+pub enum ImageFetchError {
+    #[error("images cannot be fetched from this URL")]
+    HostNotAllowed,
+    #[error("failed to fetch image: invalid redirect")]
+    InvalidRedirect,
+    #[error("failed to fetch image: too many redirects")]
+    TooManyRedirects,
+    #[error("failed to fetch image: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+impl ImageProxy for ImageProxyConfig {
+    fn proxy_url(&self, url: &Url) -> String {
+        let mut proxy_url = self.base_url.clone();
+        proxy_url
+            .query_pairs_mut()
+            .append_pair("url", url.as_str())
+            .append_pair("signature", &self.signature(url.as_str()));
+        proxy_url.into()
+    }
+}
+
+fn is_global_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            // "This network" (0.0.0.0/8), shared address space (100.64.0.0/10),
+            // IETF protocol assignments (192.0.0.0/24), benchmarking
+            // (198.18.0.0/15) and reserved (240.0.0.0/4) addresses.
+            let is_special = octets[0] == 0
+                || (octets[0] == 100 && (octets[1] & 0xc0) == 64)
+                || (octets[0] == 192 && octets[1] == 0 && octets[2] == 0)
+                || (octets[0] == 198 && (octets[1] & 0xfe) == 18)
+                || octets[0] >= 240;
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_multicast()
+                || is_special)
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            // Unique local (fc00::/7) and link-local (fe80::/10) addresses.
+            let is_local = (segments[0] & 0xfe00) == 0xfc00 || (segments[0] & 0xffc0) == 0xfe80;
+            // IPv4-compatible (::a.b.c.d), NAT64 (64:ff9b::/96) and 6to4
+            // (2002::/16) addresses, which can all reach IPv4 hosts.
+            let is_translated = segments[..6] == [0; 6]
+                || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+                || segments[0] == 0x2002;
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || is_local
+                || is_translated)
+                && ip
+                    .to_ipv4_mapped()
+                    .map_or(true, |ip| is_global_ip(ip.into()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ImageProxyConfig {
+        let base_url = Url::parse("https://crates.io/api/v1/image-proxy").unwrap();
+        ImageProxyConfig::new(base_url, b"secret")
+    }
+
+    #[test]
+    fn proxy_urls_are_signed() {
+        let config = config();
+        let url = Url::parse("https://example.com/logo.png?size=2").unwrap();
+
+        let proxy_url = Url::parse(&config.proxy_url(&url)).unwrap();
+        assert_eq!(proxy_url.path(), "/api/v1/image-proxy");
+
+        let params = proxy_url.query_pairs().collect::<Vec<_>>();
+        assert_eq!(params[0], ("url".into(), url.as_str().into()));
+        assert_eq!(params[1].0, "signature");
+        assert!(config.verify(url.as_str(), &params[1].1));
+        assert!(!config.verify("https://example.com/other.png", &params[1].1));
+        assert!(!config.verify(url.as_str(), "not hex"));
+
+        let other_key = ImageProxyConfig::new(config.base_url, b"other secret");
+        assert!(!other_key.verify(url.as_str(), &params[1].1));
+    }
+
+    #[test]
+    fn local_hosts_are_not_allowed() {
+        let mut config = config();
+        let allowed = |config: &ImageProxyConfig, url: &str| {
+            config.is_allowed_host(&Url::parse(url).unwrap())
+        };
+
+        assert!(allowed(&config, "https://example.com/logo.png"));
+        assert!(allowed(&config, "http://93.184.216.34/logo.png"));
+        assert!(allowed(&config, "http://[2606:2800:220:1::1]/logo.png"));
+        assert!(!allowed(&config, "http://localhost:8888/logo.png"));
+        assert!(!allowed(&config, "http://127.0.0.1/logo.png"));
+        assert!(!allowed(&config, "http://10.1.2.3/logo.png"));
+        assert!(!allowed(&config, "http://169.254.169.254/latest/meta-data"));
+        assert!(!allowed(&config, "http://[::1]/logo.png"));
+        assert!(!allowed(&config, "http://[::ffff:192.168.0.1]/logo.png"));
+        assert!(!allowed(&config, "http://[fd00::1]/logo.png"));
+        assert!(!allowed(&config, "http://0.1.2.3/logo.png"));
+        assert!(!allowed(&config, "http://100.64.0.1/logo.png"));
+        assert!(!allowed(&config, "http://100.127.255.254/logo.png"));
+        assert!(!allowed(&config, "http://192.0.0.8/logo.png"));
+        assert!(!allowed(&config, "http://198.18.0.1/logo.png"));
+        assert!(!allowed(&config, "http://198.19.255.254/logo.png"));
+        assert!(!allowed(&config, "http://224.0.0.1/logo.png"));
+        assert!(!allowed(&config, "http://240.0.0.1/logo.png"));
+        assert!(!allowed(&config, "http://255.255.255.255/logo.png"));
+        assert!(!allowed(&config, "http://[ff02::1]/logo.png"));
+        assert!(!allowed(&config, "http://[64:ff9b::7f00:1]/logo.png"));
+        assert!(!allowed(&config, "http://[2002:7f00:1::1]/logo.png"));
+        assert!(!allowed(&config, "http://[::7f00:1]/logo.png"));
+        assert!(!allowed(&config, "http://[::5db8:d822]/logo.png"));
+        assert!(allowed(&config, "http://100.128.0.1/logo.png"));
+        assert!(allowed(&config, "http://198.20.0.1/logo.png"));
+
+        config.allow_local_hosts = true;
+        assert!(allowed(&config, "http://127.0.0.1/logo.png"));
+    }
+
+    #[test]
+    fn local_addresses_are_not_allowed() {
+        let mut config = config();
+        let addrs =
+            |config: &ImageProxyConfig, url: &str| config.allowed_addrs(&Url::parse(url).unwrap());
+
+        assert_eq!(
+            addrs(&config, "http://93.184.216.34/logo.png"),
+            Some(vec!["93.184.216.34:80".parse().unwrap()])
+        );
+        assert_none!(addrs(&config, "http://169.254.169.254/latest/meta-data"));
+        assert_none!(addrs(&config, "https://[::1]/logo.png"));
+        assert_none!(addrs(&config, "http://100.64.0.1/logo.png"));
+        assert_none!(addrs(
+            &config,
+            "http://[64:ff9b::a9fe:a9fe]/latest/meta-data"
+        ));
+        assert_none!(addrs(&config, "ftp://93.184.216.34/logo.png"));
+
+        config.allow_local_hosts = true;
+        assert_eq!(
+            addrs(&config, "https://127.0.0.1/logo.png"),
+            Some(vec!["127.0.0.1:443".parse().unwrap()])
+        );
+    }
+}
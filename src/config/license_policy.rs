@@ -0,0 +1,202 @@
+use spdx::{LicenseItem, LicenseReq};
+
+use crate::models::license::normalized_req;
+use crate::models::LicenseExpression;
+use crate::util::errors::{cargo_err, AppResult};
+
+/// The licenses that crates published to this registry may use.
+///
+/// The policy is configured with `LICENSE_POLICY_ALLOW` and `LICENSE_POLICY_DENY`,
+/// comma separated lists of SPDX license identifiers, and with
+/// `LICENSE_POLICY_DENY_COPYLEFT` and `LICENSE_POLICY_REQUIRE_OSI_APPROVED`. If none of
+/// them are set, any valid license expression is accepted.
+///
+/// An expression is accepted if it can be satisfied by licenses that the policy
+/// allows, so `MIT OR GPL-3.0-only` is accepted even if copyleft licenses are denied.
+#[derive(Clone, Debug, Default)]
+pub struct LicensePolicy {
+    /// If not empty, only these licenses are allowed.
+    pub allowed: Vec<String>,
+    /// Licenses that are never allowed.
+    pub denied: Vec<String>,
+    pub deny_copyleft: bool,
+    /// Whether only licenses that are approved by the Open Source Initiative are allowed,
+    /// which also excludes licenses without an SPDX identifier (`LicenseRef-*`).
+    pub require_osi_approved: bool,
+}
+
+impl LicensePolicy {
+    pub fn from_environment() -> Self {
+        Self {
+            allowed: licenses_from_env("LICENSE_POLICY_ALLOW"),
+            denied: licenses_from_env("LICENSE_POLICY_DENY"),
+            deny_copyleft: dotenv::var("LICENSE_POLICY_DENY_COPYLEFT").is_ok(),
+            require_osi_approved: dotenv::var("LICENSE_POLICY_REQUIRE_OSI_APPROVED").is_ok(),
+        }
+    }
+
+    /// Whether crates may be published without a license expression, i.e. with only
+    /// a license file.
+    fn allows_non_standard_licenses(&self) -> bool {
+        self.allowed.is_empty() && !self.require_osi_approved
+    }
+
+    /// Checks the license expression of a crate that is about to be published.
+    pub fn check(&self, license: Option<&LicenseExpression>) -> AppResult<()> {
+        let license = match license {
+            Some(license) => license,
+            None if self.allows_non_standard_licenses() => return Ok(()),
+            None => {
+                return Err(cargo_err(
+                    "crates without an SPDX license expression are not allowed by the license \
+                     policy of this registry; please set the `license` field in Cargo.toml",
+                ))
+            }
+        };
+
+        if license.evaluate(|req| self.rejection_reason(req).is_none()) {
+            return Ok(());
+        }
+
+        let mut reasons = Vec::new();
+        for req in license.requirements() {
+            if let Some(reason) = self.rejection_reason(req) {
+                if !reasons.contains(&reason) {
+                    reasons.push(reason);
+                }
+            }
+        }
+
+        Err(cargo_err(&format_args!(
+            "license expression `{}` is not allowed by the license policy of this registry: {}",
+            license.normalized(),
+            reasons.join(", ")
+        )))
+    }
+
+    fn rejection_reason(&self, req: &LicenseReq) -> Option<String> {
+        let name = normalized_req(req);
+        let license_name = normalized_req(&LicenseReq {
+            license: req.license.clone(),
+            exception: None,
+        });
+        let matches = |list: &[String]| list.contains(&name) || list.contains(&license_name);
+
+        let id = match req.license {
+            LicenseItem::Spdx { id, .. } => Some(id),
+            LicenseItem::Other { .. } => None,
+        };
+
+        if matches(&self.denied) {
+            Some(format!("`{name}` is denied"))
+        } else if !self.allowed.is_empty() && !matches(&self.allowed) {
+            Some(format!("`{name}` is not in the list of allowed licenses"))
+        } else if self.require_osi_approved && id.is_none() {
+            Some(format!("`{name}` is not an SPDX license identifier"))
+        } else if self.require_osi_approved && !id.map_or(false, |id| id.is_osi_approved()) {
+            Some(format!("`{name}` is not OSI approved"))
+        } else if self.deny_copyleft && id.map_or(false, |id| id.is_copyleft()) {
+            Some(format!("`{name}` is a copyleft license"))
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads a comma separated list of licenses, normalized like the licenses of
+/// published crates, from an environment variable.
+///
+/// # Panics
+///
+/// This function panics if one of the licenses is not a valid SPDX license.
+fn licenses_from_env(name: &str) -> Vec<String> {
+    let licenses = match dotenv::var(name) {
+        Ok(licenses) => licenses,
+        Err(_) => return vec![],
+    };
+
+    licenses
+        .split(',')
+        .map(str::trim)
+        .filter(|license| !license.is_empty())
+        .map(|license| {
+            let expression = LicenseExpression::parse(license)
+                .ok()
+                .filter(|expression| expression.requirements().count() == 1)
+                .unwrap_or_else(|| panic!("{name} contains the invalid license `{license}`"));
+            expression.normalized()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(policy: &LicensePolicy, license: &str) -> Result<(), String> {
+        let license = LicenseExpression::parse(license).unwrap();
+        policy
+            .check(Some(&license))
+            .map_err(|error| error.to_string())
+    }
+
+    #[test]
+    fn default_policy_allows_everything() {
+        let policy = LicensePolicy::default();
+        assert_ok!(check(&policy, "GPL-3.0-only"));
+        assert_ok!(check(&policy, "LicenseRef-Proprietary"));
+        assert_ok!(policy.check(None));
+    }
+
+    #[test]
+    fn copyleft_licenses_are_denied() {
+        let policy = LicensePolicy {
+            deny_copyleft: true,
+            ..Default::default()
+        };
+        assert_ok!(check(&policy, "MIT"));
+        assert_ok!(check(&policy, "MIT OR GPL-3.0+"));
+        assert_eq!(
+            check(&policy, "MIT AND GPL-3.0+").unwrap_err(),
+            "license expression `MIT AND GPL-3.0-or-later` is not allowed by the license \
+             policy of this registry: `GPL-3.0-or-later` is a copyleft license"
+        );
+    }
+
+    #[test]
+    fn allow_and_deny_lists() {
+        let policy = LicensePolicy {
+            allowed: vec!["MIT".into(), "Apache-2.0".into(), "Zlib".into()],
+            denied: vec!["Zlib".into()],
+            ..Default::default()
+        };
+        assert_ok!(check(&policy, "MIT/Apache-2.0"));
+        assert_ok!(check(&policy, "Apache-2.0 WITH LLVM-exception"));
+        assert_eq!(
+            check(&policy, "Zlib OR BSD-3-Clause").unwrap_err(),
+            "license expression `Zlib OR BSD-3-Clause` is not allowed by the license policy \
+             of this registry: `Zlib` is denied, `BSD-3-Clause` is not in the list of allowed \
+             licenses"
+        );
+        assert_err!(policy.check(None));
+    }
+
+    #[test]
+    fn osi_approval_is_required() {
+        let policy = LicensePolicy {
+            require_osi_approved: true,
+            ..Default::default()
+        };
+        assert_ok!(check(&policy, "MIT"));
+        assert_eq!(
+            check(&policy, "LicenseRef-Proprietary").unwrap_err(),
+            "license expression `LicenseRef-Proprietary` is not allowed by the license policy \
+             of this registry: `LicenseRef-Proprietary` is not an SPDX license identifier"
+        );
+        assert_eq!(
+            check(&policy, "CC0-1.0").unwrap_err(),
+            "license expression `CC0-1.0` is not allowed by the license policy of this \
+             registry: `CC0-1.0` is not OSI approved"
+        );
+    }
+}
@@ -0,0 +1,24 @@
+use std::path::Path;
+use url::Url;
+
+/// Configuration of the mirror mode, in which this registry is a read-only
+/// replica of an upstream registry.
+///
+/// Mirror mode is enabled by setting `MIRROR_UPSTREAM_INDEX` to the URL of the
+/// git index of the upstream registry, or to the path of a local clone of it.
+#[derive(Clone, Debug)]
+pub struct MirrorConfig {
+    pub upstream_index: Url,
+}
+
+impl MirrorConfig {
+    pub fn from_environment() -> Option<Self> {
+        let upstream_index = dotenv::var("MIRROR_UPSTREAM_INDEX").ok()?;
+        let upstream_index = Url::parse(&upstream_index)
+            .ok()
+            .or_else(|| Url::from_directory_path(Path::new(&upstream_index)).ok())
+            .expect("MIRROR_UPSTREAM_INDEX must be a URL or an absolute path");
+
+        Some(Self { upstream_index })
+    }
+}
@@ -62,11 +62,15 @@ mod prelude {
 pub mod helpers;
 pub mod util;
 
+pub mod admin;
 pub mod category;
 mod conduit_axum;
+pub mod crate_adoption;
 pub mod crate_owner_invitation;
+pub mod crate_transfer;
 pub mod git;
 pub mod github;
+pub mod image_proxy;
 pub mod keyword;
 pub mod krate;
 pub mod metrics;
@@ -0,0 +1,4 @@
+pub mod adoption_requests;
+pub mod blocked_traffic;
+pub mod categories;
+pub mod keywords;
@@ -0,0 +1,102 @@
+//! Admin endpoints for reviewing requests to adopt orphaned crates.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::auth::AuthCheck;
+use crate::models::{insert_crate_owner_action, Crate, CrateAction, CrateAdoptionRequest, User};
+use crate::schema::{crates, users};
+use crate::util::errors::not_found;
+use crate::views::EncodableCrateAdoptionRequest;
+use std::collections::HashMap;
+
+/// Handles the `GET /api/private/admin/adoption_requests` route.
+pub async fn list(req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        AuthCheck::only_admin().check(&req)?;
+
+        let conn = req.app().db_read_prefer_primary()?;
+        let requests = CrateAdoptionRequest::all(&conn)?;
+
+        let crate_names: HashMap<i32, String> = crates::table
+            .select((crates::id, crates::name))
+            .filter(crates::id.eq_any(requests.iter().map(|r| r.crate_id)))
+            .load(&*conn)?
+            .into_iter()
+            .collect();
+        let requesters: HashMap<i32, String> = users::table
+            .select((users::id, users::gh_login))
+            .filter(users::id.eq_any(requests.iter().map(|r| r.user_id)))
+            .load(&*conn)?
+            .into_iter()
+            .collect();
+
+        let requests = requests
+            .into_iter()
+            .filter_map(|request| {
+                Some(EncodableCrateAdoptionRequest {
+                    id: request.id,
+                    crate_id: request.crate_id,
+                    crate_name: crate_names.get(&request.crate_id)?.clone(),
+                    requested_by_username: requesters.get(&request.user_id)?.clone(),
+                    reason: request.reason,
+                    created_at: request.created_at,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({ "adoption_requests": requests })))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/private/admin/adoption_requests/:id/approve` route.
+pub async fn approve(Path(id): Path<i32>, req: ConduitRequest) -> AppResult<Response> {
+    conduit_compat(move || decide(&req, id, true)).await
+}
+
+/// Handles the `PUT /api/private/admin/adoption_requests/:id/reject` route.
+pub async fn reject(Path(id): Path<i32>, req: ConduitRequest) -> AppResult<Response> {
+    conduit_compat(move || decide(&req, id, false)).await
+}
+
+fn decide<B>(req: &Request<B>, id: i32, approved: bool) -> AppResult<Response> {
+    let admin = AuthCheck::only_admin().check(req)?.user();
+
+    let app = req.app();
+    let conn = app.db_write()?;
+
+    let request = CrateAdoptionRequest::find(&conn, id)
+        .optional()?
+        .ok_or_else(not_found)?;
+    let krate: Crate = Crate::all().find(request.crate_id).first(&*conn)?;
+    let requester = User::find(&conn, request.user_id)?;
+
+    if approved && !krate.is_orphaned(&conn)? {
+        return Err(cargo_err(&format_args!(
+            "crate {} is no longer orphaned",
+            krate.name
+        )));
+    }
+
+    conn.transaction(|| -> AppResult<()> {
+        let action = if approved {
+            request.approve(&conn, &krate, admin.id)?;
+            CrateAction::AdoptionApprove
+        } else {
+            request.reject(&conn)?;
+            CrateAction::AdoptionReject
+        };
+
+        insert_crate_owner_action(&conn, &krate, admin.id, Some(requester.id), action)?;
+        Ok(())
+    })?;
+
+    if let Ok(Some(email)) = requester.verified_email(&conn) {
+        // Swallow any error, the decision has been recorded either way.
+        let _ = app
+            .emails
+            .send_crate_adoption_decision(&email, &krate.name, approved);
+    }
+
+    ok_true()
+}
@@ -0,0 +1,80 @@
+//! Admin endpoints for managing the blocked traffic rules of the `block_by_rules` middleware.
+//!
+//! Changes take effect immediately on the instance handling the request. Other instances pick
+//! them up once their cached rules expire (see `BLOCKED_TRAFFIC_RULES_TTL`).
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::auth::AuthCheck;
+use crate::models::{BlockedTrafficRule, NewBlockedTrafficRule};
+use crate::util::errors::not_found;
+use chrono::NaiveDateTime;
+
+/// Handles the `GET /api/private/admin/blocked_traffic` route.
+pub async fn list(req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        AuthCheck::only_admin().check(&req)?;
+
+        let conn = req.app().db_read_prefer_primary()?;
+        let rules = BlockedTrafficRule::all(&conn)?;
+
+        Ok(Json(json!({ "rules": rules })))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/private/admin/blocked_traffic` route.
+pub async fn create(mut req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct NewRule {
+            ip_range: Option<String>,
+            user_agent_pattern: Option<String>,
+            reason: String,
+            expires_at: Option<NaiveDateTime>,
+        }
+
+        #[derive(Deserialize)]
+        struct NewRuleRequest {
+            rule: NewRule,
+        }
+
+        let new: NewRuleRequest = serde_json::from_reader(req.body_mut())
+            .map_err(|e| bad_request(&format!("invalid new rule request: {e:?}")))?;
+
+        AuthCheck::only_admin().check(&req)?;
+
+        let new_rule = NewBlockedTrafficRule {
+            ip_range: new.rule.ip_range.as_deref(),
+            user_agent_pattern: new.rule.user_agent_pattern.as_deref(),
+            reason: &new.rule.reason,
+            expires_at: new.rule.expires_at,
+        };
+        new_rule.validate().map_err(|e| bad_request(&e))?;
+
+        let app = req.app();
+        let conn = app.db_write()?;
+        let rule = new_rule.save(&conn)?;
+        app.blocked_traffic_rules.invalidate_all();
+
+        Ok(Json(json!({ "rule": rule })))
+    })
+    .await
+}
+
+/// Handles the `DELETE /api/private/admin/blocked_traffic/:id` route.
+pub async fn delete(Path(id): Path<i32>, req: ConduitRequest) -> AppResult<Response> {
+    conduit_compat(move || {
+        AuthCheck::only_admin().check(&req)?;
+
+        let app = req.app();
+        let conn = app.db_write()?;
+        if !BlockedTrafficRule::delete(&conn, id)? {
+            return Err(not_found());
+        }
+        app.blocked_traffic_rules.invalidate_all();
+
+        ok_true()
+    })
+    .await
+}
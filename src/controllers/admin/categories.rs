@@ -0,0 +1,120 @@
+//! Admin endpoints for managing the crate categories.
+//!
+//! The former slugs of renamed and merged categories keep redirecting to the category that
+//! replaced them, both in the API and for crates that are published with them.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::auth::AuthCheck;
+use crate::models::Category;
+use crate::views::EncodableCategory;
+
+/// Handles the `PUT /api/private/admin/categories` route.
+pub async fn create(mut req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct NewCategory {
+            slug: String,
+            name: String,
+            #[serde(default)]
+            description: String,
+        }
+
+        #[derive(Deserialize)]
+        struct NewCategoryRequest {
+            category: NewCategory,
+        }
+
+        let new: NewCategoryRequest = serde_json::from_reader(req.body_mut())
+            .map_err(|e| bad_request(&format!("invalid new category request: {e:?}")))?;
+
+        AuthCheck::only_admin().check(&req)?;
+
+        let conn = req.app().db_write()?;
+        let new = new.category;
+        let category = Category::create(&conn, &new.slug, &new.name, &new.description)?;
+
+        Ok(Json(
+            json!({ "category": EncodableCategory::from(category) }),
+        ))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/private/admin/categories/:category_id/rename` route.
+pub async fn rename(Path(slug): Path<String>, mut req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct RenameRequest {
+            slug: String,
+            name: String,
+        }
+
+        let rename: RenameRequest = serde_json::from_reader(req.body_mut())
+            .map_err(|e| bad_request(&format!("invalid rename request: {e:?}")))?;
+
+        AuthCheck::only_admin().check(&req)?;
+
+        let conn = req.app().db_write()?;
+        let category: Category = Category::by_slug(&slug).first(&*conn)?;
+        let category = category.rename(&conn, &rename.slug, &rename.name)?;
+
+        Ok(Json(
+            json!({ "category": EncodableCategory::from(category) }),
+        ))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/private/admin/categories/:category_id/parent` route.
+pub async fn reparent(Path(slug): Path<String>, mut req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct ReparentRequest {
+            parent: Option<String>,
+        }
+
+        let reparent: ReparentRequest = serde_json::from_reader(req.body_mut())
+            .map_err(|e| bad_request(&format!("invalid reparent request: {e:?}")))?;
+
+        AuthCheck::only_admin().check(&req)?;
+
+        let conn = req.app().db_write()?;
+        let category: Category = Category::by_slug(&slug).first(&*conn)?;
+        let parent = match &reparent.parent {
+            Some(parent) => Some(Category::by_slug(parent).first::<Category>(&*conn)?),
+            None => None,
+        };
+        let category = category.reparent(&conn, parent.as_ref())?;
+
+        Ok(Json(
+            json!({ "category": EncodableCategory::from(category) }),
+        ))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/private/admin/categories/:category_id/merge` route.
+pub async fn merge(Path(slug): Path<String>, mut req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct MergeRequest {
+            into: String,
+        }
+
+        let merge: MergeRequest = serde_json::from_reader(req.body_mut())
+            .map_err(|e| bad_request(&format!("invalid merge request: {e:?}")))?;
+
+        AuthCheck::only_admin().check(&req)?;
+
+        let conn = req.app().db_write()?;
+        let category: Category = Category::by_slug(&slug).first(&*conn)?;
+        let target: Category = Category::by_slug(&merge.into).first(&*conn)?;
+        category.merge_into(&conn, &target)?;
+
+        // Reload the target to include the crates of the merged category in `crates_cnt`
+        let target: Category = Category::by_slug(&target.slug).first(&*conn)?;
+        Ok(Json(json!({ "category": EncodableCategory::from(target) })))
+    })
+    .await
+}
@@ -0,0 +1,111 @@
+//! Admin endpoints for moderating keywords.
+//!
+//! Aliases are replaced by their canonical keyword when a crate is published, and
+//! `/api/v1/keywords/:keyword_id` redirects them to it. Banned keywords are ignored.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::auth::AuthCheck;
+use crate::models::Keyword;
+use crate::util::errors::not_found;
+use crate::views::EncodableKeyword;
+
+/// Handles the `PUT /api/private/admin/keywords/:keyword_id/aliases` route.
+pub async fn add_alias(
+    Path(name): Path<String>,
+    mut req: ConduitRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct AliasRequest {
+            alias: String,
+        }
+
+        let alias: AliasRequest = serde_json::from_reader(req.body_mut())
+            .map_err(|e| bad_request(&format!("invalid alias request: {e:?}")))?;
+
+        AuthCheck::only_admin().check(&req)?;
+
+        let conn = req.app().db_write()?;
+        let keyword = Keyword::find_by_keyword(&conn, &name)?;
+        keyword.add_alias(&conn, &alias.alias)?;
+
+        Ok(Json(json!({ "keyword": EncodableKeyword::from(keyword) })))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/private/admin/keywords/:keyword_id/rename` route.
+pub async fn rename(Path(name): Path<String>, mut req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct RenameRequest {
+            keyword: String,
+        }
+
+        let rename: RenameRequest = serde_json::from_reader(req.body_mut())
+            .map_err(|e| bad_request(&format!("invalid rename request: {e:?}")))?;
+
+        AuthCheck::only_admin().check(&req)?;
+
+        let conn = req.app().db_write()?;
+        let keyword = Keyword::find_by_keyword(&conn, &name)?;
+        let keyword = keyword.rename(&conn, &rename.keyword)?;
+
+        Ok(Json(json!({ "keyword": EncodableKeyword::from(keyword) })))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/private/admin/keywords/:keyword_id/merge` route.
+pub async fn merge(Path(name): Path<String>, mut req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct MergeRequest {
+            into: String,
+        }
+
+        let merge: MergeRequest = serde_json::from_reader(req.body_mut())
+            .map_err(|e| bad_request(&format!("invalid merge request: {e:?}")))?;
+
+        AuthCheck::only_admin().check(&req)?;
+
+        let conn = req.app().db_write()?;
+        let keyword = Keyword::find_by_keyword(&conn, &name)?;
+        let target = Keyword::find_by_keyword(&conn, &merge.into)?;
+        keyword.merge_into(&conn, &target)?;
+
+        // Reload the target to include the crates of the merged keyword in `crates_cnt`
+        let target = Keyword::find_by_keyword(&conn, &target.keyword)?;
+        Ok(Json(json!({ "keyword": EncodableKeyword::from(target) })))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/private/admin/keywords/:keyword_id/ban` route.
+pub async fn ban(Path(name): Path<String>, req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        AuthCheck::only_admin().check(&req)?;
+
+        let conn = req.app().db_write()?;
+        let removed = Keyword::ban(&conn, &name)?;
+
+        Ok(Json(json!({ "ok": true, "crates_cnt": removed })))
+    })
+    .await
+}
+
+/// Handles the `DELETE /api/private/admin/keywords/:keyword_id/ban` route.
+pub async fn unban(Path(name): Path<String>, req: ConduitRequest) -> AppResult<Response> {
+    conduit_compat(move || {
+        AuthCheck::only_admin().check(&req)?;
+
+        let conn = req.app().db_write()?;
+        if !Keyword::unban(&conn, &name)? {
+            return Err(not_found());
+        }
+
+        ok_true()
+    })
+    .await
+}
@@ -3,6 +3,7 @@ use super::prelude::*;
 
 use crate::models::Category;
 use crate::schema::categories;
+use crate::util::errors::not_found;
 use crate::views::{EncodableCategory, EncodableCategoryWithSubcategories};
 
 /// Handles the `GET /categories` route.
@@ -36,10 +37,18 @@ pub async fn index(req: ConduitRequest) -> AppResult<Json<Value>> {
 }
 
 /// Handles the `GET /categories/:category_id` route.
-pub async fn show(Path(slug): Path<String>, req: ConduitRequest) -> AppResult<Json<Value>> {
+///
+/// The former slugs of renamed and merged categories redirect to the current one.
+pub async fn show(Path(slug): Path<String>, req: ConduitRequest) -> AppResult<Response> {
     conduit_compat(move || {
         let conn = req.app().db_read()?;
-        let cat: Category = Category::by_slug(&slug).first(&*conn)?;
+        let cat: Category = match Category::by_slug(&slug).first(&*conn).optional()? {
+            Some(cat) => cat,
+            None => {
+                let slug = Category::redirected_slug(&conn, &slug)?.ok_or_else(not_found)?;
+                return Ok(req.redirect(format!("/api/v1/categories/{slug}")));
+            }
+        };
         let subcats = cat
             .subcategories(&conn)?
             .into_iter()
@@ -63,7 +72,7 @@ pub async fn show(Path(slug): Path<String>, req: ConduitRequest) -> AppResult<Js
             parent_categories: parents,
         };
 
-        Ok(Json(json!({ "category": cat_with_subcats })))
+        Ok(Json(json!({ "category": cat_with_subcats })).into_response())
     })
     .await
 }
@@ -0,0 +1,111 @@
+//! Endpoints for abandoning a crate and for requesting to adopt an orphaned crate.
+//!
+//! Adoption requests are reviewed by admins through the endpoints in
+//! `admin::adoption_requests`.
+
+use super::frontend_prelude::*;
+
+use crate::auth::AuthCheck;
+use crate::controllers::crate_owner_invitation::load_owned_crate;
+use crate::models::{insert_crate_owner_action, Crate, CrateAction, CrateAdoptionRequest, Owner};
+
+/// Handles the `PUT /api/v1/crates/:crate_id/abandon` route.
+///
+/// Removes the last owner of the crate, leaving it orphaned until an admin
+/// approves a request to adopt it. Crates with other owners can't be abandoned,
+/// since their owners would otherwise be removed without their consent.
+pub async fn abandon(
+    Path(crate_name): Path<String>,
+    req: ConduitRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = req.app().db_write()?;
+        let (krate, user) = load_owned_crate(&req, &conn, &crate_name)?;
+
+        conn.transaction(|| -> AppResult<()> {
+            // Lock the crate, so that no owners are added while it is abandoned
+            Crate::all().find(krate.id).for_update().execute(&*conn)?;
+
+            let has_other_owners = krate.owners(&conn)?.iter().any(|owner| match owner {
+                Owner::User(owner) => owner.id != user.id,
+                Owner::Team(_) => true,
+            });
+            if has_other_owners {
+                return Err(cargo_err(&format_args!(
+                    "crate {} has other owners, who have to be removed before it can be abandoned",
+                    krate.name
+                )));
+            }
+
+            krate.remove_all_owners(&conn)?;
+            insert_crate_owner_action(&conn, &krate, user.id, None, CrateAction::Abandon)?;
+            Ok(())
+        })?;
+
+        let msg = format!(
+            "crate {} has been abandoned and can now be adopted by other users",
+            krate.name
+        );
+        Ok(Json(json!({ "ok": true, "msg": msg })))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/v1/crates/:crate_id/adoption_requests` route.
+///
+/// The format of the request body is:
+///
+/// ```json
+/// {"reason": "why you would like to adopt the crate"}
+/// ```
+pub async fn request_adoption(
+    Path(crate_name): Path<String>,
+    mut req: ConduitRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct AdoptionRequest {
+            reason: String,
+        }
+
+        let request: AdoptionRequest = serde_json::from_reader(req.body_mut())
+            .map_err(|_| bad_request("invalid json request"))?;
+
+        let auth = AuthCheck::only_cookie().check(&req)?;
+        let user = auth.user();
+
+        let reason = request.reason.trim();
+        if reason.is_empty() {
+            return Err(cargo_err(
+                "please explain why you would like to adopt the crate",
+            ));
+        }
+
+        let conn = req.app().db_write()?;
+        let krate: Crate = Crate::by_name(&crate_name).first(&*conn)?;
+        if !krate.is_orphaned(&conn)? {
+            return Err(cargo_err(&format_args!(
+                "crate {} is not orphaned, please contact its owners instead",
+                krate.name
+            )));
+        }
+
+        conn.transaction(|| -> AppResult<()> {
+            if CrateAdoptionRequest::create(&conn, krate.id, user.id, reason)?.is_none() {
+                return Err(cargo_err(&format_args!(
+                    "you have already requested to adopt crate {}",
+                    krate.name
+                )));
+            }
+            insert_crate_owner_action(&conn, &krate, user.id, None, CrateAction::AdoptionRequest)?;
+            Ok(())
+        })?;
+
+        let msg = format!(
+            "your request to adopt crate {} will be reviewed by the crates.io team",
+            krate.name
+        );
+        Ok(Json(json!({ "ok": true, "msg": msg })))
+    })
+    .await
+}
@@ -3,12 +3,16 @@ use super::frontend_prelude::*;
 use crate::auth::AuthCheck;
 use crate::auth::AuthenticatedUser;
 use crate::controllers::helpers::pagination::{Page, PaginationOptions};
-use crate::models::{Crate, CrateOwnerInvitation, Rights, User};
-use crate::schema::{crate_owner_invitations, crates, users};
+use crate::models::token::EndpointScope;
+use crate::models::{
+    insert_crate_owner_action, Crate, CrateAction, CrateOwnerInvitation, CrateOwnerTeamInvitation,
+    Owner, Rights, Team, User,
+};
+use crate::schema::{crate_owner_invitations, crate_owner_team_invitations, crates, teams, users};
 use crate::util::errors::{forbidden, internal};
 use crate::views::{
     EncodableCrateOwnerInvitation, EncodableCrateOwnerInvitationV1, EncodablePublicUser,
-    InvitationResponse,
+    EncodableSentCrateOwnerInvitation, InvitationResponse,
 };
 use chrono::{Duration, Utc};
 use diesel::{pg::Pg, sql_types::Bool};
@@ -103,7 +107,7 @@ fn prepare_list<B>(
             ListFilter::CrateName(crate_name) => {
                 // Only allow crate owners to query pending invitations for their crate.
                 let krate: Crate = Crate::by_name(&crate_name).first(&*conn)?;
-                let owners = krate.owners(&conn)?;
+                let owners = krate.owners_with_roles(&conn)?;
                 if user.rights(state, &owners)? != Rights::Full {
                     return Err(forbidden());
                 }
@@ -305,3 +309,267 @@ pub async fn handle_invite_with_token(
     })
     .await
 }
+
+/// Loads a crate and makes sure that the authenticated user is one of its
+/// individual owners with the admin role, who are allowed to manage its
+/// ownership invitations.
+pub(crate) fn load_owned_crate<B>(
+    req: &Request<B>,
+    conn: &PgConnection,
+    crate_name: &str,
+) -> AppResult<(Crate, User)> {
+    let auth = AuthCheck::default()
+        .with_endpoint_scope(EndpointScope::ChangeOwners)
+        .for_crate(crate_name)
+        .check(req)?;
+    let user = auth.user();
+
+    let krate: Crate = Crate::by_name(crate_name).first(conn)?;
+    let owners = krate.owners_with_roles(conn)?;
+    if user.rights(req.app(), &owners)? != Rights::Full {
+        return Err(forbidden());
+    }
+
+    Ok((krate, user))
+}
+
+/// Handles the `GET /api/v1/crates/:crate_id/owner_invitations` route.
+///
+/// Lists the pending invitations of users and teams to become owners of the crate.
+pub async fn list_sent(
+    Path(crate_name): Path<String>,
+    req: ConduitRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let state = req.app();
+        let conn = state.db_read_prefer_primary()?;
+        let config = &state.config;
+        let (krate, _) = load_owned_crate(&req, &conn, &crate_name)?;
+
+        let expire_cutoff = Duration::days(config.ownership_invitations_expiration_days as i64);
+        let cutoff = (Utc::now() - expire_cutoff).naive_utc();
+
+        let user_invitations: Vec<(CrateOwnerInvitation, User)> = crate_owner_invitations::table
+            .inner_join(users::table.on(users::id.eq(crate_owner_invitations::invited_user_id)))
+            .filter(crate_owner_invitations::crate_id.eq(krate.id))
+            .filter(crate_owner_invitations::created_at.gt(cutoff))
+            .order(crate_owner_invitations::created_at)
+            .load(&*conn)?;
+
+        let team_invitations: Vec<(CrateOwnerTeamInvitation, Team)> =
+            crate_owner_team_invitations::table
+                .inner_join(teams::table)
+                .filter(crate_owner_team_invitations::crate_id.eq(krate.id))
+                .filter(crate_owner_team_invitations::created_at.gt(cutoff))
+                .order(crate_owner_team_invitations::created_at)
+                .load(&*conn)?;
+
+        let inviter_ids = user_invitations
+            .iter()
+            .map(|(invitation, _)| invitation.invited_by_user_id)
+            .chain(
+                team_invitations
+                    .iter()
+                    .map(|(invitation, _)| invitation.invited_by_user_id),
+            )
+            .collect::<HashSet<_>>();
+        let inviters: HashMap<i32, String> = users::table
+            .select((users::id, users::gh_login))
+            .filter(users::id.eq_any(inviter_ids))
+            .load(&*conn)?
+            .into_iter()
+            .collect();
+
+        let encode = |invitee: Owner, inviter_id: i32, created_at, expires_at| {
+            Ok(EncodableSentCrateOwnerInvitation {
+                invitee: invitee.into(),
+                inviter_id,
+                invited_by_username: inviters
+                    .get(&inviter_id)
+                    .ok_or_else(|| internal(&format!("missing user {inviter_id}")))?
+                    .clone(),
+                crate_id: krate.id,
+                crate_name: krate.name.clone(),
+                created_at,
+                expires_at,
+            })
+        };
+
+        let user_invitations = user_invitations.into_iter().map(|(invitation, user)| {
+            let expires_at = invitation.expires_at(config);
+            let inviter_id = invitation.invited_by_user_id;
+            encode(
+                Owner::User(user),
+                inviter_id,
+                invitation.created_at,
+                expires_at,
+            )
+        });
+        let team_invitations = team_invitations.into_iter().map(|(invitation, team)| {
+            let expires_at = invitation.expires_at(config);
+            let inviter_id = invitation.invited_by_user_id;
+            encode(
+                Owner::Team(team),
+                inviter_id,
+                invitation.created_at,
+                expires_at,
+            )
+        });
+        let invitations = user_invitations
+            .chain(team_invitations)
+            .collect::<AppResult<Vec<_>>>()?;
+
+        Ok(Json(json!({ "invitations": invitations })))
+    })
+    .await
+}
+
+/// Handles the `DELETE /api/v1/crates/:crate_id/owner_invitations/:login` route.
+///
+/// Revokes the pending invitation of a user or a team. Only the owner who sent (or last
+/// resent) the invitation may revoke it, and the revocation is recorded in the ownership
+/// history of the crate.
+pub async fn revoke(
+    Path((crate_name, login)): Path<(String, String)>,
+    req: ConduitRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = req.app().db_write()?;
+        let (krate, user) = load_owned_crate(&req, &conn, &crate_name)?;
+
+        conn.transaction(|| {
+            let invitee_id = match Owner::find_by_login(&conn, &login)? {
+                Owner::User(invitee) => {
+                    let invitation = CrateOwnerInvitation::find_by_id(invitee.id, krate.id, &conn)?;
+                    if invitation.invited_by_user_id != user.id {
+                        return Err(forbidden());
+                    }
+                    invitation.decline(&conn)?;
+                    Some(invitee.id)
+                }
+                Owner::Team(team) => {
+                    let invitation =
+                        CrateOwnerTeamInvitation::find_by_id(team.id, krate.id, &conn)?;
+                    if invitation.invited_by_user_id != user.id {
+                        return Err(forbidden());
+                    }
+                    invitation.decline(&conn)?;
+                    None
+                }
+            };
+
+            let action = CrateAction::InvitationRevoke;
+            insert_crate_owner_action(&conn, &krate, user.id, invitee_id, action)?;
+            Ok(())
+        })?;
+
+        let msg = format!("the invitation of {login} to crate {crate_name} has been revoked");
+        Ok(Json(json!({ "ok": true, "msg": msg })))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/v1/crates/:crate_id/owner_invitations/:login/resend` route.
+///
+/// Invitations of users get a new token, which is sent to the verified email address of the
+/// invited user again. Invitations of teams are renewed, since they are not sent by email.
+/// In both cases the expiration period is restarted and the resending owner becomes the inviter.
+pub async fn resend(
+    Path((crate_name, login)): Path<(String, String)>,
+    req: ConduitRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let state = req.app();
+        let conn = state.db_write()?;
+        let (krate, user) = load_owned_crate(&req, &conn, &crate_name)?;
+
+        let msg = match Owner::find_by_login(&conn, &login)? {
+            Owner::User(invitee) => {
+                let invitation = CrateOwnerInvitation::find_by_id(invitee.id, krate.id, &conn)?
+                    .regenerate_token(user.id, &conn)?;
+
+                if let Ok(Some(email)) = invitee.verified_email(&conn) {
+                    // Swallow any error, like when creating the invitation.
+                    let _ = state.emails.send_owner_invite(
+                        &email,
+                        &user.gh_login,
+                        &krate.name,
+                        &invitation.token,
+                    );
+                }
+
+                format!("the invitation of {login} to crate {crate_name} has been resent")
+            }
+            Owner::Team(team) => {
+                CrateOwnerTeamInvitation::find_by_id(team.id, krate.id, &conn)?
+                    .renew(user.id, &conn)?;
+
+                format!("the invitation of {login} to crate {crate_name} has been renewed")
+            }
+        };
+
+        Ok(Json(json!({ "ok": true, "msg": msg })))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/v1/crates/:crate_id/owner_invitations/:login/accept` route.
+pub async fn accept_team_invite(
+    Path((crate_name, login)): Path<(String, String)>,
+    req: ConduitRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || handle_team_invite(&req, &crate_name, &login, true)).await
+}
+
+/// Handles the `PUT /api/v1/crates/:crate_id/owner_invitations/:login/decline` route.
+pub async fn decline_team_invite(
+    Path((crate_name, login)): Path<(String, String)>,
+    req: ConduitRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || handle_team_invite(&req, &crate_name, &login, false)).await
+}
+
+/// Accepts or declines the invitation of a team on behalf of the team, which is only allowed
+/// for maintainers of the team on GitHub.
+fn handle_team_invite<B>(
+    req: &Request<B>,
+    crate_name: &str,
+    login: &str,
+    accepted: bool,
+) -> AppResult<Json<Value>> {
+    let auth = AuthCheck::only_cookie().check(req)?;
+    let user = auth.user();
+
+    let state = req.app();
+    let conn = state.db_write()?;
+
+    let team = match Owner::find_by_login(&conn, login)? {
+        Owner::Team(team) => team,
+        Owner::User(_) => {
+            return Err(bad_request(
+                "invitations of users can only be handled by the invited user",
+            ))
+        }
+    };
+
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+    let invitation = CrateOwnerTeamInvitation::find_by_id(team.id, krate.id, &conn)?;
+
+    if !team.is_maintained_by(state, &user)? {
+        return Err(forbidden());
+    }
+
+    if accepted {
+        invitation.accept(&conn, &state.config)?;
+    } else {
+        invitation.decline(&conn)?;
+    }
+
+    Ok(Json(json!({
+        "team_owner_invitation": {
+            "crate_id": krate.id,
+            "team": team.login,
+            "accepted": accepted,
+        },
+    })))
+}
@@ -0,0 +1,195 @@
+//! Endpoints for transferring a crate to a new owner, who has to accept the transfer.
+
+use super::frontend_prelude::*;
+
+use crate::auth::AuthCheck;
+use crate::controllers::crate_owner_invitation::load_owned_crate;
+use crate::models::{
+    insert_crate_owner_action, Crate, CrateAction, CrateTransferRequest, Owner, User,
+};
+use crate::schema::{crates, users};
+use crate::util::errors::not_found;
+use crate::views::{EncodableCrateTransferRequest, InvitationResponse};
+use std::collections::HashMap;
+
+/// Handles the `PUT /api/v1/crates/:crate_id/transfer` route.
+///
+/// The format of the request body is:
+///
+/// ```json
+/// {"to": "username"}
+/// ```
+pub async fn request(
+    Path(crate_name): Path<String>,
+    mut req: ConduitRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct TransferRequest {
+            to: String,
+        }
+
+        let request: TransferRequest = serde_json::from_reader(req.body_mut())
+            .map_err(|_| bad_request("invalid json request"))?;
+
+        let app = req.app();
+        let conn = app.db_write()?;
+        let (krate, user) = load_owned_crate(&req, &conn, &crate_name)?;
+
+        let recipient = match Owner::find_by_login(&conn, &request.to)? {
+            Owner::User(recipient) => recipient,
+            Owner::Team(_) => return Err(cargo_err("crates can only be transferred to users")),
+        };
+        if recipient.id == user.id {
+            return Err(cargo_err("cannot transfer a crate to yourself"));
+        }
+
+        conn.transaction(|| -> AppResult<()> {
+            CrateTransferRequest::create(&conn, krate.id, user.id, recipient.id)?;
+            insert_crate_owner_action(
+                &conn,
+                &krate,
+                user.id,
+                Some(recipient.id),
+                CrateAction::TransferRequest,
+            )?;
+            Ok(())
+        })?;
+
+        if let Ok(Some(email)) = recipient.verified_email(&conn) {
+            // Swallow any error. The recipient will still see the request when
+            // they visit their pending invitations.
+            let _ = app
+                .emails
+                .send_crate_transfer_request(&email, &user.gh_login, &krate.name);
+        }
+
+        let msg = format!(
+            "a request to transfer crate {} to {} has been sent",
+            krate.name, recipient.gh_login
+        );
+        Ok(Json(json!({ "ok": true, "msg": msg })))
+    })
+    .await
+}
+
+/// Handles the `DELETE /api/v1/crates/:crate_id/transfer` route.
+pub async fn cancel(Path(crate_name): Path<String>, req: ConduitRequest) -> AppResult<Response> {
+    conduit_compat(move || {
+        let conn = req.app().db_write()?;
+        let (krate, user) = load_owned_crate(&req, &conn, &crate_name)?;
+
+        let request = CrateTransferRequest::for_crate(&conn, krate.id)
+            .optional()?
+            .ok_or_else(not_found)?;
+
+        conn.transaction(|| -> AppResult<()> {
+            let to_user_id = request.to_user_id;
+            request.delete(&conn)?;
+            insert_crate_owner_action(
+                &conn,
+                &krate,
+                user.id,
+                Some(to_user_id),
+                CrateAction::TransferCancel,
+            )?;
+            Ok(())
+        })?;
+
+        ok_true()
+    })
+    .await
+}
+
+/// Handles the `GET /api/v1/me/crate_transfers` route.
+pub async fn list(req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let auth = AuthCheck::only_cookie().check(&req)?;
+        let user_id = auth.user_id();
+
+        let state = req.app();
+        let conn = state.db_read_prefer_primary()?;
+        let config = &state.config;
+
+        let requests = CrateTransferRequest::for_recipient(&conn, user_id, config)?;
+
+        let crate_names: HashMap<i32, String> = crates::table
+            .select((crates::id, crates::name))
+            .filter(crates::id.eq_any(requests.iter().map(|r| r.crate_id)))
+            .load(&*conn)?
+            .into_iter()
+            .collect();
+        let senders: HashMap<i32, String> = users::table
+            .select((users::id, users::gh_login))
+            .filter(users::id.eq_any(requests.iter().map(|r| r.from_user_id)))
+            .load(&*conn)?
+            .into_iter()
+            .collect();
+
+        let crate_transfers = requests
+            .into_iter()
+            .filter_map(|request| {
+                Some(EncodableCrateTransferRequest {
+                    crate_id: request.crate_id,
+                    crate_name: crate_names.get(&request.crate_id)?.clone(),
+                    requested_by_username: senders.get(&request.from_user_id)?.clone(),
+                    created_at: request.created_at,
+                    expires_at: request.expires_at(config),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({ "crate_transfers": crate_transfers })))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/v1/me/crate_transfers/:crate_id/accept` route.
+pub async fn accept(Path(crate_id): Path<i32>, req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || handle_request(&req, crate_id, true)).await
+}
+
+/// Handles the `PUT /api/v1/me/crate_transfers/:crate_id/decline` route.
+pub async fn decline(Path(crate_id): Path<i32>, req: ConduitRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || handle_request(&req, crate_id, false)).await
+}
+
+fn handle_request<B>(req: &Request<B>, crate_id: i32, accepted: bool) -> AppResult<Json<Value>> {
+    let auth = AuthCheck::only_cookie().check(req)?;
+    let user = auth.user();
+
+    let state = req.app();
+    let conn = state.db_write()?;
+
+    let request = CrateTransferRequest::find(&conn, crate_id, user.id)
+        .optional()?
+        .ok_or_else(not_found)?;
+    let krate: Crate = Crate::all().find(crate_id).first(&*conn)?;
+    let from_user_id = request.from_user_id;
+
+    conn.transaction(|| -> AppResult<()> {
+        let action = if accepted {
+            request.accept(&conn, &state.config)?;
+            CrateAction::TransferAccept
+        } else {
+            request.delete(&conn)?;
+            CrateAction::TransferDecline
+        };
+
+        insert_crate_owner_action(&conn, &krate, user.id, Some(from_user_id), action)?;
+        Ok(())
+    })?;
+
+    if accepted {
+        let sender = User::find(&conn, from_user_id)?;
+        if let Ok(Some(email)) = sender.verified_email(&conn) {
+            // Swallow any error, the transfer has happened either way.
+            let _ = state
+                .emails
+                .send_crate_transfer_accepted(&email, &user.gh_login, &krate.name);
+        }
+    }
+
+    let crate_transfer = InvitationResponse { crate_id, accepted };
+    Ok(Json(json!({ "crate_transfer": crate_transfer })))
+}
@@ -0,0 +1,165 @@
+//! Serves the images of rendered readmes, so that readers don't load them from
+//! arbitrary third-party hosts.
+//!
+//! See [`ImageProxyConfig`] for the configuration of the image proxy.
+
+use super::frontend_prelude::*;
+
+use crate::config::ImageProxyConfig;
+use crate::schema::{proxied_image_failures, proxied_images};
+use crate::util::errors::{forbidden, internal, not_found};
+use hex::ToHex;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use url::Url;
+
+/// The content types of images that are served by the image proxy.
+const IMAGE_CONTENT_TYPES: &[&str] = &[
+    "image/avif",
+    "image/gif",
+    "image/jpeg",
+    "image/png",
+    "image/svg+xml",
+    "image/webp",
+];
+
+/// How long a failed fetch of an image is remembered, before it is fetched
+/// again.
+const FAILURE_CACHE_HOURS: i32 = 1;
+
+/// Handles the `GET /api/v1/image-proxy` route.
+///
+/// The `url` query parameter is the URL of the image, which must be signed by the
+/// `signature` parameter. The image is fetched and cached in storage the first time
+/// it is requested, and the response redirects to the cached image. Failures are
+/// cached as well, for an hour.
+///
+/// Mirrors don't serve images, as they don't write to the storage.
+pub async fn proxy_image(req: ConduitRequest) -> AppResult<Response> {
+    conduit_compat(move || {
+        use diesel::dsl::{now, IntervalDsl};
+
+        let app = req.app();
+        if app.config.mirror.is_some() {
+            return Err(not_found());
+        }
+        let config = app.config.image_proxy.as_ref().ok_or_else(not_found)?;
+
+        let query = req.query();
+        let url = query
+            .get("url")
+            .ok_or_else(|| bad_request("missing `url` query parameter"))?;
+        let signature = query.get("signature").ok_or_else(forbidden)?;
+        if !config.verify(url, signature) {
+            return Err(forbidden());
+        }
+
+        let (cached_digest, cached_error) = {
+            let conn = app.db_read_prefer_primary()?;
+            let digest = proxied_images::table
+                .find(url)
+                .select(proxied_images::digest)
+                .first::<String>(&*conn)
+                .optional()?;
+            let error = proxied_image_failures::table
+                .find(url)
+                .filter(proxied_image_failures::failed_at.gt(now - FAILURE_CACHE_HOURS.hours()))
+                .select(proxied_image_failures::error)
+                .first::<String>(&*conn)
+                .optional()?;
+            (digest, error)
+        };
+
+        let digest = match (cached_digest, cached_error) {
+            (Some(digest), _) => digest,
+            (None, Some(error)) => return Err(bad_request(&error)),
+            (None, None) => {
+                let (content_type, image) = match fetch_image(config, url) {
+                    Ok(fetched) => fetched,
+                    Err(error) => {
+                        let conn = app.db_write()?;
+                        diesel::insert_into(proxied_image_failures::table)
+                            .values((
+                                proxied_image_failures::url.eq(url),
+                                proxied_image_failures::error.eq(error.to_string()),
+                            ))
+                            .on_conflict(proxied_image_failures::url)
+                            .do_update()
+                            .set((
+                                proxied_image_failures::error.eq(error.to_string()),
+                                proxied_image_failures::failed_at.eq(now),
+                            ))
+                            .execute(&*conn)?;
+                        return Err(error);
+                    }
+                };
+
+                let digest: String = Sha256::digest(&image).encode_hex();
+                app.config
+                    .uploader()
+                    .upload_proxied_image(app.http_client(), &digest, &content_type, image)
+                    .map_err(|e| internal(&format_args!("failed to upload image: {e}")))?;
+
+                let conn = app.db_write()?;
+                conn.transaction(|| {
+                    diesel::insert_into(proxied_images::table)
+                        .values((
+                            proxied_images::url.eq(url),
+                            proxied_images::digest.eq(&digest),
+                            proxied_images::content_type.eq(&content_type),
+                        ))
+                        .on_conflict_do_nothing()
+                        .execute(&*conn)?;
+                    diesel::delete(proxied_image_failures::table.find(url)).execute(&*conn)
+                })?;
+                digest
+            }
+        };
+
+        Ok(req.redirect(app.config.uploader().proxied_image_location(&digest)))
+    })
+    .await
+}
+
+/// Fetches an image and returns its content type and content.
+fn fetch_image(config: &ImageProxyConfig, url: &str) -> AppResult<(String, Vec<u8>)> {
+    let url = Url::parse(url).map_err(|_| bad_request("invalid image URL"))?;
+    let response = config.fetch(url).map_err(|e| bad_request(&e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(bad_request(&format_args!(
+            "failed to fetch image: the server responded with {status}"
+        )));
+    }
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+    if !IMAGE_CONTENT_TYPES.contains(&content_type.as_str()) {
+        return Err(bad_request(&format_args!(
+            "unsupported image content type `{content_type}`"
+        )));
+    }
+
+    let max_size = config.max_size;
+    let too_large = || bad_request(&format_args!("images may not exceed {max_size} bytes"));
+    if response.content_length().unwrap_or(0) > max_size {
+        return Err(too_large());
+    }
+
+    let mut image = Vec::new();
+    response
+        .take(max_size + 1)
+        .read_to_end(&mut image)
+        .map_err(|e| bad_request(&format_args!("failed to fetch image: {e}")))?;
+    if image.len() as u64 > max_size {
+        return Err(too_large());
+    }
+
+    Ok((content_type, image))
+}
@@ -6,6 +6,7 @@ use axum::Json;
 use crate::controllers::helpers::pagination::PaginationOptions;
 use crate::controllers::helpers::{pagination::Paginated, Paginate};
 use crate::models::Keyword;
+use crate::util::errors::not_found;
 use crate::views::EncodableKeyword;
 
 #[derive(Deserialize)]
@@ -47,16 +48,19 @@ pub async fn index(
 }
 
 /// Handles the `GET /keywords/:keyword_id` route.
-pub async fn show(
-    Path(name): Path<String>,
-    State(state): State<AppState>,
-) -> AppResult<Json<Value>> {
+pub async fn show(Path(name): Path<String>, req: ConduitRequest) -> AppResult<Response> {
     conduit_compat(move || {
-        let conn = state.db_read()?;
+        let conn = req.app().db_read()?;
 
-        let kw = Keyword::find_by_keyword(&conn, &name)?;
+        let kw = match Keyword::find_by_keyword(&conn, &name).optional()? {
+            Some(kw) => kw,
+            None => {
+                let name = Keyword::aliased_keyword(&conn, &name)?.ok_or_else(not_found)?;
+                return Ok(req.redirect(format!("/api/v1/keywords/{name}")));
+            }
+        };
 
-        Ok(Json(json!({ "keyword": EncodableKeyword::from(kw) })))
+        Ok(Json(json!({ "keyword": EncodableKeyword::from(kw) })).into_response())
     })
     .await
 }
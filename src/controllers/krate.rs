@@ -1,3 +1,4 @@
+pub mod delete;
 pub mod downloads;
 pub mod follow;
 pub mod metadata;
@@ -0,0 +1,82 @@
+//! Endpoint for owners to delete a crate shortly after publishing it.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::controllers::crate_owner_invitation::load_owned_crate;
+use crate::models::{Crate, DeletedCrate, Version};
+use crate::schema::{dependencies, versions};
+use crate::worker;
+use chrono::{Duration, Utc};
+
+/// Handles the `DELETE /api/v1/crates/:crate_id` route.
+///
+/// Owners with the admin role may delete a crate as long as it was published
+/// recently, has few downloads and no other crate depends on it. The name of
+/// the crate is reserved for a cooldown period after the deletion.
+pub async fn delete(Path(crate_name): Path<String>, req: ConduitRequest) -> AppResult<Response> {
+    conduit_compat(move || {
+        let app = req.app();
+        let config = &app.config;
+        let conn = app.db_write()?;
+        let (krate, user) = load_owned_crate(&req, &conn, &crate_name)?;
+
+        // The crate row is locked so that no downloads or dependent versions are
+        // added between the checks and the deletion.
+        let (krate, version_nums) = conn.transaction(|| -> AppResult<_> {
+            let krate: Crate = Crate::all().find(krate.id).for_update().first(&*conn)?;
+
+            let max_age = Duration::hours(config.crate_deletion_max_age_hours as i64);
+            if krate.created_at + max_age < Utc::now().naive_utc() {
+                return Err(cargo_err(&format_args!(
+                    "only crates published less than {} hours ago can be deleted",
+                    config.crate_deletion_max_age_hours
+                )));
+            }
+
+            if krate.downloads > config.crate_deletion_max_downloads {
+                return Err(cargo_err(&format_args!(
+                    "only crates with at most {} downloads can be deleted",
+                    config.crate_deletion_max_downloads
+                )));
+            }
+
+            if has_reverse_dependencies(&conn, &krate)? {
+                return Err(cargo_err(
+                    "crates that other crates depend on cannot be deleted",
+                ));
+            }
+
+            // Staged versions have crate files as well, so they are included here
+            let version_nums: Vec<String> = Version::belonging_to(&krate)
+                .select(versions::num)
+                .load(&*conn)?;
+            let cooldown = Duration::days(config.deleted_crate_name_cooldown_days as i64);
+            let reserved_until = Utc::now().naive_utc() + cooldown;
+
+            DeletedCrate::delete_crate(&conn, &krate, user.id, reserved_until)?;
+            worker::delete_crate_from_index(krate.name.clone()).enqueue(&conn)?;
+            worker::delete_crate_files(krate.name.clone(), version_nums.clone()).enqueue(&conn)?;
+            Ok((krate, version_nums))
+        })?;
+
+        for num in version_nums {
+            app.version_id_cacher.invalidate(&(krate.name.clone(), num));
+        }
+
+        ok_true()
+    })
+    .await
+}
+
+/// Whether versions of other crates depend on the crate.
+pub(crate) fn has_reverse_dependencies(conn: &PgConnection, krate: &Crate) -> QueryResult<bool> {
+    use diesel::dsl::exists;
+
+    diesel::select(exists(
+        dependencies::table
+            .inner_join(versions::table)
+            .filter(dependencies::crate_id.eq(krate.id))
+            .filter(versions::crate_id.ne(krate.id)),
+    ))
+    .get_result(conn)
+}
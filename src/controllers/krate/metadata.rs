@@ -11,15 +11,17 @@ use crate::controllers::frontend_prelude::*;
 use crate::controllers::helpers::pagination::PaginationOptions;
 
 use crate::models::{
-    Category, Crate, CrateCategory, CrateKeyword, CrateVersions, Keyword, RecentCrateDownloads,
-    TopVersions, User, Version, VersionOwnerAction,
+    Category, Crate, CrateBadge, CrateCategory, CrateKeyword, CrateVersions, Keyword,
+    RecentCrateDownloads, TopVersions, User, Version, VersionOwnerAction,
 };
 use crate::schema::*;
 use crate::views::{
-    EncodableCategory, EncodableCrate, EncodableDependency, EncodableKeyword, EncodableVersion,
+    EncodableCategory, EncodableCrate, EncodableDependency, EncodableKeyword,
+    EncodableReadmeHeading, EncodableVersion,
 };
 
 use crate::models::krate::ALL_COLUMNS;
+use crate::util::errors::not_found;
 
 /// Handles the `GET /summary` route.
 pub async fn summary(req: ConduitRequest) -> AppResult<Json<Value>> {
@@ -199,7 +201,14 @@ pub async fn show(Path(name): Path<String>, req: ConduitRequest) -> AppResult<Js
             None
         };
 
-        let badges = if include.badges { Some(vec![]) } else { None };
+        let badges = if include.badges {
+            let badges: Vec<CrateBadge> = CrateBadge::belonging_to(&krate)
+                .order(badges::badge_type)
+                .load(&*conn)?;
+            Some(badges.iter().filter_map(CrateBadge::badge).collect())
+        } else {
+            None
+        };
 
         let top_versions = if include.versions {
             Some(krate.top_versions(&conn)?)
@@ -325,6 +334,73 @@ pub async fn readme(
     .await
 }
 
+/// Handles the `GET /crates/:crate_id/:version/changelog` route.
+pub async fn changelog(
+    Path((crate_name, version)): Path<(String, String)>,
+    req: ConduitRequest,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let redirect_url = req
+            .app()
+            .config
+            .uploader()
+            .changelog_location(&crate_name, &version);
+
+        if req.wants_json() {
+            Ok(Json(json!({ "url": redirect_url })).into_response())
+        } else {
+            Ok(req.redirect(redirect_url))
+        }
+    })
+    .await
+}
+
+/// Handles the `GET /crates/:crate_id/:version/license` route.
+pub async fn license(
+    Path((crate_name, version)): Path<(String, String)>,
+    req: ConduitRequest,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let redirect_url = req
+            .app()
+            .config
+            .uploader()
+            .license_location(&crate_name, &version);
+
+        if req.wants_json() {
+            Ok(Json(json!({ "url": redirect_url })).into_response())
+        } else {
+            Ok(req.redirect(redirect_url))
+        }
+    })
+    .await
+}
+
+/// Handles the `GET /crates/:crate_id/:version/readme/toc` route.
+pub async fn readme_toc(
+    Path((crate_name, version)): Path<(String, String)>,
+    req: ConduitRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = req.app().db_read()?;
+        let krate: Crate = Crate::by_name(&crate_name).first(&*conn)?;
+        let version = krate.find_version(&conn, &version)?;
+
+        // Readmes that were rendered before outlines were recorded have none.
+        let outline: Option<Value> = readme_renderings::table
+            .find(version.id)
+            .select(readme_renderings::outline)
+            .first(&*conn)?;
+        let toc: Vec<EncodableReadmeHeading> = match outline {
+            Some(outline) => serde_json::from_value(outline)?,
+            None => return Err(not_found()),
+        };
+
+        Ok(Json(json!({ "toc": toc })))
+    })
+    .await
+}
+
 /// Handles the `GET /crates/:crate_id/versions` route.
 // FIXME: Not sure why this is necessary since /crates/:crate_id returns
 // this information already, but ember is definitely requesting it
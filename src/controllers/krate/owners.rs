@@ -3,7 +3,8 @@
 use crate::auth::AuthCheck;
 use crate::controllers::prelude::*;
 use crate::models::token::EndpointScope;
-use crate::models::{Crate, Owner, Rights, Team, User};
+use crate::models::{Crate, Owner, OwnerRole, Rights, Team, User};
+use crate::schema::crate_owners;
 use crate::views::EncodableOwner;
 use http::Request;
 use std::io::Read;
@@ -14,9 +15,9 @@ pub async fn owners(Path(crate_name): Path<String>, req: ConduitRequest) -> AppR
         let conn = req.app().db_read()?;
         let krate: Crate = Crate::by_name(&crate_name).first(&*conn)?;
         let owners = krate
-            .owners(&conn)?
+            .owners_with_roles(&conn)?
             .into_iter()
-            .map(Owner::into)
+            .map(EncodableOwner::from)
             .collect::<Vec<EncodableOwner>>();
 
         Ok(Json(json!({ "users": owners })))
@@ -116,12 +117,17 @@ fn modify_owners<B: Read>(
 
     conn.transaction(|| {
         let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
-        let owners = krate.owners(&conn)?;
+        let owners = krate.owners_with_roles(&conn)?;
 
         match user.rights(app, &owners)? {
             Rights::Full => {}
             // Yes!
-            Rights::Publish => {
+            Rights::Publish | Rights::Yank if is_individual_owner(&owners, &user) => {
+                return Err(cargo_err(
+                    "only owners with the admin role have permission to modify owners",
+                ));
+            }
+            Rights::Publish | Rights::Yank => {
                 return Err(cargo_err(
                     "team members don't have permission to modify owners",
                 ));
@@ -136,7 +142,7 @@ fn modify_owners<B: Read>(
             for login in &logins {
                 let login_test =
                     |owner: &Owner| owner.login().to_lowercase() == *login.to_lowercase();
-                if owners.iter().any(login_test) {
+                if owners.iter().map(|(owner, _)| owner).any(login_test) {
                     return Err(cargo_err(&format_args!("`{login}` is already an owner")));
                 }
                 let msg = krate.owner_add(app, &conn, &user, login)?;
@@ -154,9 +160,96 @@ fn modify_owners<B: Read>(
                      at least one individual owner is required.",
                 ));
             }
+            if !has_admin(&krate.owners_with_roles(&conn)?) {
+                return Err(cargo_err(LAST_ADMIN_ERROR_MESSAGE));
+            }
             "owners successfully removed".to_owned()
         };
 
         Ok(Json(json!({ "ok": true, "msg": comma_sep_msg })))
     })
 }
+
+/// Handles the `PUT /crates/:crate_id/owners/:login/role` route.
+///
+/// The format of the request body is:
+///
+/// ```json
+/// {"role": "admin" | "publisher" | "yanker"}
+/// ```
+pub async fn update_owner_role(
+    Path((crate_name, login)): Path<(String, String)>,
+    mut req: ConduitRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        #[derive(Deserialize)]
+        struct Request {
+            role: String,
+        }
+
+        let auth = AuthCheck::default()
+            .with_endpoint_scope(EndpointScope::ChangeOwners)
+            .for_crate(&crate_name)
+            .check(&req)?;
+
+        let request: Request = serde_json::from_reader(req.body_mut())
+            .map_err(|_| cargo_err("invalid json request"))?;
+        let role = request
+            .role
+            .parse::<OwnerRole>()
+            .map_err(|e| cargo_err(&e))?;
+
+        let app = req.app();
+        let conn = app.db_write()?;
+        let user = auth.user();
+
+        conn.transaction(|| {
+            let krate: Crate = Crate::by_name(&crate_name).first(&*conn)?;
+            let owners = krate.owners_with_roles(&conn)?;
+            if user.rights(app, &owners)? != Rights::Full {
+                return Err(cargo_err(
+                    "only owners with the admin role have permission to change roles",
+                ));
+            }
+
+            let owner = owners
+                .iter()
+                .map(|(owner, _)| owner)
+                .find(|owner| owner.login().eq_ignore_ascii_case(&login))
+                .ok_or_else(|| cargo_err(&format_args!("`{login}` is not an owner")))?;
+
+            let target = crate_owners::table.find((krate.id, owner.id(), owner.kind()));
+            diesel::update(target)
+                .set(crate_owners::role.eq(role))
+                .execute(&*conn)?;
+
+            if !has_admin(&krate.owners_with_roles(&conn)?) {
+                return Err(cargo_err(LAST_ADMIN_ERROR_MESSAGE));
+            }
+
+            let role: &'static str = role.into();
+            let msg = format!(
+                "`{}` now has the {role} role on crate {}",
+                owner.login(),
+                krate.name
+            );
+            Ok(Json(json!({ "ok": true, "msg": msg })))
+        })
+    })
+    .await
+}
+
+const LAST_ADMIN_ERROR_MESSAGE: &str = "cannot remove the last individual owner with the \
+     admin role, since nobody else would be able to manage the owners of the crate";
+
+fn is_individual_owner(owners: &[(Owner, OwnerRole)], user: &User) -> bool {
+    owners
+        .iter()
+        .any(|(owner, _)| matches!(owner, Owner::User(u) if u.id == user.id))
+}
+
+fn has_admin(owners: &[(Owner, OwnerRole)]) -> bool {
+    owners
+        .iter()
+        .any(|(owner, role)| matches!(owner, Owner::User(_)) && *role == OwnerRole::Admin)
+}
@@ -1,28 +1,38 @@
 //! Functionality related to publishing a new crate or version of a crate.
 
+use crate::app::AppState;
 use crate::auth::AuthCheck;
+use axum::body::Bytes;
 use flate2::read::GzDecoder;
 use hex::ToHex;
 use http::Request;
 use sha2::{Digest, Sha256};
-use std::collections::BTreeMap;
-use std::io::Read;
-use std::path::Path;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Cursor, Read};
+use std::path::{self, Component, PathBuf};
 
+use crate::background_jobs::PendingIndexUpdates;
 use crate::controllers::cargo_prelude::*;
+use crate::models::krate::split_index_features;
 use crate::models::{
-    insert_version_owner_action, Category, Crate, DependencyKind, Keyword, NewCrate, NewVersion,
-    Rights, VersionAction,
+    insert_version_owner_action, Badge, Category, Crate, CrateMetadata, CrateVersions,
+    DependencyKind, Keyword, LicenseExpression, NewCrate, NewVersion, PublishState, PublishUpload,
+    Rights, User, VersionAction,
 };
+use crate::util::errors::not_found;
 use crate::worker;
 
 use crate::middleware::log_request::CustomMetadataRequestExt;
 use crate::models::token::EndpointScope;
 use crate::schema::*;
-use crate::util::errors::{cargo_err, AppResult};
-use crate::util::{read_fill, read_le_u32, CargoVcsInfo, LimitErrorReader, Maximums};
+use crate::util::errors::{cargo_err, is_cargo_err, AppResult};
+use crate::util::{
+    read_fill, read_le_u32, CargoVcsInfo, LimitErrorReader, Maximums, PackageDocument,
+    PackageDocuments,
+};
 use crate::views::{
-    EncodableCrate, EncodableCrateDependency, EncodableCrateUpload, GoodCrate, PublishWarnings,
+    EncodableCrate, EncodableCrateDependency, EncodableCrateUpload, EncodableErrorDetail,
+    EncodablePublishStatus, GoodCrate, GoodCrateBatch, PublishDryRun, PublishWarnings,
 };
 
 pub const MISSING_RIGHTS_ERROR_MESSAGE: &str =
@@ -31,6 +41,11 @@ pub const MISSING_RIGHTS_ERROR_MESSAGE: &str =
      to accept an invitation to be an owner before \
      publishing.";
 
+pub const YANKER_ROLE_ERROR_MESSAGE: &str =
+    "your role on this crate only allows yanking versions. \
+     Ask an owner with the admin role to change your role \
+     to publisher if you need to publish new versions.";
+
 pub const WILDCARD_ERROR_MESSAGE: &str = "wildcard (`*`) dependency constraints are not allowed \
      on crates.io. See https://doc.rust-lang.org/cargo/faq.html#can-\
      libraries-use--as-a-version-for-their-dependencies for more \
@@ -40,12 +55,38 @@ pub const WILDCARD_ERROR_MESSAGE: &str = "wildcard (`*`) dependency constraints
 /// Used by `cargo publish` to publish a new crate or to publish a new version of an
 /// existing crate.
 ///
-/// Currently blocks the HTTP thread, perhaps some function calls can spawn new
-/// threads and return completion or error through other methods  a `cargo publish
-/// --status` command, via crates.io's front end, or email.
-pub async fn publish(mut req: ConduitRequest) -> AppResult<Json<GoodCrate>> {
+/// With the `dry_run=1` query parameter the whole pipeline runs inside a
+/// transaction that is rolled back afterwards. Nothing is uploaded or enqueued,
+/// and the response lists every error that was found together with the
+/// warnings, instead of stopping at the first error.
+///
+/// With the `async=1` query parameter the upload is validated against the
+/// database and stored, and the crate file is verified, uploaded and added to
+/// the index by a background job. The progress can be followed with the
+/// `publish_status` endpoint.
+///
+/// With the `staged=1` query parameter the crate file is verified and uploaded,
+/// but the version is neither added to the index nor listed by the API until
+/// it is released with the `release` endpoint.
+///
+/// Otherwise this blocks the HTTP thread while the crate file is verified and
+/// uploaded.
+pub async fn publish(mut req: ConduitRequest) -> AppResult<Response> {
     conduit_compat(move || {
         let app = req.app().clone();
+        let query = req.query();
+        let is_set = |param| matches!(query.get(param).map(String::as_str), Some("1" | "true"));
+        let dry_run = is_set("dry_run");
+        let mode = match (is_set("async"), is_set("staged")) {
+            (false, false) => PublishMode::Immediate,
+            (true, false) => PublishMode::Asynchronous,
+            (false, true) => PublishMode::Staged,
+            (true, true) => {
+                return Err(cargo_err(
+                    "staged uploads cannot be published asynchronously",
+                ))
+            }
+        };
 
         // The format of the req.body() of a publish request is as follows:
         //
@@ -87,205 +128,765 @@ pub async fn publish(mut req: ConduitRequest) -> AppResult<Json<GoodCrate>> {
         let api_token_id = auth.api_token_id();
         let user = auth.user();
 
-        let verified_email_address = user.verified_email(&conn)?;
-        let verified_email_address = verified_email_address.ok_or_else(|| {
+        let mut checks = PublishChecks {
+            dry_run,
+            errors: Vec::new(),
+        };
+
+        let verified_email_address = user.verified_email(&conn)?.ok_or_else(|| {
             cargo_err(&format!(
                 "A verified email address is required to publish crates to crates.io. \
              Visit https://{}/me to set and verify your email address.",
                 app.config.domain_name,
             ))
-        })?;
+        });
+        // Only dry runs get past a missing email address, and their changes
+        // are rolled back anyway.
+        let verified_email_address = checks.check(verified_email_address)?.unwrap_or_default();
+
+        if !dry_run {
+            let crate_name = new_crate.name.to_string();
+            let num = new_crate.vers.to_string();
+
+            // Create a transaction on the database, if there are no errors,
+            // commit the transactions to record a new or updated crate.
+            let content_length = req.content_length();
+            let good_crate = conn.transaction(|| {
+                publish_crate(
+                    &app,
+                    req.body_mut(),
+                    content_length,
+                    &conn,
+                    &user,
+                    api_token_id,
+                    &verified_email_address,
+                    new_crate,
+                    &mut checks,
+                    mode,
+                )
+                .map(|published| published.good_crate)
+            })?;
 
-        // Create a transaction on the database, if there are no errors,
-        // commit the transactions to record a new or updated crate.
-        conn.transaction(|| {
-            let _ = &new_crate;
-            let name = new_crate.name;
-            let vers = &*new_crate.vers;
-            let links = new_crate.links;
-            let repo = new_crate.repository;
-            let features = new_crate
-                .features
+            if mode != PublishMode::Asynchronous {
+                return Ok(Json(good_crate).into_response());
+            }
+
+            let publish_status = load_publish_status(&conn, &crate_name, &num)?;
+            let body = json!({
+                "crate": good_crate.krate,
+                "warnings": good_crate.warnings,
+                "publish_status": publish_status,
+            });
+            return Ok((StatusCode::ACCEPTED, Json(body)).into_response());
+        }
+
+        let content_length = req.content_length();
+        let mut outcome = None;
+        let rollback = conn.transaction(|| {
+            outcome = Some(publish_crate(
+                &app,
+                req.body_mut(),
+                content_length,
+                &conn,
+                &user,
+                api_token_id,
+                &verified_email_address,
+                new_crate,
+                &mut checks,
+                PublishMode::Immediate,
+            ));
+            Err(diesel::result::Error::RollbackTransaction)
+        });
+        match rollback {
+            Err(diesel::result::Error::RollbackTransaction) => {}
+            result => result?,
+        }
+
+        let good_crate = match outcome {
+            Some(Ok(published)) => Some(published.good_crate),
+            Some(Err(error)) => checks.check::<()>(Err(error)).map(|_| None)?,
+            None => None,
+        };
+
+        let (krate, warnings) = match good_crate {
+            Some(good_crate) => (Some(good_crate.krate), good_crate.warnings),
+            None => (None, PublishWarnings::default()),
+        };
+
+        Ok(Json(PublishDryRun {
+            krate,
+            warnings,
+            errors: checks
+                .errors
                 .into_iter()
-                .map(|(k, v)| (k.0, v.into_iter().map(|v| v.0).collect()))
-                .collect();
-            let keywords = new_crate
-                .keywords
-                .iter()
-                .map(|s| s.as_str())
-                .collect::<Vec<_>>();
-            let categories = new_crate
-                .categories
-                .iter()
-                .map(|s| s.as_str())
-                .collect::<Vec<_>>();
-
-            // Persist the new crate, if it doesn't already exist
-            let persist = NewCrate {
-                name: &name,
-                description: new_crate.description.as_deref(),
-                homepage: new_crate.homepage.as_deref(),
-                documentation: new_crate.documentation.as_deref(),
-                readme: new_crate.readme.as_deref(),
-                repository: repo.as_deref(),
-                max_upload_size: None,
+                .map(|detail| EncodableErrorDetail { detail })
+                .collect(),
+        })
+        .into_response())
+    })
+    .await
+}
+
+/// The maximum number of crates that can be published in a single batch.
+const MAX_BATCH_SIZE: u32 = 50;
+
+/// Handles the `PUT /crates/new/batch` route.
+///
+/// Publishes new versions of several crates at once. The request body starts
+/// with the number of crates in the batch as a 32 bit little-endian integer,
+/// followed by each crate in the same format as for the `PUT /crates/new` route.
+///
+/// Dependencies on other crates of the batch are resolved by publishing the
+/// crates of the batch after the crates they depend on. Either all versions
+/// are published, in a single database transaction and a single index commit,
+/// or none of them are. The crate files that were already uploaded are deleted
+/// again if the batch fails.
+pub async fn publish_batch(mut req: ConduitRequest) -> AppResult<Response> {
+    conduit_compat(move || {
+        let app = req.app().clone();
+
+        let uploads = parse_batch(&mut req)?;
+        let uploads = order_batch(uploads)?;
+        req.add_custom_metadata("batch_size", uploads.len());
+
+        let conn = app.primary_database.get()?;
+
+        // Every crate of the batch has to be covered by the scopes of the token
+        let mut has_new_crates = false;
+        let mut auths = Vec::with_capacity(uploads.len());
+        for upload in &uploads {
+            let existing_crate = Crate::by_name(&upload.new_crate.name)
+                .first::<Crate>(&*conn)
+                .optional()?;
+
+            let endpoint_scope = match existing_crate {
+                Some(_) => EndpointScope::PublishUpdate,
+                None => {
+                    has_new_crates = true;
+                    EndpointScope::PublishNew
+                }
             };
 
-            let license_file = new_crate.license_file.as_deref();
-            let krate =
-                persist.create_or_update(&conn, user.id, Some(&app.config.publish_rate_limit))?;
+            let auth = AuthCheck::default()
+                .with_endpoint_scope(endpoint_scope)
+                .for_crate(&upload.new_crate.name)
+                .check(&req)?;
+            auths.push(auth);
+        }
+
+        // Batches are never empty, see `parse_batch`
+        let auth = auths.swap_remove(0);
+        let api_token_id = auth.api_token_id();
+        let user = auth.user();
+
+        let verified_email_address = user.verified_email(&conn)?.ok_or_else(|| {
+            cargo_err(&format!(
+                "A verified email address is required to publish crates to crates.io. \
+             Visit https://{}/me to set and verify your email address.",
+                app.config.domain_name,
+            ))
+        })?;
+
+        let mut checks = PublishChecks {
+            dry_run: false,
+            errors: Vec::new(),
+        };
 
-            let owners = krate.owners(&conn)?;
-            if user.rights(&app, &owners)? < Rights::Publish {
-                return Err(cargo_err(MISSING_RIGHTS_ERROR_MESSAGE));
+        // The crate files that were uploaded, which are deleted again if the
+        // transaction is rolled back afterwards
+        let mut uploaded = Vec::new();
+        let result = conn.transaction(|| -> AppResult<_> {
+            // A batch counts as a single publish towards the rate limit for new crates
+            if has_new_crates {
+                app.config
+                    .publish_rate_limit
+                    .check_rate_limit(user.id, &conn)?;
             }
 
-            if krate.name != *name {
-                return Err(cargo_err(&format_args!(
-                    "crate was previously named `{}`",
-                    krate.name
-                )));
+            let mut crates = Vec::with_capacity(uploads.len());
+            let mut deferred_uploads = Vec::with_capacity(uploads.len());
+            for upload in uploads {
+                let name = upload.new_crate.name.to_string();
+                let vers = upload.new_crate.vers.to_string();
+
+                let published = publish_crate(
+                    &app,
+                    &mut Cursor::new(upload.crate_file),
+                    upload.content_length,
+                    &conn,
+                    &user,
+                    api_token_id,
+                    &verified_email_address,
+                    upload.new_crate,
+                    &mut checks,
+                    PublishMode::Batched,
+                )
+                .map_err(|error| match is_cargo_err(&error) {
+                    true => cargo_err(&format_args!("failed to publish `{name}@{vers}`: {error}")),
+                    false => error,
+                })?;
+
+                crates.push(published.good_crate);
+                deferred_uploads.extend(published.deferred);
             }
 
-            if let Some(daily_version_limit) = app.config.new_version_rate_limit {
-                let published_today = count_versions_published_today(krate.id, &conn)?;
-                if published_today >= daily_version_limit as i64 {
-                    return Err(cargo_err(
-                        "You have published too many versions of this crate in the last 24 hours",
-                    ));
+            // Nothing is uploaded before all crates of the batch were accepted
+            let mut index_entries = Vec::with_capacity(deferred_uploads.len());
+            for deferred in deferred_uploads {
+                app.config.uploader().upload_crate(
+                    app.http_client(),
+                    deferred.tarball,
+                    &deferred.crate_name,
+                    &deferred.vers,
+                )?;
+                uploaded.push((deferred.crate_name, deferred.vers.to_string()));
+                index_entries.push(deferred.index_entry);
+            }
+
+            worker::add_crates(index_entries).enqueue(&conn)?;
+            Ok(crates)
+        });
+
+        if result.is_err() {
+            for (crate_name, vers) in &uploaded {
+                let uploader = app.config.uploader();
+                if let Err(error) = uploader.delete_crate(app.http_client(), crate_name, vers) {
+                    warn!(%crate_name, %vers, %error, "Failed to delete the crate file of a failed batch");
                 }
             }
+        }
+        let crates = result?;
 
-            // Length of the .crate tarball, which appears after the metadata in the request body.
-            // TODO: Not sure why we're using the total content length (metadata + .crate file length)
-            // to compare against the max upload size... investigate that and perhaps change to use
-            // this file length.
-            let file_length = read_le_u32(req.body_mut())?;
+        Ok(Json(GoodCrateBatch { crates }).into_response())
+    })
+    .await
+}
 
-            let content_length = req.content_length();
+/// A crate of a batch publish.
+struct BatchUpload {
+    new_crate: EncodableCrateUpload,
+    /// The length of the crate file, followed by the crate file itself
+    crate_file: Bytes,
+    /// The length of the part of the request body that describes this crate
+    content_length: u64,
+}
 
-            let maximums = Maximums::new(
-                krate.max_upload_size,
-                app.config.max_upload_size,
-                app.config.max_unpack_size,
-            );
+/// Splits the body of a batch publish request into its crates.
+fn parse_batch(req: &mut ConduitRequest) -> AppResult<Vec<BatchUpload>> {
+    let max = req.app().config.max_upload_size;
+    let body = req.body_mut();
 
-            if content_length > maximums.max_upload_size {
+    let count = read_le_u32(body)?;
+    if count == 0 {
+        return Err(cargo_err("the batch does not contain any crates"));
+    }
+    if count > MAX_BATCH_SIZE {
+        return Err(cargo_err(&format_args!(
+            "a batch can contain at most {MAX_BATCH_SIZE} crates"
+        )));
+    }
+
+    let mut uploads = Vec::with_capacity(count as usize);
+    let mut crate_names = BTreeSet::new();
+    for _ in 0..count {
+        let start = body.position();
+        let metadata_length = u64::from(read_le_u32(body)?);
+        let new_crate = parse_metadata(body, metadata_length, max)?;
+
+        let canonical_name = new_crate.name.to_lowercase().replace('-', "_");
+        if !crate_names.insert(canonical_name) {
+            return Err(cargo_err(&format_args!(
+                "crate `{}` is contained more than once in the batch",
+                *new_crate.name
+            )));
+        }
+
+        let file_start = body.position();
+        let file_length = u64::from(read_le_u32(body)?);
+        let end = body.position() + file_length;
+        if end > body.get_ref().len() as u64 {
+            return Err(cargo_err(&format_args!(
+                "invalid upload request: the crate file of `{}` is incomplete",
+                *new_crate.name
+            )));
+        }
+        body.set_position(end);
+
+        uploads.push(BatchUpload {
+            new_crate,
+            crate_file: body.get_ref().slice(file_start as usize..end as usize),
+            content_length: end - start,
+        });
+    }
+
+    Ok(uploads)
+}
+
+/// Orders the crates of a batch, so that every crate comes after the crates of
+/// the batch that it depends on. Crates without such dependencies keep the
+/// order in which they were uploaded.
+fn order_batch(uploads: Vec<BatchUpload>) -> AppResult<Vec<BatchUpload>> {
+    let positions = uploads
+        .iter()
+        .enumerate()
+        .map(|(i, upload)| (&*upload.new_crate.name, i))
+        .collect::<HashMap<_, _>>();
+
+    let batch_deps = uploads
+        .iter()
+        .map(|upload| {
+            upload
+                .new_crate
+                .deps
+                .iter()
+                .filter(|dep| dep.registry.as_deref().map_or(true, str::is_empty))
+                .filter_map(|dep| positions.get(&*dep.name).copied())
+                .collect::<BTreeSet<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut order = Vec::with_capacity(uploads.len());
+    let mut ordered = vec![false; uploads.len()];
+    while order.len() < uploads.len() {
+        let next = (0..uploads.len())
+            .find(|&i| !ordered[i] && batch_deps[i].iter().all(|&dep| ordered[dep]));
+
+        match next {
+            Some(i) => {
+                ordered[i] = true;
+                order.push(i);
+            }
+            None => {
+                let names = (0..uploads.len())
+                    .filter(|&i| !ordered[i])
+                    .map(|i| format!("`{}`", *uploads[i].new_crate.name))
+                    .collect::<Vec<_>>()
+                    .join(", ");
                 return Err(cargo_err(&format_args!(
-                    "max upload size is: {}",
-                    maximums.max_upload_size
+                    "the crates {names} of the batch depend on each other in a cycle"
                 )));
             }
+        }
+    }
 
-            // This is only redundant for now. Eventually the duplication will be removed.
-            let license = new_crate.license.clone();
-
-            // Read tarball from request
-            let mut tarball = Vec::new();
-            LimitErrorReader::new(req.body_mut(), maximums.max_upload_size)
-                .read_to_end(&mut tarball)?;
-            let hex_cksum: String = Sha256::digest(&tarball).encode_hex();
-
-            // Persist the new version of this crate
-            let version = NewVersion::new(
-                krate.id,
-                vers,
-                &features,
-                license,
-                license_file,
-                // Downcast is okay because the file length must be less than the max upload size
-                // to get here, and max upload sizes are way less than i32 max
-                file_length as i32,
-                user.id,
-                hex_cksum.clone(),
-                links.clone(),
-            )?
-            .save(&conn, &verified_email_address)?;
-
-            insert_version_owner_action(
-                &conn,
-                version.id,
-                user.id,
-                api_token_id,
-                VersionAction::Publish,
-            )?;
+    let mut uploads = uploads.into_iter().map(Some).collect::<Vec<_>>();
+    Ok(order
+        .into_iter()
+        .filter_map(|i| uploads[i].take())
+        .collect())
+}
 
-            // Link this new version to all dependencies
-            let git_deps = add_dependencies(&conn, &new_crate.deps, version.id)?;
+/// Handles the `GET /crates/:crate_id/:version/publish_status` route.
+///
+/// Reports whether a version has been published and whether its index entry
+/// has landed in the git index and the HTTP index yet.
+pub async fn publish_status(
+    Path((crate_name, version)): Path<(String, String)>,
+    req: ConduitRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = req.app().db_read_prefer_primary()?;
+        let publish_status = load_publish_status(&conn, &crate_name, &version)?;
+        Ok(Json(json!({ "publish_status": publish_status })))
+    })
+    .await
+}
 
-            // Update all keywords for this crate
-            Keyword::update_crate(&conn, &krate, &keywords)?;
+fn load_publish_status(
+    conn: &PgConnection,
+    crate_name: &str,
+    num: &str,
+) -> AppResult<EncodablePublishStatus> {
+    let upload = PublishUpload::for_version(conn, crate_name, num).optional()?;
+    let (state, error) = match upload {
+        Some(upload) => (upload.state, upload.error),
+        None => {
+            // Versions that were published synchronously have no upload record
+            Crate::by_name(crate_name)
+                .inner_join(versions::table)
+                .filter(versions::num.eq(num))
+                .filter(versions::staged.eq(false))
+                .select(versions::id)
+                .first::<i32>(conn)
+                .optional()?
+                .ok_or_else(not_found)?;
+            (PublishState::Published, None)
+        }
+    };
+
+    let pending = PendingIndexUpdates::for_crate(conn, crate_name)?;
+    let in_git_index = state == PublishState::Published && !pending.git;
+    let in_http_index = in_git_index && !pending.http;
+
+    Ok(EncodablePublishStatus {
+        crate_name: crate_name.to_string(),
+        version: num.to_string(),
+        state: <&'static str>::from(state).to_string(),
+        error,
+        in_git_index,
+        in_http_index,
+    })
+}
+
+/// How a version is made available after its upload was accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PublishMode {
+    /// The crate file is uploaded and the version is added to the index right away.
+    Immediate,
+    /// The crate file is verified and uploaded by a background job.
+    Asynchronous,
+    /// The crate file is uploaded, but the version stays hidden until it is released.
+    Staged,
+    /// The crate file is verified, and uploaded together with the other crates
+    /// of a batch once all of them were accepted.
+    Batched,
+}
 
-            // Update all categories for this crate, collecting any invalid categories
-            // in order to be able to warn about them
-            let ignored_invalid_categories = Category::update_crate(&conn, &krate, &categories)?;
+/// The outcome of `publish_crate`.
+struct PublishedVersion {
+    good_crate: GoodCrate,
+    /// The crate file and index entry of a batched publish, which are left to
+    /// `publish_batch` to upload and add to the index.
+    deferred: Option<DeferredUpload>,
+}
 
-            let top_versions = krate.top_versions(&conn)?;
+struct DeferredUpload {
+    crate_name: String,
+    vers: semver::Version,
+    tarball: Vec<u8>,
+    index_entry: cargo_registry_index::Crate,
+}
 
+/// Keeps track of the failed checks of a publish.
+///
+/// Outside of dry runs the first error aborts the publish. During dry runs
+/// errors meant for the user are recorded instead, so that the remaining
+/// checks still run and all errors can be reported at once.
+struct PublishChecks {
+    dry_run: bool,
+    errors: Vec<String>,
+}
+
+impl PublishChecks {
+    /// Returns the value of a successful check, or `None` if the check failed
+    /// during a dry run.
+    fn check<T>(&mut self, result: AppResult<T>) -> AppResult<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(error) if self.dry_run && is_user_error(&error) => {
+                self.errors.push(error.to_string());
+                Ok(None)
+            }
+            Err(error) => Err(error),
+        }
+    }
+}
+
+/// Whether the error describes a problem with the publish request, as
+/// opposed to an internal error.
+fn is_user_error(error: &BoxedAppError) -> bool {
+    let status = error.response().status();
+    status.is_success() || status.is_client_error()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn publish_crate(
+    app: &AppState,
+    body: &mut dyn Read,
+    content_length: u64,
+    conn: &PgConnection,
+    user: &User,
+    api_token_id: Option<i32>,
+    verified_email_address: &str,
+    new_crate: EncodableCrateUpload,
+    checks: &mut PublishChecks,
+    mode: PublishMode,
+) -> AppResult<PublishedVersion> {
+    let name = new_crate.name;
+    let vers = &*new_crate.vers;
+    let links = new_crate.links;
+    let repo = new_crate.repository;
+    let features = new_crate
+        .features
+        .into_iter()
+        .map(|(k, v)| (k.0, v.into_iter().map(|v| v.0).collect()))
+        .collect();
+    let keywords = new_crate
+        .keywords
+        .iter()
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>();
+    let categories = new_crate
+        .categories
+        .iter()
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>();
+
+    // Invalid license expressions are reported when the version is created
+    let license = new_crate.license.as_deref().map(LicenseExpression::parse);
+    if let Ok(license) = license.transpose() {
+        checks.check(app.config.license_policy.check(license.as_ref()))?;
+    }
+
+    // Persist the new crate, if it doesn't already exist
+    let persist = NewCrate {
+        name: &name,
+        description: new_crate.description.as_deref(),
+        homepage: new_crate.homepage.as_deref(),
+        documentation: new_crate.documentation.as_deref(),
+        readme: new_crate.readme.as_deref(),
+        repository: repo.as_deref(),
+        max_upload_size: None,
+    };
+
+    let license_file = new_crate.license_file.as_deref();
+    // Batch publishes are rate limited as a whole, see `publish_batch`
+    let rate_limit = match mode {
+        PublishMode::Batched => None,
+        _ => Some(&app.config.publish_rate_limit),
+    };
+    // The metadata of asynchronously published versions is restored if their
+    // crate file is rejected by the background job
+    let previous_metadata = match mode {
+        PublishMode::Asynchronous => Crate::by_name(&name)
+            .select(crates::id)
+            .first::<i32>(conn)
+            .optional()?
+            .map(|crate_id| CrateMetadata::load(conn, crate_id))
+            .transpose()?,
+        _ => None,
+    };
+    let krate = persist.create_or_update(conn, user.id, rate_limit)?;
+
+    let owners = krate.owners_with_roles(conn)?;
+    checks.check(match user.rights(app, &owners)? {
+        Rights::Full | Rights::Publish => Ok(()),
+        Rights::Yank => Err(cargo_err(YANKER_ROLE_ERROR_MESSAGE)),
+        Rights::None => Err(cargo_err(MISSING_RIGHTS_ERROR_MESSAGE)),
+    })?;
+
+    if krate.name != *name {
+        checks.check::<()>(Err(cargo_err(&format_args!(
+            "crate was previously named `{}`",
+            krate.name
+        ))))?;
+    }
+
+    if let Some(daily_version_limit) = app.config.new_version_rate_limit {
+        let published_today = count_versions_published_today(krate.id, conn)?;
+        if published_today >= daily_version_limit as i64 {
+            checks.check::<()>(Err(cargo_err(
+                "You have published too many versions of this crate in the last 24 hours",
+            )))?;
+        }
+    }
+
+    // Length of the .crate tarball, which appears after the metadata in the request body.
+    // TODO: Not sure why we're using the total content length (metadata + .crate file length)
+    // to compare against the max upload size... investigate that and perhaps change to use
+    // this file length.
+    let file_length = read_le_u32(body)?;
+
+    let maximums = Maximums::new(
+        krate.max_upload_size,
+        app.config.max_upload_size,
+        app.config.max_unpack_size,
+    );
+
+    if content_length > maximums.max_upload_size {
+        checks.check::<()>(Err(cargo_err(&format_args!(
+            "max upload size is: {}",
+            maximums.max_upload_size
+        ))))?;
+    }
+
+    // This is only redundant for now. Eventually the duplication will be removed.
+    let license = new_crate.license.clone();
+
+    // Read tarball from request
+    let mut tarball = Vec::new();
+    LimitErrorReader::new(body, maximums.max_upload_size).read_to_end(&mut tarball)?;
+    let hex_cksum: String = Sha256::digest(&tarball).encode_hex();
+
+    // Persist the new version of this crate
+    let version = checks.check(conn.transaction(|| {
+        NewVersion::new(
+            krate.id,
+            vers,
+            &features,
+            license,
+            license_file,
+            // Downcast is okay because the file length must be less than the max upload size
+            // to get here, and max upload sizes are way less than i32 max
+            file_length as i32,
+            user.id,
+            hex_cksum.clone(),
+            links.clone(),
+        )?
+        // Asynchronously published versions stay hidden until their crate
+        // file was verified by the background job
+        .staged(matches!(
+            mode,
+            PublishMode::Staged | PublishMode::Asynchronous
+        ))
+        .save(conn, verified_email_address)
+    }))?;
+
+    if let Some(version) = &version {
+        insert_version_owner_action(
+            conn,
+            version.id,
+            user.id,
+            api_token_id,
+            VersionAction::Publish,
+        )?;
+    }
+
+    // Link this new version to all dependencies
+    let git_deps = add_dependencies(
+        conn,
+        &new_crate.deps,
+        version.as_ref().map(|version| version.id),
+        &app.config.allowed_dependency_registries,
+        checks,
+    )?;
+
+    // Update all keywords for this crate, collecting any banned keywords in order
+    // to be able to warn about them
+    let ignored_banned_keywords = Keyword::update_crate(conn, &krate, &keywords)?;
+
+    // Update all categories for this crate, collecting any invalid categories
+    // in order to be able to warn about them
+    let ignored_invalid_categories = Category::update_crate(conn, &krate, &categories)?;
+
+    // Update all badges for this crate, collecting any invalid badges in order to be
+    // able to warn about them
+    let ignored_invalid_badges = Badge::update_crate(conn, &krate, &new_crate.badges)?;
+
+    let top_versions = krate.top_versions(conn)?;
+
+    let mut deferred = None;
+    if checks.dry_run {
+        // Nothing is uploaded or enqueued during dry runs
+        let pkg_name = format!("{}-{}", krate.name, vers);
+        checks.check(verify_tarball(
+            &pkg_name,
+            &tarball,
+            maximums.max_unpack_size,
+            license_file,
+        ))?;
+    } else if let Some(version) = version {
+        let readme_file = new_crate
+            .readme_file
+            .unwrap_or_else(|| String::from("README.md"));
+
+        let (features, features2, v) = split_index_features(features);
+
+        // Register this crate in our local git repo.
+        let git_crate = cargo_registry_index::Crate {
+            name: name.0,
+            vers: vers.to_string(),
+            cksum: hex_cksum,
+            features,
+            features2,
+            deps: git_deps,
+            yanked: Some(false),
+            links,
+            v,
+        };
+
+        if mode == PublishMode::Asynchronous {
+            // The crate file is verified and uploaded by a background job,
+            // which then releases the version and adds it to the index.
+            let upload =
+                PublishUpload::create(conn, &krate.name, &vers.to_string(), user.id, &tarball)?;
+            worker::finalize_publish(
+                upload.id,
+                version.id,
+                git_crate,
+                new_crate.readme,
+                readme_file,
+                license_file.map(String::from),
+                repo,
+                maximums.max_unpack_size,
+                previous_metadata,
+            )
+            .enqueue(conn)?;
+        } else {
             let pkg_name = format!("{}-{}", krate.name, vers);
-            let cargo_vcs_info = verify_tarball(&pkg_name, &tarball, maximums.max_unpack_size)?;
-            let pkg_path_in_vcs = cargo_vcs_info.map(|info| info.path_in_vcs);
+            let TarballInfo {
+                vcs_info,
+                documents,
+            } = verify_tarball(&pkg_name, &tarball, maximums.max_unpack_size, license_file)?;
+            let pkg_path_in_vcs = vcs_info.map(|info| info.path_in_vcs);
+
+            if !documents.is_empty() {
+                worker::render_and_upload_documents(
+                    version.id,
+                    documents,
+                    repo.clone(),
+                    pkg_path_in_vcs.clone(),
+                )
+                .enqueue(conn)?;
+            }
 
             if let Some(readme) = new_crate.readme {
                 worker::render_and_upload_readme(
                     version.id,
                     readme,
-                    new_crate
-                        .readme_file
-                        .unwrap_or_else(|| String::from("README.md")),
+                    readme_file,
                     repo,
                     pkg_path_in_vcs,
                 )
-                .enqueue(&conn)?;
+                .enqueue(conn)?;
             }
 
-            // Upload crate tarball
-            app.config
-                .uploader()
-                .upload_crate(app.http_client(), tarball, &krate, vers)?;
-
-            let (features, features2): (BTreeMap<_, _>, BTreeMap<_, _>) =
-                features.into_iter().partition(|(_k, vals)| {
-                    !vals
-                        .iter()
-                        .any(|v| v.starts_with("dep:") || v.contains("?/"))
+            if mode == PublishMode::Batched {
+                // The crate files of a batch are only uploaded once all of its
+                // crates were accepted
+                deferred = Some(DeferredUpload {
+                    crate_name: krate.name.clone(),
+                    vers: vers.clone(),
+                    tarball,
+                    index_entry: git_crate,
                 });
-            let (features2, v) = if features2.is_empty() {
-                (None, None)
             } else {
-                (Some(features2), Some(2))
-            };
-
-            // Register this crate in our local git repo.
-            let git_crate = cargo_registry_index::Crate {
-                name: name.0,
-                vers: vers.to_string(),
-                cksum: hex_cksum,
-                features,
-                features2,
-                deps: git_deps,
-                yanked: Some(false),
-                links,
-                v,
-            };
-            worker::add_crate(git_crate).enqueue(&conn)?;
-
-            // The `other` field on `PublishWarnings` was introduced to handle a temporary warning
-            // that is no longer needed. As such, crates.io currently does not return any `other`
-            // warnings at this time, but if we need to, the field is available.
-            let warnings = PublishWarnings {
-                invalid_categories: ignored_invalid_categories,
-                invalid_badges: vec![],
-                other: vec![],
-            };
+                // Upload crate tarball
+                app.config.uploader().upload_crate(
+                    app.http_client(),
+                    tarball,
+                    &krate.name,
+                    vers,
+                )?;
+
+                // Staged versions are added to the index once they are released
+                if mode != PublishMode::Staged {
+                    worker::add_crate(git_crate).enqueue(conn)?;
+                }
+            }
+        }
+    }
 
-            Ok(Json(GoodCrate {
-                krate: EncodableCrate::from_minimal(krate, Some(&top_versions), None, false, None),
-                warnings,
-            }))
-        })
+    // Banned keywords are reported as `other` warnings, which cargo displays verbatim.
+    let other = ignored_banned_keywords
+        .iter()
+        .map(|keyword| format!("the keyword `{keyword}` is not allowed and was ignored"))
+        .collect();
+    let warnings = PublishWarnings {
+        invalid_categories: ignored_invalid_categories,
+        invalid_badges: ignored_invalid_badges,
+        other,
+    };
+
+    let good_crate = GoodCrate {
+        krate: EncodableCrate::from_minimal(krate, Some(&top_versions), None, false, None),
+        warnings,
+    };
+
+    Ok(PublishedVersion {
+        good_crate,
+        deferred,
     })
-    .await
 }
 
 /// Counts the number of versions for `krate_id` that were published within
@@ -311,11 +912,21 @@ fn parse_new_headers<B: Read>(req: &mut Request<B>) -> AppResult<EncodableCrateU
     req.add_custom_metadata("metadata_length", metadata_length);
 
     let max = req.app().config.max_upload_size;
+    parse_metadata(req.body_mut(), metadata_length, max)
+}
+
+/// Reads and validates the JSON metadata of an upload, which is
+/// `metadata_length` bytes long.
+fn parse_metadata<R: Read>(
+    body: &mut R,
+    metadata_length: u64,
+    max: u64,
+) -> AppResult<EncodableCrateUpload> {
     if metadata_length > max {
         return Err(cargo_err(&format_args!("max upload size is: {max}")));
     }
     let mut json = vec![0; metadata_length as usize];
-    read_fill(req.body_mut(), &mut json)?;
+    read_fill(body, &mut json)?;
     let json = String::from_utf8(json).map_err(|_| cargo_err("json body was not valid utf-8"))?;
     let new: EncodableCrateUpload = serde_json::from_str(&json)
         .map_err(|e| cargo_err(&format_args!("invalid upload request: {e}")))?;
@@ -351,85 +962,166 @@ pub fn missing_metadata_error_message(missing: &[&str]) -> String {
     )
 }
 
-pub fn add_dependencies(
+/// Validates the dependencies of a new version and links the version to them.
+///
+/// During dry runs invalid dependencies are recorded in `checks` and the
+/// remaining dependencies are still validated. The dependencies are only
+/// stored if the version could be created.
+fn add_dependencies(
     conn: &PgConnection,
     deps: &[EncodableCrateDependency],
-    target_version_id: i32,
+    target_version_id: Option<i32>,
+    allowed_registries: &[String],
+    checks: &mut PublishChecks,
 ) -> AppResult<Vec<cargo_registry_index::Dependency>> {
     use self::dependencies::dsl::*;
     use diesel::insert_into;
 
-    let git_and_new_dependencies = deps
-        .iter()
-        .map(|dep| {
-            if let Some(registry) = &dep.registry {
-                if !registry.is_empty() {
-                    return Err(cargo_err(&format_args!("Dependency `{}` is hosted on another registry. Cross-registry dependencies are not permitted on crates.io.", &*dep.name)));
-                }
+    let mut git_deps = Vec::with_capacity(deps.len());
+    let mut new_dependencies = Vec::with_capacity(deps.len());
+    for dep in deps {
+        let validated = checks.check(validate_dependency(conn, dep, allowed_registries))?;
+        let (git_dep, dep_crate_id, dep_registry) = match validated {
+            Some(validated) => validated,
+            None => continue,
+        };
+
+        git_deps.push(git_dep);
+        if let Some(target_version_id) = target_version_id {
+            new_dependencies.push((
+                version_id.eq(target_version_id),
+                crate_id.eq(dep_crate_id),
+                req.eq(dep.version_req.to_string()),
+                dep.kind.map(|k| kind.eq(k as i32)),
+                optional.eq(dep.optional),
+                default_features.eq(dep.default_features),
+                features.eq(&dep.features),
+                target.eq(dep.target.as_deref()),
+                explicit_name.eq(dep.explicit_name_in_toml.as_deref()),
+                registry.eq(dep_registry),
+                registry_crate_name.eq(dep_registry.map(|_| &*dep.name.0)),
+            ));
+        }
+    }
+
+    insert_into(dependencies)
+        .values(&new_dependencies)
+        .execute(conn)?;
+
+    Ok(git_deps)
+}
+
+/// Checks that a dependency may be used by crates on crates.io, and returns
+/// its index entry together with the ID of the crate or the URL of the
+/// registry it refers to.
+fn validate_dependency<'a>(
+    conn: &PgConnection,
+    dep: &'a EncodableCrateDependency,
+    allowed_registries: &[String],
+) -> AppResult<(
+    cargo_registry_index::Dependency,
+    Option<i32>,
+    Option<&'a str>,
+)> {
+    // Dependencies on crates of an allowed external registry are stored
+    // with the index URL of their registry instead of a local crate.
+    let dep_registry = match dep.registry.as_deref() {
+        Some(url) if !url.is_empty() => {
+            if !is_allowed_registry(allowed_registries, url) {
+                return Err(cargo_err(&format_args!(
+                    "Dependency `{}` is hosted on the registry `{}`, which is not in this registry's list of allowed dependency registries.",
+                    &*dep.name, url
+                )));
             }
+            Some(url)
+        }
+        _ => None,
+    };
 
+    let dep_crate_id = match dep_registry {
+        Some(_) => None,
+        None => {
             // Match only identical names to ensure the index always references the original crate name
-            let krate:Crate = Crate::by_exact_name(&dep.name)
+            let krate: Crate = Crate::by_exact_name(&dep.name)
                 .first(conn)
                 .map_err(|_| cargo_err(&format_args!("no known crate named `{}`", &*dep.name)))?;
 
-            if let Ok(version_req) = semver::VersionReq::parse(&dep.version_req.0) {
-                if version_req == semver::VersionReq::STAR {
-                    return Err(cargo_err(WILDCARD_ERROR_MESSAGE));
-                }
+            // Staged versions are not in the index, so cargo could not resolve the dependency
+            let has_released_versions: bool =
+                diesel::select(diesel::dsl::exists(krate.all_versions())).get_result(conn)?;
+            if !has_released_versions {
+                return Err(cargo_err(&format_args!(
+                    "the crate `{}` has no released versions yet",
+                    &*dep.name
+                )));
             }
 
-            // If this dependency has an explicit name in `Cargo.toml` that
-            // means that the `name` we have listed is actually the package name
-            // that we're depending on. The `name` listed in the index is the
-            // Cargo.toml-written-name which is what cargo uses for
-            // `--extern foo=...`
-            let (name, package) = match &dep.explicit_name_in_toml {
-                Some(explicit) => (explicit.to_string(), Some(dep.name.to_string())),
-                None => (dep.name.to_string(), None),
-            };
+            Some(krate.id)
+        }
+    };
 
-            Ok((
-                cargo_registry_index::Dependency {
-                    name,
-                    req: dep.version_req.to_string(),
-                    features: dep.features.iter().map(|s| s.0.to_string()).collect(),
-                    optional: dep.optional,
-                    default_features: dep.default_features,
-                    target: dep.target.clone(),
-                    kind: dep.kind.or(Some(DependencyKind::Normal)).map(|dk| dk.into()),
-                    package,
-                },
-                (
-                    version_id.eq(target_version_id),
-                    crate_id.eq(krate.id),
-                    req.eq(dep.version_req.to_string()),
-                    dep.kind.map(|k| kind.eq(k as i32)),
-                    optional.eq(dep.optional),
-                    default_features.eq(dep.default_features),
-                    features.eq(&dep.features),
-                    target.eq(dep.target.as_deref()),
-                    explicit_name.eq(dep.explicit_name_in_toml.as_deref())
-                ),
-            ))
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+    if let Ok(version_req) = semver::VersionReq::parse(&dep.version_req.0) {
+        if version_req == semver::VersionReq::STAR {
+            return Err(cargo_err(WILDCARD_ERROR_MESSAGE));
+        }
+    }
 
-    let (git_deps, new_dependencies): (Vec<_>, Vec<_>) =
-        git_and_new_dependencies.into_iter().unzip();
+    // If this dependency has an explicit name in `Cargo.toml` that
+    // means that the `name` we have listed is actually the package name
+    // that we're depending on. The `name` listed in the index is the
+    // Cargo.toml-written-name which is what cargo uses for
+    // `--extern foo=...`
+    let (name, package) = match &dep.explicit_name_in_toml {
+        Some(explicit) => (explicit.to_string(), Some(dep.name.to_string())),
+        None => (dep.name.to_string(), None),
+    };
+
+    let git_dep = cargo_registry_index::Dependency {
+        name,
+        req: dep.version_req.to_string(),
+        features: dep.features.iter().map(|s| s.0.to_string()).collect(),
+        optional: dep.optional,
+        default_features: dep.default_features,
+        target: dep.target.clone(),
+        kind: dep
+            .kind
+            .or(Some(DependencyKind::Normal))
+            .map(|dk| dk.into()),
+        package,
+        registry: dep_registry.map(String::from),
+    };
+
+    Ok((git_dep, dep_crate_id, dep_registry))
+}
 
-    insert_into(dependencies)
-        .values(&new_dependencies)
-        .execute(conn)?;
+/// Checks whether the index URL of a dependency's registry is on the list of
+/// registries that crates of this registry may depend on.
+fn is_allowed_registry(allowed_registries: &[String], url: &str) -> bool {
+    let url = url.trim_end_matches('/');
+    allowed_registries
+        .iter()
+        .any(|allowed| allowed.trim_end_matches('/') == url)
+}
 
-    Ok(git_deps)
+/// Names of the changelog files that are rendered, in order of preference.
+const CHANGELOG_FILES: [&str; 2] = ["changelog.md", "changes.md"];
+
+/// Changelogs and license files larger than this are not rendered.
+const MAX_DOCUMENT_SIZE: u64 = 1024 * 1024;
+
+/// Contents of an uploaded crate file that are read while verifying it.
+#[derive(Debug, Default)]
+pub(crate) struct TarballInfo {
+    pub vcs_info: Option<CargoVcsInfo>,
+    pub documents: PackageDocuments,
 }
 
-fn verify_tarball(
+pub(crate) fn verify_tarball(
     pkg_name: &str,
     tarball: &[u8],
     max_unpack: u64,
-) -> AppResult<Option<CargoVcsInfo>> {
+    license_file: Option<&str>,
+) -> AppResult<TarballInfo> {
     // All our data is currently encoded with gzip
     let decoder = GzDecoder::new(tarball);
 
@@ -440,8 +1132,11 @@ fn verify_tarball(
     // Use this I/O object now to take a peek inside
     let mut archive = tar::Archive::new(decoder);
 
-    let vcs_info_path = Path::new(&pkg_name).join(".cargo_vcs_info.json");
-    let mut vcs_info = None;
+    let pkg_root = path::Path::new(pkg_name);
+    let vcs_info_path = pkg_root.join(".cargo_vcs_info.json");
+    let license_path = license_file.and_then(license_path_in_pkg);
+    let mut info = TarballInfo::default();
+    let mut changelog_preference = CHANGELOG_FILES.len();
 
     for entry in archive.entries()? {
         let mut entry = entry.map_err(|err| {
@@ -455,14 +1150,14 @@ fn verify_tarball(
         // upload a tarball that contains both `foo-0.1.0/` source code as well
         // as `bar-0.1.0/` source code, and this could overwrite other crates in
         // the registry!
-        let entry_path = entry.path()?;
+        let entry_path = entry.path()?.into_owned();
         if !entry_path.starts_with(pkg_name) {
             return Err(cargo_err("invalid tarball uploaded"));
         }
         if entry_path == vcs_info_path {
             let mut contents = String::new();
             entry.read_to_string(&mut contents)?;
-            vcs_info = CargoVcsInfo::from_contents(&contents).ok();
+            info.vcs_info = CargoVcsInfo::from_contents(&contents).ok();
         }
 
         // Historical versions of the `tar` crate which Cargo uses internally
@@ -474,14 +1169,60 @@ fn verify_tarball(
         if entry_type.is_hard_link() || entry_type.is_symlink() {
             return Err(cargo_err("invalid tarball uploaded"));
         }
+
+        let path_in_pkg = entry_path.strip_prefix(pkg_root).unwrap_or(&entry_path);
+        if !entry_type.is_file() {
+            continue;
+        }
+        if Some(path_in_pkg) == license_path.as_deref() {
+            info.documents.license = read_document(&mut entry, path_in_pkg)?;
+        } else if path_in_pkg.parent() == Some(path::Path::new("")) {
+            let file_name = path_in_pkg.to_string_lossy().to_lowercase();
+            let preference = CHANGELOG_FILES.iter().position(|name| *name == file_name);
+            if let Some(preference) = preference.filter(|p| *p < changelog_preference) {
+                if let Some(changelog) = read_document(&mut entry, path_in_pkg)? {
+                    info.documents.changelog = Some(changelog);
+                    changelog_preference = preference;
+                }
+            }
+        }
+    }
+    Ok(info)
+}
+
+/// Returns the path of the license file within the package. Cargo puts license files
+/// from outside of the package into its root.
+fn license_path_in_pkg(license_file: &str) -> Option<PathBuf> {
+    let license_file = path::Path::new(license_file);
+    if license_file.components().any(|c| c == Component::ParentDir) {
+        license_file.file_name().map(PathBuf::from)
+    } else {
+        let components = license_file.components();
+        Some(components.filter(|c| *c != Component::CurDir).collect())
+    }
+}
+
+/// Reads a changelog or license file, unless it is too large or not valid UTF-8.
+fn read_document(
+    entry: &mut impl Read,
+    path_in_pkg: &path::Path,
+) -> io::Result<Option<PackageDocument>> {
+    let mut text = String::new();
+    match entry.take(MAX_DOCUMENT_SIZE + 1).read_to_string(&mut text) {
+        Ok(size) if size as u64 > MAX_DOCUMENT_SIZE => Ok(None),
+        Ok(_) => Ok(Some(PackageDocument {
+            path: path_in_pkg.to_string_lossy().into_owned(),
+            text,
+        })),
+        Err(error) if error.kind() == io::ErrorKind::InvalidData => Ok(None),
+        Err(error) => Err(error),
     }
-    Ok(vcs_info)
 }
 
 #[cfg(test)]
 mod tests {
     use super::{missing_metadata_error_message, verify_tarball};
-    use crate::admin::render_readmes::tests::add_file;
+    use crate::worker::readmes::tests::add_file;
     use flate2::read::GzEncoder;
     use std::io::Read;
 
@@ -502,11 +1243,15 @@ mod tests {
             .unwrap();
 
         let limit = 512 * 1024 * 1024;
-        assert_eq!(
-            verify_tarball("foo-0.0.1", &serialized_archive, limit).unwrap(),
+        let info = verify_tarball("foo-0.0.1", &serialized_archive, limit, None).unwrap();
+        assert_eq!(info.vcs_info, None);
+        assert!(info.documents.is_empty());
+        assert_err!(verify_tarball(
+            "bar-0.0.1",
+            &serialized_archive,
+            limit,
             None
-        );
-        assert_err!(verify_tarball("bar-0.0.1", &serialized_archive, limit));
+        ));
     }
 
     #[test]
@@ -523,8 +1268,9 @@ mod tests {
             .read_to_end(&mut serialized_archive)
             .unwrap();
         let limit = 512 * 1024 * 1024;
-        let vcs_info = verify_tarball("foo-0.0.1", &serialized_archive, limit)
+        let vcs_info = verify_tarball("foo-0.0.1", &serialized_archive, limit, None)
             .unwrap()
+            .vcs_info
             .unwrap();
         assert_eq!(vcs_info.path_in_vcs, "");
     }
@@ -543,9 +1289,60 @@ mod tests {
             .read_to_end(&mut serialized_archive)
             .unwrap();
         let limit = 512 * 1024 * 1024;
-        let vcs_info = verify_tarball("foo-0.0.1", &serialized_archive, limit)
+        let vcs_info = verify_tarball("foo-0.0.1", &serialized_archive, limit, None)
             .unwrap()
+            .vcs_info
             .unwrap();
         assert_eq!(vcs_info.path_in_vcs, "path/in/vcs");
     }
+
+    #[test]
+    fn verify_tarball_test_documents() {
+        let mut pkg = tar::Builder::new(vec![]);
+        add_file(&mut pkg, "foo-0.0.1/Cargo.toml", b"");
+        add_file(&mut pkg, "foo-0.0.1/Changes.md", b"# Changes");
+        add_file(&mut pkg, "foo-0.0.1/CHANGELOG.md", b"# Changelog");
+        add_file(&mut pkg, "foo-0.0.1/docs/CHANGELOG.md", b"# Nested");
+        add_file(&mut pkg, "foo-0.0.1/LICENSE-MIT", b"MIT License");
+        add_file(&mut pkg, "foo-0.0.1/legal/LICENSE", b"\xff\xfe");
+        let mut serialized_archive = vec![];
+        GzEncoder::new(pkg.into_inner().unwrap().as_slice(), Default::default())
+            .read_to_end(&mut serialized_archive)
+            .unwrap();
+        let limit = 512 * 1024 * 1024;
+
+        let info = verify_tarball(
+            "foo-0.0.1",
+            &serialized_archive,
+            limit,
+            Some("./LICENSE-MIT"),
+        )
+        .unwrap();
+        let changelog = info.documents.changelog.unwrap();
+        assert_eq!(changelog.path, "CHANGELOG.md");
+        assert_eq!(changelog.text, "# Changelog");
+        let license = info.documents.license.unwrap();
+        assert_eq!(license.path, "LICENSE-MIT");
+        assert_eq!(license.text, "MIT License");
+
+        // License files from outside of the package are packaged into its root.
+        let info = verify_tarball(
+            "foo-0.0.1",
+            &serialized_archive,
+            limit,
+            Some("../LICENSE-MIT"),
+        )
+        .unwrap();
+        assert_eq!(info.documents.license.unwrap().path, "LICENSE-MIT");
+
+        // Files that aren't valid UTF-8 are not rendered.
+        let info = verify_tarball(
+            "foo-0.0.1",
+            &serialized_archive,
+            limit,
+            Some("legal/LICENSE"),
+        )
+        .unwrap();
+        assert_eq!(info.documents.license, None);
+    }
 }
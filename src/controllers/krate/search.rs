@@ -8,7 +8,9 @@ use indexmap::IndexMap;
 
 use crate::controllers::cargo_prelude::*;
 use crate::controllers::helpers::Paginate;
-use crate::models::{Crate, CrateOwner, CrateVersions, OwnerKind, TopVersions, Version};
+use crate::models::{
+    Badge, Crate, CrateOwner, CrateVersions, LicenseExpression, OwnerKind, TopVersions, Version,
+};
 use crate::schema::*;
 use crate::util::errors::bad_request;
 use crate::views::EncodableCrate;
@@ -40,7 +42,7 @@ use crate::sql::{array_agg, canon_crate_name, lower};
 /// for them.
 pub async fn search(req: ConduitRequest) -> AppResult<Json<Value>> {
     conduit_compat(move || {
-        use diesel::sql_types::{Bool, Text};
+        use diesel::sql_types::{Bool, Jsonb, Text};
 
         let params = req.query();
         let sort = params.get("sort").map(|s| &**s);
@@ -48,6 +50,10 @@ pub async fn search(req: ConduitRequest) -> AppResult<Json<Value>> {
             .get("include_yanked")
             .map(|s| s == "yes")
             .unwrap_or(true);
+        let include_deprecated = params
+            .get("include_deprecated")
+            .map(|s| s == "yes")
+            .unwrap_or(true);
 
         // Remove 0x00 characters from the query string because Postgres can not
         // handle them and will return an error, which would cause us to throw
@@ -115,6 +121,35 @@ pub async fn search(req: ConduitRequest) -> AppResult<Json<Value>> {
             );
         }
 
+        if let Some(license) = params.get("license") {
+            // Calculating the total number of results with filters is not supported yet.
+            supports_seek = false;
+
+            // The filter lists the licenses that the user accepts, so a crate matches if
+            // the newest version can be used under a subset of them.
+            let license = LicenseExpression::parse(license)
+                .map_err(|_| bad_request("invalid license expression"))?;
+            if license.has_conjunctions() {
+                return Err(bad_request(
+                    "the license filter only supports licenses combined with `OR`",
+                ));
+            }
+            let accepted = license.alternatives()?.concat();
+
+            query = query.filter(
+                sql::<Bool>(
+                    "EXISTS (SELECT 1 FROM jsonb_array_elements((\
+                        SELECT versions.license_alternatives FROM versions \
+                        WHERE versions.crate_id = crates.id \
+                        AND NOT versions.yanked AND NOT versions.staged \
+                        ORDER BY versions.created_at DESC, versions.id DESC LIMIT 1\
+                    )) AS alternative WHERE alternative <@ ",
+                )
+                .bind::<Jsonb, _>(serde_json::to_value(accepted)?)
+                .sql(")"),
+            );
+        }
+
         if let Some(kws) = params.get("all_keywords") {
             // Calculating the total number of results with filters is not supported yet.
             supports_seek = false;
@@ -217,10 +252,24 @@ pub async fn search(req: ConduitRequest) -> AppResult<Json<Value>> {
             query = query.filter(exists(
                 versions::table
                     .filter(versions::crate_id.eq(crates::id))
-                    .filter(versions::yanked.eq(false)),
+                    .filter(versions::yanked.eq(false))
+                    .filter(versions::staged.eq(false)),
             ));
         }
 
+        if !include_deprecated {
+            // Calculating the total number of results with filters is not supported yet.
+            supports_seek = false;
+
+            // Crates are deprecated by their `maintenance` badge
+            query = query.filter(not(exists(
+                badges::table
+                    .filter(badges::crate_id.eq(crates::id))
+                    .filter(badges::badge_type.eq("maintenance"))
+                    .filter(sql::<Bool>("badges.attributes->>'status' = 'deprecated'")),
+            )));
+        }
+
         // Any sort other than 'relevance' (default) would ignore exact crate name matches
         if sort == Some("downloads") {
             // Custom sorting is not supported yet with seek.
@@ -314,6 +363,7 @@ pub async fn search(req: ConduitRequest) -> AppResult<Json<Value>> {
             .collect::<Vec<_>>();
         let crates = data.into_iter().map(|(c, _, _)| c).collect::<Vec<_>>();
 
+        let badges = Badge::for_crates(&conn, &crates)?;
         let versions: Vec<Version> = crates.versions().load(&*conn)?;
         let versions = versions
             .grouped_by(&crates)
@@ -322,14 +372,15 @@ pub async fn search(req: ConduitRequest) -> AppResult<Json<Value>> {
 
         let crates = versions
             .zip(crates)
+            .zip(badges)
             .zip(perfect_matches)
             .zip(recent_downloads)
             .map(
-                |(((max_version, krate), perfect_match), recent_downloads)| {
+                |((((max_version, krate), badges), perfect_match), recent_downloads)| {
                     EncodableCrate::from_minimal(
                         krate,
                         Some(&max_version),
-                        Some(vec![]),
+                        Some(badges),
                         perfect_match,
                         Some(recent_downloads),
                     )
@@ -67,6 +67,7 @@ pub async fn updates(req: ConduitRequest) -> AppResult<Json<Value>> {
             .inner_join(crates::table)
             .left_outer_join(users::table)
             .filter(crates::id.eq(any(followed_crates)))
+            .filter(versions::staged.eq(false))
             .order(versions::created_at.desc())
             .select((
                 versions::all_columns,
@@ -32,6 +32,7 @@ pub async fn index(req: ConduitRequest) -> AppResult<Json<Value>> {
                 users::all_columns.nullable(),
             ))
             .filter(versions::id.eq(any(ids)))
+            .filter(versions::staged.eq(false))
             .load(&*conn)?;
         let versions = versions_and_publishers
             .iter()
@@ -59,6 +60,7 @@ pub async fn show_by_id(Path(id): Path<i32>, req: ConduitRequest) -> AppResult<J
         let conn = req.app().db_read()?;
         let (version, krate, published_by): (Version, Crate, Option<User>) = versions::table
             .find(id)
+            .filter(versions::staged.eq(false))
             .inner_join(crates::table)
             .left_outer_join(users::table)
             .select((
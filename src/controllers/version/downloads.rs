@@ -58,6 +58,7 @@ pub async fn download(
                             .select((id, crates::name))
                             .filter(Crate::with_name(&crate_name))
                             .filter(num.eq(&version))
+                            .filter(staged.eq(false))
                             .first::<(i32, String)>(&**conn)
                     })?;
 
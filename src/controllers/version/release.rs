@@ -0,0 +1,172 @@
+//! Endpoints for releasing and abandoning staged versions of crates
+
+use crate::auth::AuthCheck;
+
+use crate::controllers::cargo_prelude::*;
+use crate::controllers::krate::delete::has_reverse_dependencies;
+use crate::models::token::EndpointScope;
+use crate::models::{Crate, CrateVersions, PublishState, PublishUpload, Rights, Version};
+use crate::schema::{crates, dependencies, versions};
+use crate::worker;
+
+/// Handles the `PUT /crates/:crate_id/:version/release` route.
+///
+/// Makes a version that was uploaded with `staged=1` visible: it is added to
+/// the index and listed by the API from now on.
+pub async fn release(
+    Path((crate_name, version)): Path<(String, String)>,
+    req: ConduitRequest,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let (krate, version) = staged_version(&crate_name, &version, &req)?;
+        let conn = req.app().db_write()?;
+
+        let unreleased = unreleased_dependencies(&conn, &version)?;
+        if !unreleased.is_empty() {
+            let names = unreleased
+                .iter()
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(cargo_err(&format_args!(
+                "this version depends on the crates {names}, which have no released versions yet"
+            )));
+        }
+
+        conn.transaction(|| -> AppResult<()> {
+            let version: Version = diesel::update(&version)
+                .set((
+                    versions::staged.eq(false),
+                    versions::updated_at.eq(diesel::dsl::now),
+                ))
+                .get_result(&*conn)?;
+
+            let git_crate = krate.index_entry(&conn, version)?;
+            worker::add_crate(git_crate).enqueue(&conn)?;
+            Ok(())
+        })?;
+
+        ok_true()
+    })
+    .await
+}
+
+/// Handles the `DELETE /crates/:crate_id/:version/release` route.
+///
+/// Abandons a staged version. The version and its crate file are deleted, and
+/// so is the crate if the abandoned version was its only version.
+pub async fn abandon(
+    Path((crate_name, version)): Path<(String, String)>,
+    req: ConduitRequest,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        use diesel::dsl::exists;
+
+        let (krate, version) = staged_version(&crate_name, &version, &req)?;
+        let app = req.app();
+        let conn = app.db_write()?;
+
+        let is_only_version: bool = !diesel::select(exists(
+            Version::belonging_to(&krate).filter(versions::id.ne(version.id)),
+        ))
+        .get_result(&*conn)?;
+
+        // The crate is deleted together with its only version, which would
+        // silently drop the dependencies of other crates on it.
+        if is_only_version && has_reverse_dependencies(&conn, &krate)? {
+            return Err(cargo_err(
+                "other crates depend on this crate, abandon their staged versions first",
+            ));
+        }
+
+        conn.transaction(|| -> AppResult<()> {
+            Version::remove(&conn, version.id)?;
+            worker::delete_crate_files(krate.name.clone(), vec![version.num.clone()])
+                .enqueue(&conn)?;
+            Ok(())
+        })?;
+
+        app.version_id_cacher.invalidate(&(krate.name, version.num));
+
+        ok_true()
+    })
+    .await
+}
+
+/// Returns the names of the crates that the version depends on, but that have
+/// no released versions, e.g. because their only versions are staged.
+fn unreleased_dependencies(conn: &PgConnection, version: &Version) -> QueryResult<Vec<String>> {
+    let dependencies: Vec<(i32, String)> = dependencies::table
+        .inner_join(crates::table)
+        .filter(dependencies::version_id.eq(version.id))
+        .select((crates::id, crates::name))
+        .distinct()
+        .load(conn)?;
+
+    let crate_ids = dependencies.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    let released: Vec<i32> = versions::table
+        .filter(versions::crate_id.eq_any(&crate_ids))
+        .filter(versions::staged.eq(false))
+        .select(versions::crate_id)
+        .distinct()
+        .load(conn)?;
+
+    Ok(dependencies
+        .into_iter()
+        .filter(|(id, _)| !released.contains(id))
+        .map(|(_, name)| name)
+        .collect())
+}
+
+/// Loads a staged version after checking that the user may publish the crate.
+fn staged_version<B>(
+    crate_name: &str,
+    version: &str,
+    req: &Request<B>,
+) -> AppResult<(Crate, Version)> {
+    use diesel::dsl::exists;
+
+    if semver::Version::parse(version).is_err() {
+        return Err(cargo_err(&format_args!("invalid semver: {version}")));
+    }
+
+    let state = req.app();
+    let conn = state.db_write()?;
+    let krate: Crate = Crate::by_name(crate_name).first(&*conn)?;
+
+    // Releasing the first version of a crate is the same as publishing a new crate
+    let has_released_versions: bool =
+        diesel::select(exists(krate.all_versions())).get_result(&*conn)?;
+    let endpoint_scope = if has_released_versions {
+        EndpointScope::PublishUpdate
+    } else {
+        EndpointScope::PublishNew
+    };
+
+    let auth = AuthCheck::default()
+        .with_endpoint_scope(endpoint_scope)
+        .for_crate(crate_name)
+        .check(req)?;
+
+    let user = auth.user();
+    let owners = krate.owners_with_roles(&conn)?;
+    if user.rights(state, &owners)? < Rights::Publish {
+        return Err(cargo_err(
+            "must be an owner with the publisher role to release or abandon staged versions",
+        ));
+    }
+
+    let version = krate.find_staged_version(&conn, version)?;
+
+    // Asynchronously published versions are staged until their crate file
+    // was verified, and are then released by the background job
+    let upload = PublishUpload::for_version(&conn, &krate.name, &version.num).optional()?;
+    if upload.map_or(false, |upload| upload.state == PublishState::Pending) {
+        return Err(cargo_err(&format_args!(
+            "version `{}` of crate `{}` is still being published",
+            version.num, krate.name
+        )));
+    }
+
+    Ok((krate, version))
+}
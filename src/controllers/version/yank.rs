@@ -58,9 +58,9 @@ fn modify_yank<B>(
     let (version, krate) = version_and_crate(&conn, crate_name, version)?;
     let api_token_id = auth.api_token_id();
     let user = auth.user();
-    let owners = krate.owners(&conn)?;
+    let owners = krate.owners_with_roles(&conn)?;
 
-    if user.rights(state, &owners)? < Rights::Publish {
+    if user.rights(state, &owners)? < Rights::Yank {
         return Err(cargo_err("must already be an owner to yank or unyank"));
     }
 
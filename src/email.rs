@@ -90,6 +90,62 @@ or go to https://{domain}/me/pending-invites to manage all of your crate ownersh
         self.send(email, subject, &body)
     }
 
+    /// Attempts to send a request to transfer a crate to the recipient.
+    pub fn send_crate_transfer_request(
+        &self,
+        email: &str,
+        user_name: &str,
+        crate_name: &str,
+    ) -> AppResult<()> {
+        let subject = "Crate transfer request";
+        let body = format!(
+            "{user_name} would like to transfer the crate {crate_name} to you!\n
+Once you accept the transfer you will become the only owner of the crate.
+Go to https://{domain}/me/pending-invites to accept or decline this request.",
+            domain = crate::config::domain_name()
+        );
+
+        self.send(email, subject, &body)
+    }
+
+    /// Attempts to notify the sender of a crate transfer request that the
+    /// recipient has accepted it.
+    pub fn send_crate_transfer_accepted(
+        &self,
+        email: &str,
+        user_name: &str,
+        crate_name: &str,
+    ) -> AppResult<()> {
+        let subject = "Crate transfer accepted";
+        let body = format!(
+            "{user_name} has accepted your request to transfer the crate {crate_name}.\n
+{user_name} is now the only owner of the crate."
+        );
+
+        self.send(email, subject, &body)
+    }
+
+    /// Attempts to notify a user of the decision on their request to adopt
+    /// an orphaned crate.
+    pub fn send_crate_adoption_decision(
+        &self,
+        email: &str,
+        crate_name: &str,
+        approved: bool,
+    ) -> AppResult<()> {
+        let subject = "Crate adoption request";
+        let body = if approved {
+            format!(
+                "Your request to adopt the crate {crate_name} has been approved.\n
+You are now an owner of the crate."
+            )
+        } else {
+            format!("Your request to adopt the crate {crate_name} has been rejected.")
+        };
+
+        self.send(email, subject, &body)
+    }
+
     /// Attempts to send an API token exposure notification email
     pub fn send_token_exposed_notification(
         &self,
@@ -196,6 +196,8 @@ pub struct GitHubTeam {
 #[derive(Debug, Deserialize)]
 pub struct GitHubTeamMembership {
     pub state: String,
+    /// Either `member` or `maintainer`
+    pub role: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,6 +49,7 @@ pub mod headers;
 pub mod metrics;
 pub mod middleware;
 mod publish_rate_limit;
+pub mod rate_limiter;
 pub mod schema;
 pub mod sql;
 pub mod swirl;
@@ -56,6 +56,9 @@ metrics! {
         pub version_id_cache_hits: IntCounter,
         /// Number of version ID cache misses on the download endpoint.
         pub version_id_cache_misses: IntCounter,
+
+        /// Number of requests blocked by each blocked traffic rule
+        pub blocked_traffic_rule_hits_total: IntCounterVec["rule"],
     }
 
     // All instance metrics will be prefixed with this namespace.
@@ -10,8 +10,11 @@
 //! As a rule of thumb, if the metric is not straight up fetched from the database it's probably an
 //! instance-level metric, and you should add it to `src/metrics/instance.rs`.
 
-use crate::schema::{background_jobs, crates, versions};
+use crate::background_jobs::Job;
+use crate::models::IndexConsistencyCheck;
+use crate::schema::{background_jobs, crates, readme_renderings, versions};
 use crate::util::errors::AppResult;
+use cargo_registry_markdown::RENDERER_VERSION;
 use diesel::{dsl::count_star, prelude::*, PgConnection};
 use prometheus::{proto::MetricFamily, IntGauge};
 
@@ -23,6 +26,14 @@ metrics! {
         versions_total: IntGauge,
         /// Number of queued up background jobs
         background_jobs: IntGauge,
+        /// Number of crates whose git index file did not match the database in the last index check
+        index_git_mismatches: IntGauge,
+        /// Number of crates whose HTTP index file did not match the git index in the last index check
+        index_http_mismatches: IntGauge,
+        /// Number of readmes that were rendered by an older version of the readme renderer
+        readmes_outdated: IntGauge,
+        /// Number of queued up batches of readmes to re-render
+        readme_rerender_batches: IntGauge,
     }
 
     // All service metrics will be prefixed with this namespace.
@@ -38,6 +49,26 @@ impl ServiceMetrics {
         self.background_jobs
             .set(background_jobs::table.select(count_star()).first(conn)?);
 
+        let index_check = IndexConsistencyCheck::latest(conn)?;
+        let (git_mismatches, http_mismatches) = index_check
+            .map(|check| (check.git_mismatches.len(), check.http_mismatches.len()))
+            .unwrap_or_default();
+        self.index_git_mismatches.set(git_mismatches as i64);
+        self.index_http_mismatches.set(http_mismatches as i64);
+
+        self.readmes_outdated.set(
+            readme_renderings::table
+                .filter(readme_renderings::renderer_version.lt(RENDERER_VERSION))
+                .select(count_star())
+                .first(conn)?,
+        );
+        self.readme_rerender_batches.set(
+            background_jobs::table
+                .filter(background_jobs::job_type.eq(Job::RERENDER_READMES_BATCH))
+                .select(count_star())
+                .first(conn)?,
+        );
+
         Ok(self.registry.gather())
     }
 }
@@ -6,6 +6,7 @@ mod ember_html;
 mod head;
 pub mod log_request;
 pub mod normalize_path;
+mod rate_limit;
 mod require_user_agent;
 mod sentry;
 pub mod session;
@@ -84,8 +85,14 @@ pub fn apply_axum_middleware(state: AppState, router: Router) -> Router {
         // download counts), we consider only the primary pool here.
         .layer(HandleErrorLayer::new(dummy_error_handler))
         .option_layer(
-            (capacity >= 10).then(|| from_fn_with_state(state, balance_capacity::balance_capacity)),
-        );
+            (capacity >= 10)
+                .then(|| from_fn_with_state(state.clone(), balance_capacity::balance_capacity)),
+        )
+        .layer(from_fn_with_state(
+            state.clone(),
+            block_traffic::block_by_rules,
+        ))
+        .layer(from_fn_with_state(state, rate_limit::rate_limit));
 
     router.layer(middleware)
 }
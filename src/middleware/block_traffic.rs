@@ -7,21 +7,30 @@
 //! (c4fcfb725 2019-05-15)`, and `BLOCKED_IPS` to `192.168.0.1,127.0.0.1` to block requests from
 //! the versions of curl or Cargo specified or from either of the IPs (values are nonsensical
 //! examples). Values of the headers must match exactly.
+//!
+//! Requests can also be blocked by the rules in the `blocked_traffic_rules` database table, which
+//! match on CIDR blocks of the `X-Real-Ip` header and regular expressions of the `User-Agent`
+//! header. These rules are managed with `crates-admin block` or the admin API and don't require a
+//! restart of the application.
 
 use crate::app::AppState;
 use crate::middleware::log_request::CustomMetadataRequestExt;
-use crate::util::errors::RouteBlocked;
+use crate::models::BlockedTrafficRuleSet;
+use crate::util::errors::{AppResult, MirrorReadOnly, RouteBlocked};
+use crate::util::HeaderMapExt;
 use axum::extract::{MatchedPath, State};
 use axum::middleware::Next;
 use axum::response::IntoResponse;
-use http::StatusCode;
+use conduit_axum::spawn_blocking;
+use http::{header, Method, StatusCode};
+use std::net::IpAddr;
+use std::sync::Arc;
 
 pub async fn block_traffic<B>(
     State(state): State<AppState>,
     req: http::Request<B>,
     next: Next<B>,
 ) -> axum::response::Response {
-    let domain_name = state.config.domain_name.clone();
     let blocked_traffic = &state.config.blocked_traffic;
 
     for (header_name, blocked_values) in blocked_traffic {
@@ -33,30 +42,120 @@ pub async fn block_traffic<B>(
         if has_blocked_value {
             let cause = format!("blocked due to contents of header {header_name}");
             req.add_custom_metadata("cause", cause);
-            let body = format!(
-                "We are unable to process your request at this time. \
-                 This usually means that you are in violation of our crawler \
-                 policy (https://{}/policies#crawlers). \
-                 Please open an issue at https://github.com/rust-lang/crates.io \
-                 or email help@crates.io \
-                 and provide the request id {}",
-                domain_name,
-                // Heroku should always set this header
-                req.headers()
-                    .get("x-request-id")
-                    .map(|val| val.to_str().unwrap_or_default())
-                    .unwrap_or_default()
-            );
-
-            return (StatusCode::FORBIDDEN, body).into_response();
+            return blocked_response(&state.config.domain_name, &req);
         }
     }
 
     next.run(req).await
 }
 
+/// Block requests that match one of the active blocked traffic rules from the database.
+///
+/// The rules are cached in the `App` and reloaded once the cache entry expires, by a single
+/// request while the others wait for it. If the rules can't be loaded, e.g. because the
+/// database is unavailable, an empty set of rules is cached instead, so that no requests are
+/// blocked and the database isn't queried by every request until the cache entry expires.
+pub async fn block_by_rules<B: Send>(
+    State(state): State<AppState>,
+    req: http::Request<B>,
+    next: Next<B>,
+) -> axum::response::Response {
+    let rules = match state.blocked_traffic_rules.get(&()) {
+        Some(rules) => rules,
+        None => {
+            let app = state.clone();
+            let result = spawn_blocking(move || {
+                app.blocked_traffic_rules.get_with((), || {
+                    let load = || -> AppResult<_> {
+                        let conn = app.db_read_prefer_primary()?;
+                        Ok(BlockedTrafficRuleSet::load(&conn)?)
+                    };
+                    match load() {
+                        Ok(rules) => Arc::new(rules),
+                        Err(error) => {
+                            warn!(%error, "Failed to load blocked traffic rules");
+                            Arc::new(BlockedTrafficRuleSet::default())
+                        }
+                    }
+                })
+            })
+            .await;
+
+            match result {
+                Ok(rules) => rules,
+                Err(error) => {
+                    warn!(%error, "Failed to load blocked traffic rules");
+                    return next.run(req).await;
+                }
+            }
+        }
+    };
+
+    if rules.is_empty() {
+        return next.run(req).await;
+    }
+
+    let headers = req.headers();
+    let ip = headers
+        .get_str_or_default("x-real-ip")
+        .parse::<IpAddr>()
+        .ok();
+    let user_agent = headers.get_str_or_default(header::USER_AGENT);
+    let now = chrono::Utc::now().naive_utc();
+
+    if let Some(rule_id) = rules.matching_rule(ip, user_agent, now) {
+        state
+            .instance_metrics
+            .blocked_traffic_rule_hits_total
+            .with_label_values(&[&rule_id.to_string()])
+            .inc();
+
+        req.add_custom_metadata("cause", format!("blocked by traffic rule {rule_id}"));
+        return blocked_response(&state.config.domain_name, &req);
+    }
+
+    next.run(req).await
+}
+
+fn blocked_response<B>(domain_name: &str, req: &http::Request<B>) -> axum::response::Response {
+    let body = format!(
+        "We are unable to process your request at this time. \
+         This usually means that you are in violation of our crawler \
+         policy (https://{}/policies#crawlers). \
+         Please open an issue at https://github.com/rust-lang/crates.io \
+         or email help@crates.io \
+         and provide the request id {}",
+        domain_name,
+        // Heroku should always set this header
+        req.headers()
+            .get("x-request-id")
+            .map(|val| val.to_str().unwrap_or_default())
+            .unwrap_or_default()
+    );
+
+    (StatusCode::FORBIDDEN, body).into_response()
+}
+
+/// Routes that may be used with methods other than `GET` when running as a
+/// mirror of an upstream registry. All other routes would modify the mirrored
+/// data, so they only allow `GET` requests on mirrors.
+const MIRROR_ALLOWED_ROUTES: &[&str] = &[
+    // Session management
+    "/api/private/session",
+    // API tokens, which are local to the mirror
+    "/api/v1/me/tokens",
+    "/api/v1/me/tokens/:id",
+    "/api/v1/tokens/current",
+    "/api/github/secret-scanning/verify",
+    // Fetches of the local git index in development mode
+    "/git/index/*path",
+];
+
 /// Allow blocking individual routes by their pattern through the `BLOCKED_ROUTES`
 /// environment variable.
+///
+/// In mirror mode (see `MIRROR_UPSTREAM_INDEX`), all routes except the ones in
+/// `MIRROR_ALLOWED_ROUTES` are blocked for methods other than `GET`.
 pub async fn block_routes<B>(
     matched_path: Option<MatchedPath>,
     State(state): State<AppState>,
@@ -67,6 +166,13 @@ pub async fn block_routes<B>(
         if state.config.blocked_routes.contains(matched_path.as_str()) {
             return RouteBlocked.into_response();
         }
+
+        if state.config.mirror.is_some()
+            && req.method() != Method::GET
+            && !MIRROR_ALLOWED_ROUTES.contains(&matched_path.as_str())
+        {
+            return MirrorReadOnly.into_response();
+        }
     }
 
     next.run(req).await
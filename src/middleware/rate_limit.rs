@@ -0,0 +1,96 @@
+//! Middleware that applies the API rate limits of the `rate_limiter` module
+//!
+//! The limits are only enforced for routes that belong to a configured class.
+//! Responses of limited routes carry the `RateLimit-Limit`, `RateLimit-Remaining`
+//! and `RateLimit-Reset` headers, and rejected requests carry `Retry-After`.
+
+use crate::app::AppState;
+use crate::middleware::log_request::CustomMetadataRequestExt;
+use crate::middleware::session::RequestSession;
+use crate::rate_limiter::{LimitedAction, RateLimitKey};
+use crate::util::errors::AppResult;
+use axum::extract::{MatchedPath, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use conduit_axum::spawn_blocking;
+use http::{header, StatusCode};
+
+pub async fn rate_limit<B: Send>(
+    matched_path: Option<MatchedPath>,
+    State(state): State<AppState>,
+    req: http::Request<B>,
+    next: Next<B>,
+) -> Response {
+    let action = matched_path
+        .and_then(|path| LimitedAction::for_route(req.method(), path.as_str()))
+        .filter(|action| state.config.rate_limiter.is_enabled(*action));
+
+    let action = match action {
+        Some(action) => action,
+        None => return next.run(req).await,
+    };
+
+    let session_user_id = req
+        .session_get("user_id")
+        .and_then(|s| s.parse::<i32>().ok());
+    let authorization = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .map(String::from);
+    let ip = req
+        .headers()
+        .get("x-real-ip")
+        .and_then(|h| h.to_str().ok())
+        .filter(|ip| !ip.is_empty())
+        .map(String::from);
+
+    // Anonymous requests can only be limited by their IP address
+    if session_user_id.is_none() && authorization.is_none() && ip.is_none() {
+        return next.run(req).await;
+    }
+
+    let app = state.clone();
+    let result = spawn_blocking(move || -> AppResult<_> {
+        // The buckets of read-only classes are kept in memory, so their
+        // requests don't need a connection to the primary database
+        let conn = if action.is_read_only() {
+            app.db_read()?
+        } else {
+            app.db_write()?
+        };
+        let (authorization, ip) = (authorization.as_deref(), ip.as_deref());
+        let key = match RateLimitKey::resolve(session_user_id, authorization, ip, &conn)? {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        app.config
+            .rate_limiter
+            .check_rate_limit(action, &key, &conn)
+    })
+    .await
+    .map_err(Into::into)
+    .and_then(std::convert::identity);
+
+    match result {
+        Ok(status) => {
+            let mut response = next.run(req).await;
+            if let Some(status) = status {
+                status.insert_headers(response.headers_mut());
+            }
+            response
+        }
+        Err(error) => {
+            let response = error.response();
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                req.add_custom_metadata("cause", "rate limited");
+                return response;
+            }
+
+            // Requests are not rejected if the rate limit can't be checked,
+            // e.g. while the database is in read-only mode.
+            warn!(?action, %error, "Failed to check rate limit");
+            next.run(req).await
+        }
+    }
+}
@@ -1,13 +1,28 @@
-pub use self::action::{insert_version_owner_action, VersionAction, VersionOwnerAction};
+pub use self::action::{
+    insert_crate_owner_action, insert_version_owner_action, CrateAction, CrateOwnerAction,
+    VersionAction, VersionOwnerAction,
+};
+pub use self::badge::{Badge, CrateBadge, MaintenanceStatus};
+pub use self::blocked_traffic_rule::{
+    BlockedTrafficRule, BlockedTrafficRuleSet, NewBlockedTrafficRule,
+};
 pub use self::category::{Category, CrateCategory, NewCategory};
-pub use self::crate_owner_invitation::{CrateOwnerInvitation, NewCrateOwnerInvitationOutcome};
+pub use self::crate_adoption_request::CrateAdoptionRequest;
+pub use self::crate_owner_invitation::{
+    CrateOwnerInvitation, CrateOwnerTeamInvitation, NewCrateOwnerInvitationOutcome,
+};
+pub use self::crate_transfer_request::CrateTransferRequest;
+pub use self::deleted_crate::DeletedCrate;
 pub use self::dependency::{Dependency, DependencyKind, ReverseDependency};
 pub use self::download::VersionDownload;
 pub use self::email::{Email, NewEmail};
 pub use self::follow::Follow;
+pub use self::index_consistency_check::{IndexConsistencyCheck, NewIndexConsistencyCheck};
 pub use self::keyword::{CrateKeyword, Keyword};
-pub use self::krate::{Crate, CrateVersions, NewCrate, RecentCrateDownloads};
-pub use self::owner::{CrateOwner, Owner, OwnerKind};
+pub use self::krate::{Crate, CrateMetadata, CrateVersions, NewCrate, RecentCrateDownloads};
+pub use self::license::LicenseExpression;
+pub use self::owner::{CrateOwner, Owner, OwnerKind, OwnerRole};
+pub use self::publish_upload::{PublishState, PublishUpload};
 pub use self::rights::Rights;
 pub use self::team::{NewTeam, Team};
 pub use self::token::{ApiToken, CreatedApiToken};
@@ -17,15 +32,23 @@ pub use self::version::{NewVersion, TopVersions, Version};
 pub mod helpers;
 
 mod action;
+mod badge;
+mod blocked_traffic_rule;
 pub mod category;
+mod crate_adoption_request;
 mod crate_owner_invitation;
+mod crate_transfer_request;
+mod deleted_crate;
 pub mod dependency;
 mod download;
 mod email;
 mod follow;
+mod index_consistency_check;
 mod keyword;
 pub mod krate;
+pub mod license;
 mod owner;
+mod publish_upload;
 mod rights;
 mod team;
 pub mod token;
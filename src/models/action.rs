@@ -8,7 +8,7 @@ use diesel::{
 };
 use std::io::Write;
 
-use crate::models::{ApiToken, User, Version};
+use crate::models::{ApiToken, Crate, User, Version};
 use crate::schema::*;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromSqlRow, AsExpression)]
@@ -114,3 +114,96 @@ pub fn insert_version_owner_action(
         ))
         .get_result(conn)
 }
+
+/// An action changing the ownership of a whole crate, as opposed to a
+/// `VersionAction` on a single version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromSqlRow, AsExpression)]
+#[repr(i32)]
+#[sql_type = "Integer"]
+pub enum CrateAction {
+    TransferRequest = 0,
+    TransferAccept = 1,
+    TransferDecline = 2,
+    TransferCancel = 3,
+    Abandon = 4,
+    AdoptionRequest = 5,
+    AdoptionApprove = 6,
+    AdoptionReject = 7,
+    InvitationRevoke = 8,
+}
+
+impl From<CrateAction> for &'static str {
+    fn from(action: CrateAction) -> Self {
+        match action {
+            CrateAction::TransferRequest => "transfer_request",
+            CrateAction::TransferAccept => "transfer_accept",
+            CrateAction::TransferDecline => "transfer_decline",
+            CrateAction::TransferCancel => "transfer_cancel",
+            CrateAction::Abandon => "abandon",
+            CrateAction::AdoptionRequest => "adoption_request",
+            CrateAction::AdoptionApprove => "adoption_approve",
+            CrateAction::AdoptionReject => "adoption_reject",
+            CrateAction::InvitationRevoke => "invitation_revoke",
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for CrateAction {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(CrateAction::TransferRequest),
+            1 => Ok(CrateAction::TransferAccept),
+            2 => Ok(CrateAction::TransferDecline),
+            3 => Ok(CrateAction::TransferCancel),
+            4 => Ok(CrateAction::Abandon),
+            5 => Ok(CrateAction::AdoptionRequest),
+            6 => Ok(CrateAction::AdoptionApprove),
+            7 => Ok(CrateAction::AdoptionReject),
+            8 => Ok(CrateAction::InvitationRevoke),
+            n => Err(format!("unknown crate action: {n}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for CrateAction {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable, Associations)]
+#[belongs_to(Crate)]
+#[table_name = "crate_owner_actions"]
+pub struct CrateOwnerAction {
+    pub id: i32,
+    pub crate_id: i32,
+    pub user_id: i32,
+    pub target_user_id: Option<i32>,
+    pub action: CrateAction,
+    pub time: NaiveDateTime,
+}
+
+impl CrateOwnerAction {
+    pub fn by_crate(conn: &PgConnection, krate: &Crate) -> QueryResult<Vec<Self>> {
+        CrateOwnerAction::belonging_to(krate)
+            .order(crate_owner_actions::id)
+            .load(conn)
+    }
+}
+
+pub fn insert_crate_owner_action(
+    conn: &PgConnection,
+    krate: &Crate,
+    user_id: i32,
+    target_user_id: Option<i32>,
+    action: CrateAction,
+) -> QueryResult<CrateOwnerAction> {
+    diesel::insert_into(crate_owner_actions::table)
+        .values((
+            crate_owner_actions::crate_id.eq(krate.id),
+            crate_owner_actions::user_id.eq(user_id),
+            crate_owner_actions::target_user_id.eq(target_user_id),
+            crate_owner_actions::action.eq(action),
+        ))
+        .get_result(conn)
+}
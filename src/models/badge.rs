@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+
+use diesel::prelude::*;
+use diesel::{delete, insert_into};
+use serde_json::Value;
+
+use crate::models::Crate;
+use crate::schema::badges;
+
+/// A badge from the `[badges]` section of a crate manifest, see
+/// <https://doc.rust-lang.org/cargo/reference/manifest.html#the-badges-section>.
+///
+/// The badges are stored in the `badges` table, with the variant name as the
+/// `badge_type` and the fields as the `attributes`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "badge_type", content = "attributes")]
+pub enum Badge {
+    Maintenance {
+        status: MaintenanceStatus,
+    },
+    Appveyor {
+        repository: String,
+        branch: Option<String>,
+        service: Option<String>,
+        id: Option<String>,
+        project_name: Option<String>,
+    },
+    AzureDevops {
+        project: String,
+        pipeline: String,
+        build: Option<String>,
+    },
+    BitbucketPipelines {
+        repository: String,
+        branch: String,
+    },
+    CircleCi {
+        repository: String,
+        branch: Option<String>,
+    },
+    CirrusCi {
+        repository: String,
+        branch: Option<String>,
+    },
+    #[serde(rename = "gitlab")]
+    GitLab {
+        repository: String,
+        branch: Option<String>,
+    },
+    TravisCi {
+        repository: String,
+        branch: Option<String>,
+    },
+    Codecov {
+        repository: String,
+        branch: Option<String>,
+        service: Option<String>,
+    },
+    Coveralls {
+        repository: String,
+        branch: Option<String>,
+        service: Option<String>,
+    },
+    IsItMaintainedIssueResolution {
+        repository: String,
+    },
+    IsItMaintainedOpenIssues {
+        repository: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MaintenanceStatus {
+    ActivelyDeveloped,
+    PassivelyMaintained,
+    AsIs,
+    None,
+    Experimental,
+    LookingForMaintainer,
+    Deprecated,
+}
+
+#[derive(Queryable, Identifiable, Associations, Debug, Clone)]
+#[belongs_to(Crate)]
+#[primary_key(crate_id, badge_type)]
+#[table_name = "badges"]
+pub struct CrateBadge {
+    pub crate_id: i32,
+    pub badge_type: String,
+    pub attributes: Value,
+}
+
+impl CrateBadge {
+    /// Returns the typed badge, or `None` if the row doesn't match any of the
+    /// supported badges, e.g. because it was stored before they were validated.
+    pub fn badge(&self) -> Option<Badge> {
+        let json = json!({ "badge_type": self.badge_type, "attributes": self.attributes });
+        serde_json::from_value(json).ok()
+    }
+}
+
+impl Badge {
+    /// Parses a badge from the `[badges]` section of an uploaded manifest.
+    ///
+    /// Returns `None` if the badge type is unknown, a required attribute is
+    /// missing, or an attribute is unknown or has an invalid value.
+    pub fn from_attributes(
+        badge_type: &str,
+        attributes: &BTreeMap<String, String>,
+    ) -> Option<Badge> {
+        let json = json!({ "badge_type": badge_type, "attributes": attributes });
+        let badge: Badge = serde_json::from_value(json).ok()?;
+
+        // Serde ignores unknown attributes, which are most likely typos
+        let (_, known) = badge.to_parts();
+        let known = known.as_object()?;
+        if attributes.keys().all(|name| known.contains_key(name)) {
+            Some(badge)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the `badge_type` and the `attributes` of the badge, without the
+    /// optional attributes that are not set.
+    fn to_parts(&self) -> (String, Value) {
+        let json = serde_json::to_value(self).expect("badges always serialize");
+        let badge_type = json["badge_type"].as_str().unwrap_or_default().to_string();
+        let mut attributes = json["attributes"].clone();
+        if let Some(attributes) = attributes.as_object_mut() {
+            attributes.retain(|_, value| !value.is_null());
+        }
+        (badge_type, attributes)
+    }
+
+    /// Replaces the badges of a crate with the valid badges of an uploaded
+    /// manifest, and returns the badge types of the invalid ones.
+    pub fn update_crate(
+        conn: &PgConnection,
+        krate: &Crate,
+        badges: &BTreeMap<String, BTreeMap<String, String>>,
+    ) -> QueryResult<Vec<String>> {
+        let mut invalid_badges = Vec::new();
+        let mut new_badges = Vec::new();
+        for (badge_type, attributes) in badges {
+            match Badge::from_attributes(badge_type, attributes) {
+                Some(badge) => {
+                    let (badge_type, attributes) = badge.to_parts();
+                    new_badges.push((
+                        badges::crate_id.eq(krate.id),
+                        badges::badge_type.eq(badge_type),
+                        badges::attributes.eq(attributes),
+                    ));
+                }
+                None => invalid_badges.push(badge_type.clone()),
+            }
+        }
+
+        conn.transaction(|| {
+            delete(CrateBadge::belonging_to(krate)).execute(conn)?;
+            insert_into(badges::table)
+                .values(&new_badges)
+                .execute(conn)?;
+            Ok(invalid_badges)
+        })
+    }
+
+    /// Loads the badges of each of the crates, in the same order.
+    pub fn for_crates(conn: &PgConnection, crates: &[Crate]) -> QueryResult<Vec<Vec<Badge>>> {
+        let badges: Vec<CrateBadge> = CrateBadge::belonging_to(crates)
+            .order(badges::badge_type)
+            .load(conn)?;
+
+        Ok(badges
+            .grouped_by(crates)
+            .into_iter()
+            .map(|badges| badges.iter().filter_map(CrateBadge::badge).collect())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Badge, MaintenanceStatus};
+    use std::collections::BTreeMap;
+
+    fn parse(badge_type: &str, attributes: &[(&str, &str)]) -> Option<Badge> {
+        let attributes: BTreeMap<_, _> = attributes
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Badge::from_attributes(badge_type, &attributes)
+    }
+
+    #[test]
+    fn valid_badges() {
+        assert_eq!(
+            parse("maintenance", &[("status", "deprecated")]),
+            Some(Badge::Maintenance {
+                status: MaintenanceStatus::Deprecated
+            })
+        );
+        assert_eq!(
+            parse("gitlab", &[("repository", "foo/bar")]),
+            Some(Badge::GitLab {
+                repository: "foo/bar".into(),
+                branch: None
+            })
+        );
+        assert_eq!(
+            parse(
+                "codecov",
+                &[("repository", "foo/bar"), ("service", "github")]
+            ),
+            Some(Badge::Codecov {
+                repository: "foo/bar".into(),
+                branch: None,
+                service: Some("github".into())
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_badges() {
+        // Unknown badge type
+        assert_none!(parse("not-a-badge", &[("repository", "foo/bar")]));
+        // Missing required attribute
+        assert_none!(parse("azure-devops", &[("project", "foo")]));
+        // Unknown attribute
+        assert_none!(parse(
+            "travis-ci",
+            &[("repository", "foo/bar"), ("brnach", "main")]
+        ));
+        // Invalid attribute value
+        assert_none!(parse("maintenance", &[("status", "abandoned")]));
+    }
+
+    #[test]
+    fn optional_attributes_are_not_stored() {
+        let badge = parse("travis-ci", &[("repository", "foo/bar")]).unwrap();
+        let (badge_type, attributes) = badge.to_parts();
+        assert_eq!(badge_type, "travis-ci");
+        assert_eq!(attributes, json!({ "repository": "foo/bar" }));
+    }
+}
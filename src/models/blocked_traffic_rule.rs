@@ -0,0 +1,254 @@
+use anyhow::{anyhow, Context};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use ipnetwork::IpNetwork;
+use regex::Regex;
+use std::net::IpAddr;
+
+use crate::config::parse_cidr_block;
+use crate::schema::blocked_traffic_rules;
+
+/// A rule for blocking requests, managed by the `crates-admin block` command and the
+/// admin API.
+///
+/// A request is blocked if it matches all of the matchers that are set on a rule.
+#[derive(Queryable, Identifiable, Serialize, Debug, Clone)]
+pub struct BlockedTrafficRule {
+    pub id: i32,
+    /// CIDR block of the client IP addresses that are blocked
+    pub ip_range: Option<String>,
+    /// Regular expression matching the user agents that are blocked
+    pub user_agent_pattern: Option<String>,
+    pub reason: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "blocked_traffic_rules"]
+pub struct NewBlockedTrafficRule<'a> {
+    pub ip_range: Option<&'a str>,
+    pub user_agent_pattern: Option<&'a str>,
+    pub reason: &'a str,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+impl BlockedTrafficRule {
+    /// Returns all rules, including the expired ones.
+    pub fn all(conn: &PgConnection) -> QueryResult<Vec<Self>> {
+        blocked_traffic_rules::table
+            .order(blocked_traffic_rules::id)
+            .load(conn)
+    }
+
+    /// Returns the rules that have not expired yet.
+    pub fn active(conn: &PgConnection) -> QueryResult<Vec<Self>> {
+        blocked_traffic_rules::table
+            .filter(
+                blocked_traffic_rules::expires_at
+                    .is_null()
+                    .or(blocked_traffic_rules::expires_at.gt(diesel::dsl::now)),
+            )
+            .order(blocked_traffic_rules::id)
+            .load(conn)
+    }
+
+    /// Deletes the rule with the given id and returns whether it existed.
+    pub fn delete(conn: &PgConnection, id: i32) -> QueryResult<bool> {
+        let deleted = diesel::delete(blocked_traffic_rules::table.find(id)).execute(conn)?;
+        Ok(deleted > 0)
+    }
+
+    pub fn is_expired(&self, now: NaiveDateTime) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= now)
+    }
+}
+
+impl NewBlockedTrafficRule<'_> {
+    /// Checks that the rule has at least one valid matcher.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.ip_range.is_none() && self.user_agent_pattern.is_none() {
+            return Err(anyhow!(
+                "A rule needs an IP range or a user agent pattern to match on."
+            ));
+        }
+
+        if self.reason.trim().is_empty() {
+            return Err(anyhow!("A rule needs a reason."));
+        }
+
+        if let Some(ip_range) = self.ip_range {
+            parse_cidr_block(ip_range)?;
+        }
+
+        if let Some(pattern) = self.user_agent_pattern {
+            Regex::new(pattern)
+                .with_context(|| format!("`{pattern}` is not a valid regular expression."))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self, conn: &PgConnection) -> QueryResult<BlockedTrafficRule> {
+        diesel::insert_into(blocked_traffic_rules::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+/// The active blocked traffic rules, compiled for matching against requests.
+///
+/// The `block_by_rules` middleware keeps a cached copy of this set in the
+/// `App`, which is reloaded from the database when the cache expires or the
+/// rules are changed through the admin API.
+#[derive(Debug, Default)]
+pub struct BlockedTrafficRuleSet {
+    rules: Vec<CompiledRule>,
+}
+
+#[derive(Debug)]
+struct CompiledRule {
+    id: i32,
+    ip_range: Option<IpNetwork>,
+    user_agent_pattern: Option<Regex>,
+    expires_at: Option<NaiveDateTime>,
+}
+
+impl BlockedTrafficRuleSet {
+    pub fn load(conn: &PgConnection) -> QueryResult<Self> {
+        let rules = BlockedTrafficRule::active(conn)?
+            .into_iter()
+            .filter_map(|rule| match CompiledRule::new(&rule) {
+                Ok(compiled) => Some(compiled),
+                Err(error) => {
+                    warn!(rule.id, %error, "Skipping invalid blocked traffic rule");
+                    None
+                }
+            })
+            .collect();
+
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns the id of the first unexpired rule matching the client IP
+    /// address and user agent of a request.
+    pub fn matching_rule(
+        &self,
+        ip: Option<IpAddr>,
+        user_agent: &str,
+        now: NaiveDateTime,
+    ) -> Option<i32> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(ip, user_agent, now))
+            .map(|rule| rule.id)
+    }
+}
+
+impl CompiledRule {
+    fn new(rule: &BlockedTrafficRule) -> anyhow::Result<Self> {
+        let ip_range = rule.ip_range.as_deref().map(parse_cidr_block).transpose()?;
+        let user_agent_pattern = rule.user_agent_pattern.as_deref().map(Regex::new);
+        let user_agent_pattern = user_agent_pattern.transpose()?;
+
+        Ok(Self {
+            id: rule.id,
+            ip_range,
+            user_agent_pattern,
+            expires_at: rule.expires_at,
+        })
+    }
+
+    fn matches(&self, ip: Option<IpAddr>, user_agent: &str, now: NaiveDateTime) -> bool {
+        if matches!(self.expires_at, Some(expires_at) if expires_at <= now) {
+            return false;
+        }
+
+        let ip_matches = match (&self.ip_range, ip) {
+            (None, _) => true,
+            (Some(range), Some(ip)) => range.contains(ip),
+            (Some(_), None) => false,
+        };
+
+        let user_agent_matches = match &self.user_agent_pattern {
+            Some(pattern) => pattern.is_match(user_agent),
+            None => true,
+        };
+
+        ip_matches && user_agent_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: i32, ip_range: Option<&str>, user_agent_pattern: Option<&str>) -> CompiledRule {
+        CompiledRule::new(&BlockedTrafficRule {
+            id,
+            ip_range: ip_range.map(String::from),
+            user_agent_pattern: user_agent_pattern.map(String::from),
+            reason: "test".into(),
+            created_at: chrono::Utc::now().naive_utc(),
+            expires_at: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn rules_match_on_all_matchers() {
+        let now = chrono::Utc::now().naive_utc();
+        let set = BlockedTrafficRuleSet {
+            rules: vec![
+                rule(1, Some("10.0.0.0/16"), None),
+                rule(2, None, Some("^bad-bot/")),
+                rule(3, Some("192.168.0.0/24"), Some("curl")),
+            ],
+        };
+
+        let ip = |s: &str| Some(s.parse().unwrap());
+        assert_eq!(set.matching_rule(ip("10.0.1.2"), "cargo", now), Some(1));
+        assert_eq!(set.matching_rule(None, "bad-bot/1.0", now), Some(2));
+        assert_eq!(
+            set.matching_rule(ip("192.168.0.7"), "curl/7.1", now),
+            Some(3)
+        );
+        assert_eq!(set.matching_rule(ip("192.168.0.7"), "cargo", now), None);
+        assert_eq!(set.matching_rule(None, "curl/7.1", now), None);
+        assert_eq!(set.matching_rule(ip("10.1.0.1"), "a bad-bot/", now), None);
+    }
+
+    #[test]
+    fn expired_rules_do_not_match() {
+        let now = chrono::Utc::now().naive_utc();
+        let mut expired = rule(1, None, Some("curl"));
+        expired.expires_at = Some(now - chrono::Duration::seconds(1));
+        let set = BlockedTrafficRuleSet {
+            rules: vec![expired],
+        };
+
+        assert_eq!(set.matching_rule(None, "curl/7.1", now), None);
+    }
+
+    #[test]
+    fn validation() {
+        let new_rule = |ip_range, user_agent_pattern| NewBlockedTrafficRule {
+            ip_range,
+            user_agent_pattern,
+            reason: "abuse",
+            expires_at: None,
+        };
+
+        assert!(new_rule(Some("10.0.0.0/16"), Some("^curl"))
+            .validate()
+            .is_ok());
+        assert!(new_rule(None, None).validate().is_err());
+        assert!(new_rule(Some("10.0.0.0/8"), None).validate().is_err());
+        assert!(new_rule(Some("not an ip"), None).validate().is_err());
+        assert!(new_rule(None, Some("(unclosed")).validate().is_err());
+    }
+}
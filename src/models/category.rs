@@ -3,6 +3,7 @@ use diesel::{self, *};
 
 use crate::models::Crate;
 use crate::schema::*;
+use crate::util::errors::{cargo_err, AppResult};
 
 #[derive(Clone, Identifiable, Queryable, QueryableByName, Debug)]
 #[table_name = "categories"]
@@ -62,20 +63,42 @@ impl Category {
         slugs: &[&str],
     ) -> QueryResult<Vec<String>> {
         conn.transaction(|| {
-            let categories: Vec<Category> = Category::by_slugs_case_sensitive(slugs).load(conn)?;
+            let mut categories: Vec<(String, Category)> = Category::by_slugs_case_sensitive(slugs)
+                .load::<Category>(conn)?
+                .into_iter()
+                .map(|c| (c.slug.clone(), c))
+                .collect();
+
+            // Crates keep the former slugs of renamed and merged categories in their manifests
+            let unknown_slugs = slugs
+                .iter()
+                .filter(|s| !categories.iter().any(|(slug, _)| slug == *s))
+                .collect::<Vec<_>>();
+            if !unknown_slugs.is_empty() {
+                categories.extend(
+                    category_redirects::table
+                        .inner_join(categories::table)
+                        .filter(category_redirects::slug.eq_any(unknown_slugs))
+                        .select((category_redirects::slug, categories::all_columns))
+                        .load::<(String, Category)>(conn)?,
+                );
+            }
+
             let invalid_categories = slugs
                 .iter()
                 .cloned()
-                .filter(|s| !categories.iter().any(|c| c.slug == *s))
+                .filter(|s| !categories.iter().any(|(slug, _)| slug == *s))
                 .map(ToString::to_string)
                 .collect();
-            let crate_categories = categories
+            let mut crate_categories = categories
                 .iter()
-                .map(|c| CrateCategory {
+                .map(|(_, c)| CrateCategory {
                     category_id: c.id,
                     crate_id: krate.id,
                 })
                 .collect::<Vec<_>>();
+            crate_categories.sort_by_key(|c| c.category_id);
+            crate_categories.dedup_by_key(|c| c.category_id);
 
             delete(CrateCategory::belonging_to(krate)).execute(conn)?;
             insert_into(crates_categories::table)
@@ -136,8 +159,285 @@ impl Category {
     }
 }
 
-/// Struct for inserting categories; only used in tests. Actual categories are inserted
-/// in src/boot/categories.rs.
+/// Category management, used by the admin endpoints and `crates-admin categories`.
+impl Category {
+    /// Returns the current slug of a category that was renamed or merged into another one.
+    pub fn redirected_slug(conn: &PgConnection, slug: &str) -> QueryResult<Option<String>> {
+        category_redirects::table
+            .inner_join(categories::table)
+            .filter(category_redirects::slug.eq(crate::sql::lower(slug)))
+            .select(categories::slug)
+            .first(conn)
+            .optional()
+    }
+
+    /// Creates a new category. The slug of a subcategory starts with the slug of its
+    /// parent, e.g. `development-tools::testing`, and the name is only the name of the
+    /// subcategory itself.
+    pub fn create(
+        conn: &PgConnection,
+        slug: &str,
+        name: &str,
+        description: &str,
+    ) -> AppResult<Category> {
+        conn.transaction(|| {
+            let (parent, own_slug) = match slug.rsplit_once("::") {
+                Some((parent, own_slug)) => (Some(Self::find_by_slug(conn, parent)?), own_slug),
+                None => (None, slug),
+            };
+            validate_slug(own_slug)?;
+            validate_name(name)?;
+
+            let slug = full_slug(parent.as_ref(), own_slug);
+            ensure_slug_is_available(conn, &slug, None)?;
+
+            // A new category takes over the former slug of another category
+            delete(category_redirects::table.filter(category_redirects::slug.eq(&slug)))
+                .execute(conn)?;
+
+            let new_category = NewCategory {
+                category: &full_name(parent.as_ref(), name),
+                slug: &slug,
+                description,
+            };
+            Ok(insert_into(categories::table)
+                .values(&new_category)
+                .get_result(conn)?)
+        })
+    }
+
+    /// Changes the slug and the name of this category and its subcategories. The
+    /// new slug and name don't include the slug and name of the parent category.
+    pub fn rename(&self, conn: &PgConnection, slug: &str, name: &str) -> AppResult<Category> {
+        validate_slug(slug)?;
+        validate_name(name)?;
+
+        conn.transaction(|| {
+            let parent = self.parent(conn)?;
+            self.relocate(
+                conn,
+                &full_slug(parent.as_ref(), slug),
+                &full_name(parent.as_ref(), name),
+            )
+        })
+    }
+
+    /// Moves this category and its subcategories below another category, or to the top
+    /// level if no parent is given.
+    pub fn reparent(&self, conn: &PgConnection, parent: Option<&Category>) -> AppResult<Category> {
+        if let Some(parent) = parent {
+            if parent.id == self.id || parent.slug.starts_with(&format!("{}::", self.slug)) {
+                return Err(cargo_err(&format_args!(
+                    "category `{}` cannot be moved below itself",
+                    self.slug
+                )));
+            }
+        }
+
+        conn.transaction(|| {
+            self.relocate(
+                conn,
+                &full_slug(parent, self.own_slug()),
+                &full_name(parent, self.own_name()),
+            )
+        })
+    }
+
+    /// Moves the crates of this category to another category and deletes this one. The
+    /// slug of this category redirects to the other category afterwards.
+    pub fn merge_into(&self, conn: &PgConnection, target: &Category) -> AppResult<()> {
+        if target.id == self.id {
+            return Err(cargo_err("a category cannot be merged into itself"));
+        }
+
+        conn.transaction(|| {
+            if self.descendants(conn)?.len() > 1 {
+                return Err(cargo_err(&format_args!(
+                    "category `{}` has subcategories, which need to be merged or moved first",
+                    self.slug
+                )));
+            }
+
+            // The `crates_cnt` of both categories is kept up to date by database triggers,
+            // which also count conflicting inserts, so crates that are already in the
+            // target category are skipped explicitly.
+            let target_crates: Vec<i32> = crates_categories::table
+                .filter(crates_categories::category_id.eq(target.id))
+                .select(crates_categories::crate_id)
+                .load(conn)?;
+            insert_into(crates_categories::table)
+                .values(
+                    crates_categories::table
+                        .filter(crates_categories::category_id.eq(self.id))
+                        .filter(dsl::not(crates_categories::crate_id.eq_any(target_crates)))
+                        .select((
+                            crates_categories::crate_id,
+                            target.id.into_sql::<sql_types::Integer>(),
+                        )),
+                )
+                .into_columns((crates_categories::crate_id, crates_categories::category_id))
+                .execute(conn)?;
+            delete(crates_categories::table.filter(crates_categories::category_id.eq(self.id)))
+                .execute(conn)?;
+
+            update(category_redirects::table.filter(category_redirects::category_id.eq(self.id)))
+                .set(category_redirects::category_id.eq(target.id))
+                .execute(conn)?;
+            delete(categories::table.find(self.id)).execute(conn)?;
+            insert_redirect(conn, &self.slug, target.id)?;
+
+            Ok(())
+        })
+    }
+
+    fn find_by_slug(conn: &PgConnection, slug: &str) -> AppResult<Category> {
+        Self::by_slug(slug)
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| cargo_err(&format_args!("category `{slug}` does not exist")))
+    }
+
+    fn parent(&self, conn: &PgConnection) -> AppResult<Option<Category>> {
+        match self.slug.rsplit_once("::") {
+            Some((parent, _)) => Ok(Some(Self::find_by_slug(conn, parent)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The slug of this category without the slug of its parent category.
+    fn own_slug(&self) -> &str {
+        self.slug.rsplit("::").next().unwrap_or_default()
+    }
+
+    /// The name of this category without the name of its parent category.
+    fn own_name(&self) -> &str {
+        self.category.rsplit("::").next().unwrap_or_default()
+    }
+
+    /// Loads this category and all of its subcategories.
+    fn descendants(&self, conn: &PgConnection) -> QueryResult<Vec<Category>> {
+        categories::table
+            .filter(
+                categories::slug
+                    .eq(&self.slug)
+                    .or(categories::slug.like(format!("{}::%", escape_like(&self.slug)))),
+            )
+            .order(categories::slug)
+            .load(conn)
+    }
+
+    /// Replaces the slug and name prefix of this category and its subcategories, and
+    /// keeps redirects from their old slugs.
+    fn relocate(&self, conn: &PgConnection, slug: &str, name: &str) -> AppResult<Category> {
+        let moved_slug = |old: &str| format!("{slug}{}", &old[self.slug.len()..]);
+        let moved_name = |old: &str| match old.strip_prefix(&self.category) {
+            Some(rest) => format!("{name}{rest}"),
+            None => old.to_string(),
+        };
+
+        let categories = self.descendants(conn)?;
+        for category in &categories {
+            ensure_slug_is_available(conn, &moved_slug(&category.slug), Some(category.id))?;
+        }
+
+        let mut relocated = None;
+        for category in categories {
+            let new_slug = moved_slug(&category.slug);
+            let updated: Category = update(categories::table.find(category.id))
+                .set((
+                    categories::slug.eq(&new_slug),
+                    categories::category.eq(moved_name(&category.category)),
+                ))
+                .get_result(conn)?;
+
+            if new_slug != category.slug {
+                delete(category_redirects::table.filter(category_redirects::slug.eq(&new_slug)))
+                    .execute(conn)?;
+                insert_redirect(conn, &category.slug, category.id)?;
+            }
+            if category.id == self.id {
+                relocated = Some(updated);
+            }
+        }
+
+        relocated.ok_or_else(|| cargo_err(&format_args!("category `{}` does not exist", self.slug)))
+    }
+}
+
+/// Escapes the wildcard characters of `LIKE` patterns, since slugs may contain `_`.
+fn escape_like(pattern: &str) -> String {
+    pattern
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn full_slug(parent: Option<&Category>, slug: &str) -> String {
+    match parent {
+        Some(parent) => format!("{}::{slug}", parent.slug),
+        None => slug.to_string(),
+    }
+}
+
+fn full_name(parent: Option<&Category>, name: &str) -> String {
+    match parent {
+        Some(parent) => format!("{}::{name}", parent.category),
+        None => name.to_string(),
+    }
+}
+
+/// Slugs are used in the path of URLs, see `src/boot/categories.toml`.
+fn validate_slug(slug: &str) -> AppResult<()> {
+    let valid_char = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_';
+    if slug.is_empty() || !slug.chars().all(valid_char) {
+        return Err(cargo_err(&format_args!(
+            "invalid category slug `{slug}`; slugs may only contain lowercase letters, digits, `-` and `_`"
+        )));
+    }
+    Ok(())
+}
+
+fn validate_name(name: &str) -> AppResult<()> {
+    if name.trim().is_empty() || name.contains("::") {
+        return Err(cargo_err(&format_args!("invalid category name `{name}`")));
+    }
+    Ok(())
+}
+
+fn ensure_slug_is_available(
+    conn: &PgConnection,
+    slug: &str,
+    except_id: Option<i32>,
+) -> AppResult<()> {
+    let existing: Option<i32> = categories::table
+        .filter(categories::slug.eq(slug))
+        .select(categories::id)
+        .first(conn)
+        .optional()?;
+
+    match existing {
+        Some(id) if Some(id) != except_id => {
+            Err(cargo_err(&format_args!("category `{slug}` already exists")))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn insert_redirect(conn: &PgConnection, slug: &str, category_id: i32) -> QueryResult<()> {
+    insert_into(category_redirects::table)
+        .values((
+            category_redirects::slug.eq(slug),
+            category_redirects::category_id.eq(category_id),
+        ))
+        .on_conflict(category_redirects::slug)
+        .do_update()
+        .set(category_redirects::category_id.eq(category_id))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Struct for inserting categories. The initial categories are seeded from
+/// src/boot/categories.toml, later ones are created with `Category::create`.
 #[derive(Insertable, AsChangeset, Default, Debug)]
 #[table_name = "categories"]
 pub struct NewCategory<'a> {
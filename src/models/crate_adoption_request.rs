@@ -0,0 +1,85 @@
+use chrono::NaiveDateTime;
+use diesel::pg::upsert::excluded;
+use diesel::prelude::*;
+
+use crate::models::{Crate, CrateOwner, OwnerKind, OwnerRole};
+use crate::schema::{crate_adoption_requests, crate_owners};
+
+/// A request by a user to adopt an orphaned crate, which has to be approved
+/// by an admin.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable)]
+pub struct CrateAdoptionRequest {
+    pub id: i32,
+    pub crate_id: i32,
+    pub user_id: i32,
+    pub reason: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl CrateAdoptionRequest {
+    /// Creates an adoption request, returning `None` if the user has already
+    /// requested to adopt the crate.
+    pub fn create(
+        conn: &PgConnection,
+        crate_id: i32,
+        user_id: i32,
+        reason: &str,
+    ) -> QueryResult<Option<Self>> {
+        diesel::insert_into(crate_adoption_requests::table)
+            .values((
+                crate_adoption_requests::crate_id.eq(crate_id),
+                crate_adoption_requests::user_id.eq(user_id),
+                crate_adoption_requests::reason.eq(reason),
+            ))
+            .on_conflict_do_nothing()
+            .get_result(conn)
+            .optional()
+    }
+
+    pub fn find(conn: &PgConnection, id: i32) -> QueryResult<Self> {
+        crate_adoption_requests::table.find(id).first(conn)
+    }
+
+    /// Returns all pending adoption requests, oldest first.
+    pub fn all(conn: &PgConnection) -> QueryResult<Vec<Self>> {
+        crate_adoption_requests::table
+            .order(crate_adoption_requests::id)
+            .load(conn)
+    }
+
+    /// Makes the requester the owner of the crate, with the admin role, and
+    /// discards all other adoption requests for the crate.
+    pub fn approve(self, conn: &PgConnection, krate: &Crate, admin_id: i32) -> QueryResult<()> {
+        conn.transaction(|| {
+            diesel::insert_into(crate_owners::table)
+                .values(&CrateOwner {
+                    crate_id: krate.id,
+                    owner_id: self.user_id,
+                    created_by: admin_id,
+                    owner_kind: OwnerKind::User as i32,
+                    email_notifications: true,
+                    role: OwnerRole::Admin,
+                })
+                .on_conflict(crate_owners::table.primary_key())
+                .do_update()
+                .set((
+                    crate_owners::deleted.eq(false),
+                    crate_owners::role.eq(excluded(crate_owners::role)),
+                ))
+                .execute(conn)?;
+
+            diesel::delete(
+                crate_adoption_requests::table
+                    .filter(crate_adoption_requests::crate_id.eq(krate.id)),
+            )
+            .execute(conn)?;
+
+            Ok(())
+        })
+    }
+
+    pub fn reject(self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::delete(&self).execute(conn)?;
+        Ok(())
+    }
+}
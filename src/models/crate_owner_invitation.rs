@@ -1,9 +1,11 @@
 use chrono::{NaiveDateTime, Utc};
+use diesel::pg::upsert::excluded;
 use diesel::prelude::*;
 
 use crate::config;
-use crate::models::{CrateOwner, OwnerKind};
-use crate::schema::{crate_owner_invitations, crate_owners, crates};
+use crate::models::{CrateOwner, OwnerKind, OwnerRole};
+use crate::schema::{crate_owner_invitations, crate_owner_team_invitations, crate_owners, crates};
+use crate::sql::random_string;
 use crate::util::errors::{AppResult, OwnershipInvitationExpired};
 
 #[derive(Debug)]
@@ -110,10 +112,14 @@ impl CrateOwnerInvitation {
                     created_by: self.invited_by_user_id,
                     owner_kind: OwnerKind::User as i32,
                     email_notifications: true,
+                    role: OwnerRole::Admin,
                 })
                 .on_conflict(crate_owners::table.primary_key())
                 .do_update()
-                .set(crate_owners::deleted.eq(false))
+                .set((
+                    crate_owners::deleted.eq(false),
+                    crate_owners::role.eq(excluded(crate_owners::role)),
+                ))
                 .execute(conn)?;
 
             diesel::delete(&self).execute(conn)?;
@@ -131,12 +137,150 @@ impl CrateOwnerInvitation {
         Ok(())
     }
 
+    /// Replaces the token of the invitation and restarts its expiration period, so that it can
+    /// be sent to the invited user again on behalf of `invited_by_user_id`.
+    pub fn regenerate_token(
+        &self,
+        invited_by_user_id: i32,
+        conn: &PgConnection,
+    ) -> QueryResult<Self> {
+        diesel::update(self)
+            .set((
+                crate_owner_invitations::token.eq(random_string(26)),
+                crate_owner_invitations::invited_by_user_id.eq(invited_by_user_id),
+                crate_owner_invitations::created_at.eq(diesel::dsl::now),
+            ))
+            .get_result(conn)
+    }
+
     pub fn is_expired(&self, config: &config::Server) -> bool {
         self.expires_at(config) <= Utc::now().naive_utc()
     }
 
     pub fn expires_at(&self, config: &config::Server) -> NaiveDateTime {
-        let days = chrono::Duration::days(config.ownership_invitations_expiration_days as i64);
-        self.created_at + days
+        expires_at(self.created_at, config)
     }
 }
+
+/// The model representing a row in the `crate_owner_team_invitations` database table.
+///
+/// Teams are only invited instead of being added as owners immediately if
+/// `REQUIRE_TEAM_OWNER_INVITATIONS` is set. The invitation has to be accepted by
+/// a maintainer of the team.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable)]
+#[table_name = "crate_owner_team_invitations"]
+#[primary_key(invited_team_id, crate_id)]
+pub struct CrateOwnerTeamInvitation {
+    pub invited_team_id: i32,
+    pub crate_id: i32,
+    pub invited_by_user_id: i32,
+    pub created_at: NaiveDateTime,
+}
+
+impl CrateOwnerTeamInvitation {
+    /// Creates a new invitation and returns whether it was created, or if an
+    /// unexpired invitation for the team already exists.
+    pub fn create(
+        invited_team_id: i32,
+        invited_by_user_id: i32,
+        crate_id: i32,
+        conn: &PgConnection,
+        config: &config::Server,
+    ) -> AppResult<bool> {
+        conn.transaction(|| {
+            let existing: Option<Self> = crate_owner_team_invitations::table
+                .find((invited_team_id, crate_id))
+                .for_update()
+                .first(conn)
+                .optional()?;
+
+            match existing {
+                Some(existing) if !existing.is_expired(config) => return Ok(false),
+                Some(existing) => {
+                    diesel::delete(&existing).execute(conn)?;
+                }
+                None => {}
+            }
+
+            diesel::insert_into(crate_owner_team_invitations::table)
+                .values((
+                    crate_owner_team_invitations::invited_team_id.eq(invited_team_id),
+                    crate_owner_team_invitations::invited_by_user_id.eq(invited_by_user_id),
+                    crate_owner_team_invitations::crate_id.eq(crate_id),
+                ))
+                .execute(conn)?;
+
+            Ok(true)
+        })
+    }
+
+    pub fn find_by_id(team_id: i32, crate_id: i32, conn: &PgConnection) -> AppResult<Self> {
+        Ok(crate_owner_team_invitations::table
+            .find((team_id, crate_id))
+            .first::<Self>(conn)?)
+    }
+
+    pub fn accept(self, conn: &PgConnection, config: &config::Server) -> AppResult<()> {
+        if self.is_expired(config) {
+            let crate_name = crates::table
+                .find(self.crate_id)
+                .select(crates::name)
+                .first(conn)?;
+            return Err(Box::new(OwnershipInvitationExpired { crate_name }));
+        }
+
+        conn.transaction(|| {
+            diesel::insert_into(crate_owners::table)
+                .values(&CrateOwner {
+                    crate_id: self.crate_id,
+                    owner_id: self.invited_team_id,
+                    created_by: self.invited_by_user_id,
+                    owner_kind: OwnerKind::Team as i32,
+                    email_notifications: true,
+                    role: OwnerRole::Admin,
+                })
+                .on_conflict(crate_owners::table.primary_key())
+                .do_update()
+                .set((
+                    crate_owners::deleted.eq(false),
+                    crate_owners::role.eq(excluded(crate_owners::role)),
+                ))
+                .execute(conn)?;
+
+            diesel::delete(&self).execute(conn)?;
+
+            Ok(())
+        })
+    }
+
+    pub fn decline(self, conn: &PgConnection) -> AppResult<()> {
+        diesel::delete(&self).execute(conn)?;
+        Ok(())
+    }
+
+    /// Restarts the expiration period of the invitation on behalf of `invited_by_user_id`.
+    ///
+    /// Team invitations have no token and are not sent by email, since they are accepted
+    /// by any maintainer of the team through the API.
+    pub fn renew(&self, invited_by_user_id: i32, conn: &PgConnection) -> QueryResult<Self> {
+        diesel::update(self)
+            .set((
+                crate_owner_team_invitations::invited_by_user_id.eq(invited_by_user_id),
+                crate_owner_team_invitations::created_at.eq(diesel::dsl::now),
+            ))
+            .get_result(conn)
+    }
+
+    pub fn is_expired(&self, config: &config::Server) -> bool {
+        self.expires_at(config) <= Utc::now().naive_utc()
+    }
+
+    pub fn expires_at(&self, config: &config::Server) -> NaiveDateTime {
+        expires_at(self.created_at, config)
+    }
+}
+
+fn expires_at(created_at: NaiveDateTime, config: &config::Server) -> NaiveDateTime {
+    let days = chrono::Duration::days(config.ownership_invitations_expiration_days as i64);
+    created_at + days
+}
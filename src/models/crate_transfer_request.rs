@@ -0,0 +1,146 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::pg::upsert::excluded;
+use diesel::prelude::*;
+
+use crate::config;
+use crate::models::{Crate, CrateOwner, OwnerKind, OwnerRole};
+use crate::schema::{crate_owners, crate_transfer_requests};
+use crate::util::errors::{cargo_err, AppResult};
+
+/// A pending request by an owner of a crate to transfer it to another user.
+///
+/// Once the recipient accepts the request, they become the only owner of the
+/// crate. Each crate has at most one pending transfer request.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable)]
+#[primary_key(crate_id)]
+pub struct CrateTransferRequest {
+    pub crate_id: i32,
+    pub from_user_id: i32,
+    pub to_user_id: i32,
+    pub created_at: NaiveDateTime,
+}
+
+impl CrateTransferRequest {
+    /// Creates a transfer request, replacing any pending request for the crate.
+    pub fn create(
+        conn: &PgConnection,
+        crate_id: i32,
+        from_user_id: i32,
+        to_user_id: i32,
+    ) -> QueryResult<Self> {
+        diesel::insert_into(crate_transfer_requests::table)
+            .values((
+                crate_transfer_requests::crate_id.eq(crate_id),
+                crate_transfer_requests::from_user_id.eq(from_user_id),
+                crate_transfer_requests::to_user_id.eq(to_user_id),
+            ))
+            .on_conflict(crate_transfer_requests::crate_id)
+            .do_update()
+            .set((
+                crate_transfer_requests::from_user_id
+                    .eq(excluded(crate_transfer_requests::from_user_id)),
+                crate_transfer_requests::to_user_id
+                    .eq(excluded(crate_transfer_requests::to_user_id)),
+                crate_transfer_requests::created_at.eq(diesel::dsl::now),
+            ))
+            .get_result(conn)
+    }
+
+    pub fn for_crate(conn: &PgConnection, crate_id: i32) -> QueryResult<Self> {
+        crate_transfer_requests::table.find(crate_id).first(conn)
+    }
+
+    /// Finds the pending transfer request of a crate to the given user.
+    pub fn find(conn: &PgConnection, crate_id: i32, to_user_id: i32) -> QueryResult<Self> {
+        crate_transfer_requests::table
+            .find(crate_id)
+            .filter(crate_transfer_requests::to_user_id.eq(to_user_id))
+            .first(conn)
+    }
+
+    /// Returns the unexpired transfer requests sent to the given user.
+    pub fn for_recipient(
+        conn: &PgConnection,
+        to_user_id: i32,
+        config: &config::Server,
+    ) -> QueryResult<Vec<Self>> {
+        let requests: Vec<Self> = crate_transfer_requests::table
+            .filter(crate_transfer_requests::to_user_id.eq(to_user_id))
+            .order(crate_transfer_requests::created_at)
+            .load(conn)?;
+
+        Ok(requests
+            .into_iter()
+            .filter(|request| !request.is_expired(config))
+            .collect())
+    }
+
+    /// Makes the recipient the only owner of the crate, with the admin role.
+    ///
+    /// Fails if the sender of the request is no longer an admin owner of the crate.
+    pub fn accept(self, conn: &PgConnection, config: &config::Server) -> AppResult<()> {
+        let krate: Crate = Crate::all().find(self.crate_id).first(conn)?;
+        if self.is_expired(config) {
+            return Err(cargo_err(&format_args!(
+                "the request to transfer the crate `{}` has expired",
+                krate.name
+            )));
+        }
+
+        conn.transaction(|| {
+            // The sender may have been removed or demoted since sending the request
+            let sender_role: Option<OwnerRole> = crate_owners::table
+                .filter(crate_owners::crate_id.eq(self.crate_id))
+                .filter(crate_owners::owner_id.eq(self.from_user_id))
+                .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+                .filter(crate_owners::deleted.eq(false))
+                .select(crate_owners::role)
+                .for_update()
+                .first(conn)
+                .optional()?;
+            if sender_role != Some(OwnerRole::Admin) {
+                return Err(cargo_err(&format_args!(
+                    "the request to transfer the crate `{}` is no longer valid, \
+                     because its sender is no longer an admin owner of the crate",
+                    krate.name
+                )));
+            }
+
+            krate.remove_all_owners(conn)?;
+
+            diesel::insert_into(crate_owners::table)
+                .values(&CrateOwner {
+                    crate_id: self.crate_id,
+                    owner_id: self.to_user_id,
+                    created_by: self.from_user_id,
+                    owner_kind: OwnerKind::User as i32,
+                    email_notifications: true,
+                    role: OwnerRole::Admin,
+                })
+                .on_conflict(crate_owners::table.primary_key())
+                .do_update()
+                .set((
+                    crate_owners::deleted.eq(false),
+                    crate_owners::role.eq(excluded(crate_owners::role)),
+                ))
+                .execute(conn)?;
+
+            Ok(())
+        })
+    }
+
+    pub fn delete(self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::delete(&self).execute(conn)?;
+        Ok(())
+    }
+
+    pub fn is_expired(&self, config: &config::Server) -> bool {
+        self.expires_at(config) <= Utc::now().naive_utc()
+    }
+
+    /// Transfer requests expire after the same time as ownership invitations.
+    pub fn expires_at(&self, config: &config::Server) -> NaiveDateTime {
+        let days = chrono::Duration::days(config.ownership_invitations_expiration_days as i64);
+        self.created_at + days
+    }
+}
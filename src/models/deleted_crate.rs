@@ -0,0 +1,65 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::Crate;
+use crate::schema::{crates, deleted_crates, reserved_crate_names};
+
+/// An audit record of a crate that was deleted by one of its owners.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable)]
+pub struct DeletedCrate {
+    pub id: i32,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+    pub deleted_at: NaiveDateTime,
+    pub deleted_by: i32,
+    pub downloads: i32,
+}
+
+impl DeletedCrate {
+    /// Deletes the crate and all rows depending on it, records the deletion
+    /// and reserves the name of the crate until `reserved_until`.
+    ///
+    /// Existing reservations of the name that do not expire are left untouched.
+    pub fn delete_crate(
+        conn: &PgConnection,
+        krate: &Crate,
+        deleted_by: i32,
+        reserved_until: NaiveDateTime,
+    ) -> QueryResult<Self> {
+        conn.transaction(|| {
+            let deleted = diesel::insert_into(deleted_crates::table)
+                .values((
+                    deleted_crates::name.eq(&krate.name),
+                    deleted_crates::created_at.eq(krate.created_at),
+                    deleted_crates::deleted_by.eq(deleted_by),
+                    deleted_crates::downloads.eq(krate.downloads),
+                ))
+                .get_result(conn)?;
+
+            diesel::delete(crates::table.find(krate.id)).execute(conn)?;
+
+            diesel::delete(
+                reserved_crate_names::table
+                    .filter(reserved_crate_names::name.eq(&krate.name))
+                    .filter(reserved_crate_names::expires_at.is_not_null()),
+            )
+            .execute(conn)?;
+            diesel::insert_into(reserved_crate_names::table)
+                .values((
+                    reserved_crate_names::name.eq(&krate.name),
+                    reserved_crate_names::expires_at.eq(reserved_until),
+                ))
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+
+            Ok(deleted)
+        })
+    }
+
+    pub fn by_name(conn: &PgConnection, name: &str) -> QueryResult<Vec<Self>> {
+        deleted_crates::table
+            .filter(deleted_crates::name.eq(name))
+            .order(deleted_crates::deleted_at)
+            .load(conn)
+    }
+}
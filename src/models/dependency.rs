@@ -13,7 +13,7 @@ use cargo_registry_index::DependencyKind as IndexDependencyKind;
 pub struct Dependency {
     pub id: i32,
     pub version_id: i32,
-    pub crate_id: i32,
+    pub crate_id: Option<i32>,
     pub req: String,
     pub optional: bool,
     pub default_features: bool,
@@ -21,6 +21,36 @@ pub struct Dependency {
     pub target: Option<String>,
     pub kind: DependencyKind,
     pub explicit_name: Option<String>,
+    pub registry: Option<String>,
+    pub registry_crate_name: Option<String>,
+}
+
+impl Dependency {
+    /// Converts this dependency into its representation in the index, given the
+    /// name of the crate it depends on.
+    ///
+    /// Dependencies on crates of other registries are listed with the index URL
+    /// of their registry.
+    pub fn into_index_dependency(self, crate_name: String) -> cargo_registry_index::Dependency {
+        // If this dependency has an explicit name in `Cargo.toml` that name is
+        // used in the index, and the actual crate name is listed as `package`.
+        let (name, package) = match self.explicit_name {
+            Some(explicit_name) => (explicit_name, Some(crate_name)),
+            None => (crate_name, None),
+        };
+
+        cargo_registry_index::Dependency {
+            name,
+            req: self.req,
+            features: self.features,
+            optional: self.optional,
+            default_features: self.default_features,
+            target: self.target,
+            kind: Some(self.kind.into()),
+            package,
+            registry: self.registry,
+        }
+    }
 }
 
 #[derive(Debug, QueryableByName)]
@@ -0,0 +1,46 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::schema::index_consistency_checks;
+
+/// The result of a run of the `check_index` background job.
+#[derive(Queryable, Identifiable, Debug, Clone)]
+pub struct IndexConsistencyCheck {
+    pub id: i32,
+    pub checked_at: NaiveDateTime,
+    pub crates_checked: i32,
+    /// Names of the crates whose git index file did not match the database
+    pub git_mismatches: Vec<String>,
+    /// Names of the crates whose HTTP index file did not match the git index
+    pub http_mismatches: Vec<String>,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "index_consistency_checks"]
+pub struct NewIndexConsistencyCheck<'a> {
+    pub crates_checked: i32,
+    pub git_mismatches: &'a [String],
+    pub http_mismatches: &'a [String],
+}
+
+impl IndexConsistencyCheck {
+    /// Returns the result of the most recent consistency check, if any.
+    pub fn latest(conn: &PgConnection) -> QueryResult<Option<Self>> {
+        index_consistency_checks::table
+            .order(index_consistency_checks::id.desc())
+            .first(conn)
+            .optional()
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.git_mismatches.is_empty() && self.http_mismatches.is_empty()
+    }
+}
+
+impl NewIndexConsistencyCheck<'_> {
+    pub fn save(&self, conn: &PgConnection) -> QueryResult<IndexConsistencyCheck> {
+        diesel::insert_into(index_consistency_checks::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
@@ -1,9 +1,11 @@
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
+use diesel::{delete, insert_into, sql_query, sql_types, update};
 
 use crate::models::Crate;
 use crate::schema::*;
 use crate::sql::lower;
+use crate::util::errors::{cargo_err, AppResult};
 
 #[derive(Clone, Identifiable, Queryable, Debug)]
 pub struct Keyword {
@@ -59,9 +61,37 @@ impl Keyword {
             && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '+')
     }
 
-    pub fn update_crate(conn: &PgConnection, krate: &Crate, keywords: &[&str]) -> QueryResult<()> {
+    /// Replaces the keywords of a crate. Aliases are replaced by their canonical
+    /// keyword, and banned keywords are ignored and returned.
+    pub fn update_crate(
+        conn: &PgConnection,
+        krate: &Crate,
+        keywords: &[&str],
+    ) -> QueryResult<Vec<String>> {
         conn.transaction(|| {
-            let keywords = Keyword::find_or_create_all(conn, keywords)?;
+            let names: Vec<_> = keywords.iter().map(|s| s.to_lowercase()).collect();
+            let banned: Vec<String> = banned_keywords::table
+                .filter(banned_keywords::keyword.eq_any(&names))
+                .select(banned_keywords::keyword)
+                .load(conn)?;
+            let aliases: Vec<(String, String)> = keyword_aliases::table
+                .inner_join(keywords::table)
+                .filter(keyword_aliases::alias.eq_any(&names))
+                .select((keyword_aliases::alias, keywords::keyword))
+                .load(conn)?;
+
+            let mut canonical_names = Vec::new();
+            for name in names.iter().filter(|name| !banned.contains(name)) {
+                let name = aliases
+                    .iter()
+                    .find(|(alias, _)| alias == name)
+                    .map_or(name, |(_, keyword)| keyword);
+                if !canonical_names.contains(&name.as_str()) {
+                    canonical_names.push(name.as_str());
+                }
+            }
+
+            let keywords = Keyword::find_or_create_all(conn, &canonical_names)?;
             diesel::delete(CrateKeyword::belonging_to(krate)).execute(conn)?;
             let crate_keywords = keywords
                 .into_iter()
@@ -73,9 +103,183 @@ impl Keyword {
             diesel::insert_into(crates_keywords::table)
                 .values(&crate_keywords)
                 .execute(conn)?;
+
+            let ignored = names.into_iter().filter(|name| banned.contains(name));
+            Ok(ignored.collect())
+        })
+    }
+}
+
+/// Keyword moderation, used by the admin endpoints and `crates-admin keywords`.
+impl Keyword {
+    /// Returns the canonical keyword of an alias.
+    pub fn aliased_keyword(conn: &PgConnection, alias: &str) -> QueryResult<Option<String>> {
+        keyword_aliases::table
+            .inner_join(keywords::table)
+            .filter(keyword_aliases::alias.eq(lower(alias)))
+            .select(keywords::keyword)
+            .first(conn)
+            .optional()
+    }
+
+    /// Makes `alias` an alias of this keyword, so that crates which are published
+    /// with it get this keyword instead.
+    pub fn add_alias(&self, conn: &PgConnection, alias: &str) -> AppResult<()> {
+        let alias = validate_name(alias)?;
+        if alias == self.keyword {
+            return Err(cargo_err("a keyword cannot be an alias of itself"));
+        }
+
+        conn.transaction(|| {
+            if Keyword::find_by_keyword(conn, &alias).optional()?.is_some() {
+                return Err(cargo_err(&format_args!(
+                    "keyword `{alias}` already exists and needs to be merged into `{}` instead",
+                    self.keyword
+                )));
+            }
+            ensure_not_banned(conn, &alias)?;
+            insert_alias(conn, &alias, self.id)?;
             Ok(())
         })
     }
+
+    /// Changes the name of this keyword. The former name becomes an alias.
+    pub fn rename(&self, conn: &PgConnection, name: &str) -> AppResult<Keyword> {
+        let name = validate_name(name)?;
+
+        conn.transaction(|| {
+            if Keyword::find_by_keyword(conn, &name).optional()?.is_some() {
+                return Err(cargo_err(&format_args!("keyword `{name}` already exists")));
+            }
+            ensure_not_banned(conn, &name)?;
+
+            delete(keyword_aliases::table.find(&name)).execute(conn)?;
+            let keyword = update(keywords::table.find(self.id))
+                .set(keywords::keyword.eq(&name))
+                .get_result(conn)?;
+            insert_alias(conn, &self.keyword, self.id)?;
+            Ok(keyword)
+        })
+    }
+
+    /// Moves the crates and aliases of this keyword to `target` and removes it.
+    /// The name of this keyword becomes an alias of `target`.
+    pub fn merge_into(&self, conn: &PgConnection, target: &Keyword) -> AppResult<()> {
+        if target.id == self.id {
+            return Err(cargo_err("a keyword cannot be merged into itself"));
+        }
+
+        conn.transaction(|| {
+            insert_into(crates_keywords::table)
+                .values(
+                    crates_keywords::table
+                        .filter(crates_keywords::keyword_id.eq(self.id))
+                        .select((
+                            crates_keywords::crate_id,
+                            target.id.into_sql::<sql_types::Integer>(),
+                        )),
+                )
+                .into_columns((crates_keywords::crate_id, crates_keywords::keyword_id))
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            delete(crates_keywords::table.filter(crates_keywords::keyword_id.eq(self.id)))
+                .execute(conn)?;
+
+            update(keyword_aliases::table.filter(keyword_aliases::keyword_id.eq(self.id)))
+                .set(keyword_aliases::keyword_id.eq(target.id))
+                .execute(conn)?;
+            delete(keywords::table.find(self.id)).execute(conn)?;
+            insert_alias(conn, &self.keyword, target.id)?;
+
+            // The `crates_cnt` trigger also counts the crates that already had the
+            // target keyword, even though they are skipped by the insert above.
+            recount_crates(conn, Some(&[target.id]))?;
+            Ok(())
+        })
+    }
+
+    /// Bans a keyword, removing it from all crates and ignoring it when crates are
+    /// published. Returns the number of crates that the keyword was removed from.
+    pub fn ban(conn: &PgConnection, name: &str) -> AppResult<usize> {
+        let name = validate_name(name)?;
+
+        conn.transaction(|| {
+            insert_into(banned_keywords::table)
+                .values(banned_keywords::keyword.eq(&name))
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            delete(keyword_aliases::table.find(&name)).execute(conn)?;
+
+            let keyword = match Keyword::find_by_keyword(conn, &name).optional()? {
+                Some(keyword) => keyword,
+                None => return Ok(0),
+            };
+            let removed = delete(CrateKeyword::belonging_to(&keyword)).execute(conn)?;
+            delete(keywords::table.find(keyword.id)).execute(conn)?;
+            Ok(removed)
+        })
+    }
+
+    /// Allows a banned keyword again. Returns whether the keyword was banned.
+    pub fn unban(conn: &PgConnection, name: &str) -> QueryResult<bool> {
+        let name = name.to_lowercase();
+        let deleted = delete(banned_keywords::table.find(&name)).execute(conn)?;
+        Ok(deleted > 0)
+    }
+
+    /// Recomputes the `crates_cnt` of all keywords from their crates, and returns
+    /// the number of keywords whose count was wrong.
+    pub fn recount_crates(conn: &PgConnection) -> QueryResult<usize> {
+        recount_crates(conn, None)
+    }
+}
+
+fn validate_name(name: &str) -> AppResult<String> {
+    let name = name.to_lowercase();
+    if !Keyword::valid_name(&name) {
+        return Err(cargo_err(&format_args!(
+            "invalid keyword `{name}`; keywords must start with a letter or digit and may only \
+             contain letters, digits, `_`, `-` and `+`"
+        )));
+    }
+    Ok(name)
+}
+
+fn ensure_not_banned(conn: &PgConnection, name: &str) -> AppResult<()> {
+    let banned =
+        diesel::select(diesel::dsl::exists(banned_keywords::table.find(name))).get_result(conn)?;
+    if banned {
+        return Err(cargo_err(&format_args!("keyword `{name}` is banned")));
+    }
+    Ok(())
+}
+
+fn insert_alias(conn: &PgConnection, alias: &str, keyword_id: i32) -> QueryResult<()> {
+    insert_into(keyword_aliases::table)
+        .values((
+            keyword_aliases::alias.eq(alias),
+            keyword_aliases::keyword_id.eq(keyword_id),
+        ))
+        .on_conflict(keyword_aliases::alias)
+        .do_update()
+        .set(keyword_aliases::keyword_id.eq(keyword_id))
+        .execute(conn)?;
+    Ok(())
+}
+
+fn recount_crates(conn: &PgConnection, ids: Option<&[i32]>) -> QueryResult<usize> {
+    sql_query(
+        "UPDATE keywords SET crates_cnt = counts.crates_cnt \
+         FROM ( \
+             SELECT keywords.id, COUNT(crates_keywords.crate_id)::int AS crates_cnt \
+             FROM keywords LEFT JOIN crates_keywords ON crates_keywords.keyword_id = keywords.id \
+             WHERE $1::int[] IS NULL OR keywords.id = ANY($1) \
+             GROUP BY keywords.id \
+         ) counts \
+         WHERE keywords.id = counts.id AND keywords.crates_cnt <> counts.crates_cnt",
+    )
+    .bind::<sql_types::Nullable<sql_types::Array<sql_types::Integer>>, _>(ids)
+    .execute(conn)
 }
 
 #[cfg(test)]
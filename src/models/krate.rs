@@ -1,7 +1,9 @@
 use chrono::NaiveDateTime;
 use diesel::associations::Identifiable;
+use diesel::pg::upsert::excluded;
 use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::result::Error as DieselError;
 use diesel::sql_types::{Bool, Text};
 use url::Url;
 
@@ -9,15 +11,16 @@ use crate::app::App;
 use crate::controllers::helpers::pagination::*;
 use crate::models::version::TopVersions;
 use crate::models::{
-    CrateOwner, CrateOwnerInvitation, NewCrateOwnerInvitationOutcome, Owner, OwnerKind,
-    ReverseDependency, User, Version,
+    CrateOwner, CrateOwnerInvitation, CrateOwnerTeamInvitation, Dependency,
+    NewCrateOwnerInvitationOutcome, Owner, OwnerKind, OwnerRole, ReverseDependency, User, Version,
 };
 use crate::util::errors::{cargo_err, AppResult};
 
 use crate::models::helpers::with_count::*;
 use crate::publish_rate_limit::PublishRateLimit;
 use crate::schema::*;
-use crate::sql::canon_crate_name;
+use crate::sql::{canon_crate_name, dependency_crate_name};
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug, Queryable, Identifiable, Associations, Clone, Copy)]
 #[belongs_to(Crate)]
@@ -153,11 +156,13 @@ impl<'a> NewCrate<'a> {
 
     fn ensure_name_not_reserved(&self, conn: &PgConnection) -> AppResult<()> {
         use crate::schema::reserved_crate_names::dsl::*;
-        use diesel::dsl::exists;
+        use diesel::dsl::{exists, now};
         use diesel::select;
 
         let reserved_name: bool = select(exists(
-            reserved_crate_names.filter(canon_crate_name(name).eq(canon_crate_name(self.name))),
+            reserved_crate_names
+                .filter(canon_crate_name(name).eq(canon_crate_name(self.name)))
+                .filter(expires_at.is_null().or(expires_at.gt(now))),
         ))
         .get_result(conn)?;
         if reserved_name {
@@ -185,6 +190,7 @@ impl<'a> NewCrate<'a> {
                     created_by: user_id,
                     owner_kind: OwnerKind::User as i32,
                     email_notifications: true,
+                    role: OwnerRole::Admin,
                 };
                 diesel::insert_into(crate_owners::table)
                     .values(&owner)
@@ -248,6 +254,19 @@ impl Crate {
             })
     }
 
+    pub fn find_staged_version(&self, conn: &PgConnection, version: &str) -> AppResult<Version> {
+        Version::belonging_to(self)
+            .filter(versions::staged.eq(true))
+            .filter(versions::num.eq(version))
+            .first(conn)
+            .map_err(|_| {
+                cargo_err(&format_args!(
+                    "crate `{}` does not have a staged version `{}`",
+                    self.name, version
+                ))
+            })
+    }
+
     pub fn valid_name(name: &str) -> bool {
         let under_max_length = name.chars().take(MAX_NAME_LENGTH + 1).count() <= MAX_NAME_LENGTH;
         Crate::valid_ident(name) && under_max_length
@@ -314,21 +333,94 @@ impl Crate {
         ))
     }
 
+    /// Serializes all versions of this crate as entries of its index file, in
+    /// the order in which they were published.
+    pub fn index_metadata(
+        &self,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<cargo_registry_index::Crate>> {
+        let versions: Vec<Version> = self.all_versions().order(versions::id).load(conn)?;
+        self.index_entries(conn, versions)
+    }
+
+    /// Serializes a single version of this crate as an entry of its index file.
+    pub fn index_entry(
+        &self,
+        conn: &PgConnection,
+        version: Version,
+    ) -> QueryResult<cargo_registry_index::Crate> {
+        let mut entries = self.index_entries(conn, vec![version])?;
+        Ok(entries.remove(0))
+    }
+
+    fn index_entries(
+        &self,
+        conn: &PgConnection,
+        versions: Vec<Version>,
+    ) -> QueryResult<Vec<cargo_registry_index::Crate>> {
+        let crate_name =
+            dependency_crate_name(crates::name.nullable(), dependencies::registry_crate_name);
+        let dependencies: Vec<(Dependency, String)> = Dependency::belonging_to(&versions)
+            .left_join(crates::table)
+            .select((dependencies::all_columns, crate_name))
+            .order(dependencies::id)
+            .load(conn)?;
+
+        let mut deps_by_version: HashMap<i32, Vec<_>> = HashMap::new();
+        for (dependency, crate_name) in dependencies {
+            deps_by_version
+                .entry(dependency.version_id)
+                .or_default()
+                .push(dependency.into_index_dependency(crate_name));
+        }
+
+        versions
+            .into_iter()
+            .map(|version| {
+                let mut deps = deps_by_version.remove(&version.id).unwrap_or_default();
+                deps.sort();
+
+                let features: BTreeMap<String, Vec<String>> =
+                    serde_json::from_value(version.features)
+                        .map_err(|e| DieselError::DeserializationError(Box::new(e)))?;
+                let (features, features2, v) = split_index_features(features);
+
+                Ok(cargo_registry_index::Crate {
+                    name: self.name.clone(),
+                    vers: version.num,
+                    deps,
+                    cksum: version.checksum,
+                    features,
+                    features2,
+                    yanked: Some(version.yanked),
+                    links: version.links,
+                    v,
+                })
+            })
+            .collect()
+    }
+
     pub fn owners(&self, conn: &PgConnection) -> QueryResult<Vec<Owner>> {
+        let owners = self.owners_with_roles(conn)?;
+        Ok(owners.into_iter().map(|(owner, _)| owner).collect())
+    }
+
+    /// Returns the owners of the crate together with their roles.
+    pub fn owners_with_roles(&self, conn: &PgConnection) -> QueryResult<Vec<(Owner, OwnerRole)>> {
         let users = CrateOwner::by_owner_kind(OwnerKind::User)
             .filter(crate_owners::crate_id.eq(self.id))
             .inner_join(users::table)
-            .select(users::all_columns)
+            .select((users::all_columns, crate_owners::role))
             .load(conn)?
             .into_iter()
-            .map(Owner::User);
+            .map(|(user, role)| (Owner::User(user), role));
         let teams = CrateOwner::by_owner_kind(OwnerKind::Team)
             .filter(crate_owners::crate_id.eq(self.id))
             .inner_join(teams::table)
-            .select(teams::all_columns)
+            .select((teams::all_columns, crate_owners::role))
             .load(conn)?
             .into_iter()
-            .map(Owner::Team);
+            .map(|(team, role)| (Owner::Team(team), role));
 
         Ok(users.chain(teams).collect())
     }
@@ -373,7 +465,22 @@ impl Crate {
                     )),
                 }
             }
-            // Teams are added as owners immediately
+            // Teams are invited if configured, and must be accepted by a team maintainer
+            Owner::Team(team) if app.config.require_team_owner_invitations => {
+                let config = &app.config;
+                if CrateOwnerTeamInvitation::create(team.id, req_user.id, self.id, conn, config)? {
+                    Ok(format!(
+                        "team {} has been invited to be an owner of crate {}",
+                        team.login, self.name
+                    ))
+                } else {
+                    Ok(format!(
+                        "team {} already has a pending invitation to be an owner of crate {}",
+                        team.login, self.name
+                    ))
+                }
+            }
+            // Otherwise teams are added as owners immediately
             owner @ Owner::Team(_) => {
                 insert_into(crate_owners::table)
                     .values(&CrateOwner {
@@ -382,10 +489,14 @@ impl Crate {
                         created_by: req_user.id,
                         owner_kind: OwnerKind::Team as i32,
                         email_notifications: true,
+                        role: OwnerRole::Admin,
                     })
                     .on_conflict(crate_owners::table.primary_key())
                     .do_update()
-                    .set(crate_owners::deleted.eq(false))
+                    .set((
+                        crate_owners::deleted.eq(false),
+                        crate_owners::role.eq(excluded(crate_owners::role)),
+                    ))
                     .execute(conn)?;
 
                 Ok(format!(
@@ -413,6 +524,40 @@ impl Crate {
         Ok(())
     }
 
+    /// Removes all owners of the crate together with its pending ownership
+    /// invitations and transfer request, which leaves the crate orphaned.
+    pub fn remove_all_owners(&self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::update(crate_owners::table.filter(crate_owners::crate_id.eq(self.id)))
+            .set(crate_owners::deleted.eq(true))
+            .execute(conn)?;
+        diesel::delete(
+            crate_owner_invitations::table.filter(crate_owner_invitations::crate_id.eq(self.id)),
+        )
+        .execute(conn)?;
+        diesel::delete(
+            crate_owner_team_invitations::table
+                .filter(crate_owner_team_invitations::crate_id.eq(self.id)),
+        )
+        .execute(conn)?;
+        diesel::delete(crate_transfer_requests::table.find(self.id)).execute(conn)?;
+        Ok(())
+    }
+
+    /// Whether all owners of the crate have left it, in which case the crate
+    /// can be adopted by someone else with the approval of an admin.
+    pub fn is_orphaned(&self, conn: &PgConnection) -> QueryResult<bool> {
+        use diesel::dsl::exists;
+
+        let has_owners = diesel::select(exists(
+            crate_owners::table
+                .filter(crate_owners::crate_id.eq(self.id))
+                .filter(crate_owners::deleted.eq(false)),
+        ))
+        .get_result::<bool>(conn)?;
+
+        Ok(!has_owners)
+    }
+
     /// Returns (dependency, dependent crate name, dependent crate downloads)
     pub(crate) fn reverse_dependencies(
         &self,
@@ -434,6 +579,149 @@ impl Crate {
     }
 }
 
+/// The metadata of a crate that every published version replaces.
+///
+/// Asynchronously published versions may be rejected after their metadata was
+/// already stored, so the previous metadata is kept to restore it in that case.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrateMetadata {
+    description: Option<String>,
+    homepage: Option<String>,
+    documentation: Option<String>,
+    readme: Option<String>,
+    repository: Option<String>,
+    keyword_ids: Vec<i32>,
+    category_ids: Vec<i32>,
+    badges: Vec<(String, serde_json::Value)>,
+}
+
+impl CrateMetadata {
+    pub fn load(conn: &PgConnection, crate_id: i32) -> QueryResult<Self> {
+        let (description, homepage, documentation, readme, repository) = crates::table
+            .find(crate_id)
+            .select((
+                crates::description,
+                crates::homepage,
+                crates::documentation,
+                crates::readme,
+                crates::repository,
+            ))
+            .first(conn)?;
+
+        Ok(CrateMetadata {
+            description,
+            homepage,
+            documentation,
+            readme,
+            repository,
+            keyword_ids: crates_keywords::table
+                .filter(crates_keywords::crate_id.eq(crate_id))
+                .select(crates_keywords::keyword_id)
+                .order(crates_keywords::keyword_id)
+                .load(conn)?,
+            category_ids: crates_categories::table
+                .filter(crates_categories::crate_id.eq(crate_id))
+                .select(crates_categories::category_id)
+                .order(crates_categories::category_id)
+                .load(conn)?,
+            badges: badges::table
+                .filter(badges::crate_id.eq(crate_id))
+                .select((badges::badge_type, badges::attributes))
+                .order(badges::badge_type)
+                .load(conn)?,
+        })
+    }
+
+    /// Replaces the metadata of the crate with this metadata.
+    pub fn restore(&self, conn: &PgConnection, crate_id: i32) -> QueryResult<()> {
+        conn.transaction(|| {
+            diesel::update(crates::table.find(crate_id))
+                .set((
+                    crates::description.eq(&self.description),
+                    crates::homepage.eq(&self.homepage),
+                    crates::documentation.eq(&self.documentation),
+                    crates::readme.eq(&self.readme),
+                    crates::repository.eq(&self.repository),
+                ))
+                .execute(conn)?;
+
+            diesel::delete(crates_keywords::table.filter(crates_keywords::crate_id.eq(crate_id)))
+                .execute(conn)?;
+            let keywords = self
+                .keyword_ids
+                .iter()
+                .map(|id| {
+                    (
+                        crates_keywords::crate_id.eq(crate_id),
+                        crates_keywords::keyword_id.eq(id),
+                    )
+                })
+                .collect::<Vec<_>>();
+            diesel::insert_into(crates_keywords::table)
+                .values(&keywords)
+                .execute(conn)?;
+
+            diesel::delete(
+                crates_categories::table.filter(crates_categories::crate_id.eq(crate_id)),
+            )
+            .execute(conn)?;
+            let categories = self
+                .category_ids
+                .iter()
+                .map(|id| {
+                    (
+                        crates_categories::crate_id.eq(crate_id),
+                        crates_categories::category_id.eq(id),
+                    )
+                })
+                .collect::<Vec<_>>();
+            diesel::insert_into(crates_categories::table)
+                .values(&categories)
+                .execute(conn)?;
+
+            diesel::delete(badges::table.filter(badges::crate_id.eq(crate_id))).execute(conn)?;
+            let badges = self
+                .badges
+                .iter()
+                .map(|(badge_type, attributes)| {
+                    (
+                        badges::crate_id.eq(crate_id),
+                        badges::badge_type.eq(badge_type),
+                        badges::attributes.eq(attributes),
+                    )
+                })
+                .collect::<Vec<_>>();
+            diesel::insert_into(badges::table)
+                .values(&badges)
+                .execute(conn)?;
+
+            Ok(())
+        })
+    }
+}
+
+type FeatureMap = BTreeMap<String, Vec<String>>;
+
+/// Splits the features of a version into the `features` and `features2`
+/// fields of an index entry, together with the schema version of the entry.
+///
+/// Features using the new syntax (`dep:` and `pkg?/feat`) are moved to
+/// `features2`, so that old versions of cargo can still parse the entry.
+pub fn split_index_features(features: FeatureMap) -> (FeatureMap, Option<FeatureMap>, Option<u32>) {
+    let (features, features2): (BTreeMap<_, _>, BTreeMap<_, _>) =
+        features.into_iter().partition(|(_k, vals)| {
+            !vals
+                .iter()
+                .any(|v| v.starts_with("dep:") || v.contains("?/"))
+        });
+
+    if features2.is_empty() {
+        (features, None, None)
+    } else {
+        (features, Some(features2), Some(2))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::models::{Crate, NewCrate};
@@ -504,12 +792,16 @@ pub trait CrateVersions {
         self.all_versions().filter(versions::yanked.eq(false))
     }
 
+    /// All released versions, including yanked ones. Staged versions are
+    /// not included.
     fn all_versions(&self) -> versions::BoxedQuery<'_, Pg>;
 }
 
 impl CrateVersions for Crate {
     fn all_versions(&self) -> versions::BoxedQuery<'_, Pg> {
-        Version::belonging_to(self).into_boxed()
+        Version::belonging_to(self)
+            .filter(versions::staged.eq(false))
+            .into_boxed()
     }
 }
 
@@ -521,6 +813,8 @@ impl CrateVersions for Vec<Crate> {
 
 impl CrateVersions for [Crate] {
     fn all_versions(&self) -> versions::BoxedQuery<'_, Pg> {
-        Version::belonging_to(self).into_boxed()
+        Version::belonging_to(self)
+            .filter(versions::staged.eq(false))
+            .into_boxed()
     }
 }
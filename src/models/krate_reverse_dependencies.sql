@@ -15,7 +15,7 @@ SELECT *, COUNT(*) OVER () as total FROM (
             ORDER BY to_semver_no_prerelease(num) DESC NULLS LAST
         ) rn
         FROM versions
-        WHERE NOT yanked
+        WHERE NOT yanked AND NOT staged
         -- This is completely redundant, but it's faster to filter the versions
         -- early even if this subselect is done via an index scan.
         AND crate_id = ANY(
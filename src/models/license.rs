@@ -0,0 +1,268 @@
+use std::collections::BTreeSet;
+
+use spdx::expression::{ExprNode, Operator};
+use spdx::{LicenseItem, LicenseReq};
+
+use crate::util::errors::{cargo_err, AppResult};
+
+const PARSE_MODE: spdx::ParseMode = spdx::ParseMode {
+    allow_lower_case_operators: false,
+    allow_slash_as_or_operator: true,
+    allow_imprecise_license_names: false,
+    allow_postfix_plus_on_gpl: true,
+};
+
+/// The maximum number of licenses in an expression.
+const MAX_REQUIREMENTS: usize = 32;
+
+/// The maximum number of alternative sets of licenses of an expression, which
+/// grows exponentially with the number of `OR` expressions that are combined
+/// with `AND`.
+const MAX_ALTERNATIVES: usize = 64;
+
+/// A valid SPDX license expression, like the `license` field of a crate manifest.
+#[derive(Debug, Clone)]
+pub struct LicenseExpression {
+    expr: spdx::Expression,
+}
+
+impl LicenseExpression {
+    pub fn parse(s: &str) -> AppResult<Self> {
+        let expr = spdx::Expression::parse_mode(s, PARSE_MODE).map_err(|_| {
+            cargo_err("unknown or invalid license expression; see http://opensource.org/licenses for options, and http://spdx.org/licenses/ for their identifiers")
+        })?;
+
+        if expr.requirements().count() > MAX_REQUIREMENTS {
+            return Err(cargo_err(&format_args!(
+                "license expressions may not contain more than {MAX_REQUIREMENTS} licenses"
+            )));
+        }
+
+        Ok(Self { expr })
+    }
+
+    /// Returns the license requirements of the expression, in the order in which
+    /// they appear.
+    pub fn requirements(&self) -> impl Iterator<Item = &LicenseReq> {
+        self.expr.requirements().map(|req| &req.req)
+    }
+
+    /// Checks whether the expression is satisfied if only the requirements that
+    /// `allow` returns `true` for are met.
+    pub fn evaluate(&self, allow: impl FnMut(&LicenseReq) -> bool) -> bool {
+        self.expr.evaluate(allow)
+    }
+
+    /// Returns the expression in its canonical form, e.g. `MIT OR Apache-2.0` for
+    /// `MIT/Apache-2.0`, with redundant parentheses removed and the current SPDX
+    /// identifiers of the GNU licenses.
+    pub fn normalized(&self) -> String {
+        let mut stack: Vec<(String, Option<Operator>)> = Vec::new();
+        for node in self.expr.iter() {
+            match node {
+                ExprNode::Req(req) => stack.push((normalized_req(&req.req), None)),
+                ExprNode::Op(op) => {
+                    let (rhs, rhs_op) = stack.pop().expect("invalid postfix expression");
+                    let (lhs, lhs_op) = stack.pop().expect("invalid postfix expression");
+                    let (lhs, rhs, joiner) = match op {
+                        Operator::And => (
+                            parenthesize_or(lhs, lhs_op),
+                            parenthesize_or(rhs, rhs_op),
+                            "AND",
+                        ),
+                        Operator::Or => (lhs, rhs, "OR"),
+                    };
+                    stack.push((format!("{lhs} {joiner} {rhs}"), Some(*op)));
+                }
+            }
+        }
+
+        stack.pop().map(|(expr, _)| expr).unwrap_or_default()
+    }
+
+    /// Returns the alternative sets of licenses that satisfy the expression, e.g.
+    /// `[["MIT"], ["Apache-2.0", "BSD-3-Clause"]]` for
+    /// `MIT OR (Apache-2.0 AND BSD-3-Clause)`.
+    ///
+    /// The licenses are in their normalized form, and sorted within each set.
+    /// Expressions with more than `MAX_ALTERNATIVES` sets are rejected.
+    pub fn alternatives(&self) -> AppResult<Vec<Vec<String>>> {
+        let too_many = || {
+            cargo_err(&format_args!(
+                "license expressions may not have more than {MAX_ALTERNATIVES} alternative combinations of licenses"
+            ))
+        };
+
+        let mut stack: Vec<Vec<BTreeSet<String>>> = Vec::new();
+        for node in self.expr.iter() {
+            match node {
+                ExprNode::Req(req) => {
+                    stack.push(vec![BTreeSet::from([normalized_req(&req.req)])]);
+                }
+                ExprNode::Op(op) => {
+                    let rhs = stack.pop().expect("invalid postfix expression");
+                    let lhs = stack.pop().expect("invalid postfix expression");
+                    let combined = match op {
+                        Operator::Or => dedup(lhs.into_iter().chain(rhs)),
+                        Operator::And => {
+                            if lhs.len() * rhs.len() > MAX_ALTERNATIVES {
+                                return Err(too_many());
+                            }
+                            dedup(lhs.iter().flat_map(|l| {
+                                rhs.iter().map(move |r| l.union(r).cloned().collect())
+                            }))
+                        }
+                    };
+                    if combined.len() > MAX_ALTERNATIVES {
+                        return Err(too_many());
+                    }
+                    stack.push(combined);
+                }
+            }
+        }
+
+        Ok(stack
+            .pop()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|set| set.into_iter().collect())
+            .collect())
+    }
+
+    /// Whether the expression combines its licenses with `AND`.
+    pub fn has_conjunctions(&self) -> bool {
+        self.expr
+            .iter()
+            .any(|node| node == &ExprNode::Op(Operator::And))
+    }
+}
+
+/// Returns a license requirement with the current SPDX identifier, e.g.
+/// `GPL-3.0-or-later` instead of `GPL-3.0+`.
+pub fn normalized_req(req: &LicenseReq) -> String {
+    let license = match &req.license {
+        LicenseItem::Spdx { id, or_later } if id.is_gnu() => {
+            let suffix = if *or_later { "-or-later" } else { "-only" };
+            let name = format!("{}{suffix}", id.name);
+            match spdx::license_id(&name) {
+                Some(_) => name,
+                None => req.license.to_string(),
+            }
+        }
+        license => license.to_string(),
+    };
+
+    match req.exception {
+        Some(exception) => format!("{license} WITH {}", exception.name),
+        None => license,
+    }
+}
+
+/// Removes duplicate sets of licenses, keeping the first occurrence of each.
+fn dedup(sets: impl Iterator<Item = BTreeSet<String>>) -> Vec<BTreeSet<String>> {
+    let mut seen = BTreeSet::new();
+    sets.filter(|set| seen.insert(set.clone())).collect()
+}
+
+fn parenthesize_or(expr: String, op: Option<Operator>) -> String {
+    match op {
+        Some(Operator::Or) => format!("({expr})"),
+        _ => expr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LicenseExpression;
+
+    fn normalized(s: &str) -> String {
+        LicenseExpression::parse(s).unwrap().normalized()
+    }
+
+    fn alternatives(s: &str) -> Vec<Vec<String>> {
+        LicenseExpression::parse(s).unwrap().alternatives().unwrap()
+    }
+
+    #[test]
+    fn licenses() {
+        assert_ok!(LicenseExpression::parse("MIT"));
+        assert_ok!(LicenseExpression::parse("MIT OR Apache-2.0"));
+        assert_ok!(LicenseExpression::parse("MIT/Apache-2.0"));
+        assert_ok!(LicenseExpression::parse("MIT AND Apache-2.0"));
+        assert_ok!(LicenseExpression::parse("MIT OR (Apache-2.0 AND MIT)"));
+        assert_ok!(LicenseExpression::parse("GPL-3.0+"));
+
+        let error = assert_err!(LicenseExpression::parse("apache 2.0"));
+        let error = format!("{error}");
+        assert!(error.starts_with("unknown or invalid license expression; see http"));
+    }
+
+    #[test]
+    fn normalized_licenses() {
+        assert_eq!(normalized("MIT"), "MIT");
+        assert_eq!(normalized("MIT/Apache-2.0"), "MIT OR Apache-2.0");
+        assert_eq!(normalized("(MIT OR Apache-2.0)"), "MIT OR Apache-2.0");
+        assert_eq!(
+            normalized("MIT OR (Apache-2.0 AND Zlib)"),
+            "MIT OR Apache-2.0 AND Zlib"
+        );
+        assert_eq!(
+            normalized("(MIT OR Apache-2.0) AND Zlib"),
+            "(MIT OR Apache-2.0) AND Zlib"
+        );
+        assert_eq!(normalized("GPL-3.0+"), "GPL-3.0-or-later");
+        assert_eq!(normalized("GPL-3.0"), "GPL-3.0-only");
+        assert_eq!(normalized("LGPL-2.1-or-later"), "LGPL-2.1-or-later");
+        assert_eq!(normalized("Apache-2.0+"), "Apache-2.0+");
+        assert_eq!(
+            normalized("Apache-2.0 WITH LLVM-exception"),
+            "Apache-2.0 WITH LLVM-exception"
+        );
+        assert_eq!(normalized("LicenseRef-Foo"), "LicenseRef-Foo");
+    }
+
+    #[test]
+    fn license_alternatives() {
+        assert_eq!(alternatives("MIT"), [["MIT"]]);
+        assert_eq!(alternatives("MIT/Apache-2.0"), [["MIT"], ["Apache-2.0"]]);
+        assert_eq!(
+            alternatives("(MIT OR Apache-2.0) AND Zlib"),
+            [["MIT", "Zlib"], ["Apache-2.0", "Zlib"]]
+        );
+        assert_eq!(
+            alternatives("MIT OR (MIT AND Apache-2.0) OR MIT"),
+            vec![vec!["MIT"], vec!["Apache-2.0", "MIT"]]
+        );
+    }
+
+    #[test]
+    fn too_many_license_alternatives() {
+        let licenses = [
+            "MIT",
+            "Apache-2.0",
+            "Zlib",
+            "ISC",
+            "MPL-2.0",
+            "BSL-1.0",
+            "Unlicense",
+        ];
+        let expr = licenses
+            .iter()
+            .map(|license| format!("({license} OR {license}+)"))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let expr = LicenseExpression::parse(&expr).unwrap();
+        let error = assert_err!(expr.alternatives());
+        assert_eq!(
+            error.to_string(),
+            "license expressions may not have more than 64 alternative combinations of licenses"
+        );
+
+        let expr = vec!["MIT"; 33].join(" OR ");
+        let error = assert_err!(LicenseExpression::parse(&expr));
+        assert_eq!(
+            error.to_string(),
+            "license expressions may not contain more than 32 licenses"
+        );
+    }
+}
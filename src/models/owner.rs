@@ -1,11 +1,16 @@
+use diesel::deserialize::{self, FromSql};
 use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Integer;
+use std::io::Write;
+use std::str::FromStr;
 
 use crate::app::App;
 use crate::util::errors::{cargo_err, AppResult};
 
-use crate::models::{Crate, Team, User};
-use crate::schema::{crate_owners, users};
+use crate::models::{Crate, Rights, Team, User};
+use crate::schema::{crate_owners, teams, users};
 use crate::sql::lower;
 
 #[derive(Insertable, Associations, Identifiable, Debug, Clone, Copy)]
@@ -20,6 +25,7 @@ pub struct CrateOwner {
     pub created_by: i32,
     pub owner_kind: i32,
     pub email_notifications: bool,
+    pub role: OwnerRole,
 }
 
 type BoxedQuery<'a> = crate_owners::BoxedQuery<'a, Pg, crate_owners::SqlType>;
@@ -44,6 +50,81 @@ pub enum OwnerKind {
     Team = 1,
 }
 
+/// The role of an owner of a crate, which limits what the owner is allowed to
+/// do with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromSqlRow, AsExpression)]
+#[repr(i32)]
+#[sql_type = "Integer"]
+pub enum OwnerRole {
+    /// Can publish and yank versions, and manage the owners of the crate
+    Admin = 0,
+    /// Can publish and yank versions
+    Publisher = 1,
+    /// Can only yank and unyank versions
+    Yanker = 2,
+}
+
+impl OwnerRole {
+    /// The rights granted by this role.
+    ///
+    /// Team owners never get more than `Rights::Publish`, regardless of their
+    /// role, since the members of a team are not allowed to manage owners.
+    pub fn rights(self, kind: OwnerKind) -> Rights {
+        let rights = match self {
+            OwnerRole::Admin => Rights::Full,
+            OwnerRole::Publisher => Rights::Publish,
+            OwnerRole::Yanker => Rights::Yank,
+        };
+
+        match kind {
+            OwnerKind::User => rights,
+            OwnerKind::Team => rights.min(Rights::Publish),
+        }
+    }
+}
+
+impl From<OwnerRole> for &'static str {
+    fn from(role: OwnerRole) -> Self {
+        match role {
+            OwnerRole::Admin => "admin",
+            OwnerRole::Publisher => "publisher",
+            OwnerRole::Yanker => "yanker",
+        }
+    }
+}
+
+impl FromStr for OwnerRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "admin" => Ok(OwnerRole::Admin),
+            "publisher" => Ok(OwnerRole::Publisher),
+            "yanker" => Ok(OwnerRole::Yanker),
+            _ => Err(format!(
+                "unknown owner role `{s}`; valid roles are admin, publisher and yanker"
+            )),
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for OwnerRole {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(OwnerRole::Admin),
+            1 => Ok(OwnerRole::Publisher),
+            2 => Ok(OwnerRole::Yanker),
+            n => Err(format!("unknown owner role: {n}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for OwnerRole {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
+
 /// Unifies the notion of a User or a Team.
 #[derive(Debug)]
 pub enum Owner {
@@ -79,6 +160,26 @@ impl Owner {
         }
     }
 
+    /// Finds a user or a team that is already known to the database by name,
+    /// without asking GitHub about it.
+    pub fn find_by_login(conn: &PgConnection, name: &str) -> AppResult<Owner> {
+        if name.contains(':') {
+            teams::table
+                .filter(lower(teams::login).eq(name.to_lowercase()))
+                .first(conn)
+                .map(Owner::Team)
+                .map_err(|_| cargo_err(&format_args!("could not find team with login `{name}`")))
+        } else {
+            users::table
+                .filter(lower(users::gh_login).eq(name.to_lowercase()))
+                .filter(users::gh_id.ne(-1))
+                .order(users::gh_id.desc())
+                .first(conn)
+                .map(Owner::User)
+                .map_err(|_| cargo_err(&format_args!("could not find user with login `{name}`")))
+        }
+    }
+
     pub fn kind(&self) -> i32 {
         match *self {
             Owner::User(_) => OwnerKind::User as i32,
@@ -0,0 +1,135 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Integer,
+};
+use std::io::Write;
+
+use crate::schema::publish_uploads;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromSqlRow, AsExpression)]
+#[repr(i32)]
+#[sql_type = "Integer"]
+pub enum PublishState {
+    /// The upload has been stored and is waiting to be finalized.
+    Pending = 0,
+    /// The crate file has been uploaded and the index update has been queued.
+    Published = 1,
+    /// The upload was rejected, the reason is stored in `error`.
+    Failed = 2,
+}
+
+impl From<PublishState> for &'static str {
+    fn from(state: PublishState) -> Self {
+        match state {
+            PublishState::Pending => "pending",
+            PublishState::Published => "published",
+            PublishState::Failed => "failed",
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for PublishState {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(PublishState::Pending),
+            1 => Ok(PublishState::Published),
+            2 => Ok(PublishState::Failed),
+            n => Err(format!("unknown publish state: {n}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for PublishState {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), out)
+    }
+}
+
+/// A version that was uploaded with an asynchronous publish.
+///
+/// The version is recorded in the database right away, while a background job
+/// verifies and uploads the crate file and queues the index update.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable)]
+pub struct PublishUpload {
+    pub id: i32,
+    pub crate_name: String,
+    pub num: String,
+    pub user_id: i32,
+    pub state: PublishState,
+    pub error: Option<String>,
+    pub tarball: Option<Vec<u8>>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl PublishUpload {
+    /// Stores an uploaded crate file, replacing the record of a previous
+    /// failed upload of the same version.
+    pub fn create(
+        conn: &PgConnection,
+        crate_name: &str,
+        num: &str,
+        user_id: i32,
+        tarball: &[u8],
+    ) -> QueryResult<Self> {
+        let values = || {
+            (
+                publish_uploads::crate_name.eq(crate_name),
+                publish_uploads::num.eq(num),
+                publish_uploads::user_id.eq(user_id),
+                publish_uploads::state.eq(PublishState::Pending),
+                publish_uploads::error.eq(None::<String>),
+                publish_uploads::tarball.eq(tarball),
+                publish_uploads::created_at.eq(diesel::dsl::now),
+                publish_uploads::updated_at.eq(diesel::dsl::now),
+            )
+        };
+
+        diesel::insert_into(publish_uploads::table)
+            .values(values())
+            .on_conflict((publish_uploads::crate_name, publish_uploads::num))
+            .do_update()
+            .set(values())
+            .get_result(conn)
+    }
+
+    pub fn find(conn: &PgConnection, id: i32) -> QueryResult<Self> {
+        publish_uploads::table.find(id).first(conn)
+    }
+
+    pub fn for_version(conn: &PgConnection, crate_name: &str, num: &str) -> QueryResult<Self> {
+        publish_uploads::table
+            .filter(publish_uploads::crate_name.eq(crate_name))
+            .filter(publish_uploads::num.eq(num))
+            .first(conn)
+    }
+
+    /// Marks the upload as published and drops the stored crate file.
+    pub fn mark_published(&self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::update(self)
+            .set((
+                publish_uploads::state.eq(PublishState::Published),
+                publish_uploads::tarball.eq(None::<Vec<u8>>),
+                publish_uploads::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Marks the upload as failed and drops the stored crate file.
+    pub fn mark_failed(&self, conn: &PgConnection, error: &str) -> QueryResult<()> {
+        diesel::update(self)
+            .set((
+                publish_uploads::state.eq(PublishState::Failed),
+                publish_uploads::error.eq(error),
+                publish_uploads::tarball.eq(None::<Vec<u8>>),
+                publish_uploads::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+}
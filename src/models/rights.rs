@@ -1,8 +1,9 @@
-/// Access rights to the crate (publishing and ownership management)
+/// Access rights to the crate (yanking, publishing and ownership management)
 /// NOTE: The order of these variants matters!
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
 pub enum Rights {
     None,
+    Yank,
     Publish,
     Full,
 }
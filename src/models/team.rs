@@ -183,6 +183,34 @@ impl Team {
         }
     }
 
+    /// Phones home to Github to ask if this User is a maintainer of the team or
+    /// an owner of its organization, who are allowed to accept ownership
+    /// invitations on behalf of the team.
+    pub fn is_maintained_by(&self, app: &App, user: &User) -> AppResult<bool> {
+        let org_id = match self.org_id {
+            Some(org_id) => org_id,
+            None => return Ok(false),
+        };
+
+        let token = AccessToken::new(user.gh_access_token.clone());
+        let membership =
+            match app
+                .github
+                .team_membership(org_id, self.github_id, &user.gh_login, &token)
+            {
+                Err(ref e) if e.is::<NotFound>() => None,
+                x => Some(x?),
+            };
+
+        if let Some(membership) = membership {
+            if membership.state == "active" && membership.role == "maintainer" {
+                return Ok(true);
+            }
+        }
+
+        is_gh_org_owner(app, org_id, user)
+    }
+
     pub fn owning(krate: &Crate, conn: &PgConnection) -> QueryResult<Vec<Owner>> {
         let base_query = CrateOwner::belonging_to(krate).filter(crate_owners::deleted.eq(false));
         let teams = base_query
@@ -6,7 +6,9 @@ use crate::app::App;
 use crate::email::Emails;
 use crate::util::errors::AppResult;
 
-use crate::models::{ApiToken, Crate, CrateOwner, Email, NewEmail, Owner, OwnerKind, Rights};
+use crate::models::{
+    ApiToken, Crate, CrateOwner, Email, NewEmail, Owner, OwnerKind, OwnerRole, Rights,
+};
 use crate::schema::{crate_owners, emails, users};
 
 /// The model representing a row in the `users` database table.
@@ -20,6 +22,7 @@ pub struct User {
     pub gh_id: i32,
     pub account_lock_reason: Option<String>,
     pub account_lock_until: Option<NaiveDateTime>,
+    pub is_admin: bool,
 }
 
 /// Represents a new user record insertable to the `users` table
@@ -133,29 +136,31 @@ impl User {
         Ok(users.collect())
     }
 
-    /// Given this set of owners, determines the strongest rights the
-    /// user has.
+    /// Given this set of owners and their roles, determines the strongest
+    /// rights the user has.
     ///
-    /// Shortcircuits on `Full` because you can't beat it. In practice we'll always
-    /// see `[user, user, user, ..., team, team, team]`, so we could shortcircuit on
-    /// `Publish` as well, but this is a non-obvious invariant so we don't bother.
-    /// Sweet free optimization if teams are proving burdensome to check.
-    /// More than one team isn't really expected, though.
-    pub fn rights(&self, app: &App, owners: &[Owner]) -> AppResult<Rights> {
+    /// The rights granted by an owner are limited by its role, and team owners
+    /// never grant more than `Publish`. Shortcircuits on `Full` because you
+    /// can't beat it. More than one team isn't really expected, though.
+    pub fn rights(&self, app: &App, owners: &[(Owner, OwnerRole)]) -> AppResult<Rights> {
         let mut best = Rights::None;
-        for owner in owners {
+        for (owner, role) in owners {
             match *owner {
                 Owner::User(ref other_user) => {
                     if other_user.id == self.id {
-                        return Ok(Rights::Full);
+                        best = best.max(role.rights(OwnerKind::User));
                     }
                 }
                 Owner::Team(ref team) => {
-                    if team.contains_user(app, self)? {
-                        best = Rights::Publish;
+                    let rights = role.rights(OwnerKind::Team);
+                    if rights > best && team.contains_user(app, self)? {
+                        best = rights;
                     }
                 }
             }
+            if best == Rights::Full {
+                break;
+            }
         }
         Ok(best)
     }
@@ -5,8 +5,9 @@ use diesel::prelude::*;
 
 use crate::util::errors::{cargo_err, AppResult};
 
-use crate::models::{Crate, Dependency, User};
+use crate::models::{Crate, Dependency, LicenseExpression, User};
 use crate::schema::*;
+use crate::sql::dependency_crate_name;
 
 // Queryable has a custom implementation below
 #[derive(Clone, Identifiable, Associations, Debug, Queryable, Deserialize, Serialize)]
@@ -25,6 +26,8 @@ pub struct Version {
     pub published_by: Option<i32>,
     pub checksum: String,
     pub links: Option<String>,
+    pub staged: bool,
+    pub license_alternatives: Option<serde_json::Value>,
 }
 
 #[derive(Insertable, Debug)]
@@ -38,6 +41,8 @@ pub struct NewVersion {
     published_by: i32,
     checksum: String,
     links: Option<String>,
+    staged: bool,
+    license_alternatives: Option<serde_json::Value>,
 }
 
 /// The highest version (semver order) and the most recently updated version.
@@ -95,25 +100,69 @@ impl TopVersions {
 impl Version {
     /// Returns (dependency, crate dependency name)
     pub fn dependencies(&self, conn: &PgConnection) -> QueryResult<Vec<(Dependency, String)>> {
+        let crate_name =
+            dependency_crate_name(crates::name.nullable(), dependencies::registry_crate_name);
+
         Dependency::belonging_to(self)
-            .inner_join(crates::table)
-            .select((dependencies::all_columns, crates::name))
-            .order((dependencies::optional, crates::name))
+            .left_join(crates::table)
+            .select((dependencies::all_columns, crate_name))
+            .order((dependencies::optional, crate_name))
             .load(conn)
     }
 
+    /// Records that the readme of a version was rendered with the current version of the
+    /// renderer.
     pub fn record_readme_rendering(version_id_: i32, conn: &PgConnection) -> QueryResult<usize> {
         use crate::schema::readme_renderings::dsl::*;
+        use cargo_registry_markdown::RENDERER_VERSION;
         use diesel::dsl::now;
 
         diesel::insert_into(readme_renderings)
-            .values(version_id.eq(version_id_))
+            .values((
+                version_id.eq(version_id_),
+                renderer_version.eq(RENDERER_VERSION),
+            ))
             .on_conflict(version_id)
             .do_update()
-            .set(rendered_at.eq(now))
+            .set((rendered_at.eq(now), renderer_version.eq(RENDERER_VERSION)))
+            .execute(conn)
+    }
+
+    /// Records the outline of the headings of a version's rendered readme.
+    pub fn record_readme_outline(
+        version_id_: i32,
+        outline_: serde_json::Value,
+        conn: &PgConnection,
+    ) -> QueryResult<usize> {
+        use crate::schema::readme_renderings::dsl::*;
+
+        diesel::update(readme_renderings.find(version_id_))
+            .set(outline.eq(outline_))
             .execute(conn)
     }
 
+    /// Deletes a version, and its crate if no other versions remain.
+    pub fn remove(conn: &PgConnection, version_id: i32) -> QueryResult<()> {
+        use diesel::dsl::exists;
+
+        let crate_id = diesel::delete(versions::table.find(version_id))
+            .returning(versions::crate_id)
+            .get_result::<i32>(conn)
+            .optional()?;
+
+        if let Some(crate_id) = crate_id {
+            let has_versions: bool = diesel::select(exists(
+                versions::table.filter(versions::crate_id.eq(crate_id)),
+            ))
+            .get_result(conn)?;
+            if !has_versions {
+                diesel::delete(crates::table.find(crate_id)).execute(conn)?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Gets the User who ran `cargo publish` for this version, if recorded.
     /// Not for use when you have a group of versions you need the publishers for.
     pub fn published_by(&self, conn: &PgConnection) -> Option<User> {
@@ -148,6 +197,8 @@ impl NewVersion {
             published_by,
             checksum,
             links,
+            staged: false,
+            license_alternatives: None,
         };
 
         new_version.validate_license(license_file)?;
@@ -155,6 +206,13 @@ impl NewVersion {
         Ok(new_version)
     }
 
+    /// Marks the version as staged, keeping it out of the index and the API
+    /// until it is released.
+    pub fn staged(mut self, staged: bool) -> Self {
+        self.staged = staged;
+        self
+    }
+
     pub fn save(&self, conn: &PgConnection, published_by_email: &str) -> AppResult<Version> {
         use crate::schema::versions::dsl::*;
         use diesel::dsl::exists;
@@ -186,7 +244,9 @@ impl NewVersion {
 
     fn validate_license(&mut self, license_file: Option<&str>) -> AppResult<()> {
         if let Some(ref license) = self.license {
-            validate_license_expr(license)?;
+            let expression = LicenseExpression::parse(license)?;
+            self.license = Some(expression.normalized());
+            self.license_alternatives = Some(serde_json::to_value(expression.alternatives()?)?);
         } else if license_file.is_some() {
             // If no license is given, but a license file is given, flag this
             // crate as having a nonstandard license. Note that we don't
@@ -197,24 +257,9 @@ impl NewVersion {
     }
 }
 
-fn validate_license_expr(s: &str) -> AppResult<()> {
-    pub const PARSE_MODE: spdx::ParseMode = spdx::ParseMode {
-        allow_lower_case_operators: false,
-        allow_slash_as_or_operator: true,
-        allow_imprecise_license_names: false,
-        allow_postfix_plus_on_gpl: true,
-    };
-
-    spdx::Expression::parse_mode(s, PARSE_MODE).map_err(|_| {
-        cargo_err("unknown or invalid license expression; see http://opensource.org/licenses for options, and http://spdx.org/licenses/ for their identifiers")
-    })?;
-
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
-    use super::{validate_license_expr, TopVersions};
+    use super::TopVersions;
     use chrono::NaiveDateTime;
 
     #[track_caller]
@@ -284,18 +329,4 @@ mod tests {
             }
         );
     }
-
-    #[test]
-    fn licenses() {
-        assert_ok!(validate_license_expr("MIT"));
-        assert_ok!(validate_license_expr("MIT OR Apache-2.0"));
-        assert_ok!(validate_license_expr("MIT/Apache-2.0"));
-        assert_ok!(validate_license_expr("MIT AND Apache-2.0"));
-        assert_ok!(validate_license_expr("MIT OR (Apache-2.0 AND MIT)"));
-        assert_ok!(validate_license_expr("GPL-3.0+"));
-
-        let error = assert_err!(validate_license_expr("apache 2.0"));
-        let error = format!("{error}");
-        assert!(error.starts_with("unknown or invalid license expression; see http"));
-    }
 }
@@ -8,6 +8,10 @@ use crate::schema::{publish_limit_buckets, publish_rate_overrides};
 use crate::sql::{date_part, floor, greatest, interval_part, least};
 use crate::util::errors::{AppResult, TooManyRequests};
 
+/// The `action` of the entries in `publish_rate_overrides` that apply to the
+/// publishing of new crates.
+const PUBLISH_NEW_ACTION: &str = "publish_new";
+
 #[derive(Debug, Clone, Copy)]
 pub struct PublishRateLimit {
     pub rate: Duration,
@@ -71,7 +75,7 @@ impl PublishRateLimit {
         use self::publish_limit_buckets::dsl::*;
 
         let burst: i32 = publish_rate_overrides::table
-            .find(uploader)
+            .find((uploader, PUBLISH_NEW_ACTION))
             .filter(
                 publish_rate_overrides::expires_at
                     .is_null()
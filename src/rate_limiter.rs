@@ -0,0 +1,637 @@
+//! A general-purpose rate limiter for API endpoints.
+//!
+//! Routes are grouped into named classes (see [`LimitedAction`]), each of which
+//! can be configured with its own token bucket. Buckets are keyed by the
+//! authenticated user or API token, or by the IP address for anonymous requests.
+//! The buckets of classes that change data are stored in the database, while
+//! the buckets of read-only classes are kept in the memory of each server
+//! process, so that e.g. searches don't write to the primary database. The
+//! burst of a class can be increased for individual users with an entry in the
+//! `publish_rate_overrides` table.
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::data_types::PgInterval;
+use diesel::prelude::*;
+use diesel::sql_types::Interval;
+use http::{HeaderMap, HeaderValue, Method};
+use moka::sync::{Cache, CacheBuilder};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::env_optional;
+use crate::schema::{api_tokens, publish_rate_overrides, rate_limit_buckets};
+use crate::sql::{date_part, floor, greatest, interval_part, least};
+use crate::util::errors::{AppResult, RateLimited};
+use crate::util::token::{SecureToken, SecureTokenKind};
+
+/// A named class of routes that share a rate limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitedAction {
+    Search,
+    YankUnyank,
+    OwnerChange,
+    CreateToken,
+}
+
+impl LimitedAction {
+    pub const ALL: &'static [Self] = &[
+        Self::Search,
+        Self::YankUnyank,
+        Self::OwnerChange,
+        Self::CreateToken,
+    ];
+
+    /// The name of the class, as stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Search => "search",
+            Self::YankUnyank => "yank_unyank",
+            Self::OwnerChange => "owner_change",
+            Self::CreateToken => "create_token",
+        }
+    }
+
+    /// The prefix of the environment variables configuring the class.
+    fn env_var_prefix(&self) -> &'static str {
+        match self {
+            Self::Search => "RATE_LIMITER_SEARCH",
+            Self::YankUnyank => "RATE_LIMITER_YANK_UNYANK",
+            Self::OwnerChange => "RATE_LIMITER_OWNER_CHANGE",
+            Self::CreateToken => "RATE_LIMITER_CREATE_TOKEN",
+        }
+    }
+
+    /// A description of the limited requests, used in error messages.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Search => "search requests",
+            Self::YankUnyank => "yank or unyank requests",
+            Self::OwnerChange => "owner changes",
+            Self::CreateToken => "new API tokens",
+        }
+    }
+
+    /// Whether the class only contains routes that don't change any data,
+    /// whose buckets are kept in memory instead of the database.
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, Self::Search)
+    }
+
+    /// Returns the class of a route, given its method and route pattern, or
+    /// `None` if the route is not rate limited.
+    pub fn for_route(method: &Method, path: &str) -> Option<Self> {
+        match (method, path) {
+            (&Method::GET, "/api/v1/crates") => Some(Self::Search),
+            (&Method::DELETE, "/api/v1/crates/:crate_id/:version/yank")
+            | (&Method::PUT, "/api/v1/crates/:crate_id/:version/unyank") => Some(Self::YankUnyank),
+            (&Method::PUT | &Method::DELETE, "/api/v1/crates/:crate_id/owners")
+            | (&Method::PUT, "/api/v1/crates/:crate_id/owners/:login/role")
+            | (&Method::DELETE, "/api/v1/crates/:crate_id/owner_invitations/:login")
+            | (
+                &Method::PUT,
+                "/api/v1/crates/:crate_id/owner_invitations/:login/resend"
+                | "/api/v1/crates/:crate_id/owner_invitations/:login/accept"
+                | "/api/v1/crates/:crate_id/owner_invitations/:login/decline",
+            )
+            | (&Method::PUT | &Method::DELETE, "/api/v1/crates/:crate_id/transfer")
+            | (
+                &Method::PUT,
+                "/api/v1/crates/:crate_id/abandon"
+                | "/api/v1/crates/:crate_id/adoption_requests"
+                | "/api/v1/me/crate_transfers/:crate_id/accept"
+                | "/api/v1/me/crate_transfers/:crate_id/decline",
+            ) => Some(Self::OwnerChange),
+            (&Method::PUT, "/api/v1/me/tokens") => Some(Self::CreateToken),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimiterConfig {
+    pub rate: Duration,
+    pub burst: i32,
+}
+
+/// Identifies the client a request is rate limited for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RateLimitKey {
+    User(i32),
+    Token { id: i32, user_id: i32 },
+    Ip(String),
+}
+
+impl RateLimitKey {
+    /// Determines the key of a request from its session user, its
+    /// `Authorization` header or its IP address, in this order.
+    ///
+    /// Requests with an invalid API token are limited by their IP address, and
+    /// will be rejected by the endpoint itself. Anonymous requests without an
+    /// IP address have no key, since they would otherwise all share a bucket.
+    pub fn resolve(
+        session_user_id: Option<i32>,
+        authorization: Option<&str>,
+        ip: Option<&str>,
+        conn: &PgConnection,
+    ) -> QueryResult<Option<Self>> {
+        if let Some(user_id) = session_user_id {
+            return Ok(Some(Self::User(user_id)));
+        }
+
+        if let Some(token) = authorization.and_then(|t| SecureToken::parse(SecureTokenKind::Api, t))
+        {
+            let token = api_tokens::table
+                .filter(api_tokens::revoked.eq(false))
+                .filter(api_tokens::token.eq(&token))
+                .select((api_tokens::id, api_tokens::user_id))
+                .first::<(i32, i32)>(conn)
+                .optional()?;
+
+            if let Some((id, user_id)) = token {
+                return Ok(Some(Self::Token { id, user_id }));
+            }
+        }
+
+        Ok(ip.map(|ip| Self::Ip(ip.to_string())))
+    }
+
+    fn user_id(&self) -> Option<i32> {
+        match *self {
+            Self::User(user_id) | Self::Token { user_id, .. } => Some(user_id),
+            Self::Ip(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for RateLimitKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User(user_id) => write!(f, "user:{user_id}"),
+            Self::Token { id, .. } => write!(f, "token:{id}"),
+            Self::Ip(ip) => write!(f, "ip:{ip}"),
+        }
+    }
+}
+
+/// The state of a bucket after a request was allowed, which is reported to
+/// clients in the `RateLimit-*` response headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    pub limit: i32,
+    pub remaining: i32,
+    pub reset: Duration,
+}
+
+impl RateLimitStatus {
+    pub fn insert_headers(&self, headers: &mut HeaderMap) {
+        headers.insert("ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("ratelimit-reset", HeaderValue::from(self.reset.as_secs()));
+    }
+}
+
+#[derive(Queryable, Debug, PartialEq, Clone)]
+#[allow(dead_code)] // Most fields only read in tests
+struct Bucket {
+    action: String,
+    key: String,
+    tokens: i32,
+    last_refill: NaiveDateTime,
+}
+
+/// The in-memory buckets of a read-only class, by the key of their client.
+type MemoryBuckets = Cache<String, Arc<Mutex<Bucket>>>;
+
+/// The maximum number of in-memory buckets of a read-only class.
+const MAX_MEMORY_BUCKETS: u64 = 100_000;
+
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    config: HashMap<LimitedAction, RateLimiterConfig>,
+    memory_buckets: HashMap<LimitedAction, MemoryBuckets>,
+}
+
+impl RateLimiter {
+    pub fn new(config: HashMap<LimitedAction, RateLimiterConfig>) -> Self {
+        // A bucket that was not used until it was completely refilled is
+        // equivalent to a new one, so it can be dropped
+        let memory_buckets = config
+            .iter()
+            .filter(|(action, _)| action.is_read_only())
+            .map(|(action, config)| {
+                let refill_time = config.rate * config.burst.max(1) as u32;
+                let buckets = CacheBuilder::new(MAX_MEMORY_BUCKETS)
+                    .time_to_idle(refill_time)
+                    .build();
+                (*action, buckets)
+            })
+            .collect();
+
+        Self {
+            config,
+            memory_buckets,
+        }
+    }
+
+    /// Reads the configuration of all classes from the environment.
+    ///
+    /// A class is only limited if both `RATE_LIMITER_<CLASS>_RATE_SECONDS` and
+    /// `RATE_LIMITER_<CLASS>_BURST` are set, e.g. `RATE_LIMITER_SEARCH_BURST`.
+    pub fn from_environment() -> Self {
+        let config = LimitedAction::ALL
+            .iter()
+            .filter_map(|action| {
+                let prefix = action.env_var_prefix();
+                let rate = env_optional(&format!("{prefix}_RATE_SECONDS"))?;
+                let burst = env_optional(&format!("{prefix}_BURST"))?;
+                let config = RateLimiterConfig {
+                    rate: Duration::from_secs(rate),
+                    burst,
+                };
+                Some((*action, config))
+            })
+            .collect();
+
+        Self::new(config)
+    }
+
+    pub fn is_enabled(&self, action: LimitedAction) -> bool {
+        self.config.contains_key(&action)
+    }
+
+    /// Takes a token from the bucket of the given client, and returns an
+    /// error if the bucket is empty.
+    ///
+    /// The connection is only used to look up burst overrides for read-only
+    /// classes, so a read-only connection may be passed for them.
+    pub fn check_rate_limit(
+        &self,
+        action: LimitedAction,
+        key: &RateLimitKey,
+        conn: &PgConnection,
+    ) -> AppResult<Option<RateLimitStatus>> {
+        let config = match self.config.get(&action) {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+
+        let now = Utc::now().naive_utc();
+        let (burst, bucket) = match self.memory_buckets.get(&action) {
+            Some(buckets) => take_memory_token(buckets, action, config, key, now, conn)?,
+            None => take_token(action, config, key, now, conn)?,
+        };
+        let next_refill = bucket.last_refill + chrono::Duration::from_std(config.rate).unwrap();
+
+        if bucket.tokens >= 1 {
+            let reset = (next_refill - now).to_std().unwrap_or_default();
+            Ok(Some(RateLimitStatus {
+                limit: burst,
+                remaining: bucket.tokens - 1,
+                // Round up, so that clients don't retry too early
+                reset: Duration::from_secs(reset.as_secs() + u64::from(reset.subsec_nanos() > 0)),
+            }))
+        } else {
+            Err(Box::new(RateLimited {
+                action,
+                limit: burst,
+                retry_after: next_refill,
+            }))
+        }
+    }
+}
+
+/// Returns the burst of a client, which may be increased by an override.
+fn burst(
+    action: LimitedAction,
+    config: &RateLimiterConfig,
+    key: &RateLimitKey,
+    now: NaiveDateTime,
+    conn: &PgConnection,
+) -> QueryResult<i32> {
+    let burst = match key.user_id() {
+        Some(user_id) => publish_rate_overrides::table
+            .find((user_id, action.as_str()))
+            .filter(
+                publish_rate_overrides::expires_at
+                    .is_null()
+                    .or(publish_rate_overrides::expires_at.gt(now)),
+            )
+            .select(publish_rate_overrides::burst)
+            .first(conn)
+            .optional()?,
+        None => None,
+    }
+    .unwrap_or(config.burst);
+
+    Ok(burst)
+}
+
+/// Refills the bucket of a client as needed, takes a token from it, and
+/// returns the burst of the client together with the result.
+///
+/// See `PublishRateLimit::take_token` for more details on the bucket
+/// semantics.
+fn take_token(
+    action: LimitedAction,
+    config: &RateLimiterConfig,
+    key: &RateLimitKey,
+    now: NaiveDateTime,
+    conn: &PgConnection,
+) -> QueryResult<(i32, Bucket)> {
+    let burst = burst(action, config, key, now, conn)?;
+
+    let refill_rate: PgInterval = {
+        use diesel::dsl::*;
+        (config.rate.as_millis() as i64).milliseconds()
+    };
+
+    let tokens_to_add = floor(
+        (date_part("epoch", now) - date_part("epoch", rate_limit_buckets::last_refill))
+            / interval_part("epoch", refill_rate),
+    );
+
+    let bucket = diesel::insert_into(rate_limit_buckets::table)
+        .values((
+            rate_limit_buckets::action.eq(action.as_str()),
+            rate_limit_buckets::key.eq(key.to_string()),
+            rate_limit_buckets::tokens.eq(burst),
+            rate_limit_buckets::last_refill.eq(now),
+        ))
+        .on_conflict((rate_limit_buckets::action, rate_limit_buckets::key))
+        .do_update()
+        .set((
+            rate_limit_buckets::tokens.eq(least(
+                burst,
+                greatest(0, rate_limit_buckets::tokens - 1) + tokens_to_add,
+            )),
+            rate_limit_buckets::last_refill.eq(rate_limit_buckets::last_refill
+                + refill_rate.into_sql::<Interval>() * tokens_to_add),
+        ))
+        .get_result(conn)?;
+
+    Ok((burst, bucket))
+}
+
+/// Same as `take_token`, but for a bucket that is kept in memory.
+fn take_memory_token(
+    buckets: &MemoryBuckets,
+    action: LimitedAction,
+    config: &RateLimiterConfig,
+    key: &RateLimitKey,
+    now: NaiveDateTime,
+    conn: &PgConnection,
+) -> QueryResult<(i32, Bucket)> {
+    let burst = burst(action, config, key, now, conn)?;
+
+    let mut created = false;
+    let bucket = buckets.get_with(key.to_string(), || {
+        created = true;
+        Arc::new(Mutex::new(Bucket {
+            action: action.as_str().to_string(),
+            key: key.to_string(),
+            tokens: burst,
+            last_refill: now,
+        }))
+    });
+
+    let mut bucket = bucket.lock().unwrap();
+    if !created {
+        let refill_rate = chrono::Duration::from_std(config.rate).unwrap();
+        let elapsed = (now - bucket.last_refill).num_milliseconds();
+        let tokens_to_add = (elapsed / refill_rate.num_milliseconds().max(1)) as i32;
+
+        bucket.tokens = burst.min((bucket.tokens - 1).max(0) + tokens_to_add);
+        bucket.last_refill += refill_rate * tokens_to_add;
+    }
+
+    Ok((burst, bucket.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::Emails;
+    use crate::test_util::*;
+
+    const CONFIG: RateLimiterConfig = RateLimiterConfig {
+        rate: Duration::from_secs(1),
+        burst: 3,
+    };
+
+    #[test]
+    fn route_classes() {
+        use LimitedAction::*;
+
+        let cases = [
+            (Method::GET, "/api/v1/crates", Some(Search)),
+            (Method::GET, "/api/v1/crates/:crate_id", None),
+            (
+                Method::DELETE,
+                "/api/v1/crates/:crate_id/:version/yank",
+                Some(YankUnyank),
+            ),
+            (
+                Method::PUT,
+                "/api/v1/crates/:crate_id/:version/unyank",
+                Some(YankUnyank),
+            ),
+            (Method::GET, "/api/v1/crates/:crate_id/owners", None),
+            (
+                Method::PUT,
+                "/api/v1/crates/:crate_id/owners",
+                Some(OwnerChange),
+            ),
+            (
+                Method::DELETE,
+                "/api/v1/crates/:crate_id/owners",
+                Some(OwnerChange),
+            ),
+            (
+                Method::PUT,
+                "/api/v1/crates/:crate_id/owners/:login/role",
+                Some(OwnerChange),
+            ),
+            (
+                Method::GET,
+                "/api/v1/crates/:crate_id/owner_invitations",
+                None,
+            ),
+            (
+                Method::DELETE,
+                "/api/v1/crates/:crate_id/owner_invitations/:login",
+                Some(OwnerChange),
+            ),
+            (
+                Method::PUT,
+                "/api/v1/crates/:crate_id/owner_invitations/:login/resend",
+                Some(OwnerChange),
+            ),
+            (
+                Method::PUT,
+                "/api/v1/crates/:crate_id/owner_invitations/:login/accept",
+                Some(OwnerChange),
+            ),
+            (
+                Method::DELETE,
+                "/api/v1/crates/:crate_id/transfer",
+                Some(OwnerChange),
+            ),
+            (
+                Method::PUT,
+                "/api/v1/crates/:crate_id/abandon",
+                Some(OwnerChange),
+            ),
+            (
+                Method::PUT,
+                "/api/v1/crates/:crate_id/adoption_requests",
+                Some(OwnerChange),
+            ),
+            (
+                Method::PUT,
+                "/api/v1/me/crate_transfers/:crate_id/accept",
+                Some(OwnerChange),
+            ),
+            (Method::GET, "/api/v1/me/crate_transfers", None),
+            (Method::GET, "/api/v1/me/tokens", None),
+            (Method::PUT, "/api/v1/me/tokens", Some(CreateToken)),
+        ];
+        for (method, path, expected) in cases {
+            assert_eq!(LimitedAction::for_route(&method, path), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn take_token_with_no_bucket_creates_new_one() -> QueryResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let key = RateLimitKey::Ip("127.0.0.1".into());
+        let (burst, bucket) = take_token(LimitedAction::Search, &CONFIG, &key, now, &conn)?;
+        assert_eq!(burst, 3);
+        assert_eq!(bucket.key, "ip:127.0.0.1");
+        assert_eq!(bucket.tokens, 3);
+        assert_eq!(bucket.last_refill, now);
+        Ok(())
+    }
+
+    #[test]
+    fn buckets_are_separate_per_action_and_key() -> QueryResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let key = RateLimitKey::Ip("127.0.0.1".into());
+        let other_key = RateLimitKey::Ip("127.0.0.2".into());
+        take_token(LimitedAction::Search, &CONFIG, &key, now, &conn)?;
+        let (_, bucket) = take_token(LimitedAction::Search, &CONFIG, &key, now, &conn)?;
+        assert_eq!(bucket.tokens, 2);
+
+        let (_, bucket) = take_token(LimitedAction::Search, &CONFIG, &other_key, now, &conn)?;
+        assert_eq!(bucket.tokens, 3);
+        let (_, bucket) = take_token(LimitedAction::CreateToken, &CONFIG, &key, now, &conn)?;
+        assert_eq!(bucket.tokens, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn memory_buckets_are_refilled() -> QueryResult<()> {
+        let conn = pg_connection();
+        let limiter = RateLimiter::new([(LimitedAction::Search, CONFIG)].into());
+        let buckets = &limiter.memory_buckets[&LimitedAction::Search];
+        let take = |key: &RateLimitKey, now| {
+            take_memory_token(buckets, LimitedAction::Search, &CONFIG, key, now, &conn)
+        };
+        let now = now();
+
+        let key = RateLimitKey::Ip("127.0.0.1".into());
+        let (burst, bucket) = take(&key, now)?;
+        assert_eq!(burst, 3);
+        assert_eq!(bucket.key, "ip:127.0.0.1");
+        assert_eq!(bucket.tokens, 3);
+
+        for expected in [2, 1, 0, 0] {
+            assert_eq!(take(&key, now)?.1.tokens, expected);
+        }
+
+        let (_, bucket) = take(&key, now + chrono::Duration::milliseconds(2500))?;
+        assert_eq!(bucket.tokens, 2);
+        assert_eq!(bucket.last_refill, now + chrono::Duration::seconds(2));
+
+        let other_key = RateLimitKey::Ip("127.0.0.2".into());
+        assert_eq!(take(&other_key, now)?.1.tokens, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn check_rate_limit_rejects_empty_bucket() {
+        let conn = pg_connection();
+        let limiter = RateLimiter::new([(LimitedAction::Search, CONFIG)].into());
+        let key = RateLimitKey::Ip("127.0.0.1".into());
+
+        for remaining in (0..3).rev() {
+            let status = limiter
+                .check_rate_limit(LimitedAction::Search, &key, &conn)
+                .unwrap()
+                .unwrap();
+            assert_eq!(status.limit, 3);
+            assert_eq!(status.remaining, remaining);
+        }
+
+        assert!(limiter
+            .check_rate_limit(LimitedAction::Search, &key, &conn)
+            .is_err());
+
+        // Unconfigured actions are not limited
+        assert!(matches!(
+            limiter.check_rate_limit(LimitedAction::CreateToken, &key, &conn),
+            Ok(None)
+        ));
+    }
+
+    #[test]
+    fn overrides_apply_to_users_and_their_tokens() -> QueryResult<()> {
+        let conn = pg_connection();
+        let now = now();
+
+        let user_id = new_user(&conn, "user1")?;
+        diesel::insert_into(publish_rate_overrides::table)
+            .values((
+                publish_rate_overrides::user_id.eq(user_id),
+                publish_rate_overrides::action.eq("search"),
+                publish_rate_overrides::burst.eq(20),
+            ))
+            .execute(&conn)?;
+
+        let key = RateLimitKey::User(user_id);
+        let (burst, _) = take_token(LimitedAction::Search, &CONFIG, &key, now, &conn)?;
+        assert_eq!(burst, 20);
+
+        let key = RateLimitKey::Token { id: 1, user_id };
+        let (burst, _) = take_token(LimitedAction::Search, &CONFIG, &key, now, &conn)?;
+        assert_eq!(burst, 20);
+
+        // Overrides only apply to their own action
+        let (burst, _) = take_token(LimitedAction::CreateToken, &CONFIG, &key, now, &conn)?;
+        assert_eq!(burst, 3);
+        Ok(())
+    }
+
+    fn new_user(conn: &PgConnection, gh_login: &str) -> QueryResult<i32> {
+        use crate::models::NewUser;
+
+        let user = NewUser {
+            gh_login,
+            ..NewUser::default()
+        }
+        .create_or_update(None, &Emails::new_in_memory(), conn)?;
+        Ok(user.id)
+    }
+
+    /// Strips ns precision from `Utc::now`, see `publish_rate_limit::tests::now`.
+    fn now() -> NaiveDateTime {
+        let now = Utc::now().naive_utc();
+        let nanos = now.timestamp_subsec_nanos();
+        now - chrono::Duration::nanoseconds(nanos.into())
+    }
+}
@@ -13,12 +13,52 @@ pub fn build_axum_router(state: AppState) -> Router {
         .route("/api/v1/crates", get(krate::search::search))
         // Routes used by `cargo`
         .route("/api/v1/crates/new", put(krate::publish::publish))
+        .route(
+            "/api/v1/crates/new/batch",
+            put(krate::publish::publish_batch),
+        )
         .route(
             "/api/v1/crates/:crate_id/owners",
             get(krate::owners::owners)
                 .put(krate::owners::add_owners)
                 .delete(krate::owners::remove_owners),
         )
+        .route(
+            "/api/v1/crates/:crate_id/owners/:login/role",
+            put(krate::owners::update_owner_role),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/owner_invitations",
+            get(crate_owner_invitation::list_sent),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/owner_invitations/:login",
+            delete(crate_owner_invitation::revoke),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/owner_invitations/:login/resend",
+            put(crate_owner_invitation::resend),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/owner_invitations/:login/accept",
+            put(crate_owner_invitation::accept_team_invite),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/owner_invitations/:login/decline",
+            put(crate_owner_invitation::decline_team_invite),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/transfer",
+            put(crate_transfer::request).delete(crate_transfer::cancel),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/abandon",
+            put(crate_adoption::abandon),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/adoption_requests",
+            put(crate_adoption::request_adoption),
+        )
         .route(
             "/api/v1/crates/:crate_id/:version/yank",
             delete(version::yank::yank),
@@ -27,6 +67,10 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/crates/:crate_id/:version/unyank",
             put(version::yank::unyank),
         )
+        .route(
+            "/api/v1/crates/:crate_id/:version/release",
+            put(version::release::release).delete(version::release::abandon),
+        )
         .route(
             "/api/v1/crates/:crate_id/:version/download",
             get(version::downloads::download),
@@ -38,15 +82,34 @@ pub fn build_axum_router(state: AppState) -> Router {
             get(version::deprecated::show_by_id),
         )
         // Routes used by the frontend
-        .route("/api/v1/crates/:crate_id", get(krate::metadata::show))
+        .route(
+            "/api/v1/crates/:crate_id",
+            get(krate::metadata::show).delete(krate::delete::delete),
+        )
         .route(
             "/api/v1/crates/:crate_id/:version",
             get(version::metadata::show),
         )
+        .route(
+            "/api/v1/crates/:crate_id/:version/publish_status",
+            get(krate::publish::publish_status),
+        )
         .route(
             "/api/v1/crates/:crate_id/:version/readme",
             get(krate::metadata::readme),
         )
+        .route(
+            "/api/v1/crates/:crate_id/:version/readme/toc",
+            get(krate::metadata::readme_toc),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/:version/changelog",
+            get(krate::metadata::changelog),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/:version/license",
+            get(krate::metadata::license),
+        )
         .route(
             "/api/v1/crates/:crate_id/:version/dependencies",
             get(version::metadata::dependencies),
@@ -115,6 +178,15 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/me/crate_owner_invitations/accept/:token",
             put(crate_owner_invitation::handle_invite_with_token),
         )
+        .route("/api/v1/me/crate_transfers", get(crate_transfer::list))
+        .route(
+            "/api/v1/me/crate_transfers/:crate_id/accept",
+            put(crate_transfer::accept),
+        )
+        .route(
+            "/api/v1/me/crate_transfers/:crate_id/decline",
+            put(crate_transfer::decline),
+        )
         .route(
             "/api/v1/me/email_notifications",
             put(user::me::update_email_notifications),
@@ -132,6 +204,8 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/site_metadata",
             get(site_metadata::show_deployed_sha),
         )
+        // Images of rendered readmes
+        .route("/api/v1/image-proxy", get(image_proxy::proxy_image))
         // Session management
         .route("/api/private/session/begin", get(user::session::begin))
         .route(
@@ -146,6 +220,62 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/private/crate_owner_invitations",
             get(crate_owner_invitation::private_list),
         )
+        // Admin management of the blocked traffic rules
+        .route(
+            "/api/private/admin/blocked_traffic",
+            get(admin::blocked_traffic::list).put(admin::blocked_traffic::create),
+        )
+        .route(
+            "/api/private/admin/blocked_traffic/:id",
+            delete(admin::blocked_traffic::delete),
+        )
+        // Admin management of the crate categories
+        .route(
+            "/api/private/admin/categories",
+            put(admin::categories::create),
+        )
+        .route(
+            "/api/private/admin/categories/:category_id/rename",
+            put(admin::categories::rename),
+        )
+        .route(
+            "/api/private/admin/categories/:category_id/parent",
+            put(admin::categories::reparent),
+        )
+        .route(
+            "/api/private/admin/categories/:category_id/merge",
+            put(admin::categories::merge),
+        )
+        // Admin moderation of keywords
+        .route(
+            "/api/private/admin/keywords/:keyword_id/aliases",
+            put(admin::keywords::add_alias),
+        )
+        .route(
+            "/api/private/admin/keywords/:keyword_id/rename",
+            put(admin::keywords::rename),
+        )
+        .route(
+            "/api/private/admin/keywords/:keyword_id/merge",
+            put(admin::keywords::merge),
+        )
+        .route(
+            "/api/private/admin/keywords/:keyword_id/ban",
+            put(admin::keywords::ban).delete(admin::keywords::unban),
+        )
+        // Admin review of requests to adopt orphaned crates
+        .route(
+            "/api/private/admin/adoption_requests",
+            get(admin::adoption_requests::list),
+        )
+        .route(
+            "/api/private/admin/adoption_requests/:id/approve",
+            put(admin::adoption_requests::approve),
+        )
+        .route(
+            "/api/private/admin/adoption_requests/:id/reject",
+            put(admin::adoption_requests::reject),
+        )
         // Alerts from GitHub scanning for exposed API tokens
         .route(
             "/api/github/secret-scanning/verify",
@@ -141,6 +141,76 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `banned_keywords` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    banned_keywords (keyword) {
+        /// The `keyword` column of the `banned_keywords` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        keyword -> Text,
+        /// The `created_at` column of the `banned_keywords` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `blocked_traffic_rules` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    blocked_traffic_rules (id) {
+        /// The `id` column of the `blocked_traffic_rules` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `ip_range` column of the `blocked_traffic_rules` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        ip_range -> Nullable<Varchar>,
+        /// The `user_agent_pattern` column of the `blocked_traffic_rules` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_agent_pattern -> Nullable<Varchar>,
+        /// The `reason` column of the `blocked_traffic_rules` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reason -> Varchar,
+        /// The `created_at` column of the `blocked_traffic_rules` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `expires_at` column of the `blocked_traffic_rules` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -188,6 +258,123 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `category_redirects` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    category_redirects (slug) {
+        /// The `slug` column of the `category_redirects` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        slug -> Varchar,
+        /// The `category_id` column of the `category_redirects` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        category_id -> Int4,
+        /// The `created_at` column of the `category_redirects` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_adoption_requests` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_adoption_requests (id) {
+        /// The `id` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `user_id` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `reason` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reason -> Varchar,
+        /// The `created_at` column of the `crate_adoption_requests` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_owner_actions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_owner_actions (id) {
+        /// The `id` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `user_id` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `target_user_id` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        target_user_id -> Nullable<Int4>,
+        /// The `action` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        action -> Int4,
+        /// The `time` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        time -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -235,6 +422,41 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_owner_team_invitations` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_owner_team_invitations (invited_team_id, crate_id) {
+        /// The `invited_team_id` column of the `crate_owner_team_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        invited_team_id -> Int4,
+        /// The `crate_id` column of the `crate_owner_team_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `invited_by_user_id` column of the `crate_owner_team_invitations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        invited_by_user_id -> Int4,
+        /// The `created_at` column of the `crate_owner_team_invitations` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -291,6 +513,47 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         email_notifications -> Bool,
+        /// The `role` column of the `crate_owners` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role -> Int4,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `crate_transfer_requests` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_transfer_requests (crate_id) {
+        /// The `crate_id` column of the `crate_transfer_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `from_user_id` column of the `crate_transfer_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        from_user_id -> Int4,
+        /// The `to_user_id` column of the `crate_transfer_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        to_user_id -> Int4,
+        /// The `created_at` column of the `crate_transfer_requests` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
     }
 }
 
@@ -423,6 +686,53 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `deleted_crates` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    deleted_crates (id) {
+        /// The `id` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `name` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `created_at` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `deleted_at` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deleted_at -> Timestamp,
+        /// The `deleted_by` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deleted_by -> Int4,
+        /// The `downloads` column of the `deleted_crates` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        downloads -> Int4,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -445,10 +755,10 @@ table! {
         version_id -> Int4,
         /// The `crate_id` column of the `dependencies` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Nullable<Int4>`.
         ///
         /// (Automatically generated by Diesel.)
-        crate_id -> Int4,
+        crate_id -> Nullable<Int4>,
         /// The `req` column of the `dependencies` table.
         ///
         /// Its SQL type is `Varchar`.
@@ -491,6 +801,18 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         explicit_name -> Nullable<Varchar>,
+        /// The `registry` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        registry -> Nullable<Varchar>,
+        /// The `registry_crate_name` column of the `dependencies` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        registry_crate_name -> Nullable<Varchar>,
     }
 }
 
@@ -564,6 +886,76 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `index_consistency_checks` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    index_consistency_checks (id) {
+        /// The `id` column of the `index_consistency_checks` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `checked_at` column of the `index_consistency_checks` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        checked_at -> Timestamp,
+        /// The `crates_checked` column of the `index_consistency_checks` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crates_checked -> Int4,
+        /// The `git_mismatches` column of the `index_consistency_checks` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        git_mismatches -> Array<Text>,
+        /// The `http_mismatches` column of the `index_consistency_checks` table.
+        ///
+        /// Its SQL type is `Array<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        http_mismatches -> Array<Text>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `keyword_aliases` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    keyword_aliases (alias) {
+        /// The `alias` column of the `keyword_aliases` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        alias -> Text,
+        /// The `keyword_id` column of the `keyword_aliases` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        keyword_id -> Int4,
+        /// The `created_at` column of the `keyword_aliases` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -616,6 +1008,70 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `proxied_image_failures` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    proxied_image_failures (url) {
+        /// The `url` column of the `proxied_image_failures` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        url -> Text,
+        /// The `error` column of the `proxied_image_failures` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        error -> Text,
+        /// The `failed_at` column of the `proxied_image_failures` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        failed_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `proxied_images` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    proxied_images (url) {
+        /// The `url` column of the `proxied_images` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        url -> Text,
+        /// The `digest` column of the `proxied_images` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        digest -> Text,
+        /// The `content_type` column of the `proxied_images` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        content_type -> Varchar,
+        /// The `cached_at` column of the `proxied_images` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        cached_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -652,7 +1108,7 @@ table! {
     /// Representation of the `publish_rate_overrides` table.
     ///
     /// (Automatically generated by Diesel.)
-    publish_rate_overrides (user_id) {
+    publish_rate_overrides (user_id, action) {
         /// The `user_id` column of the `publish_rate_overrides` table.
         ///
         /// Its SQL type is `Int4`.
@@ -671,6 +1127,112 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         expires_at -> Nullable<Timestamp>,
+        /// The `action` column of the `publish_rate_overrides` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        action -> Varchar,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `publish_uploads` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    publish_uploads (id) {
+        /// The `id` column of the `publish_uploads` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_name` column of the `publish_uploads` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_name -> Varchar,
+        /// The `num` column of the `publish_uploads` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        num -> Varchar,
+        /// The `user_id` column of the `publish_uploads` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `state` column of the `publish_uploads` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        state -> Int4,
+        /// The `error` column of the `publish_uploads` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        error -> Nullable<Varchar>,
+        /// The `tarball` column of the `publish_uploads` table.
+        ///
+        /// Its SQL type is `Nullable<Bytea>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        tarball -> Nullable<Bytea>,
+        /// The `created_at` column of the `publish_uploads` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `publish_uploads` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+
+    /// Representation of the `rate_limit_buckets` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    rate_limit_buckets (action, key) {
+        /// The `action` column of the `rate_limit_buckets` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        action -> Varchar,
+        /// The `key` column of the `rate_limit_buckets` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        key -> Varchar,
+        /// The `tokens` column of the `rate_limit_buckets` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        tokens -> Int4,
+        /// The `last_refill` column of the `rate_limit_buckets` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_refill -> Timestamp,
     }
 }
 
@@ -694,6 +1256,18 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         rendered_at -> Timestamp,
+        /// The `outline` column of the `readme_renderings` table.
+        ///
+        /// Its SQL type is `Nullable<Jsonb>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        outline -> Nullable<Jsonb>,
+        /// The `renderer_version` column of the `readme_renderings` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        renderer_version -> Int4,
     }
 }
 
@@ -729,6 +1303,12 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         name -> Text,
+        /// The `expires_at` column of the `reserved_crate_names` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Nullable<Timestamp>,
     }
 }
 
@@ -835,6 +1415,12 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         account_lock_until -> Nullable<Timestamp>,
+        /// The `is_admin` column of the `users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_admin -> Bool,
     }
 }
 
@@ -1012,6 +1598,18 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         links -> Nullable<Varchar>,
+        /// The `staged` column of the `versions` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        staged -> Bool,
+        /// The `license_alternatives` column of the `versions` table.
+        ///
+        /// Its SQL type is `Nullable<Jsonb>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        license_alternatives -> Nullable<Jsonb>,
     }
 }
 
@@ -1040,21 +1638,32 @@ table! {
 
 joinable!(api_tokens -> users (user_id));
 joinable!(badges -> crates (crate_id));
+joinable!(category_redirects -> categories (category_id));
+joinable!(crate_adoption_requests -> crates (crate_id));
+joinable!(crate_adoption_requests -> users (user_id));
+joinable!(crate_owner_actions -> crates (crate_id));
 joinable!(crate_owner_invitations -> crates (crate_id));
+joinable!(crate_owner_team_invitations -> crates (crate_id));
+joinable!(crate_owner_team_invitations -> teams (invited_team_id));
+joinable!(crate_owner_team_invitations -> users (invited_by_user_id));
 joinable!(crate_owners -> crates (crate_id));
 joinable!(crate_owners -> teams (owner_id));
 joinable!(crate_owners -> users (owner_id));
+joinable!(crate_transfer_requests -> crates (crate_id));
 joinable!(crates_categories -> categories (category_id));
 joinable!(crates_categories -> crates (crate_id));
 joinable!(crates_keywords -> crates (crate_id));
 joinable!(crates_keywords -> keywords (keyword_id));
+joinable!(deleted_crates -> users (deleted_by));
 joinable!(dependencies -> crates (crate_id));
 joinable!(dependencies -> versions (version_id));
 joinable!(emails -> users (user_id));
 joinable!(follows -> crates (crate_id));
 joinable!(follows -> users (user_id));
+joinable!(keyword_aliases -> keywords (keyword_id));
 joinable!(publish_limit_buckets -> users (user_id));
 joinable!(publish_rate_overrides -> users (user_id));
+joinable!(publish_uploads -> users (user_id));
 joinable!(readme_renderings -> versions (version_id));
 joinable!(recent_crate_downloads -> crates (crate_id));
 joinable!(version_downloads -> versions (version_id));
@@ -1069,19 +1678,33 @@ allow_tables_to_appear_in_same_query!(
     api_tokens,
     background_jobs,
     badges,
+    banned_keywords,
+    blocked_traffic_rules,
     categories,
+    category_redirects,
+    crate_adoption_requests,
+    crate_owner_actions,
     crate_owner_invitations,
+    crate_owner_team_invitations,
     crate_owners,
+    crate_transfer_requests,
     crates,
     crates_categories,
     crates_keywords,
+    deleted_crates,
     dependencies,
     emails,
     follows,
+    index_consistency_checks,
+    keyword_aliases,
     keywords,
     metadata,
+    proxied_image_failures,
+    proxied_images,
     publish_limit_buckets,
     publish_rate_overrides,
+    publish_uploads,
+    rate_limit_buckets,
     readme_renderings,
     recent_crate_downloads,
     reserved_crate_names,
@@ -1,9 +1,10 @@
-use diesel::sql_types::{Array, Date, Double, Interval, Text, Timestamp};
+use diesel::sql_types::{Array, Date, Double, Integer, Interval, Nullable, Text, Timestamp};
 
 sql_function!(#[aggregate] fn array_agg<T>(x: T) -> Array<T>);
 sql_function!(fn canon_crate_name(x: Text) -> Text);
 sql_function!(fn to_char(a: Date, b: Text) -> Text);
 sql_function!(fn lower(x: Text) -> Text);
+sql_function!(fn random_string(len: Integer) -> Text);
 sql_function!(fn date_part(x: Text, y: Timestamp) -> Double);
 sql_function! {
     #[sql_name = "date_part"]
@@ -12,3 +13,11 @@ sql_function! {
 sql_function!(fn floor(x: Double) -> Integer);
 sql_function!(fn greatest<T>(x: T, y: T) -> T);
 sql_function!(fn least<T>(x: T, y: T) -> T);
+sql_function! {
+    /// Returns the name of the crate a dependency refers to, which is either a
+    /// crate of this registry or the `registry_crate_name` of a dependency on
+    /// another registry. A database constraint ensures that one of them is set.
+    #[sql_name = "coalesce"]
+    fn dependency_crate_name(x: Nullable<Text>, y: Nullable<Text>) -> Text;
+}
+no_arg_sql_function!(random, Double, "Represents the SQL `random()` function");
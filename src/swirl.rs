@@ -1,5 +1,5 @@
 mod runner;
-mod storage;
+pub(crate) mod storage;
 
 pub mod errors;
 
@@ -123,11 +123,12 @@ impl Runner {
         // FIXME: https://github.com/sfackler/r2d2/pull/70
         let connection_pool = AssertUnwindSafe(self.connection_pool().clone());
         self.get_single_job(sender, move |job| {
+            let job_id = job.id;
             let job = Job::from_value(&job.job_type, job.data)?;
 
             // Make sure to move the whole `AssertUnwindSafe`
             let connection_pool = connection_pool;
-            job.perform(&environment, &connection_pool.0)
+            job.perform(job_id, &environment, &connection_pool.0)
         })
     }
 
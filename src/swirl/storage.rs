@@ -1,3 +1,4 @@
+use chrono::NaiveDateTime;
 use diesel::dsl::now;
 use diesel::pg::Pg;
 use diesel::prelude::*;
@@ -7,10 +8,10 @@ use diesel::{delete, update};
 use crate::schema::{self, background_jobs};
 
 #[derive(Queryable, Identifiable, Debug, Clone)]
-pub(super) struct BackgroundJob {
-    pub(super) id: i64,
-    pub(super) job_type: String,
-    pub(super) data: serde_json::Value,
+pub(crate) struct BackgroundJob {
+    pub(crate) id: i64,
+    pub(crate) job_type: String,
+    pub(crate) data: serde_json::Value,
 }
 
 fn retriable() -> Box<dyn BoxableExpression<background_jobs::table, Pg, SqlType = Bool>> {
@@ -36,6 +37,46 @@ pub(super) fn find_next_unlocked_job(conn: &PgConnection) -> QueryResult<Backgro
         .first::<BackgroundJob>(conn)
 }
 
+/// Finds other pending jobs of the given types that were enqueued no later
+/// than `created_before`, and locks them.
+///
+/// Jobs that are already locked by another worker, or that have failed at
+/// least once, are skipped.
+pub(crate) fn find_pending_jobs_of_types(
+    conn: &PgConnection,
+    types: &[&str],
+    exclude_id: i64,
+    created_before: NaiveDateTime,
+    limit: i64,
+) -> QueryResult<Vec<BackgroundJob>> {
+    use schema::background_jobs::dsl::*;
+
+    background_jobs
+        .select((id, job_type, data))
+        .filter(job_type.eq_any(types))
+        .filter(id.ne(exclude_id))
+        .filter(retries.eq(0))
+        .filter(created_at.le(created_before))
+        .order(id)
+        .limit(limit)
+        .for_update()
+        .skip_locked()
+        .load(conn)
+}
+
+/// Loads a job together with the time at which it was enqueued
+pub(crate) fn find_job(
+    conn: &PgConnection,
+    job_id: i64,
+) -> QueryResult<(BackgroundJob, NaiveDateTime)> {
+    use schema::background_jobs::dsl::*;
+
+    background_jobs
+        .find(job_id)
+        .select(((id, job_type, data), created_at))
+        .first(conn)
+}
+
 /// The number of jobs that have failed at least once
 pub(super) fn failed_job_count(conn: &PgConnection) -> QueryResult<i64> {
     use schema::background_jobs::dsl::*;
@@ -47,7 +88,7 @@ pub(super) fn failed_job_count(conn: &PgConnection) -> QueryResult<i64> {
 }
 
 /// Deletes a job that has successfully completed running
-pub(super) fn delete_successful_job(conn: &PgConnection, job_id: i64) -> QueryResult<()> {
+pub(crate) fn delete_successful_job(conn: &PgConnection, job_id: i64) -> QueryResult<()> {
     use schema::background_jobs::dsl::*;
 
     delete(background_jobs.find(job_id)).execute(conn)?;
@@ -58,7 +99,7 @@ pub(super) fn delete_successful_job(conn: &PgConnection, job_id: i64) -> QueryRe
 ///
 /// Ignores any database errors that may have occurred. If the DB has gone away,
 /// we assume that just trying again with a new connection will succeed.
-pub(super) fn update_failed_job(conn: &PgConnection, job_id: i64) {
+pub(crate) fn update_failed_job(conn: &PgConnection, job_id: i64) {
     use schema::background_jobs::dsl::*;
 
     let _ = update(background_jobs.find(job_id))
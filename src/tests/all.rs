@@ -13,7 +13,7 @@ extern crate tracing;
 
 use crate::util::{RequestHelper, TestApp};
 use cargo_registry::{
-    models::{Crate, CrateOwner, NewCategory, NewTeam, NewUser, Team, User},
+    models::{Crate, CrateOwner, NewCategory, NewTeam, NewUser, OwnerRole, Team, User},
     schema::crate_owners,
     views::{
         EncodableCategory, EncodableCategoryWithSubcategories, EncodableCrate, EncodableKeyword,
@@ -30,15 +30,22 @@ use diesel::prelude::*;
 mod account_lock;
 mod authentication;
 mod blocked_routes;
+mod blocked_traffic_rules;
 mod builders;
 mod categories;
+mod category_management;
+mod crate_transfers;
 mod dump_db;
 mod github_secret_scanning;
+mod image_proxy;
+mod keyword_moderation;
 mod krate;
 mod middleware;
+mod mirror;
 mod not_found_error;
 mod owners;
 mod pagination;
+mod rate_limiter;
 mod read_only_mode;
 mod record;
 mod routes;
@@ -139,6 +146,7 @@ fn add_team_to_crate(t: &Team, krate: &Crate, u: &User, conn: &PgConnection) ->
         created_by: u.id,
         owner_kind: 1, // Team owner kind is 1 according to owner.rs
         email_notifications: true,
+        role: OwnerRole::Admin,
     };
 
     diesel::insert_into(crate_owners::table)
@@ -0,0 +1,126 @@
+use crate::util::{MockCookieUser, RequestHelper, TestApp};
+use cargo_registry::models::NewBlockedTrafficRule;
+use chrono::{Duration, Utc};
+use http::{header, Method, StatusCode};
+use serde_json::Value;
+
+const URL: &str = "/api/private/admin/blocked_traffic";
+
+fn make_admin(app: &TestApp, user: &MockCookieUser) {
+    use cargo_registry::schema::users;
+    use diesel::prelude::*;
+
+    app.db(|conn| {
+        diesel::update(users::table.find(user.as_model().id))
+            .set(users::is_admin.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+}
+
+fn get_as(user: &impl RequestHelper, user_agent: &str) -> StatusCode {
+    let mut request = user.request_builder(Method::GET, "/api/v1/summary");
+    request.header(header::USER_AGENT, user_agent);
+    user.run::<()>(request).status()
+}
+
+#[test]
+fn only_admins_can_manage_rules() {
+    let (_, anon, user) = TestApp::init().with_user();
+    let body = br#"{ "rule": { "user_agent_pattern": "bot", "reason": "abuse" } }"#;
+
+    assert_eq!(anon.get::<()>(URL).status(), StatusCode::FORBIDDEN);
+    assert_eq!(user.get::<()>(URL).status(), StatusCode::FORBIDDEN);
+    assert_eq!(user.put::<()>(URL, body).status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        user.delete::<()>(&format!("{URL}/1")).status(),
+        StatusCode::FORBIDDEN
+    );
+}
+
+#[test]
+fn admins_can_add_and_remove_rules() {
+    let (app, anon, user) = TestApp::init().with_user();
+    make_admin(&app, &user);
+
+    assert_eq!(get_as(&anon, "bad-bot/1.0"), StatusCode::OK);
+
+    let body = br#"{ "rule": { "user_agent_pattern": "^bad-bot/", "reason": "abuse" } }"#;
+    let json = user.put::<Value>(URL, body).good();
+    let id = json["rule"]["id"].as_i64().unwrap();
+    assert_eq!(json["rule"]["user_agent_pattern"], "^bad-bot/");
+
+    let json = user.get::<Value>(URL).good();
+    assert_eq!(json["rules"].as_array().unwrap().len(), 1);
+
+    assert_eq!(get_as(&anon, "bad-bot/1.0"), StatusCode::FORBIDDEN);
+    assert_eq!(get_as(&anon, "good-bot/1.0"), StatusCode::OK);
+
+    let metrics = &app.as_inner().instance_metrics;
+    let hits = metrics
+        .blocked_traffic_rule_hits_total
+        .with_label_values(&[&id.to_string()])
+        .get();
+    assert_eq!(hits, 1);
+
+    user.delete::<Value>(&format!("{URL}/{id}")).good();
+    assert_eq!(get_as(&anon, "bad-bot/1.0"), StatusCode::OK);
+
+    let response = user.delete::<()>(&format!("{URL}/{id}"));
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn invalid_rules_are_rejected() {
+    let (app, _, user) = TestApp::init().with_user();
+    make_admin(&app, &user);
+
+    for body in [
+        r#"{ "rule": { "reason": "no matcher" } }"#,
+        r#"{ "rule": { "ip_range": "10.0.0.0/8", "reason": "too broad" } }"#,
+        r#"{ "rule": { "ip_range": "not an ip", "reason": "invalid" } }"#,
+        r#"{ "rule": { "user_agent_pattern": "(", "reason": "invalid" } }"#,
+    ] {
+        let response = user.put::<()>(URL, body.as_bytes());
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{body}");
+    }
+
+    let json = user.get::<Value>(URL).good();
+    assert_eq!(json["rules"], json!([]));
+}
+
+#[test]
+fn rules_match_on_ip_ranges() {
+    let (app, anon) = TestApp::init().empty();
+
+    app.db(|conn| {
+        NewBlockedTrafficRule {
+            ip_range: Some("127.0.0.0/24"),
+            user_agent_pattern: None,
+            reason: "abuse",
+            expires_at: None,
+        }
+        .save(conn)
+        .unwrap();
+    });
+
+    assert_eq!(get_as(&anon, "cargo"), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn expired_rules_are_ignored() {
+    let (app, anon) = TestApp::init().empty();
+
+    app.db(|conn| {
+        NewBlockedTrafficRule {
+            ip_range: None,
+            user_agent_pattern: Some("bad-bot"),
+            reason: "abuse",
+            expires_at: Some((Utc::now() - Duration::hours(1)).naive_utc()),
+        }
+        .save(conn)
+        .unwrap();
+    });
+
+    assert_eq!(get_as(&anon, "bad-bot/1.0"), StatusCode::OK);
+}
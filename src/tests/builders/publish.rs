@@ -24,6 +24,7 @@ fn generate_empty_tarball() -> Vec<u8> {
 /// a crate to exist and don't need to test behavior caused by the publish request, inserting
 /// a crate into the database directly by using CrateBuilder will be faster.
 pub struct PublishBuilder {
+    badges: BTreeMap<String, BTreeMap<String, String>>,
     categories: Vec<String>,
     deps: Vec<u::EncodableCrateDependency>,
     desc: Option<String>,
@@ -43,6 +44,7 @@ impl PublishBuilder {
     /// in its tarball.
     pub fn new(krate_name: &str) -> Self {
         PublishBuilder {
+            badges: BTreeMap::new(),
             categories: vec![],
             deps: vec![],
             desc: Some("description".to_string()),
@@ -148,6 +150,22 @@ impl PublishBuilder {
         self
     }
 
+    /// Add a badge with the given attributes to this crate.
+    pub fn badge(mut self, badge_type: &str, attributes: &[(&str, &str)]) -> Self {
+        let attributes = attributes
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        self.badges.insert(badge_type.into(), attributes);
+        self
+    }
+
+    /// Set the license expression of this crate
+    pub fn license(mut self, license: &str) -> Self {
+        self.license = Some(license.into());
+        self
+    }
+
     /// Remove the license from this crate. Publish will fail unless license or license file is set.
     pub fn unset_license(mut self) -> Self {
         self.license = None;
@@ -195,6 +213,7 @@ impl PublishBuilder {
             license_file: self.license_file,
             repository: None,
             links: None,
+            badges: self.badges,
         };
 
         (serde_json::to_string(&new_crate).unwrap(), self.tarball)
@@ -1,3 +1,4 @@
+use cargo_registry::models::Category;
 use cargo_registry::schema::categories;
 
 use diesel::*;
@@ -53,35 +54,72 @@ fn select_slugs(conn: &PgConnection) -> Vec<String> {
         .unwrap()
 }
 
+fn seed(toml: &str, conn: &PgConnection) {
+    ::cargo_registry::boot::categories::seed_with_connection(toml, conn).unwrap();
+}
+
 #[test]
-fn sync_adds_new_categories() {
+fn seed_adds_new_categories() {
     let conn = pg_connection();
 
-    ::cargo_registry::boot::categories::sync_with_connection(ALGORITHMS_AND_SUCH, &conn).unwrap();
+    seed(ALGORITHMS_AND_SUCH, &conn);
 
     let categories = select_slugs(&conn);
     assert_eq!(categories, vec!["algorithms", "algorithms::such"]);
 }
 
 #[test]
-fn sync_removes_missing_categories() {
+fn seed_keeps_missing_categories() {
     let conn = pg_connection();
 
-    ::cargo_registry::boot::categories::sync_with_connection(ALGORITHMS_AND_SUCH, &conn).unwrap();
-    ::cargo_registry::boot::categories::sync_with_connection(ALGORITHMS, &conn).unwrap();
+    seed(ALGORITHMS_AND_SUCH, &conn);
+    seed(ALGORITHMS, &conn);
 
     let categories = select_slugs(&conn);
-    assert_eq!(categories, vec!["algorithms"]);
+    assert_eq!(categories, vec!["algorithms", "algorithms::such"]);
 }
 
 #[test]
-fn sync_adds_and_removes() {
+fn seed_adds_missing_categories() {
     let conn = pg_connection();
 
-    ::cargo_registry::boot::categories::sync_with_connection(ALGORITHMS_AND_SUCH, &conn).unwrap();
-    ::cargo_registry::boot::categories::sync_with_connection(ALGORITHMS_AND_ANOTHER, &conn)
+    seed(ALGORITHMS_AND_SUCH, &conn);
+    seed(ALGORITHMS_AND_ANOTHER, &conn);
+
+    let categories = select_slugs(&conn);
+    assert_eq!(
+        categories,
+        vec!["algorithms", "algorithms::such", "another"]
+    );
+}
+
+#[test]
+fn seed_does_not_update_existing_categories() {
+    let conn = pg_connection();
+
+    seed(ALGORITHMS, &conn);
+    diesel::update(categories::table)
+        .set(categories::description.eq("Changed by an admin"))
+        .execute(&conn)
+        .unwrap();
+    seed(ALGORITHMS, &conn);
+
+    let description: String = categories::table
+        .select(categories::description)
+        .first(&conn)
         .unwrap();
+    assert_eq!(description, "Changed by an admin");
+}
+
+#[test]
+fn seed_does_not_add_renamed_categories_again() {
+    let conn = pg_connection();
+
+    seed(ALGORITHMS_AND_SUCH, &conn);
+    let category: Category = Category::by_slug("algorithms").first(&conn).unwrap();
+    category.rename(&conn, "algos", "Algos").unwrap();
+    seed(ALGORITHMS_AND_SUCH, &conn);
 
     let categories = select_slugs(&conn);
-    assert_eq!(categories, vec!["algorithms", "another"]);
+    assert_eq!(categories, vec!["algos", "algos::such"]);
 }
@@ -0,0 +1,184 @@
+use crate::builders::CrateBuilder;
+use crate::util::{MockCookieUser, RequestHelper, Response, TestApp};
+use cargo_registry::models::Category;
+use http::StatusCode;
+use serde_json::Value;
+
+const URL: &str = "/api/private/admin/categories";
+
+fn make_admin(app: &TestApp, user: &MockCookieUser) {
+    use cargo_registry::schema::users;
+    use diesel::prelude::*;
+
+    app.db(|conn| {
+        diesel::update(users::table.find(user.as_model().id))
+            .set(users::is_admin.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+}
+
+fn create(user: &MockCookieUser, slug: &str, name: &str) -> Value {
+    let body = json!({ "category": { "slug": slug, "name": name } });
+    user.put::<Value>(URL, body.to_string().as_bytes()).good()
+}
+
+fn error(response: Response<Value>) -> String {
+    assert_eq!(response.status(), StatusCode::OK);
+    response.into_json()["errors"][0]["detail"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn only_admins_can_manage_categories() {
+    let (_, anon, user) = TestApp::init().with_user();
+    let body = br#"{ "category": { "slug": "foo", "name": "Foo" } }"#;
+
+    assert_eq!(anon.put::<()>(URL, body).status(), StatusCode::FORBIDDEN);
+    assert_eq!(user.put::<()>(URL, body).status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        user.put::<()>(&format!("{URL}/foo/merge"), br#"{ "into": "bar" }"#)
+            .status(),
+        StatusCode::FORBIDDEN
+    );
+}
+
+#[test]
+fn renamed_categories_redirect_from_their_old_slugs() {
+    let (app, anon, user) = TestApp::init().with_user();
+    make_admin(&app, &user);
+
+    let json = create(&user, "tools", "Tools");
+    assert_eq!(json["category"]["slug"], "tools");
+    let json = create(&user, "tools::testing", "Testing");
+    assert_eq!(json["category"]["slug"], "tools::testing");
+    assert_eq!(json["category"]["category"], "Testing");
+
+    let krate = app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id)
+            .category("tools::testing")
+            .expect_build(conn)
+    });
+
+    let body = br#"{ "slug": "development-tools", "name": "Development tools" }"#;
+    let json = user
+        .put::<Value>(&format!("{URL}/tools/rename"), body)
+        .good();
+    assert_eq!(json["category"]["slug"], "development-tools");
+
+    anon.get::<()>("/api/v1/categories/tools::testing")
+        .assert_redirect_ends_with("/api/v1/categories/development-tools::testing");
+    let json = anon.show_category("development-tools::testing");
+    assert_eq!(json.category.category, "Testing");
+    assert_eq!(json.category.crates_cnt, 1);
+    assert_eq!(json.category.parent_categories[0].slug, "development-tools");
+
+    let body = br#"{ "parent": null }"#;
+    let json = user
+        .put::<Value>(&format!("{URL}/development-tools::testing/parent"), body)
+        .good();
+    assert_eq!(json["category"]["slug"], "testing");
+
+    // Crates that are published with an old slug stay in the category
+    anon.get::<()>("/api/v1/categories/tools::testing")
+        .assert_redirect_ends_with("/api/v1/categories/testing");
+    let invalid = app.db(|conn| Category::update_crate(conn, &krate, &["tools::testing"]).unwrap());
+    assert!(invalid.is_empty());
+    assert_eq!(anon.show_category("testing").category.crates_cnt, 1);
+
+    let body = br#"{ "parent": "testing" }"#;
+    assert_eq!(
+        error(user.put(&format!("{URL}/testing/parent"), body)),
+        "category `testing` cannot be moved below itself"
+    );
+    assert_eq!(
+        error(user.put(
+            URL,
+            br#"{ "category": { "slug": "testing", "name": "Testing" } }"#
+        )),
+        "category `testing` already exists"
+    );
+    assert_eq!(
+        error(user.put(URL, br#"{ "category": { "slug": "Not Valid", "name": "Foo" } }"#)),
+        "invalid category slug `Not Valid`; slugs may only contain lowercase letters, digits, `-` and `_`"
+    );
+    assert_eq!(
+        error(user.put(
+            URL,
+            br#"{ "category": { "slug": "missing::foo", "name": "Foo" } }"#
+        )),
+        "category `missing` does not exist"
+    );
+}
+
+#[test]
+fn merged_categories_move_their_crates() {
+    let (app, anon, user) = TestApp::init().with_user();
+    make_admin(&app, &user);
+
+    create(&user, "async", "Async");
+    create(&user, "asynchronous", "Asynchronous");
+    create(&user, "asynchronous::runtimes", "Runtimes");
+
+    app.db(|conn| {
+        let user_id = user.as_model().id;
+        CrateBuilder::new("both", user_id)
+            .category("async")
+            .category("asynchronous")
+            .expect_build(conn);
+        CrateBuilder::new("only_async", user_id)
+            .category("async")
+            .expect_build(conn);
+        CrateBuilder::new("only_asynchronous", user_id)
+            .category("asynchronous")
+            .expect_build(conn);
+    });
+
+    assert_eq!(
+        error(user.put(
+            &format!("{URL}/asynchronous/merge"),
+            br#"{ "into": "async" }"#
+        )),
+        "category `asynchronous` has subcategories, which need to be merged or moved first"
+    );
+    assert_eq!(
+        error(user.put(&format!("{URL}/async/merge"), br#"{ "into": "async" }"#)),
+        "a category cannot be merged into itself"
+    );
+
+    let json = user
+        .put::<Value>(
+            &format!("{URL}/async/merge"),
+            br#"{ "into": "asynchronous" }"#,
+        )
+        .good();
+    assert_eq!(json["category"]["crates_cnt"], 3);
+
+    anon.get::<()>("/api/v1/categories/async")
+        .assert_redirect_ends_with("/api/v1/categories/asynchronous");
+    assert_eq!(anon.show_category("asynchronous").category.crates_cnt, 3);
+    assert_eq!(anon.search("category=asynchronous").meta.total, 3);
+
+    // A new category can take over the old slug
+    create(&user, "async", "Async");
+    assert_eq!(anon.show_category("async").category.crates_cnt, 0);
+}
+
+#[test]
+fn renaming_leaves_categories_with_similar_slugs_alone() {
+    let (app, anon, user) = TestApp::init().with_user();
+    make_admin(&app, &user);
+
+    create(&user, "a_b", "A B");
+    create(&user, "a_b::c", "C");
+    create(&user, "axb", "Axb");
+    create(&user, "axb::c", "C");
+
+    let body = br#"{ "slug": "d", "name": "D" }"#;
+    user.put::<Value>(&format!("{URL}/a_b/rename"), body).good();
+
+    assert_eq!(anon.show_category("d::c").category.slug, "d::c");
+    assert_eq!(anon.show_category("axb::c").category.slug, "axb::c");
+}
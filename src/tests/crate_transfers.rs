@@ -0,0 +1,309 @@
+use crate::builders::CrateBuilder;
+use crate::util::{MockCookieUser, RequestHelper, TestApp};
+use cargo_registry::models::{Crate, CrateAction, CrateOwnerAction, OwnerRole};
+use diesel::prelude::*;
+use http::StatusCode;
+use serde_json::Value;
+
+fn make_admin(app: &TestApp, user: &MockCookieUser) {
+    use cargo_registry::schema::users;
+
+    app.db(|conn| {
+        diesel::update(users::table.find(user.as_model().id))
+            .set(users::is_admin.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+}
+
+fn audit_actions(app: &TestApp, crate_name: &str) -> Vec<CrateAction> {
+    app.db(|conn| {
+        let krate: Crate = Crate::by_name(crate_name).first(conn).unwrap();
+        CrateOwnerAction::by_crate(conn, &krate)
+            .unwrap()
+            .into_iter()
+            .map(|action| action.action)
+            .collect()
+    })
+}
+
+fn owner_logins(app: &TestApp, crate_name: &str) -> Vec<String> {
+    app.db(|conn| {
+        let krate: Crate = Crate::by_name(crate_name).first(conn).unwrap();
+        krate
+            .owners(conn)
+            .unwrap()
+            .iter()
+            .map(|owner| owner.login().to_string())
+            .collect()
+    })
+}
+
+fn email_subjects(app: &TestApp) -> Vec<String> {
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    emails.into_iter().map(|email| email.subject).collect()
+}
+
+#[test]
+fn accepted_transfer_replaces_all_owners() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let recipient = app.db_new_user("recipient");
+    let other = app.db_new_user("other");
+    let krate =
+        app.db(|conn| CrateBuilder::new("transferred", owner.as_model().id).expect_build(conn));
+    app.db(|conn| {
+        use cargo_registry::models::{CrateOwner, OwnerKind};
+        use cargo_registry::schema::crate_owners;
+
+        diesel::insert_into(crate_owners::table)
+            .values(&CrateOwner {
+                crate_id: krate.id,
+                owner_id: other.as_model().id,
+                created_by: owner.as_model().id,
+                owner_kind: OwnerKind::User as i32,
+                email_notifications: true,
+                role: OwnerRole::Publisher,
+            })
+            .execute(conn)
+            .unwrap();
+    });
+
+    let body = br#"{ "to": "recipient" }"#;
+    let json = owner
+        .put::<Value>("/api/v1/crates/transferred/transfer", body)
+        .good();
+    assert_eq!(
+        json["msg"],
+        "a request to transfer crate transferred to recipient has been sent"
+    );
+
+    let json = recipient.get::<Value>("/api/v1/me/crate_transfers").good();
+    let transfers = json["crate_transfers"].as_array().unwrap();
+    assert_eq!(transfers.len(), 1);
+    assert_eq!(transfers[0]["crate_name"], "transferred");
+    assert_eq!(transfers[0]["requested_by_username"], "foo");
+
+    // Only the recipient can accept the transfer
+    let url = format!("/api/v1/me/crate_transfers/{}/accept", krate.id);
+    assert_eq!(other.put::<()>(&url, &[]).status(), StatusCode::NOT_FOUND);
+
+    let json = recipient.put::<Value>(&url, &[]).good();
+    assert_eq!(json["crate_transfer"]["accepted"], true);
+
+    assert_eq!(owner_logins(&app, "transferred"), vec!["recipient"]);
+    assert_eq!(
+        audit_actions(&app, "transferred"),
+        vec![CrateAction::TransferRequest, CrateAction::TransferAccept]
+    );
+    assert_eq!(
+        email_subjects(&app),
+        vec!["Crate transfer request", "Crate transfer accepted"]
+    );
+
+    let json = recipient.get::<Value>("/api/v1/me/crate_transfers").good();
+    assert_eq!(json["crate_transfers"].as_array().unwrap().len(), 0);
+}
+
+#[test]
+fn declined_and_cancelled_transfers() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let recipient = app.db_new_user("recipient");
+    let krate = app.db(|conn| CrateBuilder::new("kept", owner.as_model().id).expect_build(conn));
+
+    let body = br#"{ "to": "recipient" }"#;
+    owner
+        .put::<Value>("/api/v1/crates/kept/transfer", body)
+        .good();
+    let url = format!("/api/v1/me/crate_transfers/{}/decline", krate.id);
+    let json = recipient.put::<Value>(&url, &[]).good();
+    assert_eq!(json["crate_transfer"]["accepted"], false);
+    assert_eq!(owner_logins(&app, "kept"), vec!["foo"]);
+
+    owner
+        .put::<Value>("/api/v1/crates/kept/transfer", body)
+        .good();
+    owner.delete::<Value>("/api/v1/crates/kept/transfer").good();
+    let response = owner.delete::<()>("/api/v1/crates/kept/transfer");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let url = format!("/api/v1/me/crate_transfers/{}/accept", krate.id);
+    assert_eq!(
+        recipient.put::<()>(&url, &[]).status(),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(owner_logins(&app, "kept"), vec!["foo"]);
+
+    assert_eq!(
+        audit_actions(&app, "kept"),
+        vec![
+            CrateAction::TransferRequest,
+            CrateAction::TransferDecline,
+            CrateAction::TransferRequest,
+            CrateAction::TransferCancel,
+        ]
+    );
+}
+
+#[test]
+fn transfers_from_demoted_owners_cannot_be_accepted() {
+    use cargo_registry::schema::crate_owners;
+
+    let (app, _, owner) = TestApp::init().with_user();
+    let recipient = app.db_new_user("recipient");
+    let krate = app.db(|conn| CrateBuilder::new("demoted", owner.as_model().id).expect_build(conn));
+
+    let body = br#"{ "to": "recipient" }"#;
+    owner
+        .put::<Value>("/api/v1/crates/demoted/transfer", body)
+        .good();
+
+    app.db(|conn| {
+        diesel::update(crate_owners::table.filter(crate_owners::crate_id.eq(krate.id)))
+            .set(crate_owners::role.eq(OwnerRole::Publisher))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let url = format!("/api/v1/me/crate_transfers/{}/accept", krate.id);
+    let response = recipient.put::<()>(&url, &[]);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the request to transfer the crate `demoted` is no longer valid, because its sender is no longer an admin owner of the crate" }] })
+    );
+    assert_eq!(owner_logins(&app, "demoted"), vec!["foo"]);
+}
+
+#[test]
+fn transfers_require_an_admin_owner() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let recipient = app.db_new_user("recipient");
+    app.db(|conn| CrateBuilder::new("not_yours", owner.as_model().id).expect_build(conn));
+
+    let body = br#"{ "to": "foo" }"#;
+    let response = recipient.put::<()>("/api/v1/crates/not_yours/transfer", body);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = owner.put::<()>("/api/v1/crates/not_yours/transfer", body);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "cannot transfer a crate to yourself" }] })
+    );
+}
+
+#[test]
+fn abandoned_crates_can_be_adopted() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let adopter = app.db_new_user("adopter");
+    let admin = app.db_new_user("admin");
+    make_admin(&app, &admin);
+    app.db(|conn| CrateBuilder::new("orphan", owner.as_model().id).expect_build(conn));
+
+    let body = br#"{ "reason": "I would like to maintain it" }"#;
+    let response = adopter.put::<()>("/api/v1/crates/orphan/adoption_requests", body);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "crate orphan is not orphaned, please contact its owners instead" }] })
+    );
+
+    owner
+        .put::<Value>("/api/v1/crates/orphan/abandon", &[])
+        .good();
+    assert!(owner_logins(&app, "orphan").is_empty());
+
+    adopter
+        .put::<Value>("/api/v1/crates/orphan/adoption_requests", body)
+        .good();
+    let response = adopter.put::<()>("/api/v1/crates/orphan/adoption_requests", body);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "you have already requested to adopt crate orphan" }] })
+    );
+
+    let url = "/api/private/admin/adoption_requests";
+    assert_eq!(adopter.get::<()>(url).status(), StatusCode::FORBIDDEN);
+    let json = admin.get::<Value>(url).good();
+    let requests = json["adoption_requests"].as_array().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["crate_name"], "orphan");
+    assert_eq!(requests[0]["requested_by_username"], "adopter");
+    assert_eq!(requests[0]["reason"], "I would like to maintain it");
+
+    let approve_url = format!("{url}/{}/approve", requests[0]["id"]);
+    admin.put::<Value>(&approve_url, &[]).good();
+
+    assert_eq!(owner_logins(&app, "orphan"), vec!["adopter"]);
+    assert_eq!(
+        admin.get::<Value>(url).good()["adoption_requests"],
+        json!([])
+    );
+    assert_eq!(
+        audit_actions(&app, "orphan"),
+        vec![
+            CrateAction::Abandon,
+            CrateAction::AdoptionRequest,
+            CrateAction::AdoptionApprove,
+        ]
+    );
+    assert_eq!(email_subjects(&app), vec!["Crate adoption request"]);
+}
+
+#[test]
+fn crates_with_other_owners_cannot_be_abandoned() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+    let krate = app.db(|conn| CrateBuilder::new("shared", owner.as_model().id).expect_build(conn));
+    app.db(|conn| {
+        use cargo_registry::models::{CrateOwner, OwnerKind};
+        use cargo_registry::schema::crate_owners;
+
+        diesel::insert_into(crate_owners::table)
+            .values(&CrateOwner {
+                crate_id: krate.id,
+                owner_id: other.as_model().id,
+                created_by: owner.as_model().id,
+                owner_kind: OwnerKind::User as i32,
+                email_notifications: true,
+                role: OwnerRole::Admin,
+            })
+            .execute(conn)
+            .unwrap();
+    });
+
+    let response = owner.put::<()>("/api/v1/crates/shared/abandon", &[]);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "crate shared has other owners, who have to be removed before it can be abandoned" }] })
+    );
+    assert_eq!(owner_logins(&app, "shared"), vec!["foo", "other"]);
+    assert!(audit_actions(&app, "shared").is_empty());
+}
+
+#[test]
+fn adoption_requests_can_be_rejected() {
+    let (app, _, owner) = TestApp::init().with_user();
+    let adopter = app.db_new_user("adopter");
+    let admin = app.db_new_user("admin");
+    make_admin(&app, &admin);
+    app.db(|conn| CrateBuilder::new("unwanted", owner.as_model().id).expect_build(conn));
+
+    owner
+        .put::<Value>("/api/v1/crates/unwanted/abandon", &[])
+        .good();
+    let body = br#"{ "reason": "squatting" }"#;
+    adopter
+        .put::<Value>("/api/v1/crates/unwanted/adoption_requests", body)
+        .good();
+
+    let url = "/api/private/admin/adoption_requests";
+    let json = admin.get::<Value>(url).good();
+    let reject_url = format!("{url}/{}/reject", json["adoption_requests"][0]["id"]);
+    admin.put::<Value>(&reject_url, &[]).good();
+    assert_eq!(
+        admin.put::<()>(&reject_url, &[]).status(),
+        StatusCode::NOT_FOUND
+    );
+
+    assert!(owner_logins(&app, "unwanted").is_empty());
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    assert!(emails.last().unwrap().body.contains("has been rejected"));
+}
@@ -0,0 +1,268 @@
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::config::{ImageProxyConfig, MirrorConfig};
+use cargo_registry::schema::{proxied_image_failures, proxied_images};
+use cargo_registry_markdown::ImageProxy;
+use diesel::prelude::*;
+use hex::ToHex;
+use http::StatusCode;
+use sha2::{Digest, Sha256};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use url::Url;
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n not really a png";
+
+/// A local HTTP server that answers every request with the same response.
+struct StubServer {
+    url: Url,
+    requests: Arc<AtomicUsize>,
+}
+
+impl StubServer {
+    fn new(content_type: &'static str, body: &'static [u8]) -> Self {
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        Self::serve(head, body)
+    }
+
+    /// A server that redirects every request to the given URL.
+    fn redirect(location: &Url) -> Self {
+        let head = format!(
+            "HTTP/1.1 302 Found\r\nLocation: {location}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        );
+        Self::serve(head, b"")
+    }
+
+    fn serve(head: String, body: &'static [u8]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/logo.png", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let counter = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { return };
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                    line.clear();
+                }
+
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(body);
+            }
+        });
+
+        Self {
+            url: Url::parse(&url).unwrap(),
+            requests,
+        }
+    }
+
+    fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+fn image_proxy_config() -> ImageProxyConfig {
+    let base_url = Url::parse("https://crates.io/api/v1/image-proxy").unwrap();
+    let mut config = ImageProxyConfig::new(base_url, b"secret");
+    config.allow_local_hosts = true;
+    config
+}
+
+/// Returns the query of the signed proxy URL of an image.
+fn proxy_query(config: &ImageProxyConfig, url: &Url) -> String {
+    let proxy_url = Url::parse(&config.proxy_url(url)).unwrap();
+    proxy_url.query().unwrap().to_string()
+}
+
+#[test]
+fn images_are_cached_and_redirected_to() {
+    let config = image_proxy_config();
+    let (app, anon) = TestApp::with_proxy()
+        .with_config(|server| server.image_proxy = Some(config.clone()))
+        .empty();
+    let server = StubServer::new("image/png", PNG);
+    let query = proxy_query(&config, &server.url);
+    let digest: String = Sha256::digest(PNG).encode_hex();
+
+    let response = anon.get_with_query::<()>("/api/v1/image-proxy", &query);
+    assert_eq!(response.status(), StatusCode::FOUND);
+    response.assert_redirect_ends_with(&format!("/image-proxy/{digest}"));
+    assert_eq!(server.requests(), 1);
+
+    let (cached_digest, content_type): (String, String) = app.db(|conn| {
+        proxied_images::table
+            .find(server.url.as_str())
+            .select((proxied_images::digest, proxied_images::content_type))
+            .first(conn)
+            .unwrap()
+    });
+    assert_eq!(cached_digest, digest);
+    assert_eq!(content_type, "image/png");
+
+    // The cached image is served without fetching it again.
+    let response = anon.get_with_query::<()>("/api/v1/image-proxy", &query);
+    assert_eq!(response.status(), StatusCode::FOUND);
+    response.assert_redirect_ends_with(&format!("/image-proxy/{digest}"));
+    assert_eq!(server.requests(), 1);
+}
+
+#[test]
+fn unsigned_images_are_forbidden() {
+    let config = image_proxy_config();
+    let (_, anon) = TestApp::init()
+        .with_config(|server| server.image_proxy = Some(config.clone()))
+        .empty();
+    let server = StubServer::new("image/png", PNG);
+
+    let other_key = ImageProxyConfig::new(config.base_url, b"other secret");
+    let query = proxy_query(&other_key, &server.url);
+    anon.get_with_query::<()>("/api/v1/image-proxy", &query)
+        .assert_forbidden();
+
+    let query = format!("url={}", server.url);
+    anon.get_with_query::<()>("/api/v1/image-proxy", &query)
+        .assert_forbidden();
+
+    assert_eq!(server.requests(), 0);
+}
+
+#[test]
+fn image_proxy_is_disabled_by_default() {
+    let (_, anon) = TestApp::init().empty();
+    let server = StubServer::new("image/png", PNG);
+
+    let query = proxy_query(&image_proxy_config(), &server.url);
+    anon.get_with_query::<()>("/api/v1/image-proxy", &query)
+        .assert_not_found();
+}
+
+#[test]
+fn invalid_images_are_rejected() {
+    let mut config = image_proxy_config();
+    config.max_size = PNG.len() as u64 - 1;
+    let (app, anon) = TestApp::init()
+        .with_config(|server| server.image_proxy = Some(config.clone()))
+        .empty();
+
+    let server = StubServer::new("text/html; charset=utf-8", b"<script></script>");
+    let query = proxy_query(&config, &server.url);
+    let response = anon.get_with_query::<()>("/api/v1/image-proxy", &query);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "unsupported image content type `text/html`" }] })
+    );
+
+    let server = StubServer::new("image/png", PNG);
+    let query = proxy_query(&config, &server.url);
+    let response = anon.get_with_query::<()>("/api/v1/image-proxy", &query);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": format!("images may not exceed {} bytes", PNG.len() - 1) }] })
+    );
+
+    let count: i64 = app.db(|conn| proxied_images::table.count().get_result(conn).unwrap());
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn failed_fetches_are_cached() {
+    let config = image_proxy_config();
+    let (app, anon) = TestApp::init()
+        .with_config(|server| server.image_proxy = Some(config.clone()))
+        .empty();
+    let server = StubServer::new("text/html", b"<html></html>");
+    let query = proxy_query(&config, &server.url);
+    let expected =
+        json!({ "errors": [{ "detail": "unsupported image content type `text/html`" }] });
+
+    let response = anon.get_with_query::<()>("/api/v1/image-proxy", &query);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.into_json(), expected);
+    assert_eq!(server.requests(), 1);
+
+    // The failure is returned without fetching the image again.
+    let response = anon.get_with_query::<()>("/api/v1/image-proxy", &query);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.into_json(), expected);
+    assert_eq!(server.requests(), 1);
+
+    // The image is fetched again once the failure expired.
+    app.db(|conn| {
+        use diesel::dsl::{now, IntervalDsl};
+        diesel::update(proxied_image_failures::table)
+            .set(proxied_image_failures::failed_at.eq(now - 2.hours()))
+            .execute(conn)
+            .unwrap();
+    });
+    let response = anon.get_with_query::<()>("/api/v1/image-proxy", &query);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(server.requests(), 2);
+}
+
+#[test]
+fn image_proxy_is_disabled_on_mirrors() {
+    let config = image_proxy_config();
+    let (_, anon) = TestApp::init()
+        .with_config(|server| {
+            server.image_proxy = Some(config.clone());
+            server.mirror = Some(MirrorConfig {
+                upstream_index: Url::parse("https://github.com/rust-lang/crates.io-index").unwrap(),
+            });
+        })
+        .empty();
+    let server = StubServer::new("image/png", PNG);
+
+    let query = proxy_query(&config, &server.url);
+    anon.get_with_query::<()>("/api/v1/image-proxy", &query)
+        .assert_not_found();
+    assert_eq!(server.requests(), 0);
+}
+
+#[test]
+fn images_from_local_hosts_are_rejected() {
+    let mut config = image_proxy_config();
+    config.allow_local_hosts = false;
+    let (_, anon) = TestApp::init()
+        .with_config(|server| server.image_proxy = Some(config.clone()))
+        .empty();
+    let server = StubServer::new("image/png", PNG);
+
+    let query = proxy_query(&config, &server.url);
+    let response = anon.get_with_query::<()>("/api/v1/image-proxy", &query);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "images cannot be fetched from this URL" }] })
+    );
+    assert_eq!(server.requests(), 0);
+}
+
+#[test]
+fn redirects_are_followed() {
+    let config = image_proxy_config();
+    let (_, anon) = TestApp::with_proxy()
+        .with_config(|server| server.image_proxy = Some(config.clone()))
+        .empty();
+    let server = StubServer::new("image/png", PNG);
+    let redirect = StubServer::redirect(&server.url);
+    let digest: String = Sha256::digest(PNG).encode_hex();
+
+    let query = proxy_query(&config, &redirect.url);
+    let response = anon.get_with_query::<()>("/api/v1/image-proxy", &query);
+    assert_eq!(response.status(), StatusCode::FOUND);
+    response.assert_redirect_ends_with(&format!("/image-proxy/{digest}"));
+    assert_eq!(redirect.requests(), 1);
+    assert_eq!(server.requests(), 1);
+}
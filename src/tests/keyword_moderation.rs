@@ -0,0 +1,192 @@
+use crate::builders::{CrateBuilder, PublishBuilder};
+use crate::util::{MockCookieUser, RequestHelper, Response, TestApp};
+use cargo_registry::views::EncodableKeyword;
+use http::StatusCode;
+use serde_json::Value;
+
+const URL: &str = "/api/private/admin/keywords";
+
+#[derive(Deserialize)]
+struct GoodKeyword {
+    keyword: EncodableKeyword,
+}
+
+fn make_admin(app: &TestApp, user: &MockCookieUser) {
+    use cargo_registry::schema::users;
+    use diesel::prelude::*;
+
+    app.db(|conn| {
+        diesel::update(users::table.find(user.as_model().id))
+            .set(users::is_admin.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+}
+
+fn error(response: Response<Value>) -> String {
+    assert_eq!(response.status(), StatusCode::OK);
+    response.into_json()["errors"][0]["detail"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+fn crates_cnt(anon: &impl RequestHelper, keyword: &str) -> i32 {
+    let json: GoodKeyword = anon.get(&format!("/api/v1/keywords/{keyword}")).good();
+    json.keyword.crates_cnt
+}
+
+#[test]
+fn only_admins_can_moderate_keywords() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id)
+            .keyword("async")
+            .expect_build(conn);
+    });
+
+    let body = br#"{ "alias": "asynchronous" }"#;
+    let url = format!("{URL}/async/aliases");
+    assert_eq!(anon.put::<()>(&url, body).status(), StatusCode::FORBIDDEN);
+    assert_eq!(user.put::<()>(&url, body).status(), StatusCode::FORBIDDEN);
+    let url = format!("{URL}/async/ban");
+    assert_eq!(user.put::<()>(&url, b"").status(), StatusCode::FORBIDDEN);
+    assert_eq!(user.delete::<()>(&url).status(), StatusCode::FORBIDDEN);
+    assert_eq!(crates_cnt(&anon, "async"), 1);
+}
+
+#[test]
+fn aliases_are_replaced_when_publishing() {
+    let (app, anon, user, token) = TestApp::full().with_token();
+    make_admin(&app, &user);
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id)
+            .keyword("async")
+            .expect_build(conn);
+    });
+
+    let body = br#"{ "alias": "Asynchronous" }"#;
+    let json = user
+        .put::<Value>(&format!("{URL}/async/aliases"), body)
+        .good();
+    assert_eq!(json["keyword"]["keyword"], "async");
+
+    let crate_to_publish = PublishBuilder::new("bar")
+        .keyword("asynchronous")
+        .keyword("async");
+    token.publish_crate(crate_to_publish).good();
+
+    let keywords = anon.show_crate("bar").keywords.unwrap();
+    assert_eq!(keywords.len(), 1);
+    assert_eq!(keywords[0].keyword, "async");
+    assert_eq!(crates_cnt(&anon, "async"), 2);
+    anon.get::<()>("/api/v1/keywords/asynchronous")
+        .assert_redirect_ends_with("/api/v1/keywords/async");
+
+    assert_eq!(
+        error(user.put(&format!("{URL}/async/aliases"), br#"{ "alias": "async" }"#)),
+        "a keyword cannot be an alias of itself"
+    );
+    assert_eq!(
+        error(user.put(&format!("{URL}/async/aliases"), br#"{ "alias": "not valid" }"#)),
+        "invalid keyword `not valid`; keywords must start with a letter or digit and may only contain letters, digits, `_`, `-` and `+`"
+    );
+}
+
+#[test]
+fn merged_and_renamed_keywords_redirect() {
+    let (app, anon, user) = TestApp::init().with_user();
+    make_admin(&app, &user);
+    app.db(|conn| {
+        let user_id = user.as_model().id;
+        CrateBuilder::new("both", user_id)
+            .keyword("async")
+            .keyword("async-await")
+            .expect_build(conn);
+        CrateBuilder::new("only_async", user_id)
+            .keyword("async")
+            .expect_build(conn);
+        CrateBuilder::new("only_async_await", user_id)
+            .keyword("async-await")
+            .expect_build(conn);
+    });
+
+    assert_eq!(
+        error(user.put(
+            &format!("{URL}/async/aliases"),
+            br#"{ "alias": "async-await" }"#
+        )),
+        "keyword `async-await` already exists and needs to be merged into `async` instead"
+    );
+    assert_eq!(
+        error(user.put(&format!("{URL}/async/merge"), br#"{ "into": "async" }"#)),
+        "a keyword cannot be merged into itself"
+    );
+
+    let json = user
+        .put::<Value>(
+            &format!("{URL}/async-await/merge"),
+            br#"{ "into": "async" }"#,
+        )
+        .good();
+    assert_eq!(json["keyword"]["crates_cnt"], 3);
+    anon.get::<()>("/api/v1/keywords/async-await")
+        .assert_redirect_ends_with("/api/v1/keywords/async");
+
+    let json = user
+        .put::<Value>(
+            &format!("{URL}/async/rename"),
+            br#"{ "keyword": "asynchronous" }"#,
+        )
+        .good();
+    assert_eq!(json["keyword"]["keyword"], "asynchronous");
+    assert_eq!(crates_cnt(&anon, "asynchronous"), 3);
+
+    // The aliases of the former name move with the keyword
+    anon.get::<()>("/api/v1/keywords/async")
+        .assert_redirect_ends_with("/api/v1/keywords/asynchronous");
+    anon.get::<()>("/api/v1/keywords/async-await")
+        .assert_redirect_ends_with("/api/v1/keywords/asynchronous");
+    anon.get::<()>("/api/v1/keywords/unknown")
+        .assert_not_found();
+}
+
+#[test]
+fn banned_keywords_are_ignored() {
+    let (app, anon, user, token) = TestApp::full().with_token();
+    make_admin(&app, &user);
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id)
+            .keyword("spam")
+            .keyword("async")
+            .expect_build(conn);
+    });
+
+    let json = user.put::<Value>(&format!("{URL}/spam/ban"), b"").good();
+    assert_eq!(json["crates_cnt"], 1);
+    anon.get::<()>("/api/v1/keywords/spam").assert_not_found();
+    assert_eq!(anon.show_crate("foo").keywords.unwrap().len(), 1);
+
+    let crate_to_publish = PublishBuilder::new("bar").keyword("SPAM").keyword("async");
+    let json = token.publish_crate(crate_to_publish).good();
+    assert_eq!(
+        json.warnings.other,
+        vec!["the keyword `spam` is not allowed and was ignored"]
+    );
+    let keywords = anon.show_crate("bar").keywords.unwrap();
+    assert_eq!(keywords.len(), 1);
+    assert_eq!(keywords[0].keyword, "async");
+
+    assert_eq!(
+        error(user.put(&format!("{URL}/async/rename"), br#"{ "keyword": "spam" }"#)),
+        "keyword `spam` is banned"
+    );
+
+    user.delete::<Value>(&format!("{URL}/spam/ban")).good();
+    user.delete::<()>(&format!("{URL}/spam/ban"))
+        .assert_not_found();
+    let crate_to_publish = PublishBuilder::new("bar").version("1.1.0").keyword("spam");
+    let json = token.publish_crate(crate_to_publish).good();
+    assert!(json.warnings.other.is_empty());
+    assert_eq!(crates_cnt(&anon, "spam"), 1);
+}
@@ -0,0 +1,93 @@
+use crate::builders::{CrateBuilder, PublishBuilder, VersionBuilder};
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::models::{Crate, DeletedCrate};
+use cargo_registry::schema::crates;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use http::StatusCode;
+use serde_json::Value;
+
+#[test]
+fn delete_removes_crate_and_reserves_name() {
+    let (app, anon, user, token) = TestApp::full().with_token();
+
+    token.publish_crate(PublishBuilder::new("deleted")).good();
+    assert_eq!(app.crates_from_index_head("deleted").len(), 1);
+
+    let response = anon.delete::<()>("/api/v1/crates/deleted");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    user.delete::<Value>("/api/v1/crates/deleted").good();
+    app.run_pending_background_jobs();
+
+    let response = anon.get::<()>("/api/v1/crates/deleted");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_err!(app.upstream_index().crates_from_index_head("deleted"));
+
+    let deleted = app.db(|conn| DeletedCrate::by_name(conn, "deleted").unwrap());
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].deleted_by, user.as_model().id);
+
+    let response = token.publish_crate(PublishBuilder::new("deleted"));
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "cannot upload a crate with a reserved name" }] })
+    );
+}
+
+#[test]
+fn delete_is_only_allowed_for_young_unpopular_crates_without_dependents() {
+    let (app, _, user) = TestApp::full().with_user();
+    let user_id = user.as_model().id;
+
+    let (old, dependency) = app.db(|conn| {
+        let old = CrateBuilder::new("old", user_id).expect_build(conn);
+        diesel::update(crates::table.find(old.id))
+            .set(crates::created_at.eq(Utc::now().naive_utc() - Duration::days(4)))
+            .execute(conn)
+            .unwrap();
+
+        CrateBuilder::new("popular", user_id)
+            .downloads(1000)
+            .expect_build(conn);
+
+        let dependency = CrateBuilder::new("dependency", user_id).expect_build(conn);
+        CrateBuilder::new("dependent", user_id)
+            .version(VersionBuilder::new("1.0.0").dependency(&dependency, None))
+            .expect_build(conn);
+
+        (old, dependency)
+    });
+
+    let assert_error = |name: &str, detail: &str| {
+        let response = user.delete::<()>(&format!("/api/v1/crates/{name}"));
+        assert_eq!(
+            response.into_json(),
+            json!({ "errors": [{ "detail": detail }] })
+        );
+    };
+    assert_error(
+        "old",
+        "only crates published less than 72 hours ago can be deleted",
+    );
+    assert_error(
+        "popular",
+        "only crates with at most 500 downloads can be deleted",
+    );
+    assert_error(
+        "dependency",
+        "crates that other crates depend on cannot be deleted",
+    );
+
+    let other = app.db_new_user("other");
+    let response = other.delete::<()>("/api/v1/crates/dependent");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    user.delete::<Value>("/api/v1/crates/dependent").good();
+    user.delete::<Value>("/api/v1/crates/dependency").good();
+
+    app.db(|conn| {
+        assert_ok!(Crate::by_name(&old.name).first::<Crate>(conn));
+        assert_err!(Crate::by_name(&dependency.name).first::<Crate>(conn));
+    });
+}
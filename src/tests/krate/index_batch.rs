@@ -0,0 +1,111 @@
+use crate::builders::PublishBuilder;
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::schema::background_jobs;
+use cargo_registry::swirl::errors::FailedJobsError;
+use cargo_registry::views::GoodCrate;
+use cargo_registry::worker;
+use diesel::prelude::*;
+use std::time::Duration;
+
+#[test]
+fn index_updates_are_pushed_in_a_single_commit() {
+    let (app, _, _, token) = TestApp::full()
+        .with_index_batch(Duration::ZERO, 100)
+        .with_token();
+
+    // Publish without running background jobs, so that the index jobs pile up
+    token
+        .put::<GoodCrate>("/api/v1/crates/new", &PublishBuilder::new("foo").body())
+        .good();
+    token
+        .put::<GoodCrate>("/api/v1/crates/new", &PublishBuilder::new("bar").body())
+        .good();
+    token
+        .put::<GoodCrate>(
+            "/api/v1/crates/new",
+            &PublishBuilder::new("foo").version("1.1.0").body(),
+        )
+        .good();
+
+    app.run_pending_background_jobs();
+
+    let foo_versions = app.crates_from_index_head("foo");
+    assert_eq!(foo_versions.len(), 2);
+    assert_eq!(foo_versions[0].vers, "1.0.0");
+    assert_eq!(foo_versions[1].vers, "1.1.0");
+
+    let bar_versions = app.crates_from_index_head("bar");
+    assert_eq!(bar_versions.len(), 1);
+
+    let repo = &app.upstream_index().repository;
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(
+        head.message().unwrap(),
+        "Updating 3 index entries\n\n\
+         Updating crate `foo#1.0.0`\n\
+         Updating crate `bar#1.0.0`\n\
+         Updating crate `foo#1.1.0`"
+    );
+
+    // The parent of the batch commit is the initial commit of the index
+    let parent = head.parent(0).unwrap();
+    assert_eq!(parent.parent_count(), 0);
+}
+
+#[test]
+fn failed_index_updates_do_not_block_the_batch() {
+    let (app, _, _, token) = TestApp::full()
+        .with_index_batch(Duration::ZERO, 100)
+        .with_token();
+
+    token
+        .put::<GoodCrate>("/api/v1/crates/new", &PublishBuilder::new("foo").body())
+        .good();
+
+    // This version does not exist, so syncing its yanked status must fail
+    let failing_job_id = app.db(|conn| {
+        worker::sync_yanked("missing".into(), "1.0.0".into())
+            .enqueue(conn)
+            .unwrap();
+
+        background_jobs::table
+            .select(background_jobs::id)
+            .order(background_jobs::id.desc())
+            .first::<i64>(conn)
+            .unwrap()
+    });
+
+    token
+        .put::<GoodCrate>("/api/v1/crates/new", &PublishBuilder::new("bar").body())
+        .good();
+
+    assert_eq!(
+        app.try_run_pending_background_jobs(),
+        Err(FailedJobsError::JobsFailed(1))
+    );
+
+    assert_eq!(app.crates_from_index_head("foo").len(), 1);
+    assert_eq!(app.crates_from_index_head("bar").len(), 1);
+
+    let repo = &app.upstream_index().repository;
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(
+        head.message().unwrap(),
+        "Updating 2 index entries\n\n\
+         Updating crate `foo#1.0.0`\n\
+         Updating crate `bar#1.0.0`"
+    );
+
+    // Only the failing job remains in the queue, scheduled for a retry
+    app.db(|conn| {
+        let remaining = background_jobs::table
+            .select((background_jobs::id, background_jobs::retries))
+            .load::<(i64, i32)>(conn)
+            .unwrap();
+        assert_eq!(remaining, vec![(failing_job_id, 1)]);
+
+        diesel::delete(background_jobs::table)
+            .execute(conn)
+            .unwrap();
+    });
+}
@@ -0,0 +1,121 @@
+use crate::builders::PublishBuilder;
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::models::IndexConsistencyCheck;
+use cargo_registry::schema::{background_jobs, versions};
+use cargo_registry::views::GoodCrate;
+use cargo_registry::worker;
+use diesel::prelude::*;
+
+#[test]
+fn index_check_records_and_repairs_mismatches() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    for name in ["bar", "baz", "foo"] {
+        token
+            .put::<GoodCrate>("/api/v1/crates/new", &PublishBuilder::new(name).body())
+            .good();
+    }
+    app.run_pending_background_jobs();
+
+    // `bar` is yanked in the database only, and the HTTP index file of `baz`
+    // is missing (see the recorded HTTP responses)
+    app.db(|conn| {
+        let bar_version = versions::table
+            .inner_join(cargo_registry::schema::crates::table)
+            .filter(cargo_registry::schema::crates::name.eq("bar"))
+            .select(versions::id)
+            .first::<i32>(conn)
+            .unwrap();
+
+        diesel::update(versions::table.find(bar_version))
+            .set(versions::yanked.eq(true))
+            .execute(conn)
+            .unwrap();
+
+        worker::check_index(None).enqueue(conn).unwrap();
+    });
+
+    app.run_pending_background_jobs();
+
+    let check = app.db(|conn| IndexConsistencyCheck::latest(conn).unwrap().unwrap());
+    assert_eq!(check.crates_checked, 3);
+    assert_eq!(check.git_mismatches, vec!["bar"]);
+    assert_eq!(check.http_mismatches, vec!["baz"]);
+    assert!(!check.is_consistent());
+
+    // The repair jobs have updated the git index
+    let bar = app.crates_from_index_head("bar");
+    assert_eq!(bar[0].yanked, Some(true));
+
+    let remaining_jobs = app.db(|conn| {
+        background_jobs::table
+            .count()
+            .get_result::<i64>(conn)
+            .unwrap()
+    });
+    assert_eq!(remaining_jobs, 0);
+}
+
+#[test]
+fn index_check_skips_crates_with_pending_index_jobs() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    token
+        .put::<GoodCrate>("/api/v1/crates/new", &PublishBuilder::new("foo").body())
+        .good();
+
+    app.db(|conn| {
+        // Run the check before the `add_crate` job of the publish above
+        diesel::update(background_jobs::table)
+            .set(background_jobs::id.eq(background_jobs::id + 1000))
+            .execute(conn)
+            .unwrap();
+
+        worker::check_index(None).enqueue(conn).unwrap();
+    });
+
+    app.run_pending_background_jobs();
+
+    let check = app.db(|conn| IndexConsistencyCheck::latest(conn).unwrap().unwrap());
+    assert_eq!(check.crates_checked, 0);
+    assert!(check.is_consistent());
+
+    assert_eq!(app.crates_from_index_head("foo").len(), 1);
+}
+
+#[test]
+fn index_check_rebuilds_crates_with_missing_versions() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo").version("1.0.0");
+    token
+        .put::<GoodCrate>("/api/v1/crates/new", &crate_to_publish.body())
+        .good();
+    app.run_pending_background_jobs();
+
+    let crate_to_publish = PublishBuilder::new("foo").version("1.1.0");
+    token
+        .put::<GoodCrate>("/api/v1/crates/new", &crate_to_publish.body())
+        .good();
+
+    app.db(|conn| {
+        // The index jobs of the second version got lost
+        diesel::delete(background_jobs::table)
+            .execute(conn)
+            .unwrap();
+
+        worker::check_index(None).enqueue(conn).unwrap();
+    });
+
+    app.run_pending_background_jobs();
+
+    let check = app.db(|conn| IndexConsistencyCheck::latest(conn).unwrap().unwrap());
+    assert_eq!(check.git_mismatches, vec!["foo"]);
+
+    let versions = app
+        .crates_from_index_head("foo")
+        .into_iter()
+        .map(|krate| krate.vers)
+        .collect::<Vec<_>>();
+    assert_eq!(versions, vec!["1.0.0", "1.1.0"]);
+}
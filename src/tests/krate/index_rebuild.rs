@@ -0,0 +1,65 @@
+use crate::builders::{CrateBuilder, PublishBuilder, VersionBuilder};
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::schema::versions;
+use cargo_registry::views::GoodCrate;
+use cargo_registry::worker;
+use diesel::prelude::*;
+
+#[test]
+fn rebuild_index_restores_entries_from_the_database() {
+    let (app, _, user, token) = TestApp::full().with_token();
+
+    token
+        .put::<GoodCrate>("/api/v1/crates/new", &PublishBuilder::new("foo").body())
+        .good();
+    app.run_pending_background_jobs();
+
+    let head_before = app.upstream_index().repository.head().unwrap().target();
+
+    // Nothing to do if the index already matches the database
+    app.db(|conn| worker::rebuild_index().enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+
+    let head = app.upstream_index().repository.head().unwrap().target();
+    assert_eq!(head, head_before);
+
+    // Let the database drift away from the index
+    app.db(|conn| {
+        CrateBuilder::new("bar", user.as_model().id)
+            .version(
+                VersionBuilder::new("1.0.0")
+                    .checksum("abababababababababababababababababababababababababababababababab"),
+            )
+            .expect_build(conn);
+
+        diesel::update(versions::table)
+            .set(versions::yanked.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+
+    app.db(|conn| worker::rebuild_index().enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+
+    let foo_versions = app.crates_from_index_head("foo");
+    assert_eq!(foo_versions.len(), 1);
+    assert_eq!(foo_versions[0].yanked, Some(true));
+
+    let bar_versions = app.crates_from_index_head("bar");
+    assert_eq!(bar_versions.len(), 1);
+    assert_eq!(bar_versions[0].vers, "1.0.0");
+    assert_eq!(
+        bar_versions[0].cksum,
+        "abababababababababababababababababababababababababababababababab"
+    );
+    assert_eq!(bar_versions[0].yanked, Some(true));
+
+    let repo = &app.upstream_index().repository;
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(
+        head.message().unwrap(),
+        "Rebuild 2 index files from the database\n\n\
+         - `bar` was missing from the index\n\
+         - `foo` was outdated"
+    );
+}
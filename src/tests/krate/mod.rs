@@ -1,4 +1,11 @@
+mod delete;
 mod following;
+mod index_batch;
+mod index_check;
+mod index_rebuild;
 mod publish;
+mod publish_batch;
+mod rerender_readmes;
+mod staged;
 mod versions;
 mod yanking;
@@ -5,12 +5,14 @@ use cargo_registry::controllers::krate::publish::{
     missing_metadata_error_message, MISSING_RIGHTS_ERROR_MESSAGE, WILDCARD_ERROR_MESSAGE,
 };
 use cargo_registry::models::krate::MAX_NAME_LENGTH;
+use cargo_registry::models::{Badge, MaintenanceStatus};
 use cargo_registry::schema::{api_tokens, emails, versions_published_by};
-use cargo_registry::views::GoodCrate;
+use cargo_registry::views::{GoodCrate, PublishDryRun};
 use diesel::{delete, update, ExpressionMethods, QueryDsl, RunQueryDsl};
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use http::StatusCode;
+use serde_json::Value;
 use std::collections::BTreeMap;
 use std::io::Read;
 use std::iter::FromIterator;
@@ -309,7 +311,60 @@ fn reject_new_crate_with_alternative_registry_dependency() {
     assert_eq!(response.status(), StatusCode::OK);
     assert_eq!(
         response.into_json(),
-        json!({ "errors": [{ "detail": "Dependency `dep` is hosted on another registry. Cross-registry dependencies are not permitted on crates.io." }] })
+        json!({ "errors": [{ "detail": "Dependency `dep` is hosted on the registry `https://server.example/path/to/registry`, which is not in this registry's list of allowed dependency registries." }] })
+    );
+}
+
+#[test]
+fn new_krate_with_allowed_registry_dependency() {
+    use crate::routes::crates::versions::dependencies::Deps;
+
+    const REGISTRY: &str = "https://github.com/rust-lang/crates.io-index";
+
+    let (app, anon, _, token) = TestApp::full()
+        .with_config(|config| config.allowed_dependency_registries = vec![REGISTRY.into()])
+        .with_token();
+
+    // The dependency does not exist on this registry
+    let dependency = DependencyBuilder::new("serde")
+        .version_req("1.0.0")
+        .registry(REGISTRY);
+    let crate_to_publish = PublishBuilder::new("depends-on-crates-io").dependency(dependency);
+    token.publish_crate(crate_to_publish).good();
+
+    let crates = app.crates_from_index_head("depends-on-crates-io");
+    assert_eq!(crates[0].deps.len(), 1);
+    assert_eq!(crates[0].deps[0].name, "serde");
+    assert_eq!(crates[0].deps[0].registry.as_deref(), Some(REGISTRY));
+
+    let dependencies = anon
+        .get::<Deps>("/api/v1/crates/depends-on-crates-io/1.0.0/dependencies")
+        .good()
+        .dependencies;
+
+    assert_eq!(dependencies.len(), 1);
+    assert_eq!(dependencies[0].crate_id, "serde");
+    assert_eq!(dependencies[0].registry.as_deref(), Some(REGISTRY));
+}
+
+#[test]
+fn reject_new_crate_with_unlisted_registry_dependency() {
+    let (_, _, _, token) = TestApp::full()
+        .with_config(|config| {
+            config.allowed_dependency_registries =
+                vec!["https://github.com/rust-lang/crates.io-index".into()]
+        })
+        .with_token();
+
+    let dependency =
+        DependencyBuilder::new("dep").registry("https://server.example/path/to/registry");
+
+    let crate_to_publish = PublishBuilder::new("depends-on-alt-registry").dependency(dependency);
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "Dependency `dep` is hosted on the registry `https://server.example/path/to/registry`, which is not in this registry's list of allowed dependency registries." }] })
     );
 }
 
@@ -594,6 +649,31 @@ fn new_krate_with_readme() {
     assert_eq!(json.krate.max_version, "1.0.0");
 }
 
+#[test]
+fn new_krate_with_changelog_and_license() {
+    let (_, anon, _, token) = TestApp::full()
+        .with_config(|config| config.max_unpack_size = 10 * 1024)
+        .with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo_documents")
+        .license_file("LICENSE")
+        .files(&[
+            ("foo_documents-1.0.0/Cargo.toml", b""),
+            ("foo_documents-1.0.0/CHANGES.md", b"# Changes"),
+            (
+                "foo_documents-1.0.0/CHANGELOG.md",
+                b"# Changelog\n\n## 1.0.0\n\n- First release",
+            ),
+            ("foo_documents-1.0.0/LICENSE", b"Copyright (c) 2023 Foo"),
+        ]);
+    token.publish_crate(crate_to_publish).good();
+
+    anon.get::<()>("/api/v1/crates/foo_documents/1.0.0/changelog")
+        .assert_redirect_ends_with("/changelogs/foo_documents/foo_documents-1.0.0.html");
+    anon.get::<()>("/api/v1/crates/foo_documents/1.0.0/license")
+        .assert_redirect_ends_with("/licenses/foo_documents/foo_documents-1.0.0.html");
+}
+
 #[test]
 fn new_krate_without_any_email_fails() {
     let (app, _, _, token) = TestApp::full().with_token();
@@ -781,6 +861,39 @@ fn ignored_categories() {
     assert_eq!(json.warnings.invalid_categories, vec!["bar"]);
 }
 
+#[test]
+fn badges() {
+    let (_, anon, _, token) = TestApp::full().with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo_badges")
+        .badge("maintenance", &[("status", "deprecated")])
+        .badge("travis-ci", &[("repository", "foo/bar")])
+        .badge("codecov", &[("repository", "foo/bar"), ("brnach", "main")])
+        .badge("not-a-badge", &[("repository", "foo/bar")]);
+    let json = token.publish_crate(crate_to_publish).good();
+    assert_eq!(json.warnings.invalid_badges, vec!["codecov", "not-a-badge"]);
+
+    let json = anon.show_crate("foo_badges");
+    assert_eq!(
+        json.krate.badges.unwrap(),
+        vec![
+            Badge::Maintenance {
+                status: MaintenanceStatus::Deprecated
+            },
+            Badge::TravisCi {
+                repository: "foo/bar".into(),
+                branch: None
+            },
+        ]
+    );
+
+    // Publishing a new version replaces the badges
+    let crate_to_publish = PublishBuilder::new("foo_badges").version("1.1.0");
+    token.publish_crate(crate_to_publish).good();
+    let json = anon.show_crate("foo_badges");
+    assert_eq!(json.krate.badges.unwrap(), vec![]);
+}
+
 #[test]
 fn license_and_description_required() {
     let (_, _, _, token) = TestApp::full().with_token();
@@ -822,6 +935,53 @@ fn license_and_description_required() {
     );
 }
 
+#[test]
+fn license_policy_is_enforced() {
+    let (_, anon, _, token) = TestApp::full()
+        .with_config(|config| {
+            config.license_policy.deny_copyleft = true;
+            config.license_policy.require_osi_approved = true;
+        })
+        .with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo_license_policy").license("MIT AND GPL-3.0+");
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "license expression `MIT AND GPL-3.0-or-later` is not allowed by the license policy of this registry: `GPL-3.0-or-later` is a copyleft license" }] })
+    );
+
+    let crate_to_publish =
+        PublishBuilder::new("foo_license_policy").license("LicenseRef-Proprietary");
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "license expression `LicenseRef-Proprietary` is not allowed by the license policy of this registry: `LicenseRef-Proprietary` is not an SPDX license identifier" }] })
+    );
+
+    let crate_to_publish = PublishBuilder::new("foo_license_policy")
+        .unset_license()
+        .license_file("LICENSE");
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "crates without an SPDX license expression are not allowed by the license policy of this registry; please set the `license` field in Cargo.toml" }] })
+    );
+
+    let crate_to_publish = PublishBuilder::new("foo_license_policy").license("apache 2.0");
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.into_json()["errors"][0]["detail"]
+        .as_str()
+        .unwrap()
+        .starts_with("unknown or invalid license expression"));
+
+    assert_eq!(anon.search("").meta.total, 0);
+}
+
 #[test]
 fn new_krate_tarball_with_hard_links() {
     let (_, _, _, token) = TestApp::full().with_token();
@@ -995,3 +1155,186 @@ fn features_version_2() {
     )]);
     assert_eq!(crates[0].features2, Some(features2));
 }
+
+#[test]
+fn dry_run_does_not_publish_anything() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    let body = PublishBuilder::new("dry_run").category("unknown").body();
+    let json = token
+        .put::<PublishDryRun>("/api/v1/crates/new?dry_run=1", &body)
+        .good();
+    assert!(json.errors.is_empty());
+    assert_eq!(json.krate.unwrap().name, "dry_run");
+    assert_eq!(json.warnings.invalid_categories, vec!["unknown"]);
+
+    app.run_pending_background_jobs();
+    let response = anon.get::<()>("/api/v1/crates/dry_run");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_err!(app.upstream_index().crates_from_index_head("dry_run"));
+}
+
+#[test]
+fn dry_run_reports_all_errors() {
+    let (app, _, _, token) = TestApp::full().with_token();
+    let other = app.db_new_user("other");
+
+    app.db(|conn| {
+        CrateBuilder::new("taken", other.as_model().id).expect_build(conn);
+        CrateBuilder::new("dep", other.as_model().id).expect_build(conn);
+    });
+
+    let body = PublishBuilder::new("taken")
+        .dependency(DependencyBuilder::new("dep").version_req("*"))
+        .dependency(DependencyBuilder::new("unknown"))
+        .files(&[("other-1.0.0/lib.rs", b"")])
+        .body();
+    let json = token
+        .put::<PublishDryRun>("/api/v1/crates/new?dry_run=1", &body)
+        .good();
+    let errors = json
+        .errors
+        .into_iter()
+        .map(|error| error.detail)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            MISSING_RIGHTS_ERROR_MESSAGE,
+            WILDCARD_ERROR_MESSAGE,
+            "no known crate named `unknown`",
+            "invalid tarball uploaded",
+        ]
+    );
+
+    app.db(|conn| {
+        use cargo_registry::schema::versions;
+
+        let count: i64 = versions::table.count().get_result(conn).unwrap();
+        assert_eq!(count, 2);
+    });
+}
+
+#[test]
+fn async_publish_is_finalized_by_a_background_job() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    let body = PublishBuilder::new("async_foo").body();
+    let response = token.put::<Value>("/api/v1/crates/new?async=1", &body);
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let json = response.into_json();
+    assert_eq!(json["crate"]["name"], "async_foo");
+    assert_eq!(json["publish_status"]["state"], "pending");
+    assert_eq!(json["publish_status"]["in_git_index"], false);
+
+    let url = "/api/v1/crates/async_foo/1.0.0/publish_status";
+    let json = anon.get::<Value>(url).good();
+    assert_eq!(json["publish_status"]["state"], "pending");
+
+    // The version stays hidden until its crate file was verified
+    let json = anon
+        .get::<Value>("/api/v1/crates/async_foo/versions")
+        .good();
+    assert_eq!(json["versions"].as_array().unwrap().len(), 0);
+    let status = anon
+        .get::<()>("/api/v1/crates/async_foo/1.0.0/download")
+        .status();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let response = token.put::<()>("/api/v1/crates/async_foo/1.0.0/release", b"");
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "version `1.0.0` of crate `async_foo` is still being published" }] })
+    );
+
+    app.run_pending_background_jobs();
+
+    let json = anon.get::<Value>(url).good();
+    assert_eq!(
+        json["publish_status"],
+        json!({
+            "crate_name": "async_foo",
+            "version": "1.0.0",
+            "state": "published",
+            "error": null,
+            "in_git_index": true,
+            "in_http_index": true,
+        })
+    );
+    assert_eq!(app.crates_from_index_head("async_foo").len(), 1);
+    let json = anon
+        .get::<Value>("/api/v1/crates/async_foo/versions")
+        .good();
+    assert_eq!(json["versions"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn async_publish_with_invalid_tarball_fails() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    let body = PublishBuilder::new("async_bad")
+        .files(&[("other-1.0.0/lib.rs", b"")])
+        .body();
+    let response = token.put::<()>("/api/v1/crates/new?async=1", &body);
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+    app.run_pending_background_jobs();
+
+    let json = anon
+        .get::<Value>("/api/v1/crates/async_bad/1.0.0/publish_status")
+        .good();
+    assert_eq!(json["publish_status"]["state"], "failed");
+    assert_eq!(json["publish_status"]["error"], "invalid tarball uploaded");
+    assert_eq!(json["publish_status"]["in_git_index"], false);
+
+    let response = anon.get::<()>("/api/v1/crates/async_bad");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // The version can be uploaded again after fixing the crate file
+    token.publish_crate(PublishBuilder::new("async_bad")).good();
+}
+
+#[test]
+fn rejected_async_publish_restores_crate_metadata() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    let crate_to_publish = PublishBuilder::new("async_meta")
+        .description("old description")
+        .keyword("old");
+    token.publish_crate(crate_to_publish).good();
+
+    let body = PublishBuilder::new("async_meta")
+        .version("1.1.0")
+        .description("new description")
+        .keyword("new")
+        .files(&[("other-1.1.0/lib.rs", b"")])
+        .body();
+    let response = token.put::<()>("/api/v1/crates/new?async=1", &body);
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let json = anon.show_crate("async_meta");
+    assert_eq!(json.krate.description.unwrap(), "new description");
+
+    app.run_pending_background_jobs();
+
+    let json = anon.show_crate("async_meta");
+    assert_eq!(json.krate.description.unwrap(), "old description");
+    assert_eq!(json.krate.max_version, "1.0.0");
+    let keywords = json.keywords.unwrap();
+    assert_eq!(keywords.len(), 1);
+    assert_eq!(keywords[0].keyword, "old");
+}
+
+#[test]
+fn publish_status_of_synchronous_publish() {
+    let (_, anon, _, token) = TestApp::full().with_token();
+
+    token.publish_crate(PublishBuilder::new("sync_foo")).good();
+
+    let json = anon
+        .get::<Value>("/api/v1/crates/sync_foo/1.0.0/publish_status")
+        .good();
+    assert_eq!(json["publish_status"]["state"], "published");
+    assert_eq!(json["publish_status"]["in_http_index"], true);
+
+    let response = anon.get::<()>("/api/v1/crates/sync_foo/2.0.0/publish_status");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
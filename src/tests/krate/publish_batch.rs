@@ -0,0 +1,122 @@
+use crate::builders::{DependencyBuilder, PublishBuilder};
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::views::GoodCrateBatch;
+use http::StatusCode;
+
+fn batch_body(crates: Vec<PublishBuilder>) -> Vec<u8> {
+    let mut body = (crates.len() as u32).to_le_bytes().to_vec();
+    for krate in crates {
+        body.extend(krate.body());
+    }
+    body
+}
+
+#[test]
+fn batch_is_published_in_dependency_order_with_a_single_commit() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    let body = batch_body(vec![
+        PublishBuilder::new("batch_cli").dependency(DependencyBuilder::new("batch_lib")),
+        PublishBuilder::new("batch_lib").dependency(DependencyBuilder::new("batch_core")),
+        PublishBuilder::new("batch_core"),
+    ]);
+    let json: GoodCrateBatch = token.put("/api/v1/crates/new/batch", &body).good();
+
+    let names = json
+        .crates
+        .iter()
+        .map(|good_crate| good_crate.krate.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["batch_core", "batch_lib", "batch_cli"]);
+
+    app.run_pending_background_jobs();
+
+    let crates = app.crates_from_index_head("batch_cli");
+    assert_eq!(crates.len(), 1);
+    assert_eq!(crates[0].deps[0].name, "batch_lib");
+    assert_eq!(app.crates_from_index_head("batch_core").len(), 1);
+
+    let repo = &app.upstream_index().repository;
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(
+        head.message().unwrap(),
+        "Updating 3 index entries\n\n\
+         Updating crate `batch_core#1.0.0`\n\
+         Updating crate `batch_lib#1.0.0`\n\
+         Updating crate `batch_cli#1.0.0`"
+    );
+    // The parent of the batch commit is the initial commit of the index
+    assert_eq!(head.parent(0).unwrap().parent_count(), 0);
+
+    let response = anon.get::<()>("/api/v1/crates/batch_lib/reverse_dependencies");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json()["meta"]["total"], 1);
+}
+
+#[test]
+fn failing_crate_rolls_back_the_whole_batch() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    let body = batch_body(vec![
+        PublishBuilder::new("batch_ok"),
+        PublishBuilder::new("batch_broken").dependency(DependencyBuilder::new("missing_dep")),
+    ]);
+    let response = token.put::<()>("/api/v1/crates/new/batch", &body);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "failed to publish `batch_broken@1.0.0`: no known crate named `missing_dep`" }] })
+    );
+    app.run_pending_background_jobs();
+
+    let response = anon.get::<()>("/api/v1/crates/batch_ok");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_err!(app.upstream_index().crates_from_index_head("batch_ok"));
+}
+
+#[test]
+fn failed_upload_deletes_the_uploaded_crate_files() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    // The upload of `batch_second` fails (see the recorded HTTP responses),
+    // after which the crate file of `batch_first` is deleted again
+    let body = batch_body(vec![
+        PublishBuilder::new("batch_first"),
+        PublishBuilder::new("batch_second"),
+    ]);
+    let response = token.put::<()>("/api/v1/crates/new/batch", &body);
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    app.run_pending_background_jobs();
+
+    let response = anon.get::<()>("/api/v1/crates/batch_first");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn dependency_cycles_in_a_batch_are_rejected() {
+    let (_, _, _, token) = TestApp::full().with_token();
+
+    let body = batch_body(vec![
+        PublishBuilder::new("batch_a").dependency(DependencyBuilder::new("batch_b")),
+        PublishBuilder::new("batch_b").dependency(DependencyBuilder::new("batch_a")),
+    ]);
+    let response = token.put::<()>("/api/v1/crates/new/batch", &body);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the crates `batch_a`, `batch_b` of the batch depend on each other in a cycle" }] })
+    );
+}
+
+#[test]
+fn duplicate_crates_in_a_batch_are_rejected() {
+    let (_, _, _, token) = TestApp::full().with_token();
+
+    let body = batch_body(vec![
+        PublishBuilder::new("batch_dup"),
+        PublishBuilder::new("batch-dup").version("1.1.0"),
+    ]);
+    let response = token.put::<()>("/api/v1/crates/new/batch", &body);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "crate `batch-dup` is contained more than once in the batch" }] })
+    );
+}
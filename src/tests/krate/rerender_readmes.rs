@@ -0,0 +1,175 @@
+use crate::builders::PublishBuilder;
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::schema::{background_jobs, readme_renderings};
+use cargo_registry::worker;
+use cargo_registry_markdown::RENDERER_VERSION;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+fn publish_with_readme(version: &str) -> PublishBuilder {
+    PublishBuilder::new("foo")
+        .version(version)
+        .readme("# Foo")
+        .files(&[
+            (
+                &format!("foo-{version}/Cargo.toml"),
+                b"[package]\nname = \"foo\"\n",
+            ),
+            (&format!("foo-{version}/README.md"), b"# Foo"),
+        ])
+}
+
+#[test]
+fn outdated_readmes_are_rerendered_in_batches() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| config.max_unpack_size = 10 * 1024)
+        .with_token();
+
+    token.publish_crate(publish_with_readme("1.0.0")).good();
+    token.publish_crate(publish_with_readme("1.1.0")).good();
+
+    // Pretend that the readmes were rendered by an older version of the renderer
+    app.db(|conn| {
+        diesel::update(readme_renderings::table)
+            .set((
+                readme_renderings::renderer_version.eq(RENDERER_VERSION - 1),
+                readme_renderings::outline.eq(None::<serde_json::Value>),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    app.db(|conn| {
+        worker::rerender_readmes(1, None, None)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+
+    let renderings: Vec<(i32, Option<serde_json::Value>)> = app.db(|conn| {
+        readme_renderings::table
+            .select((
+                readme_renderings::renderer_version,
+                readme_renderings::outline,
+            ))
+            .load(conn)
+            .unwrap()
+    });
+    assert_eq!(renderings.len(), 2);
+    for (renderer_version, outline) in renderings {
+        assert_eq!(renderer_version, RENDERER_VERSION);
+        assert_eq!(
+            outline,
+            Some(json!([{ "level": 1, "text": "Foo", "anchor": "user-content-foo" }]))
+        );
+    }
+
+    // Nothing is left to re-render
+    app.db(|conn| {
+        worker::rerender_readmes(1, None, None)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+
+    let pending_jobs: i64 = app.db(|conn| background_jobs::table.count().get_result(conn).unwrap());
+    assert_eq!(pending_jobs, 0);
+}
+
+#[test]
+fn readmes_missing_from_crate_files_are_skipped() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| config.max_unpack_size = 10 * 1024)
+        .with_token();
+
+    let crate_without_readme = PublishBuilder::new("foo")
+        .readme("# Foo")
+        .files(&[("foo-1.0.0/Cargo.toml", b"[package]\nname = \"foo\"\n")]);
+    token.publish_crate(crate_without_readme).good();
+
+    app.db(|conn| {
+        diesel::update(readme_renderings::table)
+            .set(readme_renderings::renderer_version.eq(RENDERER_VERSION - 1))
+            .execute(conn)
+            .unwrap();
+    });
+
+    // The job succeeds, and the readme is not rendered again by the next run
+    app.db(|conn| {
+        worker::rerender_readmes(1, None, None)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+
+    let renderer_version: i32 = app.db(|conn| {
+        readme_renderings::table
+            .select(readme_renderings::renderer_version)
+            .first(conn)
+            .unwrap()
+    });
+    assert_eq!(renderer_version, RENDERER_VERSION);
+}
+
+#[test]
+fn missing_and_old_readmes_are_rerendered() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| config.max_unpack_size = 10 * 1024)
+        .with_token();
+
+    // The readme of the crate file was not rendered when it was published
+    let crate_without_rendering = PublishBuilder::new("foo").files(&[
+        ("foo-1.0.0/Cargo.toml", b"[package]\nname = \"foo\"\n"),
+        ("foo-1.0.0/README.md", b"# Foo"),
+    ]);
+    token.publish_crate(crate_without_rendering).good();
+    app.run_pending_background_jobs();
+
+    let num_renderings = |app: &TestApp| -> i64 {
+        app.db(|conn| readme_renderings::table.count().get_result(conn).unwrap())
+    };
+    assert_eq!(num_renderings(&app), 0);
+
+    app.db(|conn| {
+        worker::rerender_readmes(1, None, None)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+    assert_eq!(num_renderings(&app), 1);
+
+    // Readmes rendered by the current renderer are only re-rendered if they are
+    // older than `older_than`
+    let date = |s| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+    app.db(|conn| {
+        diesel::update(readme_renderings::table)
+            .set(readme_renderings::rendered_at.eq(date("2020-01-01 00:00:00")))
+            .execute(conn)
+            .unwrap();
+    });
+    let rendered_at = || -> NaiveDateTime {
+        app.db(|conn| {
+            readme_renderings::table
+                .select(readme_renderings::rendered_at)
+                .first(conn)
+                .unwrap()
+        })
+    };
+
+    app.db(|conn| {
+        worker::rerender_readmes(1, None, None)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+    assert_eq!(rendered_at(), date("2020-01-01 00:00:00"));
+
+    let older_than = Some(date("2021-01-01 00:00:00"));
+    app.db(|conn| {
+        worker::rerender_readmes(1, None, older_than)
+            .enqueue(conn)
+            .unwrap()
+    });
+    app.run_pending_background_jobs();
+    assert!(rendered_at() > date("2021-01-01 00:00:00"));
+}
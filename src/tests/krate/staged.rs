@@ -0,0 +1,171 @@
+use crate::builders::{DependencyBuilder, PublishBuilder};
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::schema::{crates, versions};
+use diesel::prelude::*;
+use http::StatusCode;
+use serde_json::Value;
+
+#[test]
+fn staged_version_is_hidden_until_released() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    token
+        .publish_crate(PublishBuilder::new("staged_foo"))
+        .good();
+
+    let body = PublishBuilder::new("staged_foo").version("1.1.0").body();
+    token
+        .put::<Value>("/api/v1/crates/new?staged=1", &body)
+        .good();
+    app.run_pending_background_jobs();
+
+    assert_eq!(app.crates_from_index_head("staged_foo").len(), 1);
+    let json = anon
+        .get::<Value>("/api/v1/crates/staged_foo/versions")
+        .good();
+    assert_eq!(json["versions"].as_array().unwrap().len(), 1);
+    let json = anon.get::<Value>("/api/v1/crates/staged_foo").good();
+    assert_eq!(json["crate"]["max_version"], "1.0.0");
+
+    // Staged versions can't be yanked before they are released
+    let response = token.delete::<()>("/api/v1/crates/staged_foo/1.1.0/yank");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.into_json()["errors"].is_array());
+
+    // Staged versions can't be downloaded before they are released
+    let status = anon
+        .get::<()>("/api/v1/crates/staged_foo/1.1.0/download")
+        .status();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let response = anon.put::<()>("/api/v1/crates/staged_foo/1.1.0/release", b"");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    token
+        .put::<Value>("/api/v1/crates/staged_foo/1.1.0/release", b"")
+        .good();
+    app.run_pending_background_jobs();
+
+    let crates = app.crates_from_index_head("staged_foo");
+    assert_eq!(crates.len(), 2);
+    assert_eq!(crates[1].vers, "1.1.0");
+    let json = anon
+        .get::<Value>("/api/v1/crates/staged_foo/versions")
+        .good();
+    assert_eq!(json["versions"].as_array().unwrap().len(), 2);
+    let json = anon.get::<Value>("/api/v1/crates/staged_foo").good();
+    assert_eq!(json["crate"]["max_version"], "1.1.0");
+    let status = anon
+        .get::<()>("/api/v1/crates/staged_foo/1.1.0/download")
+        .status();
+    assert_eq!(status, StatusCode::FOUND);
+
+    // Released versions are no longer staged
+    let response = token.put::<()>("/api/v1/crates/staged_foo/1.1.0/release", b"");
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "crate `staged_foo` does not have a staged version `1.1.0`" }] })
+    );
+}
+
+#[test]
+fn abandoned_staged_version_is_deleted() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+
+    let body = PublishBuilder::new("staged_bar").body();
+    token
+        .put::<Value>("/api/v1/crates/new?staged=1", &body)
+        .good();
+
+    // Staged versions can't be uploaded a second time
+    let body = PublishBuilder::new("staged_bar").body();
+    let response = token.put::<()>("/api/v1/crates/new?staged=1", &body);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "crate version `1.0.0` is already uploaded" }] })
+    );
+
+    token
+        .delete::<Value>("/api/v1/crates/staged_bar/1.0.0/release")
+        .good();
+    app.run_pending_background_jobs();
+
+    let response = anon.get::<()>("/api/v1/crates/staged_bar");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_err!(app.upstream_index().crates_from_index_head("staged_bar"));
+
+    // The name is free to be published again
+    token
+        .publish_crate(PublishBuilder::new("staged_bar"))
+        .good();
+    assert_eq!(app.crates_from_index_head("staged_bar").len(), 1);
+}
+
+#[test]
+fn staged_publish_cannot_be_asynchronous() {
+    let (_, _, _, token) = TestApp::full().with_token();
+
+    let body = PublishBuilder::new("staged_async").body();
+    let response = token.put::<()>("/api/v1/crates/new?staged=1&async=1", &body);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "staged uploads cannot be published asynchronously" }] })
+    );
+}
+
+#[test]
+fn dependencies_on_staged_crates_are_rejected() {
+    let (_, _, _, token) = TestApp::full().with_token();
+
+    let body = PublishBuilder::new("staged_dep").body();
+    token
+        .put::<Value>("/api/v1/crates/new?staged=1", &body)
+        .good();
+
+    let crate_to_publish =
+        PublishBuilder::new("staged_dependent").dependency(DependencyBuilder::new("staged_dep"));
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the crate `staged_dep` has no released versions yet" }] })
+    );
+}
+
+#[test]
+fn staged_versions_with_unreleased_dependencies_cannot_be_released() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    token
+        .publish_crate(PublishBuilder::new("unreleased_dep"))
+        .good();
+
+    let body = PublishBuilder::new("staged_baz")
+        .dependency(DependencyBuilder::new("unreleased_dep"))
+        .body();
+    token
+        .put::<Value>("/api/v1/crates/new?staged=1", &body)
+        .good();
+
+    // Dependencies are only accepted on released crates, but the released
+    // versions may be gone by the time the staged version is released
+    app.db(|conn| {
+        let crate_id = crates::table
+            .filter(crates::name.eq("unreleased_dep"))
+            .select(crates::id)
+            .first::<i32>(conn)
+            .unwrap();
+        diesel::update(versions::table.filter(versions::crate_id.eq(crate_id)))
+            .set(versions::staged.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let response = token.put::<()>("/api/v1/crates/staged_baz/1.0.0/release", b"");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "this version depends on the crates `unreleased_dep`, which have no released versions yet" }] })
+    );
+    assert_err!(app.upstream_index().crates_from_index_head("staged_baz"));
+}
@@ -0,0 +1,269 @@
+use crate::builders::PublishBuilder;
+use crate::util::{MockAnonymousUser, RequestHelper, TestApp};
+use cargo_registry::config::MirrorConfig;
+use cargo_registry::schema::background_jobs;
+use cargo_registry::swirl::errors::FailedJobsError;
+use cargo_registry::views::EncodableVersion;
+use cargo_registry::worker;
+use cargo_registry_index::testing::UpstreamRegistryIndex;
+use cargo_registry_index::{Crate, Dependency, DependencyKind};
+use diesel::prelude::*;
+use hex::ToHex;
+use http::{Method, StatusCode};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+#[derive(Deserialize)]
+struct VersionResponse {
+    version: EncodableVersion,
+}
+
+/// Writes the tarball of a crate version into `dir` and returns its index
+/// entry.
+fn upstream_version(dir: &Path, name: &str, vers: &str, deps: Vec<Dependency>) -> Crate {
+    let tarball = format!("{name}-{vers} tarball").into_bytes();
+    fs::write(dir.join(format!("{name}-{vers}.crate")), &tarball).unwrap();
+
+    Crate {
+        name: name.into(),
+        vers: vers.into(),
+        deps,
+        cksum: Sha256::digest(&tarball).encode_hex(),
+        features: Default::default(),
+        features2: None,
+        yanked: Some(false),
+        links: None,
+        v: None,
+    }
+}
+
+fn index_file(versions: &[&Crate]) -> String {
+    versions
+        .iter()
+        .map(|krate| serde_json::to_string(krate).unwrap() + "\n")
+        .collect()
+}
+
+fn assert_index_eq(app: &TestApp, krate: &Crate) {
+    let index = app.crates_from_index_head(&krate.name);
+    assert_eq!(json!(index), json!([krate]));
+}
+
+fn setup() -> (
+    TestApp,
+    MockAnonymousUser,
+    UpstreamRegistryIndex,
+    tempfile::TempDir,
+) {
+    let upstream = UpstreamRegistryIndex::new().unwrap();
+    let tarballs = tempfile::tempdir().unwrap();
+
+    let config = json!({
+        "dl": format!("file://{}/{{crate}}-{{version}}.crate", tarballs.path().display()),
+    });
+    upstream
+        .commit_file("config.json", &config.to_string())
+        .unwrap();
+
+    let upstream_index = upstream.url();
+    let (app, anon) = TestApp::full()
+        .with_config(|config| config.mirror = Some(MirrorConfig { upstream_index }))
+        .empty();
+
+    (app, anon, upstream, tarballs)
+}
+
+fn sync(app: &TestApp) {
+    app.db(|conn| worker::sync_mirror().enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn sync_imports_crates_from_upstream() {
+    let (app, anon, upstream, tarballs) = setup();
+
+    let mut bar = upstream_version(tarballs.path(), "bar", "1.0.0", vec![]);
+    bar.yanked = Some(true);
+    let dep = Dependency {
+        name: "renamed".into(),
+        req: "^1.0".into(),
+        features: vec![],
+        optional: false,
+        default_features: true,
+        target: None,
+        kind: Some(DependencyKind::Normal),
+        package: Some("bar".into()),
+        registry: None,
+    };
+    let foo_entry = upstream_version(tarballs.path(), "foo", "1.0.0", vec![dep]);
+
+    upstream
+        .commit_file("3/b/bar", &index_file(&[&bar]))
+        .unwrap();
+    upstream
+        .commit_file("3/f/foo", &index_file(&[&foo_entry]))
+        .unwrap();
+
+    sync(&app);
+
+    assert_index_eq(&app, &bar);
+    assert_index_eq(&app, &foo_entry);
+
+    let json: VersionResponse = anon.get("/api/v1/crates/bar/1.0.0").good();
+    assert!(json.version.yanked);
+
+    let json: VersionResponse = anon.get("/api/v1/crates/foo/1.0.0").good();
+    assert!(!json.version.yanked);
+
+    let deps: serde_json::Value = anon.get("/api/v1/crates/foo/1.0.0/dependencies").good();
+    assert_eq!(deps["dependencies"][0]["crate_id"], "bar");
+    assert_eq!(deps["dependencies"][0]["req"], "^1.0");
+}
+
+#[test]
+fn sync_updates_yanked_versions() {
+    let (app, anon, upstream, tarballs) = setup();
+
+    let mut foo_entry = upstream_version(tarballs.path(), "foo", "1.0.0", vec![]);
+    upstream
+        .commit_file("3/f/foo", &index_file(&[&foo_entry]))
+        .unwrap();
+    sync(&app);
+
+    let json: VersionResponse = anon.get("/api/v1/crates/foo/1.0.0").good();
+    assert!(!json.version.yanked);
+
+    foo_entry.yanked = Some(true);
+    upstream
+        .commit_file("3/f/foo", &index_file(&[&foo_entry]))
+        .unwrap();
+    sync(&app);
+
+    assert_index_eq(&app, &foo_entry);
+    let json: VersionResponse = anon.get("/api/v1/crates/foo/1.0.0").good();
+    assert!(json.version.yanked);
+}
+
+#[test]
+fn sync_rejects_checksum_mismatch() {
+    let (app, anon, upstream, tarballs) = setup();
+
+    let mut foo_entry = upstream_version(tarballs.path(), "foo", "1.0.0", vec![]);
+    foo_entry.cksum = "0".repeat(64);
+    upstream
+        .commit_file("3/f/foo", &index_file(&[&foo_entry]))
+        .unwrap();
+
+    app.db(|conn| worker::sync_mirror().enqueue(conn).unwrap());
+    assert_eq!(
+        app.try_run_pending_background_jobs(),
+        Err(FailedJobsError::JobsFailed(1))
+    );
+
+    // The failing job remains in the queue, scheduled for a retry
+    app.db(|conn| {
+        diesel::delete(background_jobs::table)
+            .execute(conn)
+            .unwrap();
+    });
+
+    let response = anon.get::<()>("/api/v1/crates/foo");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn only_session_and_token_routes_accept_changes() {
+    let (app, _, _upstream, _tarballs) = setup();
+    let user = app.db_new_user("foo");
+
+    let allowed_routes = [
+        "/api/private/session",
+        "/api/v1/me/tokens",
+        "/api/v1/me/tokens/:id",
+        "/api/v1/tokens/current",
+        "/api/github/secret-scanning/verify",
+        // Only served in development mode
+        "/git/index/*path",
+    ];
+
+    // Every route of the router, with placeholders for its parameters
+    let routes = include_str!("../router.rs")
+        .split(".route(")
+        .skip(1)
+        .filter_map(|route| route.split('"').nth(1))
+        .filter(|route| route.starts_with('/'))
+        .collect::<Vec<_>>();
+    assert!(routes.len() > 50);
+
+    for route in routes {
+        let path = route
+            .split('/')
+            .map(|segment| match segment.starts_with([':', '*']) {
+                true => "foo",
+                false => segment,
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+
+        for method in [Method::PUT, Method::DELETE, Method::POST] {
+            let request = user.request_builder(method.clone(), &path);
+            let response = user.run::<()>(request);
+            let read_only = response.status() == StatusCode::FORBIDDEN
+                && response.into_json()["errors"][0]["detail"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .contains("read-only mirror");
+            assert_eq!(
+                read_only,
+                !allowed_routes.contains(&route),
+                "{method} {route}"
+            );
+        }
+    }
+}
+
+#[test]
+fn publish_routes_are_blocked() {
+    let (app, _, _upstream, _tarballs) = setup();
+    let user = app.db_new_user("foo");
+    let token = user.db_new_token("bar");
+
+    let response = token.publish_crate(PublishBuilder::new("foo"));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(response.into_json()["errors"][0]["detail"]
+        .as_str()
+        .unwrap()
+        .contains("read-only mirror"));
+
+    let response = token.delete::<()>("/api/v1/crates/foo/1.0.0/yank");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let body = PublishBuilder::new("foo").body();
+    let response = token.put::<()>("/api/v1/crates/new/batch", &body);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = token.delete::<()>("/api/v1/crates/foo");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = token.put::<()>("/api/v1/crates/foo/1.0.0/release", b"");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = token.delete::<()>("/api/v1/crates/foo/1.0.0/release");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = token.delete::<()>("/api/v1/crates/foo/owner_invitations/bar");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    for url in [
+        "/api/v1/crates/foo/transfer",
+        "/api/v1/crates/foo/abandon",
+        "/api/v1/crates/foo/adoption_requests",
+        "/api/v1/me/crate_transfers/foo/accept",
+        "/api/v1/crates/foo/owners/bar/role",
+        "/api/v1/crates/foo/owner_invitations/bar/resend",
+        "/api/v1/crates/foo/owner_invitations/github:org:team/accept",
+    ] {
+        let response = token.put::<()>(url, b"{}");
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -2,11 +2,13 @@ use crate::{
     add_team_to_crate,
     builders::{CrateBuilder, PublishBuilder},
     new_team,
+    routes::crates::versions::yank_unyank::YankRequestHelper,
     util::{MockAnonymousUser, MockCookieUser, MockTokenUser, RequestHelper, Response},
     TestApp,
 };
 use cargo_registry::{
-    models::Crate,
+    controllers::krate::publish::YANKER_ROLE_ERROR_MESSAGE,
+    models::{Crate, CrateAction, CrateOwnerAction},
     views::{
         EncodableCrateOwnerInvitation, EncodableCrateOwnerInvitationV1, EncodableOwner,
         EncodablePublicUser, InvitationResponse,
@@ -18,6 +20,7 @@ use cargo_registry::models::token::{CrateScope, EndpointScope};
 use chrono::{Duration, Utc};
 use diesel::prelude::*;
 use http::StatusCode;
+use serde_json::Value;
 
 #[derive(Deserialize)]
 struct TeamResponse {
@@ -1235,3 +1238,220 @@ fn invitation_list_other_crates() {
         owner.get_with_query::<()>("/api/private/crate_owner_invitations", "crate_name=crate_2");
     assert_eq!(resp.status(), StatusCode::FORBIDDEN);
 }
+
+//
+// Tests for the management of sent invitations by the owners of a crate
+//
+
+#[test]
+fn sent_invitations_are_listed_for_owners() {
+    let (app, _, owner, owner_token) = TestApp::init().with_token();
+    let invited_user = app.db_new_user("user_bar");
+    app.db(|conn| CrateBuilder::new("sent_invitations", owner.as_model().id).expect_build(conn));
+
+    owner_token.add_user_owner("sent_invitations", "user_bar");
+
+    let url = "/api/v1/crates/sent_invitations/owner_invitations";
+    let json = owner.get::<Value>(url).good();
+    let invitations = json["invitations"].as_array().unwrap();
+    assert_eq!(invitations.len(), 1);
+    assert_eq!(invitations[0]["invitee"]["login"], "user_bar");
+    assert_eq!(invitations[0]["invitee"]["kind"], "user");
+    assert_eq!(invitations[0]["invited_by_username"], "foo");
+    assert_eq!(invitations[0]["crate_name"], "sent_invitations");
+
+    let response = invited_user.get::<()>(url);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn revoke_invitation() {
+    let (app, _, owner, owner_token) = TestApp::init().with_token();
+    let invited_user = app.db_new_user("user_bar");
+    let krate = app
+        .db(|conn| CrateBuilder::new("revoke_invitation", owner.as_model().id).expect_build(conn));
+    let other_owner = create_and_add_owner(&app, &owner_token, "user_baz", &krate);
+
+    owner_token.add_user_owner("revoke_invitation", "user_bar");
+    assert_eq!(
+        invited_user
+            .list_invitations()
+            .crate_owner_invitations
+            .len(),
+        1
+    );
+
+    let url = "/api/v1/crates/revoke_invitation/owner_invitations/user_bar";
+    let response = invited_user.delete::<()>(url);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Only the owner who sent the invitation can revoke it
+    let response = other_owner.delete::<()>(url);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let json = owner_token.delete::<Value>(url).good();
+    assert_eq!(json["ok"], true);
+    assert_eq!(
+        invited_user
+            .list_invitations()
+            .crate_owner_invitations
+            .len(),
+        0
+    );
+
+    let actions = app.db(|conn| CrateOwnerAction::by_crate(conn, &krate).unwrap());
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].action, CrateAction::InvitationRevoke);
+    assert_eq!(actions[0].user_id, owner.as_model().id);
+    assert_eq!(actions[0].target_user_id, Some(invited_user.as_model().id));
+
+    let response = owner.delete::<()>(url);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn resend_invitation() {
+    let (app, anon, owner, owner_token) = TestApp::init().with_token();
+    app.db_new_user("user_bar");
+    app.db(|conn| CrateBuilder::new("resend_invitation", owner.as_model().id).expect_build(conn));
+
+    owner_token.add_user_owner("resend_invitation", "user_bar");
+    let old_token = extract_token_from_invite_email(&app.as_inner().emails);
+
+    let url = "/api/v1/crates/resend_invitation/owner_invitations/user_bar/resend";
+    owner.put::<Value>(url, &[]).good();
+
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    let invites = emails.iter().filter(|m| m.subject.contains("invitation"));
+    assert_eq!(invites.count(), 2);
+
+    let new_token = app.db(|conn| {
+        use cargo_registry::schema::crate_owner_invitations;
+        crate_owner_invitations::table
+            .select(crate_owner_invitations::token)
+            .first::<String>(conn)
+            .unwrap()
+    });
+    assert_ne!(old_token, new_token);
+    assert!(emails.last().unwrap().body.contains(&new_token));
+
+    let response = anon.try_accept_ownership_invitation_by_token::<()>(&old_token);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    anon.accept_ownership_invitation_by_token(&new_token);
+    assert_eq!(anon.show_crate_owners("resend_invitation").users.len(), 2);
+}
+
+//
+// Tests for the roles of crate owners
+//
+
+fn change_owner_role<T: serde::de::DeserializeOwned>(
+    user: &impl RequestHelper,
+    krate_name: &str,
+    login: &str,
+    role: &str,
+) -> Response<T> {
+    let url = format!("/api/v1/crates/{krate_name}/owners/{login}/role");
+    let body = json!({ "role": role }).to_string();
+    user.put(&url, body.as_bytes())
+}
+
+#[test]
+fn owner_roles_are_listed_and_can_be_changed() {
+    let (app, anon, _, token) = TestApp::init().with_token();
+    let krate = app
+        .db(|conn| CrateBuilder::new("owner_roles", token.as_model().user_id).expect_build(conn));
+    let publisher = create_and_add_owner(&app, &token, "publisher", &krate);
+    let publisher = publisher.db_new_token("bar");
+
+    let json = change_owner_role::<Value>(&token, "owner_roles", "publisher", "publisher").good();
+    assert_eq!(
+        json["msg"],
+        "`publisher` now has the publisher role on crate owner_roles"
+    );
+
+    let owners = anon.show_crate_owners("owner_roles").users;
+    let role_of = |login: &str| {
+        let owner = owners.iter().find(|o| o.login == login).unwrap();
+        owner.role.clone().unwrap()
+    };
+    assert_eq!(role_of("foo"), "admin");
+    assert_eq!(role_of("publisher"), "publisher");
+
+    let response = publisher.add_named_owner("owner_roles", "foo");
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "only owners with the admin role have permission to modify owners" }] })
+    );
+
+    let response = change_owner_role::<()>(&publisher, "owner_roles", "publisher", "admin");
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "only owners with the admin role have permission to change roles" }] })
+    );
+
+    let response = change_owner_role::<()>(&token, "owner_roles", "publisher", "owner");
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "unknown owner role `owner`; valid roles are admin, publisher and yanker" }] })
+    );
+
+    let response = change_owner_role::<()>(&token, "owner_roles", "someone", "yanker");
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "`someone` is not an owner" }] })
+    );
+}
+
+#[test]
+fn last_admin_cannot_be_demoted_or_removed() {
+    let (app, _, _, token) = TestApp::init().with_token();
+    let krate =
+        app.db(|conn| CrateBuilder::new("last_admin", token.as_model().user_id).expect_build(conn));
+    create_and_add_owner(&app, &token, "publisher", &krate);
+    change_owner_role::<Value>(&token, "last_admin", "publisher", "publisher").good();
+
+    let response = change_owner_role::<()>(&token, "last_admin", "foo", "publisher");
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "cannot remove the last individual owner with the admin role, since nobody else would be able to manage the owners of the crate" }] })
+    );
+
+    let response = token.remove_named_owner("last_admin", "foo");
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "cannot remove the last individual owner with the admin role, since nobody else would be able to manage the owners of the crate" }] })
+    );
+
+    change_owner_role::<Value>(&token, "last_admin", "publisher", "admin").good();
+    change_owner_role::<Value>(&token, "last_admin", "foo", "publisher").good();
+}
+
+#[test]
+fn roles_limit_publishing_and_yanking() {
+    let (app, _, _, token) = TestApp::full().with_token();
+    token
+        .publish_crate(PublishBuilder::new("role_limits"))
+        .good();
+    let krate = app.db(|conn| Crate::by_name("role_limits").first::<Crate>(conn).unwrap());
+
+    let publisher = create_and_add_owner(&app, &token, "publisher", &krate);
+    let yanker = create_and_add_owner(&app, &token, "yanker", &krate);
+    change_owner_role::<Value>(&token, "role_limits", "publisher", "publisher").good();
+    change_owner_role::<Value>(&token, "role_limits", "yanker", "yanker").good();
+
+    let publisher = publisher.db_new_token("publish");
+    let crate_to_publish = PublishBuilder::new("role_limits").version("1.1.0");
+    publisher.publish_crate(crate_to_publish).good();
+    publisher.yank("role_limits", "1.1.0").good();
+
+    let yanker = yanker.db_new_token("yank");
+    let crate_to_publish = PublishBuilder::new("role_limits").version("1.2.0");
+    let response = yanker.publish_crate(crate_to_publish);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": YANKER_ROLE_ERROR_MESSAGE }] })
+    );
+    yanker.yank("role_limits", "1.0.0").good();
+    yanker.unyank("role_limits", "1.0.0").good();
+}
@@ -0,0 +1,137 @@
+use crate::util::{RequestHelper, Response, TestApp};
+use cargo_registry::rate_limiter::{LimitedAction, RateLimiter, RateLimiterConfig};
+use http::StatusCode;
+use std::time::Duration;
+
+static NEW_TOKEN: &[u8] = br#"{ "api_token": { "name": "bar" } }"#;
+
+fn rate_limiter(action: LimitedAction, burst: i32) -> RateLimiter {
+    let config = RateLimiterConfig {
+        rate: Duration::from_secs(60),
+        burst,
+    };
+    RateLimiter::new([(action, config)].into())
+}
+
+fn header<T>(response: &Response<T>, name: &str) -> String {
+    response.headers()[name].to_str().unwrap().to_string()
+}
+
+#[test]
+fn anonymous_requests_are_limited_by_ip() {
+    let (_, anon) = TestApp::init()
+        .with_config(|config| config.rate_limiter = rate_limiter(LimitedAction::Search, 2))
+        .empty();
+
+    for remaining in ["1", "0"] {
+        let response = anon.get::<()>("/api/v1/crates");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(&response, "ratelimit-limit"), "2");
+        assert_eq!(header(&response, "ratelimit-remaining"), remaining);
+        assert!(header(&response, "ratelimit-reset").parse::<u64>().unwrap() <= 60);
+    }
+
+    let response = anon.get::<()>("/api/v1/crates");
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&response, "ratelimit-remaining"), "0");
+    assert!(response.headers().contains_key("retry-after"));
+
+    let json = response.into_json();
+    let detail = json["errors"][0]["detail"].as_str().unwrap();
+    assert!(detail.starts_with("You have made too many search requests"));
+}
+
+#[test]
+fn anonymous_requests_without_ip_are_not_limited() {
+    let (_, anon) = TestApp::init()
+        .with_config(|config| config.rate_limiter = rate_limiter(LimitedAction::Search, 1))
+        .empty();
+
+    for _ in 0..3 {
+        let mut request = anon.get_request("/api/v1/crates");
+        request.header("x-real-ip", "");
+        let response = anon.run::<()>(request);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key("ratelimit-limit"));
+    }
+
+    // Requests with an IP address don't share a bucket with them
+    let response = anon.get::<()>("/api/v1/crates");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "ratelimit-remaining"), "0");
+}
+
+#[test]
+fn searches_do_not_store_buckets_in_the_database() {
+    use cargo_registry::schema::rate_limit_buckets;
+    use diesel::prelude::*;
+
+    let (app, anon) = TestApp::init()
+        .with_config(|config| config.rate_limiter = rate_limiter(LimitedAction::Search, 2))
+        .empty();
+
+    let response = anon.get::<()>("/api/v1/crates");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "ratelimit-remaining"), "1");
+
+    let buckets: i64 = app.db(|conn| rate_limit_buckets::table.count().get_result(conn).unwrap());
+    assert_eq!(buckets, 0);
+}
+
+#[test]
+fn unlimited_routes_have_no_headers() {
+    let (_, anon) = TestApp::init()
+        .with_config(|config| config.rate_limiter = rate_limiter(LimitedAction::Search, 2))
+        .empty();
+
+    let response = anon.get::<()>("/api/v1/summary");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("ratelimit-limit"));
+}
+
+#[test]
+fn authenticated_requests_are_limited_per_user() {
+    let (app, _, user) = TestApp::init()
+        .with_config(|config| config.rate_limiter = rate_limiter(LimitedAction::CreateToken, 1))
+        .with_user();
+    let other_user = app.db_new_user("other");
+
+    let response = user.put::<()>("/api/v1/me/tokens", NEW_TOKEN);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = user.put::<()>("/api/v1/me/tokens", NEW_TOKEN);
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let response = other_user.put::<()>("/api/v1/me/tokens", NEW_TOKEN);
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn overrides_increase_the_burst_of_a_user() {
+    use cargo_registry::schema::publish_rate_overrides;
+    use diesel::prelude::*;
+
+    let (app, _, user) = TestApp::init()
+        .with_config(|config| config.rate_limiter = rate_limiter(LimitedAction::CreateToken, 1))
+        .with_user();
+
+    app.db(|conn| {
+        diesel::insert_into(publish_rate_overrides::table)
+            .values((
+                publish_rate_overrides::user_id.eq(user.as_model().id),
+                publish_rate_overrides::action.eq("create_token"),
+                publish_rate_overrides::burst.eq(2),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    for _ in 0..2 {
+        let response = user.put::<()>("/api/v1/me/tokens", NEW_TOKEN);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(&response, "ratelimit-limit"), "2");
+    }
+
+    let response = user.put::<()>("/api/v1/me/tokens", NEW_TOKEN);
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
@@ -1,10 +1,11 @@
 use crate::builders::{CrateBuilder, VersionBuilder};
 use crate::util::{RequestHelper, TestApp};
 use crate::{new_category, new_user};
-use cargo_registry::models::Category;
+use cargo_registry::models::{Badge, Category, MaintenanceStatus};
 use cargo_registry::schema::crates;
 use diesel::{dsl::*, prelude::*, update};
 use http::StatusCode;
+use std::collections::BTreeMap;
 
 #[test]
 fn index() {
@@ -515,6 +516,107 @@ fn index_include_yanked() {
     assert_eq!(json.crates[2].name, "unyanked");
 }
 
+#[test]
+fn index_license_filter() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("mit", user.id)
+            .version(VersionBuilder::new("1.0.0").license(Some("MIT")))
+            .expect_build(conn);
+
+        CrateBuilder::new("dual", user.id)
+            .version(VersionBuilder::new("1.0.0").license(Some("MIT/Apache-2.0")))
+            .expect_build(conn);
+
+        CrateBuilder::new("mit_and_zlib", user.id)
+            .version(VersionBuilder::new("1.0.0").license(Some("MIT AND Zlib")))
+            .expect_build(conn);
+
+        CrateBuilder::new("relicensed", user.id)
+            .version(VersionBuilder::new("1.0.0").license(Some("MIT")))
+            .version(VersionBuilder::new("2.0.0").license(Some("GPL-3.0+")))
+            .version(
+                VersionBuilder::new("2.1.0")
+                    .license(Some("MIT"))
+                    .yanked(true),
+            )
+            .expect_build(conn);
+    });
+
+    let names = |query: &str| {
+        let json = anon.search(&format!("sort=alphabetical&{query}"));
+        json.crates.into_iter().map(|c| c.name).collect::<Vec<_>>()
+    };
+
+    assert_eq!(names("license=MIT"), ["dual", "mit"]);
+    assert_eq!(names("license=Apache-2.0"), ["dual"]);
+    assert_eq!(
+        names("license=MIT%20OR%20Zlib"),
+        ["dual", "mit", "mit_and_zlib"]
+    );
+    assert_eq!(names("license=GPL-3.0-or-later"), ["relicensed"]);
+    assert_eq!(names("license=GPL-3.0%2B"), ["relicensed"]);
+    assert_eq!(names("license=BSD-3-Clause"), Vec::<String>::new());
+
+    let response = anon.get_with_query::<()>("/api/v1/crates", "license=MIT%20AND%20Zlib");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the license filter only supports licenses combined with `OR`" }] })
+    );
+
+    let response = anon.get_with_query::<()>("/api/v1/crates", "license=apache");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "invalid license expression" }] })
+    );
+}
+
+#[test]
+fn index_include_deprecated() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    let badge = |status: &str| {
+        let attributes = BTreeMap::from([("status".to_string(), status.to_string())]);
+        BTreeMap::from([("maintenance".to_string(), attributes)])
+    };
+
+    app.db(|conn| {
+        let deprecated = CrateBuilder::new("deprecated", user.id).expect_build(conn);
+        Badge::update_crate(conn, &deprecated, &badge("deprecated")).unwrap();
+
+        let maintained = CrateBuilder::new("maintained", user.id).expect_build(conn);
+        Badge::update_crate(conn, &maintained, &badge("actively-developed")).unwrap();
+
+        CrateBuilder::new("without_badges", user.id).expect_build(conn);
+    });
+
+    let names = |query: &str| {
+        let json = anon.search(&format!("sort=alphabetical&{query}"));
+        json.crates.into_iter().map(|c| c.name).collect::<Vec<_>>()
+    };
+
+    assert_eq!(names(""), ["deprecated", "maintained", "without_badges"]);
+    assert_eq!(
+        names("include_deprecated=no"),
+        ["maintained", "without_badges"]
+    );
+
+    let json = anon.search("q=deprecated");
+    assert_eq!(
+        json.crates[0].badges.as_deref(),
+        Some(
+            &[Badge::Maintenance {
+                status: MaintenanceStatus::Deprecated
+            }][..]
+        )
+    );
+}
+
 #[test]
 fn yanked_versions_are_not_considered_for_max_version() {
     let (app, anon, user) = TestApp::init().with_user();
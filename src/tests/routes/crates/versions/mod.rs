@@ -2,4 +2,5 @@ mod authors;
 pub mod dependencies;
 pub mod download;
 mod read;
+mod readme;
 pub mod yank_unyank;
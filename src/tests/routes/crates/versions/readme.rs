@@ -0,0 +1,40 @@
+use crate::builders::{CrateBuilder, PublishBuilder, VersionBuilder};
+use crate::util::{RequestHelper, TestApp};
+use serde_json::{json, Value};
+
+#[test]
+fn toc_of_rendered_readme() {
+    let (_, anon, _, token) = TestApp::full().with_token();
+
+    let readme = "# foo_toc\n\n## Usage\n\n### With `serde`\n\n## Usage\n";
+    let crate_to_publish = PublishBuilder::new("foo_toc").readme(readme);
+    token.publish_crate(crate_to_publish).good();
+
+    let json: Value = anon.get("/api/v1/crates/foo_toc/1.0.0/readme/toc").good();
+    assert_eq!(
+        json,
+        json!({
+            "toc": [
+                { "level": 1, "text": "foo_toc", "anchor": "user-content-foo_toc" },
+                { "level": 2, "text": "Usage", "anchor": "user-content-usage" },
+                { "level": 3, "text": "With serde", "anchor": "user-content-with-serde" },
+                { "level": 2, "text": "Usage", "anchor": "user-content-usage-1" },
+            ]
+        })
+    );
+}
+
+#[test]
+fn toc_of_version_without_rendered_readme_is_not_found() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_no_toc", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    anon.get::<()>("/api/v1/crates/foo_no_toc/1.0.0/readme/toc")
+        .assert_not_found();
+}
@@ -7,6 +7,7 @@ use cargo_registry::models::{Crate, NewTeam};
 
 use diesel::*;
 use http::StatusCode;
+use serde_json::Value;
 
 impl crate::util::MockAnonymousUser {
     /// List the team owners of the specified crate.
@@ -431,3 +432,130 @@ fn crates_by_team_id_not_including_deleted_owners() {
     let json = anon.search(&format!("team_id={}", team.id));
     assert_eq!(json.crates.len(), 0);
 }
+
+#[test]
+fn add_team_with_invitation() {
+    let (app, anon) = TestApp::init()
+        .with_config(|config| config.require_team_owner_invitations = true)
+        .empty();
+    let owner = app.db_new_user("user-org-owner");
+    let token = owner.db_new_token("arbitrary token name");
+    let maintainer = app.db_new_user("user-all-teams");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_team_invitation", owner.as_model().id).expect_build(conn);
+    });
+
+    let json = token
+        .add_named_owner("foo_team_invitation", "github:test-org:core")
+        .into_json();
+    assert_eq!(
+        json["msg"],
+        "team github:test-org:core has been invited to be an owner of crate foo_team_invitation"
+    );
+    let json = anon.crate_owner_teams("foo_team_invitation").good();
+    assert_eq!(json.teams.len(), 0);
+
+    let json = owner
+        .get::<Value>("/api/v1/crates/foo_team_invitation/owner_invitations")
+        .good();
+    assert_eq!(json["invitations"][0]["invitee"]["kind"], "team");
+    assert_eq!(
+        json["invitations"][0]["invitee"]["login"],
+        "github:test-org:core"
+    );
+
+    let url = "/api/v1/crates/foo_team_invitation/owner_invitations/github:test-org:core/accept";
+    let json = maintainer.put::<Value>(url, &[]).good();
+    assert_eq!(json["team_owner_invitation"]["accepted"], true);
+
+    let json = anon.crate_owner_teams("foo_team_invitation").good();
+    assert_eq!(json.teams.len(), 1);
+    assert_eq!(json.teams[0].login, "github:test-org:core");
+}
+
+#[test]
+fn resend_team_invitation() {
+    use cargo_registry::schema::crate_owner_team_invitations;
+    use chrono::NaiveDateTime;
+    use diesel::dsl::{now, IntervalDsl};
+
+    let (app, _) = TestApp::init()
+        .with_config(|config| config.require_team_owner_invitations = true)
+        .empty();
+    let owner = app.db_new_user("user-org-owner");
+    let token = owner.db_new_token("arbitrary token name");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_team_resend", owner.as_model().id).expect_build(conn);
+    });
+    token
+        .add_named_owner("foo_team_resend", "github:test-org:core")
+        .good();
+
+    let created_at = app.db(|conn| {
+        diesel::update(crate_owner_team_invitations::table)
+            .set(crate_owner_team_invitations::created_at.eq(now - 3.days()))
+            .returning(crate_owner_team_invitations::created_at)
+            .get_result::<NaiveDateTime>(conn)
+            .unwrap()
+    });
+
+    let url = "/api/v1/crates/foo_team_resend/owner_invitations/github:test-org:core/resend";
+    let json = owner.put::<Value>(url, &[]).good();
+    assert_eq!(
+        json["msg"],
+        "the invitation of github:test-org:core to crate foo_team_resend has been renewed"
+    );
+
+    let renewed_at = app.db(|conn| {
+        crate_owner_team_invitations::table
+            .select(crate_owner_team_invitations::created_at)
+            .first::<NaiveDateTime>(conn)
+            .unwrap()
+    });
+    assert!(renewed_at > created_at);
+
+    let json = owner
+        .get::<Value>("/api/v1/crates/foo_team_resend/owner_invitations")
+        .good();
+    assert_eq!(
+        json["invitations"][0]["invited_by_username"],
+        "user-org-owner"
+    );
+}
+
+#[test]
+fn team_invitation_requires_team_maintainer() {
+    let (app, anon) = TestApp::init()
+        .with_config(|config| config.require_team_owner_invitations = true)
+        .empty();
+    let owner = app.db_new_user("user-all-teams");
+    let token = owner.db_new_token("arbitrary token name");
+    let member = app.db_new_user("user-one-team");
+    let org_owner = app.db_new_user("user-org-owner");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_team_maintainer", owner.as_model().id).expect_build(conn);
+    });
+
+    token
+        .add_named_owner("foo_team_maintainer", "github:test-org:all")
+        .good();
+
+    let url = "/api/v1/crates/foo_team_maintainer/owner_invitations/github:test-org:all";
+    let response = member.put::<()>(&format!("{url}/accept"), &[]);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let json = org_owner
+        .put::<Value>(&format!("{url}/decline"), &[])
+        .good();
+    assert_eq!(json["team_owner_invitation"]["accepted"], false);
+
+    let json = anon.crate_owner_teams("foo_team_maintainer").good();
+    assert_eq!(json.teams.len(), 0);
+    let json = owner
+        .get::<Value>("/api/v1/crates/foo_team_maintainer/owner_invitations")
+        .good();
+    assert_eq!(json["invitations"], json!([]));
+}
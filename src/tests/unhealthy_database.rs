@@ -2,6 +2,7 @@ use crate::{
     builders::CrateBuilder,
     util::{MockAnonymousUser, RequestHelper, TestApp, TestDatabase},
 };
+use cargo_registry::models::NewBlockedTrafficRule;
 use http::StatusCode;
 use std::time::Duration;
 
@@ -70,6 +71,39 @@ fn assert_unconditional_redirects(anon: &MockAnonymousUser) {
         .assert_redirect_ends_with("/awesome-project/awesome-project-1.0.0.crate");
 }
 
+#[test]
+fn blocked_traffic_rules_are_not_reloaded_after_a_failed_load() {
+    let (app, anon) = TestApp::init()
+        .with_database(TestDatabase::SlowRealPool { replica: false })
+        .empty();
+
+    // Without a database the rules can't be loaded, and no requests are blocked
+    app.primary_db_chaosproxy().break_networking();
+    anon.get::<()>("/api/v1/crates/crate_name/1.0.0/download")
+        .assert_redirect_ends_with("/crate_name/crate_name-1.0.0.crate");
+
+    app.primary_db_chaosproxy().restore_networking();
+    app.as_inner()
+        .primary_database
+        .wait_until_healthy(DB_HEALTHY_TIMEOUT)
+        .expect("the database did not return healthy");
+
+    app.db(|conn| {
+        NewBlockedTrafficRule {
+            ip_range: Some("127.0.0.0/24"),
+            user_agent_pattern: None,
+            reason: "abuse",
+            expires_at: None,
+        }
+        .save(conn)
+        .unwrap();
+    });
+
+    // The empty set of rules stays cached until it expires
+    let response = anon.get::<()>("/api/v1/crates/crate_name/1.0.0/download");
+    assert_ne!(response.status(), StatusCode::FORBIDDEN);
+}
+
 #[test]
 fn http_error_with_unhealthy_database() {
     let (app, anon) = TestApp::init()
@@ -16,11 +16,13 @@ pub(crate) const MOCK_GITHUB_DATA: MockData = MockData {
                 id: 2000,
                 name: "all",
                 members: &["user-all-teams", "user-one-team"],
+                maintainers: &[],
             },
             MockTeam {
                 id: 2001,
                 name: "core",
                 members: &["user-all-teams"],
+                maintainers: &["user-all-teams"],
             },
         ],
     }],
@@ -130,8 +132,13 @@ impl GitHubClient for MockGitHubClient {
             .find(|team| team.id == team_id)
             .ok_or_else(not_found)?;
         if team.members.contains(&username) {
+            let role = match team.maintainers.contains(&username) {
+                true => "maintainer",
+                false => "member",
+            };
             Ok(GitHubTeamMembership {
                 state: "active".into(),
+                role: role.into(),
             })
         } else {
             Err(not_found())
@@ -198,6 +205,7 @@ struct MockTeam {
     id: i32,
     name: &'static str,
     members: &'static [&'static str],
+    maintainers: &'static [&'static str],
 }
 
 struct MockPublicKey {
@@ -9,7 +9,9 @@ use std::{rc::Rc, sync::Arc, time::Duration};
 
 use crate::util::github::{MockGitHubClient, MOCK_GITHUB_DATA};
 use cargo_registry::models::token::{CrateScope, EndpointScope};
+use cargo_registry::swirl::errors::FailedJobsError;
 use cargo_registry::swirl::Runner;
+use cargo_registry::worker::IndexBatchConfig;
 use diesel::PgConnection;
 use reqwest::{blocking::Client, Proxy};
 use std::collections::HashSet;
@@ -72,6 +74,7 @@ impl TestApp {
             proxy: None,
             bomb: None,
             index: None,
+            index_batch: None,
             build_job_runner: false,
             test_database: TestDatabase::TestPool,
         }
@@ -141,13 +144,18 @@ impl TestApp {
 
     #[track_caller]
     pub fn run_pending_background_jobs(&self) {
+        self.try_run_pending_background_jobs()
+            .expect("Could not determine if jobs failed");
+    }
+
+    /// Runs all pending background jobs, returning an error if any of them failed
+    #[track_caller]
+    pub fn try_run_pending_background_jobs(&self) -> Result<(), FailedJobsError> {
         let runner = &self.0.runner;
         let runner = runner.as_ref().expect("Index has not been initialized");
 
         runner.run_all_pending_jobs().expect("Could not run jobs");
-        runner
-            .check_for_failed_jobs()
-            .expect("Could not determine if jobs failed");
+        runner.check_for_failed_jobs()
     }
 
     /// Obtain a reference to the inner `App` value
@@ -189,6 +197,7 @@ pub struct TestAppBuilder {
     proxy: Option<String>,
     bomb: Option<record::Bomb>,
     index: Option<UpstreamIndex>,
+    index_batch: Option<IndexBatchConfig>,
     build_job_runner: bool,
     test_database: TestDatabase,
 }
@@ -238,7 +247,11 @@ impl TestAppBuilder {
                 app.config.uploader().clone(),
                 app.http_client().clone(),
                 None,
-            );
+            )
+            .with_index_batch(self.index_batch)
+            .with_mirror(app.config.mirror.clone())
+            .with_image_proxy(app.config.image_proxy.clone())
+            .with_code_highlighting(app.config.highlight_code_blocks);
 
             Some(Runner::test_runner(
                 environment,
@@ -316,6 +329,12 @@ impl TestAppBuilder {
         self
     }
 
+    /// Coalesces index jobs enqueued within `window` into a single commit
+    pub fn with_index_batch(mut self, window: Duration, max_size: i64) -> Self {
+        self.index_batch = Some(IndexBatchConfig { window, max_size });
+        self
+    }
+
     pub fn with_job_runner(mut self) -> Self {
         self.build_job_runner = true;
         self
@@ -340,16 +359,23 @@ fn simple_config() -> config::Server {
         max_upload_size: 3000,
         max_unpack_size: 2000,
         publish_rate_limit: Default::default(),
+        rate_limiter: Default::default(),
         new_version_rate_limit: Some(10),
         blocked_traffic: Default::default(),
+        blocked_traffic_rules_ttl: Duration::from_secs(30),
         max_allowed_page_offset: 200,
         page_offset_ua_blocklist: vec![],
         page_offset_cidr_blocklist: vec![],
         excluded_crate_names: vec![],
+        allowed_dependency_registries: vec![],
         domain_name: "crates.io".into(),
         allowed_origins: Default::default(),
         downloads_persist_interval_ms: 1000,
         ownership_invitations_expiration_days: 30,
+        crate_deletion_max_age_hours: 72,
+        crate_deletion_max_downloads: 500,
+        deleted_crate_name_cooldown_days: 30,
+        require_team_owner_invitations: false,
         metrics_authorization_token: None,
         use_test_database_pool: true,
         instance_metrics_log_every_seconds: None,
@@ -359,6 +385,10 @@ fn simple_config() -> config::Server {
         version_id_cache_ttl: Duration::from_secs(5 * 60),
         cdn_user_agent: "Amazon CloudFront".to_string(),
         balance_capacity: BalanceCapacityConfig::for_testing(),
+        mirror: None,
+        image_proxy: None,
+        highlight_code_blocks: false,
+        license_policy: Default::default(),
     }
 }
 
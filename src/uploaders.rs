@@ -1,15 +1,13 @@
 use anyhow::Result;
-use reqwest::{blocking::Client, header};
+use reqwest::{blocking::Client, header, StatusCode};
 
 use crate::util::errors::{internal, AppResult};
 
 use std::env;
 use std::fs::{self, File};
-use std::io::{Cursor, SeekFrom};
+use std::io::{Cursor, ErrorKind, SeekFrom};
 use std::path::PathBuf;
 
-use crate::models::Crate;
-
 const CACHE_CONTROL_IMMUTABLE: &str = "public,max-age=31536000,immutable";
 const CACHE_CONTROL_README: &str = "public,max-age=604800";
 const CACHE_CONTROL_INDEX: &str = "public,max-age=600";
@@ -39,27 +37,39 @@ impl Uploader {
     ///
     /// The function doesn't check for the existence of the file.
     pub fn crate_location(&self, crate_name: &str, version: &str) -> String {
-        match *self {
-            Uploader::S3 {
-                ref bucket,
-                ref cdn,
-                ..
-            } => {
-                let host = match *cdn {
-                    Some(ref s) => s.clone(),
-                    None => bucket.host(),
-                };
-                let path = Uploader::crate_path(crate_name, version);
-                format!("https://{host}/{path}")
-            }
-            Uploader::Local => format!("/{}", Uploader::crate_path(crate_name, version)),
-        }
+        self.location(&Uploader::crate_path(crate_name, version))
     }
 
     /// Returns the URL of an uploaded crate's version readme.
     ///
     /// The function doesn't check for the existence of the file.
     pub fn readme_location(&self, crate_name: &str, version: &str) -> String {
+        self.location(&Uploader::readme_path(crate_name, version))
+    }
+
+    /// Returns the URL of an uploaded crate's version changelog.
+    ///
+    /// The function doesn't check for the existence of the file.
+    pub fn changelog_location(&self, crate_name: &str, version: &str) -> String {
+        self.location(&Uploader::changelog_path(crate_name, version))
+    }
+
+    /// Returns the URL of an uploaded crate's version license file.
+    ///
+    /// The function doesn't check for the existence of the file.
+    pub fn license_location(&self, crate_name: &str, version: &str) -> String {
+        self.location(&Uploader::license_path(crate_name, version))
+    }
+
+    /// Returns the URL of an image cached by the image proxy.
+    ///
+    /// The function doesn't check for the existence of the file.
+    pub fn proxied_image_location(&self, digest: &str) -> String {
+        self.location(&Uploader::proxied_image_path(digest))
+    }
+
+    /// Returns the URL of an uploaded file from its internal path.
+    fn location(&self, path: &str) -> String {
         match *self {
             Uploader::S3 {
                 ref bucket,
@@ -70,10 +80,9 @@ impl Uploader {
                     Some(ref s) => s.clone(),
                     None => bucket.host(),
                 };
-                let path = Uploader::readme_path(crate_name, version);
                 format!("https://{host}/{path}")
             }
-            Uploader::Local => format!("/{}", Uploader::readme_path(crate_name, version)),
+            Uploader::Local => format!("/{path}"),
         }
     }
 
@@ -87,6 +96,21 @@ impl Uploader {
         format!("readmes/{name}/{name}-{version}.html")
     }
 
+    /// Returns the internal path of an uploaded crate's version changelog.
+    fn changelog_path(name: &str, version: &str) -> String {
+        format!("changelogs/{name}/{name}-{version}.html")
+    }
+
+    /// Returns the internal path of an uploaded crate's version license file.
+    fn license_path(name: &str, version: &str) -> String {
+        format!("licenses/{name}/{name}-{version}.html")
+    }
+
+    /// Returns the internal path of an image cached by the image proxy.
+    fn proxied_image_path(digest: &str) -> String {
+        format!("image-proxy/{digest}")
+    }
+
     /// Returns the internal path of an uploaded crate's index file.
     fn index_path(name: &str) -> String {
         cargo_registry_index::Repository::relative_index_file_for_url(name)
@@ -182,15 +206,64 @@ impl Uploader {
         Ok(())
     }
 
+    /// Downloads a file using the configured uploader (either `S3`, `Local`).
+    ///
+    /// It returns `None` if the file does not exist, or if the requested
+    /// bucket is not configured.
+    pub fn download(
+        &self,
+        client: &Client,
+        path: &str,
+        upload_bucket: UploadBucket,
+    ) -> Result<Option<Vec<u8>>> {
+        match *self {
+            Uploader::S3 {
+                ref bucket,
+                ref index_bucket,
+                ..
+            } => {
+                let bucket = match upload_bucket {
+                    UploadBucket::Default => Some(bucket),
+                    UploadBucket::Index => index_bucket.as_ref(),
+                };
+
+                match bucket.map(|bucket| bucket.get(client, path)) {
+                    Some(Ok(response)) => Ok(Some(response.bytes()?.to_vec())),
+                    Some(Err(e)) if e.status() == Some(StatusCode::NOT_FOUND) => Ok(None),
+                    Some(Err(e)) => Err(e.into()),
+                    None => Ok(None),
+                }
+            }
+            Uploader::Local => {
+                let filename = Self::local_uploads_path(path, upload_bucket);
+                match fs::read(filename) {
+                    Ok(contents) => Ok(Some(contents)),
+                    Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if index files are uploaded to an HTTP-based index.
+    pub(crate) fn has_http_index(&self) -> bool {
+        match *self {
+            Uploader::S3 {
+                ref index_bucket, ..
+            } => index_bucket.is_some(),
+            Uploader::Local => true,
+        }
+    }
+
     /// Uploads a crate and returns the checksum of the uploaded crate file.
     pub fn upload_crate(
         &self,
         http_client: &Client,
         body: Vec<u8>,
-        krate: &Crate,
+        crate_name: &str,
         vers: &semver::Version,
     ) -> AppResult<()> {
-        let path = Uploader::crate_path(&krate.name, &vers.to_string());
+        let path = Uploader::crate_path(crate_name, &vers.to_string());
         let content = Cursor::new(body);
         let mut extra_headers = header::HeaderMap::new();
         extra_headers.insert(
@@ -209,6 +282,17 @@ impl Uploader {
         Ok(())
     }
 
+    /// Downloads a crate file, or returns `None` if it does not exist.
+    pub(crate) fn download_crate(
+        &self,
+        http_client: &Client,
+        crate_name: &str,
+        vers: &str,
+    ) -> Result<Option<Vec<u8>>> {
+        let path = Uploader::crate_path(crate_name, vers);
+        self.download(http_client, &path, UploadBucket::Default)
+    }
+
     pub(crate) fn upload_readme(
         &self,
         http_client: &Client,
@@ -217,7 +301,39 @@ impl Uploader {
         readme: String,
     ) -> Result<()> {
         let path = Uploader::readme_path(crate_name, vers);
-        let content = Cursor::new(readme);
+        self.upload_rendered_document(http_client, &path, readme)
+    }
+
+    pub(crate) fn upload_changelog(
+        &self,
+        http_client: &Client,
+        crate_name: &str,
+        vers: &str,
+        changelog: String,
+    ) -> Result<()> {
+        let path = Uploader::changelog_path(crate_name, vers);
+        self.upload_rendered_document(http_client, &path, changelog)
+    }
+
+    pub(crate) fn upload_license(
+        &self,
+        http_client: &Client,
+        crate_name: &str,
+        vers: &str,
+        license: String,
+    ) -> Result<()> {
+        let path = Uploader::license_path(crate_name, vers);
+        self.upload_rendered_document(http_client, &path, license)
+    }
+
+    /// Uploads a readme, changelog or license file rendered to HTML.
+    fn upload_rendered_document(
+        &self,
+        http_client: &Client,
+        path: &str,
+        html: String,
+    ) -> Result<()> {
+        let content = Cursor::new(html);
         let mut extra_headers = header::HeaderMap::new();
         extra_headers.insert(
             header::CACHE_CONTROL,
@@ -225,7 +341,7 @@ impl Uploader {
         );
         self.upload(
             http_client,
-            &path,
+            path,
             content,
             "text/html",
             extra_headers,
@@ -234,6 +350,33 @@ impl Uploader {
         Ok(())
     }
 
+    /// Uploads an image fetched by the image proxy. Images are stored under the digest
+    /// of their content, so they never change.
+    pub(crate) fn upload_proxied_image(
+        &self,
+        http_client: &Client,
+        digest: &str,
+        content_type: &str,
+        image: Vec<u8>,
+    ) -> Result<()> {
+        let path = Uploader::proxied_image_path(digest);
+        let content = Cursor::new(image);
+        let mut extra_headers = header::HeaderMap::new();
+        extra_headers.insert(
+            header::CACHE_CONTROL,
+            header::HeaderValue::from_static(CACHE_CONTROL_IMMUTABLE),
+        );
+        self.upload(
+            http_client,
+            &path,
+            content,
+            content_type,
+            extra_headers,
+            UploadBucket::Default,
+        )?;
+        Ok(())
+    }
+
     pub(crate) fn upload_index(
         &self,
         http_client: &Client,
@@ -258,12 +401,52 @@ impl Uploader {
         Ok(())
     }
 
+    /// Deletes the uploaded crate file of a version.
+    pub(crate) fn delete_crate(
+        &self,
+        http_client: &Client,
+        crate_name: &str,
+        vers: &str,
+    ) -> Result<()> {
+        let path = Uploader::crate_path(crate_name, vers);
+        self.delete(http_client, &path, UploadBucket::Default)
+    }
+
     pub(crate) fn delete_index(&self, http_client: &Client, crate_name: &str) -> Result<()> {
         let path = Uploader::index_path(crate_name);
         self.delete(http_client, &path, UploadBucket::Index)?;
         Ok(())
     }
 
+    /// Deletes the uploaded crate file and rendered documents of a version.
+    pub(crate) fn delete_version_files(
+        &self,
+        http_client: &Client,
+        crate_name: &str,
+        vers: &str,
+    ) -> Result<()> {
+        let paths = [
+            Uploader::crate_path(crate_name, vers),
+            Uploader::readme_path(crate_name, vers),
+            Uploader::changelog_path(crate_name, vers),
+            Uploader::license_path(crate_name, vers),
+        ];
+        for path in paths {
+            self.delete(http_client, &path, UploadBucket::Default)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn download_index(
+        &self,
+        http_client: &Client,
+        crate_name: &str,
+    ) -> Result<Option<String>> {
+        let path = Uploader::index_path(crate_name);
+        let contents = self.download(http_client, &path, UploadBucket::Index)?;
+        Ok(contents.map(String::from_utf8).transpose()?)
+    }
+
     pub(crate) fn sync_index(
         &self,
         http_client: &Client,
@@ -46,6 +46,27 @@ impl CargoVcsInfo {
     }
 }
 
+/// A text file of an uploaded crate that is rendered next to its readme
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PackageDocument {
+    /// Path to the file within the package
+    pub path: String,
+    pub text: String,
+}
+
+/// The documents found in an uploaded crate file, besides the readme
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PackageDocuments {
+    pub changelog: Option<PackageDocument>,
+    pub license: Option<PackageDocument>,
+}
+
+impl PackageDocuments {
+    pub fn is_empty(&self) -> bool {
+        self.changelog.is_none() && self.license.is_none()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::CargoVcsInfo;
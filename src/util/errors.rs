@@ -31,8 +31,8 @@ mod json;
 
 pub use json::TOKEN_FORMAT_ERROR;
 pub(crate) use json::{
-    InsecurelyGeneratedTokenRevoked, MetricsDisabled, NotFound, OwnershipInvitationExpired,
-    ReadOnlyMode, RouteBlocked, TooManyRequests,
+    InsecurelyGeneratedTokenRevoked, MetricsDisabled, MirrorReadOnly, NotFound,
+    OwnershipInvitationExpired, RateLimited, ReadOnlyMode, RouteBlocked, TooManyRequests,
 };
 
 pub type BoxedAppError = Box<dyn AppError>;
@@ -46,6 +46,11 @@ pub fn cargo_err<S: ToString + ?Sized>(error: &S) -> BoxedAppError {
     Box::new(json::Ok(error.to_string()))
 }
 
+/// Whether the error was created with `cargo_err`
+pub fn is_cargo_err(error: &BoxedAppError) -> bool {
+    error.is::<json::Ok>()
+}
+
 // The following are intended to be used for errors being sent back to the Ember
 // frontend, not to cargo as cargo does not handle non-200 response codes well
 // (see <https://github.com/rust-lang/cargo/issues/3995>), but Ember requires
@@ -3,8 +3,9 @@ use axum::Json;
 use std::fmt;
 
 use super::{AppError, BoxedAppError, InternalAppErrorStatic};
+use crate::rate_limiter::LimitedAction;
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use http::{header, StatusCode};
 
 /// Generates a response with the provided status and description as JSON
@@ -153,6 +154,49 @@ impl fmt::Display for TooManyRequests {
     }
 }
 
+#[derive(Debug)]
+pub(crate) struct RateLimited {
+    pub action: LimitedAction,
+    pub limit: i32,
+    pub retry_after: NaiveDateTime,
+}
+
+impl AppError for RateLimited {
+    fn response(&self) -> Response {
+        const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+        let retry_after = self.retry_after.format(HTTP_DATE_FORMAT);
+        let reset = (self.retry_after - Utc::now().naive_utc())
+            .num_seconds()
+            .max(0);
+
+        let detail = format!(
+            "You have made too many {} in a short period of time. \
+             Please try again after {retry_after} or email help@crates.io \
+             to have your limit increased.",
+            self.action.description()
+        );
+        let mut response = json_error(&detail, StatusCode::TOO_MANY_REQUESTS);
+        let headers = response.headers_mut();
+        headers.insert(
+            header::RETRY_AFTER,
+            retry_after
+                .to_string()
+                .try_into()
+                .expect("HTTP_DATE_FORMAT contains invalid char"),
+        );
+        headers.insert("ratelimit-limit", self.limit.into());
+        headers.insert("ratelimit-remaining", 0.into());
+        headers.insert("ratelimit-reset", reset.into());
+        response
+    }
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rate limit exceeded for {}", self.action.as_str())
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct InsecurelyGeneratedTokenRevoked;
 
@@ -281,3 +325,22 @@ impl IntoResponse for RouteBlocked {
         (StatusCode::SERVICE_UNAVAILABLE, body).into_response()
     }
 }
+
+#[derive(Debug)]
+pub(crate) struct MirrorReadOnly;
+
+impl fmt::Display for MirrorReadOnly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(
+            "This registry is a read-only mirror of an upstream registry. \
+             Please publish and manage your crates on the upstream registry instead.",
+        )
+    }
+}
+
+impl IntoResponse for MirrorReadOnly {
+    fn into_response(self) -> Response {
+        let body = Json(json!({ "errors": [{ "detail": self.to_string() }] }));
+        (StatusCode::FORBIDDEN, body).into_response()
+    }
+}
@@ -1,11 +1,12 @@
+use cargo_registry_markdown::Heading;
 use chrono::NaiveDateTime;
 use url::Url;
 
 use crate::github;
 use crate::models::{
-    Category, Crate, CrateOwnerInvitation, CreatedApiToken, Dependency, DependencyKind, Keyword,
-    Owner, ReverseDependency, Team, TopVersions, User, Version, VersionDownload,
-    VersionOwnerAction,
+    Badge, Category, Crate, CrateOwnerInvitation, CreatedApiToken, Dependency, DependencyKind,
+    Keyword, Owner, OwnerRole, ReverseDependency, Team, TopVersions, User, Version,
+    VersionDownload, VersionOwnerAction,
 };
 use crate::util::rfc3339;
 
@@ -106,6 +107,44 @@ pub struct EncodableCrateOwnerInvitation {
     pub expires_at: NaiveDateTime,
 }
 
+/// A pending ownership invitation of a user or a team, as listed for the owners of the crate.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EncodableSentCrateOwnerInvitation {
+    pub invitee: EncodableOwner,
+    pub inviter_id: i32,
+    pub invited_by_username: String,
+    pub crate_id: i32,
+    pub crate_name: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339")]
+    pub expires_at: NaiveDateTime,
+}
+
+/// A pending request to transfer a crate, as listed for its recipient.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EncodableCrateTransferRequest {
+    pub crate_id: i32,
+    pub crate_name: String,
+    pub requested_by_username: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339")]
+    pub expires_at: NaiveDateTime,
+}
+
+/// A pending request to adopt an orphaned crate, as listed for admins.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EncodableCrateAdoptionRequest {
+    pub id: i32,
+    pub crate_id: i32,
+    pub crate_name: String,
+    pub requested_by_username: String,
+    pub reason: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
 #[derive(Deserialize, Serialize, Debug, Copy, Clone)]
 pub struct InvitationResponse {
     pub crate_id: i32,
@@ -124,6 +163,9 @@ pub struct EncodableDependency {
     pub target: Option<String>,
     pub kind: DependencyKind,
     pub downloads: i32,
+    /// The index URL of the registry hosting the dependency, if it is not
+    /// hosted on this registry.
+    pub registry: Option<String>,
 }
 
 impl EncodableDependency {
@@ -149,6 +191,7 @@ impl EncodableDependency {
             target: dependency.target,
             kind: dependency.kind,
             downloads: downloads.unwrap_or(0),
+            registry: dependency.registry,
         }
     }
 }
@@ -205,7 +248,7 @@ pub struct EncodableCrate {
     pub versions: Option<Vec<i32>>,
     pub keywords: Option<Vec<String>>,
     pub categories: Option<Vec<String>>,
-    pub badges: Option<Vec<()>>,
+    pub badges: Option<Vec<Badge>>,
     #[serde(with = "rfc3339")]
     pub created_at: NaiveDateTime,
     // NOTE: Used by shields.io, altering `downloads` requires a PR with shields.io
@@ -231,7 +274,7 @@ impl EncodableCrate {
         versions: Option<Vec<i32>>,
         keywords: Option<&[Keyword]>,
         categories: Option<&[Category]>,
-        badges: Option<Vec<()>>,
+        badges: Option<Vec<Badge>>,
         exact_match: bool,
         recent_downloads: Option<i64>,
     ) -> Self {
@@ -252,7 +295,6 @@ impl EncodableCrate {
         };
         let keyword_ids = keywords.map(|kws| kws.iter().map(|kw| kw.keyword.clone()).collect());
         let category_ids = categories.map(|cats| cats.iter().map(|cat| cat.slug.clone()).collect());
-        let badges = badges.map(|_| vec![]);
         let documentation = Self::remove_blocked_documentation_urls(documentation);
 
         let max_version = top_versions
@@ -313,7 +355,7 @@ impl EncodableCrate {
     pub fn from_minimal(
         krate: Crate,
         top_versions: Option<&TopVersions>,
-        badges: Option<Vec<()>>,
+        badges: Option<Vec<Badge>>,
         exact_match: bool,
         recent_downloads: Option<i64>,
     ) -> Self {
@@ -391,6 +433,8 @@ pub struct EncodableOwner {
     pub url: Option<String>,
     pub name: Option<String>,
     pub avatar: Option<String>,
+    /// The role of the owner on a crate, if the owner is listed for a crate
+    pub role: Option<String>,
 }
 
 impl From<Owner> for EncodableOwner {
@@ -411,6 +455,7 @@ impl From<Owner> for EncodableOwner {
                     url: Some(url),
                     name,
                     kind: String::from("user"),
+                    role: None,
                 }
             }
             Owner::Team(Team {
@@ -428,12 +473,23 @@ impl From<Owner> for EncodableOwner {
                     avatar,
                     name,
                     kind: String::from("team"),
+                    role: None,
                 }
             }
         }
     }
 }
 
+impl From<(Owner, OwnerRole)> for EncodableOwner {
+    fn from((owner, role): (Owner, OwnerRole)) -> Self {
+        let role: &'static str = role.into();
+        Self {
+            role: Some(role.to_string()),
+            ..owner.into()
+        }
+    }
+}
+
 #[derive(Serialize, Debug)]
 pub struct EncodableTeam {
     pub id: i32,
@@ -684,7 +740,67 @@ pub struct GoodCrate {
     pub warnings: PublishWarnings,
 }
 
+/// The crates of a batch publish, in the order in which they were published.
 #[derive(Serialize, Deserialize, Debug)]
+pub struct GoodCrateBatch {
+    pub crates: Vec<GoodCrate>,
+}
+
+/// The outcome of a publish with the `dry_run` query parameter.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PublishDryRun {
+    /// The crate as it would have been published, if it got that far.
+    #[serde(rename = "crate")]
+    pub krate: Option<EncodableCrate>,
+    pub warnings: PublishWarnings,
+    /// Omitted if there are no errors, since cargo treats any `errors` in a
+    /// response as a failure.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<EncodableErrorDetail>,
+}
+
+/// The progress of publishing a version, see `PublishUpload`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct EncodablePublishStatus {
+    pub crate_name: String,
+    pub version: String,
+    /// One of `pending`, `published` or `failed`.
+    pub state: String,
+    pub error: Option<String>,
+    pub in_git_index: bool,
+    pub in_http_index: bool,
+}
+
+/// A heading of a rendered readme, as an entry of its table of contents.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct EncodableReadmeHeading {
+    pub level: u8,
+    pub text: String,
+    /// The `id` of the heading's anchor in the rendered readme.
+    pub anchor: String,
+}
+
+impl From<Heading> for EncodableReadmeHeading {
+    fn from(heading: Heading) -> Self {
+        let Heading {
+            level,
+            text,
+            anchor,
+        } = heading;
+        Self {
+            level,
+            text,
+            anchor,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct EncodableErrorDetail {
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct PublishWarnings {
     pub invalid_categories: Vec<String>,
     pub invalid_badges: Vec<String>,
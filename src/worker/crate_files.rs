@@ -0,0 +1,28 @@
+//! Remove the files of deleted crates from storage.
+
+use crate::swirl::PerformError;
+
+use crate::background_jobs::{DeleteCrateFilesJob, Environment, Job};
+
+/// Deletes the uploaded crate files and rendered readmes of all versions of
+/// a deleted crate.
+#[instrument(skip(env))]
+pub fn perform_delete_crate_files(
+    env: &Environment,
+    crate_name: &str,
+    versions: &[String],
+) -> Result<(), PerformError> {
+    for vers in versions {
+        info!(%vers, "Deleting crate file and readme");
+        env.uploader
+            .delete_version_files(env.http_client(), crate_name, vers)?;
+    }
+    Ok(())
+}
+
+pub fn delete_crate_files(crate_name: String, versions: Vec<String>) -> Job {
+    Job::DeleteCrateFiles(DeleteCrateFilesJob {
+        crate_name,
+        versions,
+    })
+}
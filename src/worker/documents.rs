@@ -0,0 +1,58 @@
+//! Render the changelog and license file of a package to HTML.
+
+use crate::swirl::PerformError;
+use cargo_registry_markdown::render_text;
+use diesel::prelude::*;
+
+use crate::background_jobs::{Environment, Job, RenderAndUploadDocumentsJob};
+use crate::schema::{crates, versions};
+use crate::util::{PackageDocument, PackageDocuments};
+
+pub fn perform_render_and_upload_documents(
+    conn: &PgConnection,
+    env: &Environment,
+    args: &RenderAndUploadDocumentsJob,
+) -> Result<(), PerformError> {
+    let options = env.render_options();
+    let render = |document: &PackageDocument| {
+        render_text(
+            &document.text,
+            &document.path,
+            args.base_url.as_deref(),
+            args.pkg_path_in_vcs.as_deref(),
+            &options,
+        )
+        .html
+    };
+
+    let (crate_name, vers): (String, String) = versions::table
+        .find(args.version_id)
+        .inner_join(crates::table)
+        .select((crates::name, versions::num))
+        .first(conn)?;
+
+    let client = env.http_client();
+    if let Some(changelog) = &args.documents.changelog {
+        env.uploader
+            .upload_changelog(client, &crate_name, &vers, render(changelog))?;
+    }
+    if let Some(license) = &args.documents.license {
+        env.uploader
+            .upload_license(client, &crate_name, &vers, render(license))?;
+    }
+    Ok(())
+}
+
+pub fn render_and_upload_documents(
+    version_id: i32,
+    documents: PackageDocuments,
+    base_url: Option<String>,
+    pkg_path_in_vcs: Option<String>,
+) -> Job {
+    Job::RenderAndUploadDocuments(RenderAndUploadDocumentsJob {
+        version_id,
+        documents,
+        base_url,
+        pkg_path_in_vcs,
+    })
+}
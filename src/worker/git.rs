@@ -1,15 +1,22 @@
 use crate::background_jobs::{
-    Environment, IndexAddCrateJob, IndexSyncToHttpJob, IndexUpdateYankedJob, Job, NormalizeIndexJob,
+    Environment, IndexAddCrateJob, IndexAddCratesJob, IndexDeleteCrateJob, IndexSyncToHttpJob,
+    IndexUpdateYankedJob, Job, NormalizeIndexJob,
 };
-use crate::schema;
+use crate::swirl::storage::{self, BackgroundJob};
 use crate::swirl::PerformError;
+use crate::{env_optional, schema};
 use anyhow::Context;
 use cargo_registry_index::{Crate, Repository};
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
+use std::collections::BTreeSet;
+use std::fmt;
 use std::fs::{self, OpenOptions};
 use std::io::{BufRead, BufReader, ErrorKind};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::thread;
+use std::time::Duration;
 
 #[instrument(skip_all, fields(krate.name = ?krate.name, krate.vers = ?krate.vers))]
 pub fn perform_index_add_crate(
@@ -19,29 +26,125 @@ pub fn perform_index_add_crate(
 ) -> Result<(), PerformError> {
     info!("Syncing git index to HTTP-based index");
 
+    let repo = env.lock_index()?;
+    let (dst, message) = add_crate_to_index_file(&repo, krate)?;
+    repo.commit_and_push(&message, &dst)?;
+
+    // Queue another background job to update the http-based index as well.
+    update_crate_index(krate.name.clone()).enqueue(conn)?;
+    Ok(())
+}
+
+/// Appends the crate to its index file in the working tree, and returns the
+/// path of the modified file together with a description of the change.
+fn add_crate_to_index_file(
+    repo: &Repository,
+    krate: &Crate,
+) -> Result<(PathBuf, String), PerformError> {
     use std::io::prelude::*;
 
-    let repo = env.lock_index()?;
     let dst = repo.index_file(&krate.name);
 
+    // Serialize the line up front, so that the file is modified in a single write
+    let mut line = serde_json::to_vec(&krate)?;
+    line.push(b'\n');
+
     // Add the crate to its relevant file
     fs::create_dir_all(dst.parent().unwrap())?;
     let mut file = OpenOptions::new().append(true).create(true).open(&dst)?;
-    serde_json::to_writer(&mut file, &krate)?;
-    file.write_all(b"\n")?;
+    file.write_all(&line)?;
 
     let message: String = format!("Updating crate `{}#{}`", krate.name, krate.vers);
-    repo.commit_and_push(&message, &dst)?;
-
-    // Queue another background job to update the http-based index as well.
-    update_crate_index(krate.name.clone()).enqueue(conn)?;
-    Ok(())
+    Ok((dst, message))
 }
 
 pub fn add_crate(krate: Crate) -> Job {
     Job::IndexAddCrate(IndexAddCrateJob { krate })
 }
 
+/// Adds all versions of a batch publish to the index in a single commit, and
+/// then queues jobs to update the HTTP-based index of each crate as well.
+#[instrument(skip_all, fields(batch_size = krates.len()))]
+pub fn perform_index_add_crates(
+    env: &Environment,
+    conn: &PgConnection,
+    krates: &[Crate],
+) -> Result<(), PerformError> {
+    info!("Adding a batch of crates to the git index");
+
+    let repo = env.lock_index()?;
+
+    let mut modifications = Vec::with_capacity(krates.len());
+    for krate in krates {
+        modifications.push(add_crate_to_index_file(&repo, krate)?);
+    }
+
+    match modifications.as_slice() {
+        [] => debug!("Skipping commit because the batch is empty"),
+        [(dst, message)] => repo.commit_and_push(message, dst)?,
+        modifications => {
+            let paths = modifications
+                .iter()
+                .map(|(dst, _)| dst.as_path())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect::<Vec<&Path>>();
+
+            let messages = modifications
+                .iter()
+                .map(|(_, message)| message.as_str())
+                .collect::<Vec<_>>();
+
+            repo.commit_and_push_all(&batch_commit_message(&messages), &paths)?;
+        }
+    }
+
+    let crate_names = krates
+        .iter()
+        .map(|krate| krate.name.as_str())
+        .collect::<BTreeSet<_>>();
+    for crate_name in crate_names {
+        update_crate_index(crate_name.to_string()).enqueue(conn)?;
+    }
+
+    Ok(())
+}
+
+pub fn add_crates(krates: Vec<Crate>) -> Job {
+    Job::IndexAddCrates(IndexAddCratesJob { krates })
+}
+
+/// Removes the index file of a deleted crate from the git index, and then
+/// queues a job to remove it from the HTTP-based index as well.
+#[instrument(skip(env, conn))]
+pub fn perform_index_delete_crate(
+    env: &Environment,
+    conn: &PgConnection,
+    crate_name: &str,
+) -> Result<(), PerformError> {
+    info!("Deleting crate from the git index");
+
+    let repo = env.lock_index()?;
+    let dst = repo.index_file(crate_name);
+
+    match fs::remove_file(&dst) {
+        Ok(()) => {
+            let message = format!("Deleting crate `{crate_name}`");
+            repo.commit_and_push(&message, &dst)?;
+        }
+        // Nothing to commit if a previous attempt already removed the file.
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    update_crate_index(crate_name.to_string()).enqueue(conn)?;
+    Ok(())
+}
+
+pub fn delete_crate_from_index(crate_name: String) -> Job {
+    Job::IndexDeleteCrate(IndexDeleteCrateJob { crate_name })
+}
+
 #[instrument(skip(env))]
 pub fn perform_index_sync_to_http(
     env: &Environment,
@@ -87,6 +190,22 @@ pub fn perform_index_update_yanked(
 ) -> Result<(), PerformError> {
     info!("Syncing yanked status from database into the index");
 
+    let yanked = load_yanked(conn, krate, version_num)?;
+
+    let repo = env.lock_index()?;
+    if let Some((dst, message)) = update_yanked_in_index_file(&repo, krate, version_num, yanked)? {
+        repo.commit_and_push(&message, &dst)?;
+    } else {
+        debug!("Skipping `yanked` update because index is up-to-date");
+    }
+
+    // Queue another background job to update the http-based index as well.
+    update_crate_index(krate.to_string()).enqueue(conn)?;
+
+    Ok(())
+}
+
+fn load_yanked(conn: &PgConnection, krate: &str, version_num: &str) -> Result<bool, PerformError> {
     debug!("Loading yanked status from database");
 
     let yanked: bool = schema::versions::table
@@ -99,7 +218,20 @@ pub fn perform_index_update_yanked(
 
     debug!(yanked);
 
-    let repo = env.lock_index()?;
+    Ok(yanked)
+}
+
+/// Sets the `yanked` flag of a crate version in its index file in the working
+/// tree.
+///
+/// Returns the path of the modified file together with a description of the
+/// change, or `None` if the index file was already up-to-date.
+fn update_yanked_in_index_file(
+    repo: &Repository,
+    krate: &str,
+    version_num: &str,
+    yanked: bool,
+) -> Result<Option<(PathBuf, String)>, PerformError> {
     let dst = repo.index_file(krate);
 
     let prev = fs::read_to_string(&dst)?;
@@ -117,25 +249,373 @@ pub fn perform_index_update_yanked(
         .collect::<Result<Vec<_>, PerformError>>();
     let new = new?.join("\n") + "\n";
 
-    if new != prev {
-        fs::write(&dst, new.as_bytes())?;
+    if new == prev {
+        return Ok(None);
+    }
 
-        let action = if yanked { "Yanking" } else { "Unyanking" };
-        let message = format!("{action} crate `{krate}#{version_num}`");
+    fs::write(&dst, new.as_bytes())?;
 
-        repo.commit_and_push(&message, &dst)?;
-    } else {
-        debug!("Skipping `yanked` update because index is up-to-date");
+    let action = if yanked { "Yanking" } else { "Unyanking" };
+    let message = format!("{action} crate `{krate}#{version_num}`");
+    Ok(Some((dst, message)))
+}
+
+pub fn sync_yanked(krate: String, version_num: String) -> Job {
+    Job::IndexUpdateYanked(IndexUpdateYankedJob { krate, version_num })
+}
+
+/// Configuration for coalescing index jobs into batched git commits.
+///
+/// When batching is enabled, an `add_crate` or `sync_yanked` job picked up by
+/// the background worker also applies all other pending jobs of these types
+/// that were enqueued within `window` of it, and pushes all of their changes
+/// to the index as a single commit.
+#[derive(Clone, Debug)]
+pub struct IndexBatchConfig {
+    /// How long to wait for further index jobs after the first job of a batch
+    /// was enqueued.
+    pub window: Duration,
+    /// The maximum number of index jobs applied in a single commit.
+    pub max_size: i64,
+}
+
+impl IndexBatchConfig {
+    const DEFAULT_MAX_SIZE: i64 = 100;
+
+    /// Reads the batching configuration from the environment.
+    ///
+    /// - `INDEX_BATCH_WINDOW_MS`: Enables batching, collecting all index jobs
+    ///   enqueued within this many milliseconds into one commit.
+    /// - `INDEX_BATCH_MAX_SIZE`: The maximum number of index jobs per commit.
+    ///   Defaults to 100.
+    ///
+    /// Returns `None` if batching is disabled.
+    pub fn from_environment() -> Option<Self> {
+        let window = env_optional("INDEX_BATCH_WINDOW_MS")?;
+        let max_size = env_optional("INDEX_BATCH_MAX_SIZE").unwrap_or(Self::DEFAULT_MAX_SIZE);
+
+        Some(Self {
+            window: Duration::from_millis(window),
+            max_size,
+        })
     }
+}
 
-    // Queue another background job to update the http-based index as well.
-    update_crate_index(krate.to_string()).enqueue(conn)?;
+/// The outcome of applying a single job of an index batch to the working tree
+struct BatchedUpdate {
+    crate_name: String,
+    /// The modified index file and a description of the change, or `None` if
+    /// the index file was already up-to-date.
+    modification: Option<(PathBuf, String)>,
+}
+
+/// Applies the `add_crate` or `sync_yanked` job with the id `job_id`, together
+/// with all other pending index jobs enqueued within the batch window, and
+/// pushes the resulting changes to the index as a single commit.
+///
+/// A job that fails to apply does not block the rest of the batch: its changes
+/// are left out of the commit, and the job stays in the queue to be retried.
+#[instrument(skip(env, conn, config))]
+pub fn perform_index_batch(
+    env: &Environment,
+    conn: &PgConnection,
+    config: &IndexBatchConfig,
+    job_id: i64,
+) -> Result<(), PerformError> {
+    info!("Applying a batch of index updates");
+
+    let (job, created_at) = storage::find_job(conn, job_id)?;
+
+    // Give index jobs enqueued shortly after this one a chance to join the batch
+    let deadline = created_at + chrono::Duration::from_std(config.window)?;
+    let db_now: NaiveDateTime = diesel::select(diesel::dsl::now).get_result(conn)?;
+    if let Ok(remaining) = (deadline - db_now).to_std() {
+        debug!(?remaining, "Waiting for the batch window to close");
+        thread::sleep(remaining);
+    }
+
+    conn.transaction(|| -> Result<_, PerformError> {
+        let batch_limit = (config.max_size - 1).max(0);
+        let others = storage::find_pending_jobs_of_types(
+            conn,
+            Job::BATCHED_INDEX_JOBS,
+            job_id,
+            deadline,
+            batch_limit,
+        )?;
+        info!(batch_size = others.len() + 1);
+
+        let repo = env.lock_index()?;
+
+        let mut job_result = Ok(());
+        let mut updates = Vec::new();
+        let mut failed_job_ids = Vec::new();
+        for job in std::iter::once(job).chain(others) {
+            let id = job.id;
+            match apply_batched_job(&repo, conn, job) {
+                Ok(update) => updates.push((id, update)),
+                Err(error) => {
+                    warn!(job_id = id, %error, "Failed to apply index update");
+                    if id == job_id {
+                        job_result = Err(error);
+                    } else {
+                        failed_job_ids.push(id);
+                    }
+                }
+            }
+        }
+
+        let modifications = updates
+            .iter()
+            .filter_map(|(_, update)| update.modification.as_ref())
+            .collect::<Vec<_>>();
+
+        match modifications.as_slice() {
+            [] => debug!("Skipping commit because index is up-to-date"),
+            [(dst, message)] => repo.commit_and_push(message, dst)?,
+            modifications => {
+                let paths = modifications
+                    .iter()
+                    .map(|(dst, _)| dst.as_path())
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect::<Vec<&Path>>();
+
+                let messages = modifications
+                    .iter()
+                    .map(|(_, message)| message.as_str())
+                    .collect::<Vec<_>>();
+
+                repo.commit_and_push_all(&batch_commit_message(&messages), &paths)?;
+            }
+        }
+
+        // Queue background jobs to update the http-based index as well.
+        let crate_names = updates
+            .iter()
+            .map(|(_, update)| update.crate_name.as_str())
+            .collect::<BTreeSet<_>>();
+        for crate_name in crate_names {
+            update_crate_index(crate_name.to_string()).enqueue(conn)?;
+        }
+
+        // The job that started this batch is removed by the job runner itself
+        for (id, _) in updates.iter().filter(|(id, _)| *id != job_id) {
+            storage::delete_successful_job(conn, *id)?;
+        }
+        for id in failed_job_ids {
+            storage::update_failed_job(conn, id);
+        }
+
+        Ok(job_result)
+    })?
+}
+
+fn apply_batched_job(
+    repo: &Repository,
+    conn: &PgConnection,
+    job: BackgroundJob,
+) -> Result<BatchedUpdate, PerformError> {
+    match Job::from_value(&job.job_type, job.data)? {
+        Job::IndexAddCrate(args) => {
+            let modification = add_crate_to_index_file(repo, &args.krate)?;
+            Ok(BatchedUpdate {
+                crate_name: args.krate.name,
+                modification: Some(modification),
+            })
+        }
+        Job::IndexUpdateYanked(args) => {
+            let yanked = load_yanked(conn, &args.krate, &args.version_num)?;
+            let modification =
+                update_yanked_in_index_file(repo, &args.krate, &args.version_num, yanked)?;
+            Ok(BatchedUpdate {
+                crate_name: args.krate,
+                modification,
+            })
+        }
+        _ => Err(format!("Job type {} cannot be batched", job.job_type).into()),
+    }
+}
+
+fn batch_commit_message(messages: &[&str]) -> String {
+    format!(
+        "Updating {} index entries\n\n{}",
+        messages.len(),
+        messages.join("\n")
+    )
+}
+
+/// How an index file has to change to match the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexFileChange {
+    /// The crate has versions in the database, but no index file
+    Created,
+    /// The index file does not match the versions in the database
+    Updated,
+    /// The index file belongs to a crate without any versions in the database
+    Removed,
+}
+
+impl fmt::Display for IndexFileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexFileChange::Created => f.write_str("missing from the index"),
+            IndexFileChange::Updated => f.write_str("outdated"),
+            IndexFileChange::Removed => f.write_str("not in the database"),
+        }
+    }
+}
+
+/// An index file that does not match the contents of the database
+#[derive(Debug)]
+pub struct IndexDiscrepancy {
+    pub crate_name: String,
+    pub change: IndexFileChange,
+    path: PathBuf,
+    /// The contents of the index file as generated from the database, or
+    /// `None` if the file should not exist.
+    expected: Option<String>,
+}
+
+/// Regenerates all index files from the database and compares them to the
+/// files in the working tree of `repo`, without modifying any files.
+pub fn find_index_discrepancies(
+    repo: &Repository,
+    conn: &PgConnection,
+) -> anyhow::Result<Vec<IndexDiscrepancy>> {
+    let mut discrepancies = Vec::new();
+
+    // All files in the index that are not named after a crate (e.g. `config.json`)
+    // are ignored.
+    let mut orphaned_files = repo
+        .get_files_modified_since(None)?
+        .into_iter()
+        .filter_map(|file| {
+            let crate_name = file.file_name()?.to_str()?.to_string();
+            (Repository::relative_index_file(&crate_name) == file).then_some(crate_name)
+        })
+        .collect::<BTreeSet<_>>();
+
+    let crates: Vec<crate::models::Crate> = crate::models::Crate::all()
+        .order(schema::crates::name)
+        .load(conn)?;
+    let num_crates = crates.len();
+
+    for (i, krate) in crates.into_iter().enumerate() {
+        if i % 500 == 0 {
+            info!(num_crates, i, crate_name = %krate.name);
+        }
+
+        orphaned_files.remove(&krate.name.to_lowercase());
+
+        let entries = krate
+            .index_metadata(conn)
+            .with_context(|| format!("Failed to load index metadata of `{}`", krate.name))?;
+
+        let expected = if entries.is_empty() {
+            None
+        } else {
+            let mut body = String::new();
+            for entry in entries {
+                body += &serde_json::to_string(&entry)?;
+                body.push('\n');
+            }
+            Some(body)
+        };
+
+        let path = repo.index_file(&krate.name);
+        let actual = match fs::read_to_string(&path) {
+            Ok(contents) => Some(contents),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        let change = match (&actual, &expected) {
+            (None, None) => continue,
+            (Some(actual), Some(expected)) if actual == expected => continue,
+            (None, Some(_)) => IndexFileChange::Created,
+            (Some(_), Some(_)) => IndexFileChange::Updated,
+            (Some(_), None) => IndexFileChange::Removed,
+        };
+
+        discrepancies.push(IndexDiscrepancy {
+            crate_name: krate.name,
+            change,
+            path,
+            expected,
+        });
+    }
+
+    for crate_name in orphaned_files {
+        discrepancies.push(IndexDiscrepancy {
+            path: repo.index_file(&crate_name),
+            crate_name,
+            change: IndexFileChange::Removed,
+            expected: None,
+        });
+    }
+
+    Ok(discrepancies)
+}
+
+/// Regenerates all index files from the `versions`, `dependencies` and
+/// `features` data in the database, and pushes all files that did not match
+/// the database to the index in a single commit.
+#[instrument(skip_all)]
+pub fn perform_rebuild_index(env: &Environment, conn: &PgConnection) -> Result<(), PerformError> {
+    info!("Rebuilding the index from the database");
+
+    let repo = env.lock_index()?;
+
+    let discrepancies = find_index_discrepancies(&repo, conn)?;
+    if discrepancies.is_empty() {
+        info!("The index matches the database");
+        return Ok(());
+    }
+
+    for discrepancy in &discrepancies {
+        let crate_name = &discrepancy.crate_name;
+        let change = discrepancy.change;
+        info!(%crate_name, %change, "Index file does not match the database");
+
+        match &discrepancy.expected {
+            Some(expected) => {
+                fs::create_dir_all(discrepancy.path.parent().unwrap())?;
+                fs::write(&discrepancy.path, expected)?;
+            }
+            None => fs::remove_file(&discrepancy.path)?,
+        }
+    }
+
+    let paths = discrepancies
+        .iter()
+        .map(|discrepancy| discrepancy.path.as_path())
+        .collect::<Vec<_>>();
+
+    let message = format!(
+        "Rebuild {} index files from the database\n\n{}",
+        discrepancies.len(),
+        discrepancies
+            .iter()
+            .map(|d| format!("- `{}` was {}", d.crate_name, d.change))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+
+    repo.commit_and_push_all(&message, &paths)?;
+
+    // Queue background jobs to update the http-based index as well.
+    for discrepancy in discrepancies {
+        update_crate_index(discrepancy.crate_name).enqueue(conn)?;
+    }
+
+    info!("Index rebuild completed");
 
     Ok(())
 }
 
-pub fn sync_yanked(krate: String, version_num: String) -> Job {
-    Job::IndexUpdateYanked(IndexUpdateYankedJob { krate, version_num })
+pub fn rebuild_index() -> Job {
+    Job::RebuildIndex
 }
 
 /// Collapse the index into a single commit, archiving the current history in a snapshot branch.
@@ -0,0 +1,199 @@
+use crate::background_jobs::{Environment, IndexCheckJob, Job};
+use crate::models::{Crate, NewIndexConsistencyCheck};
+use crate::schema::{background_jobs, crates, versions};
+use crate::sql::random;
+use crate::swirl::PerformError;
+use crate::worker::git::{rebuild_index, sync_yanked, update_crate_index};
+use diesel::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::ErrorKind;
+
+/// Compares the version sets and yanked flags in the database with the git
+/// index, and the git index with the HTTP-based index.
+///
+/// The result is recorded in the `index_consistency_checks` table, which is
+/// used by the `monitor` binary and the service metrics. For every crate that
+/// does not match, repair jobs are enqueued.
+#[instrument(skip(env, conn))]
+pub fn perform_index_check(
+    env: &Environment,
+    conn: &PgConnection,
+    sample_size: Option<i64>,
+) -> Result<(), PerformError> {
+    info!("Checking the consistency of the index");
+
+    // The index files of these crates are expected to lag behind the database
+    let pending = crates_with_pending_index_jobs(conn)?;
+
+    let query = Crate::all().into_boxed();
+    let crates: Vec<Crate> = match sample_size {
+        Some(sample_size) => query.order(random).limit(sample_size).load(conn)?,
+        None => query.order(crates::name).load(conn)?,
+    };
+
+    // The index is only locked while its files are read, so that index updates
+    // aren't blocked by the database queries and downloads below
+    let git_files = {
+        let repo = env.lock_index()?;
+        crates
+            .iter()
+            .filter(|krate| !pending.contains(&krate.name))
+            .map(|krate| {
+                let git_file = match fs::read_to_string(repo.index_file(&krate.name)) {
+                    Ok(contents) => Some(contents),
+                    Err(e) if e.kind() == ErrorKind::NotFound => None,
+                    Err(e) => return Err(e),
+                };
+                Ok((krate.name.clone(), git_file))
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?
+    };
+
+    let check_http = env.uploader.has_http_index();
+
+    let mut crates_checked = 0;
+    let mut git_mismatches = Vec::new();
+    let mut http_mismatches = Vec::new();
+    let mut repair_jobs = Vec::new();
+    let mut rebuild_crates = BTreeSet::new();
+
+    for krate in crates {
+        let git_file = match git_files.get(&krate.name) {
+            Some(git_file) => git_file,
+            None => {
+                debug!(crate_name = %krate.name, "Skipping crate with pending index jobs");
+                continue;
+            }
+        };
+        crates_checked += 1;
+
+        let db_versions: BTreeMap<String, bool> = versions::table
+            .filter(versions::crate_id.eq(krate.id))
+            .filter(versions::staged.eq(false))
+            .select((versions::num, versions::yanked))
+            .load(conn)?
+            .into_iter()
+            .collect();
+
+        let git_versions = parse_index_versions(git_file.as_deref().unwrap_or_default())?;
+
+        let git_consistent = db_versions == git_versions;
+        if !git_consistent {
+            warn!(crate_name = %krate.name, "Git index does not match the database");
+            git_mismatches.push(krate.name.clone());
+
+            for (num, yanked) in &db_versions {
+                if matches!(git_versions.get(num), Some(git_yanked) if git_yanked != yanked) {
+                    repair_jobs.push(sync_yanked(krate.name.clone(), num.clone()));
+                }
+            }
+
+            // Missing versions are added by a rebuild instead of an `add_crate`
+            // job, since the latter would duplicate versions that were added to
+            // the index after it was read. Versions that only exist in the git
+            // index can't be removed by the regular index jobs either.
+            if git_versions.keys().ne(db_versions.keys()) {
+                rebuild_crates.insert(krate.name.clone());
+            }
+        }
+
+        if check_http {
+            let http_file = env
+                .uploader
+                .download_index(env.http_client(), &krate.name)?;
+            if &http_file != git_file {
+                warn!(crate_name = %krate.name, "HTTP index does not match the git index");
+                http_mismatches.push(krate.name.clone());
+
+                // The git repair jobs sync the HTTP index once they are done
+                if git_consistent {
+                    repair_jobs.push(update_crate_index(krate.name.clone()));
+                }
+            }
+        }
+    }
+
+    // Crates that were published or changed while they were checked are left
+    // to their index jobs, like the crates that were pending from the start
+    let pending = crates_with_pending_index_jobs(conn)?;
+    git_mismatches.retain(|name| !pending.contains(name));
+    http_mismatches.retain(|name| !pending.contains(name));
+    repair_jobs.retain(|job| {
+        job.index_crate_names()
+            .iter()
+            .all(|name| !pending.contains(*name))
+    });
+    rebuild_crates.retain(|name| !pending.contains(name));
+    let needs_rebuild = !rebuild_crates.is_empty();
+
+    NewIndexConsistencyCheck {
+        crates_checked,
+        git_mismatches: &git_mismatches,
+        http_mismatches: &http_mismatches,
+    }
+    .save(conn)?;
+
+    info!(
+        crates_checked,
+        git_mismatches = git_mismatches.len(),
+        http_mismatches = http_mismatches.len(),
+        repair_jobs = repair_jobs.len(),
+        needs_rebuild,
+        "Index consistency check completed"
+    );
+
+    for job in repair_jobs {
+        job.enqueue(conn)?;
+    }
+    if needs_rebuild {
+        rebuild_index().enqueue(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Checks the consistency of the index. If `sample_size` is set, only that
+/// many randomly selected crates are checked, instead of all crates.
+pub fn check_index(sample_size: Option<i64>) -> Job {
+    Job::IndexCheck(IndexCheckJob { sample_size })
+}
+
+/// Returns the names of all crates that have index jobs in the queue.
+fn crates_with_pending_index_jobs(conn: &PgConnection) -> QueryResult<BTreeSet<String>> {
+    let jobs: Vec<(String, serde_json::Value)> = background_jobs::table
+        .select((background_jobs::job_type, background_jobs::data))
+        .filter(background_jobs::job_type.eq_any(Job::CRATE_INDEX_JOBS))
+        .load(conn)?;
+
+    Ok(jobs
+        .into_iter()
+        .filter_map(|(job_type, data)| Job::from_value(&job_type, data).ok())
+        .flat_map(|job| {
+            job.index_crate_names()
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        })
+        .collect())
+}
+
+/// The parts of an index entry that are compared with the database
+#[derive(Deserialize)]
+struct IndexVersion {
+    vers: String,
+    #[serde(default)]
+    yanked: Option<bool>,
+}
+
+/// Parses the versions and their yanked flags out of an index file.
+fn parse_index_versions(contents: &str) -> serde_json::Result<BTreeMap<String, bool>> {
+    contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let entry: IndexVersion = serde_json::from_str(line)?;
+            Ok((entry.vers, entry.yanked.unwrap_or(false)))
+        })
+        .collect()
+}
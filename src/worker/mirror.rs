@@ -0,0 +1,261 @@
+use crate::admin::git_import;
+use crate::background_jobs::{Environment, Job};
+use crate::schema::{crates, versions};
+use crate::swirl::PerformError;
+use crate::worker::git::update_crate_index;
+use anyhow::{anyhow, Context};
+use cargo_registry_index::{Credentials, Repository, RepositoryConfig};
+use diesel::dsl::exists;
+use diesel::prelude::*;
+use hex::ToHex;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::ErrorKind;
+use url::Url;
+
+/// The parts of the `config.json` file of the upstream index that are used by
+/// the mirror.
+#[derive(Deserialize)]
+struct UpstreamConfig {
+    dl: String,
+}
+
+/// Imports all index entries of the upstream registry that have changed since
+/// the last sync.
+///
+/// For every index file that differs from the index of this registry, the
+/// tarballs of the new versions are downloaded, verified against the `cksum`
+/// of their index entries and uploaded. The versions are then created in the
+/// database, and the index file is copied verbatim into the index of this
+/// registry. Crates that fail to sync are retried on the next run.
+#[instrument(skip_all)]
+pub fn perform_sync_mirror(env: &Environment, conn: &PgConnection) -> Result<(), PerformError> {
+    let mirror = env
+        .mirror()
+        .ok_or("Mirror mode is not configured (see `MIRROR_UPSTREAM_INDEX`)")?;
+
+    info!(upstream_index = %mirror.upstream_index, "Syncing mirror from upstream registry");
+
+    let upstream = Repository::open(&RepositoryConfig {
+        index_location: mirror.upstream_index.clone(),
+        credentials: Credentials::Missing,
+    })?;
+
+    let config = fs::read_to_string(upstream.checkout_path().join("config.json"))
+        .context("Failed to read `config.json` of the upstream index")?;
+    let config: UpstreamConfig = serde_json::from_str(&config)?;
+
+    let repo = env.lock_index()?;
+
+    let mut synced = Vec::new();
+    let mut num_failed = 0;
+    for file in upstream.get_files_modified_since(None)? {
+        // All files in the index that are not named after a crate (e.g. `config.json`)
+        // are ignored.
+        let crate_name = match file.file_name().and_then(|name| name.to_str()) {
+            Some(name) if Repository::relative_index_file(name) == file => name.to_string(),
+            _ => continue,
+        };
+
+        let contents = fs::read_to_string(upstream.checkout_path().join(&file))?;
+
+        let path = repo.index_file(&crate_name);
+        match fs::read_to_string(&path) {
+            Ok(existing) if existing == contents => continue,
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        if let Err(error) = sync_crate(env, conn, &config, &contents) {
+            warn!(%crate_name, ?error, "Failed to sync crate from upstream registry");
+            num_failed += 1;
+            continue;
+        }
+
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, contents)?;
+        synced.push((crate_name, path));
+    }
+
+    if !synced.is_empty() {
+        let message = format!(
+            "Sync {} crates from upstream\n\n{}",
+            synced.len(),
+            synced
+                .iter()
+                .map(|(crate_name, _)| format!("- `{crate_name}`"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+
+        let paths = synced
+            .iter()
+            .map(|(_, path)| path.as_path())
+            .collect::<Vec<_>>();
+        repo.commit_and_push_all(&message, &paths)?;
+
+        // Queue background jobs to update the http-based index as well.
+        for (crate_name, _) in synced {
+            update_crate_index(crate_name).enqueue(conn)?;
+        }
+    }
+
+    if num_failed > 0 {
+        return Err(format!("Failed to sync {num_failed} crates from upstream registry").into());
+    }
+
+    info!("Mirror sync completed");
+
+    Ok(())
+}
+
+pub fn sync_mirror() -> Job {
+    Job::SyncMirror
+}
+
+/// Imports all versions of an upstream index file that are not in the
+/// database yet, and updates the `yanked` flags of the existing ones.
+fn sync_crate(
+    env: &Environment,
+    conn: &PgConnection,
+    config: &UpstreamConfig,
+    contents: &str,
+) -> anyhow::Result<()> {
+    for line in contents.lines().filter(|line| !line.is_empty()) {
+        let krate: cargo_registry_index::Crate = serde_json::from_str(line)?;
+
+        let existing_version = versions::table
+            .inner_join(crates::table)
+            .filter(crates::name.eq(&krate.name))
+            .filter(versions::num.eq(&krate.vers));
+        if diesel::select(exists(existing_version)).get_result(conn)? {
+            git_import::import_yanked(conn, &krate)?;
+            continue;
+        }
+
+        let tarball = download_tarball(env, config, &krate)?;
+
+        let cksum: String = Sha256::digest(&tarball).encode_hex();
+        if cksum != krate.cksum {
+            return Err(anyhow!(
+                "Checksum mismatch for {}#{}: expected {}, got {cksum}",
+                krate.name,
+                krate.vers,
+                krate.cksum
+            ));
+        }
+
+        let crate_size = tarball.len() as i32;
+        let vers = semver::Version::parse(&krate.vers)?;
+        env.uploader
+            .upload_crate(env.http_client(), tarball, &krate.name, &vers)
+            .map_err(|e| anyhow!("{e}"))?;
+
+        git_import::import_version(conn, &krate, crate_size)?;
+    }
+
+    Ok(())
+}
+
+/// Downloads the tarball of an index entry from the upstream registry.
+///
+/// `file://` URLs are supported, so that a local copy of the upstream registry
+/// can be used in air-gapped networks.
+fn download_tarball(
+    env: &Environment,
+    config: &UpstreamConfig,
+    krate: &cargo_registry_index::Crate,
+) -> anyhow::Result<Vec<u8>> {
+    let url = download_url(&config.dl, krate);
+    let url = Url::parse(&url).with_context(|| format!("Invalid download URL: {url}"))?;
+    debug!(%url, "Downloading tarball from upstream registry");
+
+    if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| anyhow!("Invalid download URL: {url}"))?;
+        return fs::read(&path).with_context(|| format!("Failed to read {}", path.display()));
+    }
+
+    let response = env.http_client().get(url).send()?.error_for_status()?;
+    Ok(response.bytes()?.to_vec())
+}
+
+/// Builds the download URL of a crate version from the `dl` template of the
+/// upstream index.
+///
+/// see <https://doc.rust-lang.org/cargo/reference/registries.html#index-format>
+fn download_url(template: &str, krate: &cargo_registry_index::Crate) -> String {
+    const MARKERS: &[&str] = &[
+        "{crate}",
+        "{version}",
+        "{prefix}",
+        "{lowerprefix}",
+        "{sha256-checksum}",
+    ];
+
+    if !MARKERS.iter().any(|marker| template.contains(marker)) {
+        return format!("{template}/{}/{}/download", krate.name, krate.vers);
+    }
+
+    let prefix = index_prefix(&krate.name);
+    template
+        .replace("{crate}", &krate.name)
+        .replace("{version}", &krate.vers)
+        .replace("{lowerprefix}", &prefix.to_lowercase())
+        .replace("{prefix}", &prefix)
+        .replace("{sha256-checksum}", &krate.cksum)
+}
+
+/// Returns the directory of the index file of a crate, without converting
+/// the crate name to lowercase.
+fn index_prefix(name: &str) -> String {
+    match name.len() {
+        1 => "1".to_string(),
+        2 => "2".to_string(),
+        3 => format!("3/{}", &name[..1]),
+        _ => format!("{}/{}", &name[0..2], &name[2..4]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn krate(name: &str) -> cargo_registry_index::Crate {
+        cargo_registry_index::Crate {
+            name: name.into(),
+            vers: "1.0.0".into(),
+            deps: vec![],
+            cksum: "abc".into(),
+            features: Default::default(),
+            features2: None,
+            yanked: None,
+            links: None,
+            v: None,
+        }
+    }
+
+    #[test]
+    fn download_url_without_markers() {
+        assert_eq!(
+            download_url("https://crates.io/api/v1/crates", &krate("Foo")),
+            "https://crates.io/api/v1/crates/Foo/1.0.0/download"
+        );
+    }
+
+    #[test]
+    fn download_url_with_markers() {
+        let template =
+            "file:///crates/{prefix}/{lowerprefix}/{crate}-{version}.crate?{sha256-checksum}";
+        assert_eq!(
+            download_url(template, &krate("Serde")),
+            "file:///crates/Se/rd/se/rd/Serde-1.0.0.crate?abc"
+        );
+        assert_eq!(
+            download_url(template, &krate("Foo")),
+            "file:///crates/3/F/3/f/Foo-1.0.0.crate?abc"
+        );
+    }
+}
@@ -4,23 +4,44 @@
 //! and uploading them to S3.
 
 pub mod cloudfront;
+mod crate_files;
 mod daily_db_maintenance;
+mod documents;
 pub mod dump_db;
 mod git;
-mod readmes;
+mod index_check;
+mod mirror;
+mod publish;
+pub(crate) mod readmes;
 mod update_downloads;
 
+pub use crate_files::delete_crate_files;
 pub use daily_db_maintenance::daily_db_maintenance;
+pub use documents::render_and_upload_documents;
 pub use dump_db::dump_db;
-pub use git::{add_crate, normalize_index, squash_index, sync_yanked};
-pub use readmes::render_and_upload_readme;
+pub use git::{
+    add_crate, add_crates, delete_crate_from_index, find_index_discrepancies, normalize_index,
+    rebuild_index, squash_index, sync_yanked, IndexBatchConfig, IndexDiscrepancy, IndexFileChange,
+};
+pub use index_check::check_index;
+pub use mirror::sync_mirror;
+pub use publish::finalize_publish;
+pub use readmes::{render_and_upload_readme, rerender_readmes};
 pub use update_downloads::update_downloads;
 
+pub(crate) use crate_files::perform_delete_crate_files;
 pub(crate) use daily_db_maintenance::perform_daily_db_maintenance;
+pub(crate) use documents::perform_render_and_upload_documents;
 pub(crate) use dump_db::perform_dump_db;
 pub(crate) use git::{
-    perform_index_add_crate, perform_index_squash, perform_index_sync_to_http,
-    perform_index_update_yanked, perform_normalize_index,
+    perform_index_add_crate, perform_index_add_crates, perform_index_batch,
+    perform_index_delete_crate, perform_index_squash, perform_index_sync_to_http,
+    perform_index_update_yanked, perform_normalize_index, perform_rebuild_index,
+};
+pub(crate) use index_check::perform_index_check;
+pub(crate) use mirror::perform_sync_mirror;
+pub(crate) use publish::perform_finalize_publish;
+pub(crate) use readmes::{
+    perform_render_and_upload_readme, perform_rerender_readmes, perform_rerender_readmes_batch,
 };
-pub(crate) use readmes::perform_render_and_upload_readme;
 pub(crate) use update_downloads::perform_update_downloads;
@@ -0,0 +1,122 @@
+//! Finalize versions that were uploaded with an asynchronous publish.
+
+use crate::swirl::PerformError;
+use anyhow::anyhow;
+use diesel::prelude::*;
+
+use crate::background_jobs::{Environment, FinalizePublishJob, Job};
+use crate::controllers::krate::publish::{verify_tarball, TarballInfo};
+use crate::models::{Crate, CrateMetadata, PublishState, PublishUpload, Version};
+use crate::schema::{crates, versions};
+use crate::worker;
+
+/// Verifies the stored crate file of an asynchronous publish, uploads it,
+/// releases the version, which stays staged until then, and queues the
+/// rendering of its readme and other documents and the index update.
+///
+/// If the crate file is invalid, the upload is marked as failed and the
+/// version is removed again, together with the crate if it has no other
+/// versions. Otherwise the metadata of the crate is restored to the state
+/// before the upload.
+#[instrument(skip_all, fields(krate.name = ?args.krate.name, krate.vers = ?args.krate.vers))]
+pub fn perform_finalize_publish(
+    env: &Environment,
+    conn: &PgConnection,
+    args: &FinalizePublishJob,
+) -> Result<(), PerformError> {
+    conn.transaction(|| {
+        let upload = PublishUpload::find(conn, args.upload_id)?;
+        if upload.state != PublishState::Pending {
+            return Ok(());
+        }
+
+        let krate = &args.krate;
+        let tarball = upload.tarball.clone().unwrap_or_default();
+        let pkg_name = format!("{}-{}", krate.name, krate.vers);
+        let license_file = args.license_file.as_deref();
+        let TarballInfo {
+            vcs_info,
+            documents,
+        } = match verify_tarball(&pkg_name, &tarball, args.max_unpack_size, license_file) {
+            Ok(info) => info,
+            Err(error) => {
+                info!(%error, "Rejecting uploaded crate file");
+                upload.mark_failed(conn, &error.to_string())?;
+                Version::remove(conn, args.version_id)?;
+                if let Some(metadata) = &args.previous_metadata {
+                    let crate_id = Crate::by_name(&krate.name)
+                        .select(crates::id)
+                        .first::<i32>(conn)
+                        .optional()?;
+                    if let Some(crate_id) = crate_id {
+                        metadata.restore(conn, crate_id)?;
+                    }
+                }
+                return Ok(());
+            }
+        };
+
+        let vers = semver::Version::parse(&krate.vers)?;
+        env.uploader
+            .upload_crate(env.http_client(), tarball, &krate.name, &vers)
+            .map_err(|e| anyhow!("{e}"))?;
+
+        let pkg_path_in_vcs = vcs_info.map(|info| info.path_in_vcs);
+        if let Some(readme) = &args.readme {
+            worker::render_and_upload_readme(
+                args.version_id,
+                readme.clone(),
+                args.readme_file.clone(),
+                args.repository.clone(),
+                pkg_path_in_vcs.clone(),
+            )
+            .enqueue(conn)?;
+        }
+
+        if !documents.is_empty() {
+            worker::render_and_upload_documents(
+                args.version_id,
+                documents,
+                args.repository.clone(),
+                pkg_path_in_vcs,
+            )
+            .enqueue(conn)?;
+        }
+
+        diesel::update(versions::table.find(args.version_id))
+            .set((
+                versions::staged.eq(false),
+                versions::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn)?;
+
+        worker::add_crate(krate.clone()).enqueue(conn)?;
+        upload.mark_published(conn)?;
+        Ok(())
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn finalize_publish(
+    upload_id: i32,
+    version_id: i32,
+    krate: cargo_registry_index::Crate,
+    readme: Option<String>,
+    readme_file: String,
+    license_file: Option<String>,
+    repository: Option<String>,
+    max_unpack_size: u64,
+    previous_metadata: Option<CrateMetadata>,
+) -> Job {
+    Job::FinalizePublish(FinalizePublishJob {
+        upload_id,
+        version_id,
+        krate,
+        readme,
+        readme_file,
+        license_file,
+        repository,
+        max_unpack_size,
+        previous_metadata,
+    })
+}
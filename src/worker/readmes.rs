@@ -1,11 +1,21 @@
 //! Render README files to HTML.
 
 use crate::swirl::PerformError;
-use cargo_registry_markdown::text_to_html;
-use diesel::PgConnection;
+use anyhow::{anyhow, Context};
+use cargo_registry_markdown::{render_text, RenderedText, RENDERER_VERSION};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use flate2::read::GzDecoder;
+use std::io::Read;
+use std::path::Path;
+use tar::Archive;
 
-use crate::background_jobs::{Environment, Job, RenderAndUploadReadmeJob};
+use crate::background_jobs::{
+    Environment, Job, RenderAndUploadReadmeJob, RerenderReadmesBatchJob, RerenderReadmesJob,
+};
 use crate::models::Version;
+use crate::util::CargoVcsInfo;
+use crate::views::EncodableReadmeHeading;
 
 pub fn perform_render_and_upload_readme(
     conn: &PgConnection,
@@ -19,17 +29,29 @@ pub fn perform_render_and_upload_readme(
     use crate::schema::*;
     use diesel::prelude::*;
 
-    let rendered = text_to_html(text, readme_path, base_url, pkg_path_in_vcs);
+    let RenderedText { html, outline } = render_text(
+        text,
+        readme_path,
+        base_url,
+        pkg_path_in_vcs,
+        &env.render_options(),
+    );
+    let outline = outline
+        .into_iter()
+        .map(EncodableReadmeHeading::from)
+        .collect::<Vec<_>>();
+    let outline = serde_json::to_value(outline)?;
 
     conn.transaction(|| {
         Version::record_readme_rendering(version_id, conn)?;
+        Version::record_readme_outline(version_id, outline, conn)?;
         let (crate_name, vers): (String, String) = versions::table
             .find(version_id)
             .inner_join(crates::table)
             .select((crates::name, versions::num))
             .first(conn)?;
         env.uploader
-            .upload_readme(env.http_client(), &crate_name, &vers, rendered)?;
+            .upload_readme(env.http_client(), &crate_name, &vers, html)?;
         Ok(())
     })
 }
@@ -49,3 +71,330 @@ pub fn render_and_upload_readme(
         pkg_path_in_vcs,
     })
 }
+
+/// Enqueues jobs that re-render the readmes of all versions that were rendered by an
+/// older version of the renderer or were never rendered, in batches of `batch_size`
+/// consecutive version ids. With `older_than`, the readmes that were rendered before
+/// that time are re-rendered as well.
+///
+/// The batches are enqueued in a single transaction, so that a run that is retried
+/// after a crash does not enqueue any batch twice.
+pub fn perform_rerender_readmes(
+    conn: &PgConnection,
+    args: &RerenderReadmesJob,
+) -> Result<(), PerformError> {
+    let crate_name = args.crate_name.as_deref();
+    let older_than = args.older_than;
+
+    conn.transaction(|| {
+        let mut num_batches = 0;
+        let mut last_id = 0;
+        loop {
+            let batch = outdated_readmes(
+                conn,
+                crate_name,
+                older_than,
+                last_id,
+                i32::MAX,
+                args.batch_size,
+            )?;
+            let (start_id, end_id) = match (batch.first(), batch.last()) {
+                (Some((start_id, ..)), Some((end_id, ..))) => (*start_id, *end_id),
+                _ => break,
+            };
+
+            rerender_readmes_batch(start_id, end_id, args.crate_name.clone(), older_than)
+                .enqueue(conn)?;
+            num_batches += 1;
+            last_id = end_id;
+        }
+
+        info!(num_batches, "Enqueued batches of readmes to re-render");
+        Ok(())
+    })
+}
+
+/// Re-renders the outdated readmes of the versions in a range of version ids.
+///
+/// Readmes are downloaded from the crate files, and every rendering is recorded
+/// separately, so that a batch that is retried after a crash or a failure only
+/// re-renders the readmes that are still outdated. Readmes that can never be
+/// rendered, e.g. because the crate file has no readme, are skipped, and only
+/// the other failures fail the job.
+#[instrument(skip_all, fields(start_id = args.start_id, end_id = args.end_id))]
+pub fn perform_rerender_readmes_batch(
+    conn: &PgConnection,
+    env: &Environment,
+    args: &RerenderReadmesBatchJob,
+) -> Result<(), PerformError> {
+    let crate_name = args.crate_name.as_deref();
+    let versions = outdated_readmes(
+        conn,
+        crate_name,
+        args.older_than,
+        args.start_id - 1,
+        args.end_id,
+        i64::MAX,
+    )?;
+
+    let mut num_failed = 0;
+    for (version_id, crate_name, num) in versions {
+        if let Err(error) = rerender_readme(conn, env, version_id, &crate_name, &num) {
+            warn!(%crate_name, %num, ?error, "Failed to re-render readme");
+            num_failed += 1;
+        }
+    }
+
+    if num_failed > 0 {
+        return Err(format!("Failed to re-render {num_failed} readmes").into());
+    }
+
+    Ok(())
+}
+
+/// Loads the ids, crate names and version numbers of the versions with ids in
+/// `after_id + 1..=until_id`, whose readmes were rendered by an older version of the
+/// renderer, before `older_than` or not at all, in the order of their ids.
+fn outdated_readmes(
+    conn: &PgConnection,
+    crate_name: Option<&str>,
+    older_than: Option<NaiveDateTime>,
+    after_id: i32,
+    until_id: i32,
+    limit: i64,
+) -> QueryResult<Vec<(i32, String, String)>> {
+    use crate::schema::*;
+
+    let mut query = versions::table
+        .inner_join(crates::table)
+        .left_outer_join(readme_renderings::table)
+        .filter(versions::id.gt(after_id))
+        .filter(versions::id.le(until_id))
+        .select((versions::id, crates::name, versions::num))
+        .order(versions::id)
+        .limit(limit)
+        .into_boxed();
+
+    let outdated = readme_renderings::renderer_version
+        .lt(RENDERER_VERSION)
+        .or(readme_renderings::version_id.is_null());
+    query = match older_than {
+        Some(older_than) => {
+            query.filter(outdated.or(readme_renderings::rendered_at.lt(older_than)))
+        }
+        None => query.filter(outdated),
+    };
+
+    if let Some(crate_name) = crate_name {
+        query = query.filter(crates::name.eq(crate_name));
+    }
+
+    query.load(conn)
+}
+
+/// Re-renders the readme of a version from its crate file.
+///
+/// If the crate file or its readme does not exist, the rendering is recorded as
+/// current anyway, since retrying won't change the outcome.
+fn rerender_readme(
+    conn: &PgConnection,
+    env: &Environment,
+    version_id: i32,
+    crate_name: &str,
+    num: &str,
+) -> Result<(), PerformError> {
+    let readme = match env
+        .uploader
+        .download_crate(env.http_client(), crate_name, num)?
+    {
+        Some(tarball) => read_pkg_readme(&tarball, &format!("{crate_name}-{num}")),
+        None => Err(anyhow!("Crate file does not exist")),
+    };
+
+    let readme = match readme {
+        Ok(readme) => readme,
+        Err(error) => {
+            info!(%crate_name, %num, ?error, "Skipping readme that cannot be rendered");
+            Version::record_readme_rendering(version_id, conn)?;
+            return Ok(());
+        }
+    };
+
+    perform_render_and_upload_readme(
+        conn,
+        env,
+        version_id,
+        &readme.text,
+        &readme.path,
+        readme.repository.as_deref(),
+        readme.pkg_path_in_vcs.as_deref(),
+    )
+}
+
+/// The readme of a crate file, with everything needed to render it.
+#[derive(Debug)]
+struct PackageReadme {
+    text: String,
+    /// Path to the readme within the package
+    path: String,
+    repository: Option<String>,
+    pkg_path_in_vcs: Option<String>,
+}
+
+/// Reads the readme of a crate file from the path in its manifest.
+fn read_pkg_readme(tarball: &[u8], pkg_name: &str) -> anyhow::Result<PackageReadme> {
+    #[derive(Deserialize)]
+    struct Package {
+        readme: Option<String>,
+        repository: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct Manifest {
+        package: Package,
+    }
+
+    let pkg_root = Path::new(pkg_name);
+
+    let manifest = read_file(tarball, &pkg_root.join("Cargo.toml"))?
+        .context("Failed to find Cargo.toml file")?;
+    let manifest: Manifest = toml::from_str(&manifest).context("Failed to parse manifest file")?;
+
+    let pkg_path_in_vcs = read_file(tarball, &pkg_root.join(".cargo_vcs_info.json"))?
+        .and_then(|vcs_info| CargoVcsInfo::from_contents(&vcs_info).ok())
+        .map(|vcs_info| vcs_info.path_in_vcs);
+
+    let path = manifest
+        .package
+        .readme
+        .unwrap_or_else(|| "README.md".into());
+    let text = read_file(tarball, &pkg_root.join(&path))?
+        .with_context(|| format!("Failed to find {path} file"))?;
+
+    Ok(PackageReadme {
+        text,
+        path,
+        repository: manifest.package.repository,
+        pkg_path_in_vcs,
+    })
+}
+
+/// Reads a file of a crate file, or returns `None` if it does not exist.
+fn read_file(tarball: &[u8], path: &Path) -> anyhow::Result<Option<String>> {
+    let mut archive = Archive::new(GzDecoder::new(tarball));
+    for entry in archive.entries().context("Invalid tar archive entries")? {
+        let mut entry = entry.context("Invalid tar archive entry")?;
+        if entry.path().map_or(false, |entry_path| entry_path == path) {
+            let mut contents = String::new();
+            entry
+                .read_to_string(&mut contents)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            return Ok(Some(contents));
+        }
+    }
+    Ok(None)
+}
+
+pub fn rerender_readmes(
+    batch_size: i64,
+    crate_name: Option<String>,
+    older_than: Option<NaiveDateTime>,
+) -> Job {
+    Job::RerenderReadmes(RerenderReadmesJob {
+        batch_size,
+        crate_name,
+        older_than,
+    })
+}
+
+fn rerender_readmes_batch(
+    start_id: i32,
+    end_id: i32,
+    crate_name: Option<String>,
+    older_than: Option<NaiveDateTime>,
+) -> Job {
+    Job::RerenderReadmesBatch(RerenderReadmesBatchJob {
+        start_id,
+        end_id,
+        crate_name,
+        older_than,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::read_pkg_readme;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    pub fn add_file<W: Write>(pkg: &mut tar::Builder<W>, path: &str, content: &[u8]) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_cksum();
+        pkg.append_data(&mut header, path, content).unwrap();
+    }
+
+    fn tarball(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut pkg = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+        for (path, content) in files {
+            add_file(&mut pkg, path, content);
+        }
+        pkg.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_read_pkg_readme() {
+        let tarball = tarball(&[
+            (
+                "foo-0.0.1/Cargo.toml",
+                b"[package]\nreadme = \"README.md\"\nrepository = \"https://github.com/foo/foo\"\n",
+            ),
+            ("foo-0.0.1/README.md", b"readme"),
+        ]);
+        let readme = read_pkg_readme(&tarball, "foo-0.0.1").unwrap();
+        assert_eq!(readme.text, "readme");
+        assert_eq!(readme.path, "README.md");
+        assert_eq!(
+            readme.repository.as_deref(),
+            Some("https://github.com/foo/foo")
+        );
+        assert_eq!(readme.pkg_path_in_vcs, None);
+    }
+
+    #[test]
+    fn test_read_pkg_no_readme() {
+        let tarball = tarball(&[("foo-0.0.1/Cargo.toml", b"[package]\n")]);
+        assert_err!(read_pkg_readme(&tarball, "foo-0.0.1"));
+    }
+
+    #[test]
+    fn test_read_pkg_implicit_readme() {
+        let tarball = tarball(&[
+            ("foo-0.0.1/README.md", b"readme"),
+            ("foo-0.0.1/Cargo.toml", b"[package]\n"),
+        ]);
+        let readme = read_pkg_readme(&tarball, "foo-0.0.1").unwrap();
+        assert_eq!(readme.text, "readme");
+        assert_eq!(readme.path, "README.md");
+    }
+
+    #[test]
+    fn test_read_pkg_readme_not_at_root() {
+        let tarball = tarball(&[
+            (
+                "foo-0.0.1/Cargo.toml",
+                b"[package]\nreadme = \"docs/README.md\"\n",
+            ),
+            (
+                "foo-0.0.1/.cargo_vcs_info.json",
+                br#"{"path_in_vcs": "crates/foo"}"#,
+            ),
+            ("foo-0.0.1/docs/README.md", b"docs/readme"),
+        ]);
+        let readme = read_pkg_readme(&tarball, "foo-0.0.1").unwrap();
+        assert_eq!(readme.text, "docs/readme");
+        assert_eq!(readme.path, "docs/README.md");
+        assert_eq!(readme.pkg_path_in_vcs.as_deref(), Some("crates/foo"));
+    }
+}